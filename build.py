@@ -119,6 +119,11 @@ def make_parser():
         action='store_true',
         help='Enable feature vram, only available on windows now.'
     )
+    parser.add_argument(
+        '--openh264',
+        action='store_true',
+        help='Enable feature openh264, software H264 codec with Cisco\'s OpenH264 library loaded at runtime.'
+    )
     parser.add_argument(
         '--portable',
         action='store_true',
@@ -277,6 +282,8 @@ def get_features(args):
         features.append('hwcodec')
     if args.vram:
         features.append('vram')
+    if args.openh264:
+        features.append('openh264')
     if args.flutter:
         features.append('flutter')
     if args.unix_file_copy_paste:
@@ -299,7 +306,7 @@ Version: %s
 Architecture: %s
 Maintainer: rustdesk <info@rustdesk.com>
 Homepage: https://rustdesk.com
-Depends: libgtk-3-0t64 | libgtk-3-0, libxcb-randr0, libxdo3 | libxdo4, libxfixes3, libxcb-shape0, libxcb-xfixes0, libasound2t64 | libasound2, libsystemd0, curl, libva2, libva-drm2, libva-x11-2, libgstreamer-plugins-base1.0-0, libpam0g, gstreamer1.0-pipewire%s
+Depends: libgtk-3-0t64 | libgtk-3-0, libxcb-damage0, libxcb-randr0, libxdo3 | libxdo4, libxfixes3, libxcb-shape0, libxcb-xfixes0, libasound2t64 | libasound2, libsystemd0, curl, libva2, libva-drm2, libva-x11-2, libgstreamer-plugins-base1.0-0, libpam0g, gstreamer1.0-pipewire%s
 Recommends: libayatana-appindicator3-1
 Description: A remote control software.
 
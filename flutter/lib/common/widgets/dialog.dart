@@ -256,8 +256,15 @@ void changeWhiteList({Function()? callback}) async {
                     r"^(25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9][0-9]?|0)\.(25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9][0-9]?|0)\.(25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9][0-9]?|0)\.(25[0-5]|2[0-4][0-9]|1[0-9][0-9]|[1-9][0-9]?|0)(\/([1-9]|[1-2][0-9]|3[0-2])){0,1}$");
                 final ipv6Match = RegExp(
                     r"^(((?:[0-9A-Fa-f]{1,4}))*((?::[0-9A-Fa-f]{1,4}))*::((?:[0-9A-Fa-f]{1,4}))*((?::[0-9A-Fa-f]{1,4}))*|((?:[0-9A-Fa-f]{1,4}))((?::[0-9A-Fa-f]{1,4})){7})(\/([1-9]|[1-9][0-9]|1[0-1][0-9]|12[0-8])){0,1}$");
+                // office.example.com, resolved by the controlled side
+                final hostMatch = RegExp(
+                    r"^(?=.*[A-Za-z])([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)(\.[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)*$");
                 for (final ip in ips) {
-                  if (!ipMatch.hasMatch(ip) && !ipv6Match.hasMatch(ip)) {
+                  // "!" prefix denies the entry
+                  final entry = ip.startsWith('!') ? ip.substring(1) : ip;
+                  if (!ipMatch.hasMatch(entry) &&
+                      !ipv6Match.hasMatch(entry) &&
+                      !hostMatch.hasMatch(entry)) {
                     msg = "${translate("Invalid IP")} $ip";
                     setState(() {
                       isInProgress = false;
@@ -1352,6 +1359,373 @@ void showRestartRemoteDevice(PeerInfo pi, String id, SessionID sessionId,
   if (res == true) bind.sessionRestartRemoteDevice(sessionId: sessionId);
 }
 
+void showClipboardHistoryDialog(FFI ffi) {
+  final sessionId = ffi.sessionId;
+  List<dynamic> entries = [];
+  try {
+    entries = jsonDecode(
+        bind.sessionGetClipboardHistory(sessionId: sessionId)) as List<dynamic>;
+  } catch (e) {
+    debugPrint('Failed to decode clipboard history: $e');
+  }
+
+  Widget buildEntry(BuildContext context, dynamic e, VoidCallback close) {
+    final String? text = e['text'];
+    final String? thumbnail = e['thumbnail'];
+    final time = DateTime.fromMillisecondsSinceEpoch(e['time'] as int);
+    final sent = e['direction'] == 'sent';
+    final formats = (e['formats'] as List<dynamic>).join(', ');
+    return ListTile(
+      contentPadding: EdgeInsets.zero,
+      leading: Icon(sent ? Icons.upload_rounded : Icons.download_rounded),
+      title: thumbnail != null
+          ? Align(
+              alignment: Alignment.centerLeft,
+              child: Image.memory(base64Decode(thumbnail),
+                  height: 64, fit: BoxFit.contain))
+          : Text(text ?? formats, maxLines: 3, overflow: TextOverflow.ellipsis),
+      subtitle: Text(
+          '${time.toLocal().toString().substring(0, 19)}  $formats',
+          style: Theme.of(context).textTheme.bodySmall),
+      trailing: IconButton(
+        tooltip: translate('Send'),
+        icon: Icon(Icons.send_rounded),
+        onPressed: () {
+          if (bind.sessionResendClipboardHistory(
+              sessionId: sessionId, historyId: e['id'] as int)) {
+            close();
+          } else {
+            showToast(translate('Failed'));
+          }
+        },
+      ),
+    );
+  }
+
+  ffi.dialogManager.show((setState, close, context) {
+    return CustomAlertDialog(
+      title: Text(translate('Clipboard history')),
+      content: SizedBox(
+        width: 420,
+        child: entries.isEmpty
+            ? Text(translate('Empty'))
+            : ConstrainedBox(
+                constraints: BoxConstraints(maxHeight: 400),
+                child: ListView(
+                  shrinkWrap: true,
+                  children: entries
+                      .map((e) => buildEntry(context, e, close))
+                      .toList(),
+                ),
+              ),
+      ),
+      actions: [
+        dialogButton('Close', onPressed: close, isOutline: true),
+      ],
+      onCancel: close,
+    );
+  }, clickMaskDismiss: true);
+}
+
+void showKeyRemapDialog(FFI ffi) {
+  final sessionId = ffi.sessionId;
+  String format(String json) {
+    if (json.isEmpty) return '';
+    try {
+      return JsonEncoder.withIndent('  ').convert(jsonDecode(json));
+    } catch (_) {
+      return json;
+    }
+  }
+
+  final controller = TextEditingController(
+      text: format(bind.sessionGetKeyRemap(sessionId: sessionId)));
+  String? errorText;
+  ffi.dialogManager.show((setState, close, context) {
+    submit() {
+      final err = bind.sessionSetKeyRemap(
+          sessionId: sessionId, json: controller.text.trim());
+      if (err.isNotEmpty) {
+        setState(() => errorText = err);
+        return;
+      }
+      close();
+    }
+
+    // Import and export by the clipboard, the json can be shared between peers.
+    importRemap() async {
+      final data = await Clipboard.getData(Clipboard.kTextPlain);
+      final text = data?.text ?? '';
+      if (text.isEmpty) return;
+      setState(() {
+        controller.text = format(text);
+        errorText = null;
+      });
+    }
+
+    exportRemap() {
+      Clipboard.setData(ClipboardData(text: controller.text.trim()));
+      showToast(translate('Copied'));
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Key remapping')),
+      content: SizedBox(
+        width: 420,
+        child: Column(
+          mainAxisSize: MainAxisSize.min,
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            Text(translate('key-remapping-tip')),
+            const SizedBox(height: 8),
+            TextField(
+              controller: controller,
+              maxLines: 10,
+              minLines: 4,
+              style: const TextStyle(fontFamily: 'monospace', fontSize: 13),
+              decoration: InputDecoration(
+                hintText: '{\n  "CapsLock": "Escape",\n  "AltGr": "MetaRight"\n}',
+                errorText: errorText,
+                errorMaxLines: 3,
+              ),
+            ),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Import', onPressed: importRemap, isOutline: true),
+        dialogButton('Export', onPressed: exportRemap, isOutline: true),
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
+void showPasteAsKeystrokesDialog(FFI ffi, String text) {
+  final sessionId = ffi.sessionId;
+  var mode = 'translate';
+  final intervalController = TextEditingController(text: '20');
+  ffi.dialogManager.show((setState, close, context) {
+    submit() {
+      final interval = int.tryParse(intervalController.text.trim()) ?? 20;
+      if (bind.sessionPasteAsKeystrokes(
+          sessionId: sessionId,
+          text: text,
+          mode: mode,
+          intervalMs: interval)) {
+        close();
+      } else {
+        showToast(translate('Failed'));
+      }
+    }
+
+    Widget buildMode(String value, String label) {
+      return RadioListTile<String>(
+        contentPadding: EdgeInsets.zero,
+        dense: true,
+        title: Text(translate(label)),
+        value: value,
+        groupValue: mode,
+        onChanged: (v) => setState(() => mode = v ?? mode),
+      );
+    }
+
+    final preview = text.length > 200 ? '${text.substring(0, 200)}...' : text;
+    final playing = bind.sessionIsMacroPlaying(sessionId: sessionId);
+    return CustomAlertDialog(
+      title: Text(translate('Paste as keystrokes')),
+      content: SizedBox(
+        width: 420,
+        child: Column(
+          mainAxisSize: MainAxisSize.min,
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            Text(translate('paste-as-keystrokes-tip')),
+            const SizedBox(height: 8),
+            Text(preview,
+                maxLines: 4,
+                overflow: TextOverflow.ellipsis,
+                style: Theme.of(context).textTheme.bodySmall),
+            buildMode('translate', 'Translate mode'),
+            buildMode('layout', 'Remote keyboard layout'),
+            TextField(
+              controller: intervalController,
+              keyboardType: TextInputType.number,
+              inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+              decoration: InputDecoration(
+                  labelText: translate('Key interval (ms)')),
+            ),
+          ],
+        ),
+      ),
+      actions: [
+        if (playing)
+          dialogButton('Stop', onPressed: () {
+            bind.sessionStopMacro(sessionId: sessionId);
+            close();
+          }, isOutline: true),
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void showSaveMacroDialog(FFI ffi) {
+  final sessionId = ffi.sessionId;
+  final nameController = TextEditingController();
+  final hotkeyController = TextEditingController();
+  String? errorText;
+  ffi.dialogManager.show((setState, close, context) {
+    discard() {
+      bind.sessionMacroStopRecording(
+          sessionId: sessionId, name: '', hotkey: '');
+      close();
+    }
+
+    submit() {
+      final name = nameController.text.trim();
+      if (name.isEmpty) {
+        setState(() => errorText = translate('Empty'));
+        return;
+      }
+      final err = bind.sessionMacroStopRecording(
+          sessionId: sessionId,
+          name: name,
+          hotkey: hotkeyController.text.trim());
+      if (err.isNotEmpty) {
+        setState(() => errorText = translate(err));
+        return;
+      }
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Stop recording macro')),
+      content: Column(
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          TextField(
+            controller: nameController,
+            autofocus: true,
+            decoration: InputDecoration(
+                labelText: translate('Name'), errorText: errorText),
+          ),
+          TextField(
+            controller: hotkeyController,
+            decoration: InputDecoration(
+                labelText: translate('Hotkey'), hintText: 'Ctrl+Alt+1'),
+          ),
+        ],
+      ),
+      actions: [
+        dialogButton('Discard', onPressed: discard, isOutline: true),
+        dialogButton('Save', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: discard,
+    );
+  });
+}
+
+void showMacrosDialog(FFI ffi) {
+  final sessionId = ffi.sessionId;
+  List<dynamic> loadMacros() {
+    try {
+      return jsonDecode(bind.mainGetMacros()) as List<dynamic>;
+    } catch (e) {
+      debugPrint('Failed to decode macros: $e');
+      return [];
+    }
+  }
+
+  var macros = loadMacros();
+  var speed = 1.0;
+  ffi.dialogManager.show((setState, close, context) {
+    Widget buildEntry(dynamic m) {
+      final String name = m['name'];
+      final String hotkey = m['hotkey'];
+      final seconds = ((m['duration'] as int) / 1000).toStringAsFixed(1);
+      return ListTile(
+        contentPadding: EdgeInsets.zero,
+        title: Text(name),
+        subtitle: Text(
+            '${hotkey.isEmpty ? '' : '$hotkey  '}${m['events']}, ${seconds}s',
+            style: Theme.of(context).textTheme.bodySmall),
+        trailing: Row(mainAxisSize: MainAxisSize.min, children: [
+          IconButton(
+            tooltip: translate('Play'),
+            icon: Icon(Icons.play_arrow_rounded),
+            onPressed: () {
+              if (bind.sessionPlayMacro(
+                  sessionId: sessionId, name: name, speed: speed)) {
+                close();
+              } else {
+                showToast(translate('Failed'));
+              }
+            },
+          ),
+          IconButton(
+            tooltip: translate('Delete'),
+            icon: Icon(Icons.delete_outline_rounded),
+            onPressed: () async {
+              await bind.mainRemoveMacro(name: name);
+              setState(() => macros = loadMacros());
+            },
+          ),
+        ]),
+      );
+    }
+
+    final playing = bind.sessionIsMacroPlaying(sessionId: sessionId);
+    return CustomAlertDialog(
+      title: Text(translate('Macros')),
+      content: SizedBox(
+        width: 420,
+        child: Column(
+          mainAxisSize: MainAxisSize.min,
+          children: [
+            Row(children: [
+              Text('${translate('Speed')}: ${speed.toStringAsFixed(1)}x'),
+              Expanded(
+                child: Slider(
+                  value: speed,
+                  min: 0.1,
+                  max: 10.0,
+                  divisions: 99,
+                  onChanged: (v) => setState(() => speed = v),
+                ),
+              ),
+            ]),
+            macros.isEmpty
+                ? Text(translate('Empty'))
+                : ConstrainedBox(
+                    constraints: BoxConstraints(maxHeight: 400),
+                    child: ListView(
+                      shrinkWrap: true,
+                      children: macros.map(buildEntry).toList(),
+                    ),
+                  ),
+          ],
+        ),
+      ),
+      actions: [
+        if (playing)
+          dialogButton('Stop', onPressed: () {
+            bind.sessionStopMacro(sessionId: sessionId);
+            close();
+          }, isOutline: true),
+        dialogButton('Close', onPressed: close, isOutline: true),
+      ],
+      onCancel: close,
+    );
+  }, clickMaskDismiss: true);
+}
+
 showSetOSPassword(
   SessionID sessionId,
   bool login,
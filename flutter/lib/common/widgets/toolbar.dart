@@ -397,6 +397,47 @@ List<TTextMenu> toolbarControls(BuildContext context, String id, FFI ffi) {
           }
           await sendClipboardKeystrokes();
         }));
+    if (!isWeb) {
+      v.add(TTextMenu(
+          child: Text(translate('Paste as keystrokes')),
+          onPressed: () async {
+            ClipboardData? data = await Clipboard.getData(Clipboard.kTextPlain);
+            final text = data?.text ?? '';
+            if (text.isEmpty) {
+              showToast(translate('Empty'));
+              return;
+            }
+            showPasteAsKeystrokesDialog(ffi, text);
+          }));
+    }
+  }
+  // clipboard history
+  if (isDefaultConn && perms['clipboard'] != false) {
+    v.add(TTextMenu(
+        child: Text(translate('Clipboard history')),
+        onPressed: () => showClipboardHistoryDialog(ffi)));
+  }
+  // macros
+  if (isDefaultConn && !isWeb && perms['keyboard'] != false) {
+    if (bind.sessionMacroIsRecording(sessionId: sessionId)) {
+      v.add(TTextMenu(
+          child: Text(translate('Stop recording macro')),
+          onPressed: () => showSaveMacroDialog(ffi)));
+    } else {
+      v.add(TTextMenu(
+          child: Text(translate('Record macro')),
+          onPressed: () =>
+              bind.sessionMacroStartRecording(sessionId: sessionId)));
+    }
+    v.add(TTextMenu(
+        child: Text(translate('Macros')),
+        onPressed: () => showMacrosDialog(ffi)));
+  }
+  // key remapping, the key codes of mobile are not remapped
+  if (isDefaultConn && isDesktop && perms['keyboard'] != false) {
+    v.add(TTextMenu(
+        child: Text(translate('Key remapping')),
+        onPressed: () => showKeyRemapDialog(ffi)));
   }
   if (isDefaultConn &&
       isWaylandPeer &&
@@ -888,6 +929,19 @@ Future<List<TToggleMenu>> toolbarDisplayToggle(
         ffi.qualityMonitorModel.checkShowQualityMonitor(sessionId);
       },
       child: Text(translate('Show quality monitor'))));
+  // lossless text tiles
+  if (pi.features.losslessTiles) {
+    final option = 'lossless-text-tiles';
+    final value =
+        bind.sessionGetToggleOptionSync(sessionId: sessionId, arg: option);
+    v.add(TToggleMenu(
+        value: value,
+        onChanged: (value) {
+          if (value == null) return;
+          bind.sessionToggleOption(sessionId: sessionId, value: option);
+        },
+        child: Text(translate('Sharp text when idle'))));
+  }
   // mute
   if (isDefaultConn && perms['audio'] != false) {
     final option = 'disable-audio';
@@ -857,6 +857,7 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
                 if (!isChangeIdDisabled())
                   _Card(title: 'ID', children: [changeId()]),
                 more(context),
+                if (!isWeb) auditQueue(),
               ]),
             ),
           ],
@@ -1244,6 +1245,27 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
         })));
   }
 
+  Widget auditQueue() {
+    return futureBuilder(future: () async {
+      final status = await bind.mainGetAuditQueueStatus();
+      return status.isEmpty ? {} : jsonDecode(status);
+    }(), hasData: (data) {
+      text(String label, String key) =>
+          Text('${translate(label)}: ${data[key] ?? 0}')
+              .marginSymmetric(vertical: 4.0);
+      return _Card(title: 'Audit events', children: [
+        Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            text('Pending', 'pending'),
+            text('Dropped', 'dropped'),
+            text('Rejected', 'dead_letters'),
+          ],
+        ).marginOnly(left: _kContentHMargin),
+      ]);
+    });
+  }
+
   Widget more(BuildContext context) {
     bool enabled = !locked;
     return _Card(title: 'Security', children: [
@@ -735,9 +735,41 @@ class InputModel {
     }
   }
 
+  // Play the recorded macro bound to the pressed key combination, e.g. "Ctrl+Alt+1".
+  bool _playMacroByHotkey(LogicalKeyboardKey key) {
+    if (isWeb) return false;
+    if (_isModifierKey(key)) return false;
+    final keyboard = HardwareKeyboard.instance;
+    if (!keyboard.logicalKeysPressed
+        .every((k) => k == key || _isModifierKey(k))) {
+      return false;
+    }
+    final hotkey = [
+      if (keyboard.isControlPressed) 'Ctrl',
+      if (keyboard.isAltPressed) 'Alt',
+      if (keyboard.isShiftPressed) 'Shift',
+      if (keyboard.isMetaPressed) 'Meta',
+      key.keyLabel,
+    ].join('+');
+    return bind.sessionPlayMacroByHotkey(sessionId: sessionId, hotkey: hotkey);
+  }
+
+  static bool _isModifierKey(LogicalKeyboardKey k) =>
+      k == LogicalKeyboardKey.controlLeft ||
+      k == LogicalKeyboardKey.controlRight ||
+      k == LogicalKeyboardKey.altLeft ||
+      k == LogicalKeyboardKey.altRight ||
+      k == LogicalKeyboardKey.shiftLeft ||
+      k == LogicalKeyboardKey.shiftRight ||
+      k == LogicalKeyboardKey.metaLeft ||
+      k == LogicalKeyboardKey.metaRight;
+
   KeyEventResult handleRawKeyEvent(RawKeyEvent e) {
     if (isViewOnly) return KeyEventResult.handled;
     if (isViewCamera) return KeyEventResult.handled;
+    if (e is RawKeyDownEvent && !e.repeat && _playMacroByHotkey(e.logicalKey)) {
+      return KeyEventResult.handled;
+    }
     if (!isInputSourceFlutter) {
       if (isDesktop) {
         return KeyEventResult.handled;
@@ -823,6 +855,9 @@ class InputModel {
   KeyEventResult handleKeyEvent(KeyEvent e) {
     if (isViewOnly) return KeyEventResult.handled;
     if (isViewCamera) return KeyEventResult.handled;
+    if (e is KeyDownEvent && _playMacroByHotkey(e.logicalKey)) {
+      return KeyEventResult.handled;
+    }
     if (!isInputSourceFlutter) {
       if (isDesktop) {
         return KeyEventResult.handled;
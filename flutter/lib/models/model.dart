@@ -1379,6 +1379,7 @@ class FfiModel with ChangeNotifier {
       }
       Map<String, dynamic> features = json.decode(evt['features']);
       _pi.features.privacyMode = features['privacy_mode'] == true;
+      _pi.features.losslessTiles = features['lossless_tiles'] == true;
       if (!isCache) {
         handleResolutions(peerId, evt["resolutions"]);
       }
@@ -4030,6 +4031,7 @@ class Resolution {
 
 class Features {
   bool privacyMode = false;
+  bool losslessTiles = false;
 }
 
 const kInvalidDisplayIndex = -1;
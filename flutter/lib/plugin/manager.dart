@@ -44,6 +44,14 @@ class UiType {
       return UiButton.fromJson(json['c']);
     } else if (json['t'] == 'Checkbox') {
       return UiCheckbox.fromJson(json['c']);
+    } else if (json['t'] == 'TextInput') {
+      return UiTextInput.fromJson(json['c']);
+    } else if (json['t'] == 'Select') {
+      return UiSelect.fromJson(json['c']);
+    } else if (json['t'] == 'Number') {
+      return UiNumber.fromJson(json['c']);
+    } else if (json['t'] == 'Form') {
+      return UiForm.fromJson(json['c']);
     } else {
       return null;
     }
@@ -77,6 +85,68 @@ class UiCheckbox extends UiType {
   UiCheckbox.fromJson(Map<String, dynamic> json) : super.fromJson(json);
 }
 
+class UiTextInput extends UiType {
+  String placeholder;
+  bool multiline;
+
+  UiTextInput.fromJson(Map<String, dynamic> json)
+      : placeholder = json['placeholder'] ?? '',
+        multiline = json['multiline'] ?? false,
+        super.fromJson(json);
+}
+
+class UiSelectOption {
+  String value;
+  String text;
+
+  UiSelectOption.fromJson(Map<String, dynamic> json)
+      : value = json['value'] ?? '',
+        text = json['text'] ?? '';
+}
+
+class UiSelect extends UiType {
+  List<UiSelectOption> options;
+
+  UiSelect.fromJson(Map<String, dynamic> json)
+      : options = ((json['options'] ?? []) as List<dynamic>)
+            .map((e) => UiSelectOption.fromJson(e))
+            .toList(),
+        super.fromJson(json);
+}
+
+class UiNumber extends UiType {
+  double min;
+  double max;
+  double step;
+  bool slider;
+
+  UiNumber.fromJson(Map<String, dynamic> json)
+      : min = (json['min'] ?? 0).toDouble(),
+        max = (json['max'] ?? 0).toDouble(),
+        step = (json['step'] ?? 0).toDouble(),
+        slider = json['slider'] ?? false,
+        super.fromJson(json);
+
+  // The number of slider divisions, null for a continuous slider.
+  int? get divisions =>
+      step > 0 && max > min ? ((max - min) / step).round() : null;
+}
+
+// The fields are submitted together, in a dialog if `dialog` is true.
+class UiForm extends UiType {
+  List<UiType> fields;
+  bool dialog;
+
+  UiForm.fromJson(Map<String, dynamic> json)
+      : fields = ((json['fields'] ?? []) as List<dynamic>)
+            .map((e) => UiType.create(e))
+            .whereType<UiType>()
+            .where((e) => e is! UiForm)
+            .toList(),
+        dialog = json['dialog'] ?? false,
+        super.fromJson(json);
+}
+
 class Location {
   // location key:
   //  host|main|settings|plugin
@@ -128,6 +128,36 @@ class PluginItem extends StatelessWidget {
           child = _buildCheckbox(ui as UiCheckbox);
         }
         break;
+      case UiTextInput:
+      case UiSelect:
+      case UiNumber:
+      case UiForm:
+        final fields = ui is UiForm ? ui.fields : [ui];
+        final inDialog = isMenu || (ui is UiForm && ui.dialog);
+        if (inDialog) {
+          if (isMenu) {
+            if (ffi != null) {
+              child = MenuButton(
+                onPressed: () => _showInputsDialog(ui, fields),
+                child: Text(ui.text),
+                ffi: ffi,
+              );
+            }
+          } else {
+            child = TextButton(
+              onPressed: () => _showInputsDialog(ui, fields),
+              child: Text(ui.text),
+            );
+          }
+        } else {
+          child = _UiInputs(
+            title: ui is UiForm ? ui.text : null,
+            fields: fields,
+            getValue: _getValue,
+            onSubmit: (values) => _submit(ui, values),
+          ).marginOnly(left: _kCheckBoxLeftMargin);
+        }
+        break;
       default:
         break;
     }
@@ -241,18 +271,64 @@ class PluginItem extends StatelessWidget {
     );
   }
 
+  void _showInputsDialog(UiType ui, List<UiType> fields) {
+    (ffi ?? gFFI).dialogManager.show((setState, close, context) {
+      return CustomAlertDialog(
+        title: Text(ui.text),
+        content: _UiInputs(
+          fields: fields,
+          getValue: _getValue,
+          onSubmit: (values) {
+            final ok = _submit(ui, values);
+            if (ok) {
+              close();
+            }
+            return ok;
+          },
+        ),
+        onCancel: close,
+      );
+    });
+  }
+
+  String? _getValue(String key) =>
+      _getOption(getOptionModel(location, pluginId, peerId, key), key);
+
+  // The values are validated and stored by RustDesk before the plugin is notified.
+  bool _submit(UiType ui, Map<String, String> values) {
+    final err = bind.pluginSetUiValues(
+        id: pluginId, peer: peerId, values: jsonEncode(values));
+    if (err.isNotEmpty) {
+      showToast(err);
+      return false;
+    }
+    values.forEach((k, v) => updateOption(location, pluginId, peerId, k, v));
+    bind.pluginEvent(
+      id: pluginId,
+      peer: peerId,
+      event: _makeEvent(
+        ui.key,
+        value: ui is UiForm ? jsonEncode(values) : values[ui.key],
+        action: ui.action,
+      ),
+    );
+    return true;
+  }
+
   Uint8List _makeEvent(
     String key, {
     bool? v,
+    String? value,
+    String action = '',
   }) {
     final event = MsgFromUi(
       id: pluginId,
       name: pluginManager.getPlugin(pluginId)?.meta.name ?? '',
       location: location,
       key: key,
-      value:
-          v != null ? (v ? ConfigItem.trueValue : ConfigItem.falseValue) : '',
-      action: '',
+      value: value ??
+          (v != null ? (v ? ConfigItem.trueValue : ConfigItem.falseValue) : ''),
+      action: action,
     );
     return Uint8List.fromList(event.toString().codeUnits);
   }
@@ -299,3 +375,138 @@ void handleOption(Map<String, dynamic> evt) {
   updateOption(
       evt['location'], evt['id'], evt['peer'] ?? '', evt['key'], evt['value']);
 }
+
+// The inputs of a form or a single input, the values are submitted together.
+class _UiInputs extends StatefulWidget {
+  final String? title;
+  final List<UiType> fields;
+  final String? Function(String key) getValue;
+  final bool Function(Map<String, String> values) onSubmit;
+
+  _UiInputs({
+    Key? key,
+    this.title,
+    required this.fields,
+    required this.getValue,
+    required this.onSubmit,
+  }) : super(key: key);
+
+  @override
+  State<_UiInputs> createState() => _UiInputsState();
+}
+
+class _UiInputsState extends State<_UiInputs> {
+  final Map<String, String> _values = {};
+  final Map<String, TextEditingController> _controllers = {};
+
+  @override
+  void initState() {
+    super.initState();
+    for (final f in widget.fields) {
+      final v = widget.getValue(f.key) ?? '';
+      _values[f.key] = v;
+      if (f is UiTextInput || (f is UiNumber && !f.slider)) {
+        _controllers[f.key] = TextEditingController(text: v);
+      }
+    }
+  }
+
+  @override
+  void dispose() {
+    for (final c in _controllers.values) {
+      c.dispose();
+    }
+    super.dispose();
+  }
+
+  void _submit() {
+    _controllers.forEach((k, c) => _values[k] = c.text);
+    widget.onSubmit(Map.from(_values));
+  }
+
+  Widget _buildField(UiType ui) {
+    if (ui is UiTextInput) {
+      return TextField(
+        controller: _controllers[ui.key],
+        minLines: 1,
+        maxLines: ui.multiline ? 5 : 1,
+        decoration: InputDecoration(
+          labelText: ui.text,
+          hintText: ui.placeholder,
+        ),
+      );
+    } else if (ui is UiSelect) {
+      final v = _values[ui.key];
+      return Row(
+        children: [
+          Expanded(child: Text(ui.text)),
+          DropdownButton<String>(
+            value: ui.options.any((e) => e.value == v) ? v : null,
+            items: ui.options
+                .map((e) => DropdownMenuItem(value: e.value, child: Text(e.text)))
+                .toList(),
+            onChanged: (v) {
+              if (v != null) {
+                setState(() => _values[ui.key] = v);
+              }
+            },
+          ),
+        ],
+      );
+    } else if (ui is UiNumber) {
+      if (!ui.slider) {
+        return TextField(
+          controller: _controllers[ui.key],
+          keyboardType: TextInputType.numberWithOptions(
+              decimal: true, signed: ui.min < 0),
+          decoration: InputDecoration(
+            labelText: ui.text,
+            hintText: '${ui.min} - ${ui.max}',
+          ),
+        );
+      }
+      final v = (double.tryParse(_values[ui.key] ?? '') ?? ui.min)
+          .clamp(ui.min, ui.max)
+          .toDouble();
+      return Row(
+        children: [
+          Text(ui.text),
+          Expanded(
+            child: Slider(
+              value: v,
+              min: ui.min,
+              max: ui.max,
+              divisions: ui.divisions,
+              label: _formatNumber(v),
+              onChanged: (v) =>
+                  setState(() => _values[ui.key] = _formatNumber(v)),
+            ),
+          ),
+        ],
+      );
+    }
+    return Container();
+  }
+
+  String _formatNumber(double v) =>
+      v == v.roundToDouble() ? v.toInt().toString() : v.toStringAsFixed(2);
+
+  @override
+  Widget build(BuildContext context) {
+    return Column(
+      crossAxisAlignment: CrossAxisAlignment.start,
+      mainAxisSize: MainAxisSize.min,
+      children: [
+        if (widget.title != null) Text(widget.title!),
+        ...widget.fields.map((f) => _buildField(f)),
+        Align(
+          alignment: Alignment.centerRight,
+          child: TextButton(
+            onPressed: _submit,
+            child: Text(translate('OK')),
+          ),
+        ),
+      ],
+    );
+  }
+}
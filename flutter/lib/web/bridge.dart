@@ -1163,6 +1163,10 @@ class RustdeskImpl {
     return Future.value('');
   }
 
+  Future<String> mainGetAuditQueueStatus({dynamic hint}) {
+    return Future.value('');
+  }
+
   Future<String> cmGetClientsState({dynamic hint}) {
     throw UnimplementedError("cmGetClientsState");
   }
@@ -1687,6 +1691,14 @@ class RustdeskImpl {
     throw UnimplementedError("pluginSetSharedOption");
   }
 
+  String pluginSetUiValues(
+      {required String id,
+      required String peer,
+      required String values,
+      dynamic hint}) {
+    throw UnimplementedError("pluginSetUiValues");
+  }
+
   Future<void> pluginReload({required String id, dynamic hint}) {
     throw UnimplementedError("pluginReload");
   }
@@ -2019,6 +2031,80 @@ class RustdeskImpl {
     return js.context.callMethod('getByName', ['audit_guid']);
   }
 
+  String sessionGetClipboardHistory(
+      {required UuidValue sessionId, dynamic hint}) {
+    return '[]';
+  }
+
+  bool sessionResendClipboardHistory(
+      {required UuidValue sessionId, required int historyId, dynamic hint}) {
+    return false;
+  }
+
+  String sessionGetKeyRemap({required UuidValue sessionId, dynamic hint}) {
+    return '';
+  }
+
+  String sessionSetKeyRemap(
+      {required UuidValue sessionId, required String json, dynamic hint}) {
+    return '';
+  }
+
+  Future<void> sessionMacroStartRecording(
+      {required UuidValue sessionId, dynamic hint}) {
+    throw UnimplementedError("sessionMacroStartRecording");
+  }
+
+  bool sessionMacroIsRecording({required UuidValue sessionId, dynamic hint}) {
+    return false;
+  }
+
+  String sessionMacroStopRecording(
+      {required UuidValue sessionId,
+      required String name,
+      required String hotkey,
+      dynamic hint}) {
+    return '';
+  }
+
+  bool sessionPlayMacro(
+      {required UuidValue sessionId,
+      required String name,
+      required double speed,
+      dynamic hint}) {
+    return false;
+  }
+
+  bool sessionPlayMacroByHotkey(
+      {required UuidValue sessionId, required String hotkey, dynamic hint}) {
+    return false;
+  }
+
+  bool sessionPasteAsKeystrokes(
+      {required UuidValue sessionId,
+      required String text,
+      required String mode,
+      required int intervalMs,
+      dynamic hint}) {
+    return false;
+  }
+
+  Future<void> sessionStopMacro({required UuidValue sessionId, dynamic hint}) {
+    throw UnimplementedError("sessionStopMacro");
+  }
+
+  bool sessionIsMacroPlaying({required UuidValue sessionId, dynamic hint}) {
+    return false;
+  }
+
+  String mainGetMacros({dynamic hint}) {
+    return '[]';
+  }
+
+  Future<void> mainRemoveMacro({required String name, dynamic hint}) {
+    throw UnimplementedError("mainRemoveMacro");
+  }
+
   bool mainSetCursorPosition({required int x, required int y, dynamic hint}) {
     return false;
   }
@@ -8,6 +8,9 @@ pub use filetype::{FileDescription, FileType};
 pub mod fuse;
 #[cfg(target_os = "macos")]
 pub mod macos;
+/// stage files in a temp directory if FUSE is not available
+#[cfg(target_os = "linux")]
+pub mod staging;
 
 pub mod local_file;
 pub mod serv_files;
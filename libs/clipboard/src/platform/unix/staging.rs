@@ -0,0 +1,386 @@
+//! Fallback of the FUSE file clipboard for the environments without `/dev/fuse`, e.g. containers.
+//!
+//! Instead of serving the remote files lazily, all files are downloaded through
+//! `FileContentsRequest` into a temp directory first.
+//! The urls are published to the clipboard only after all files are staged,
+//! so that a paste never gets a partial file.
+//!
+//! The files are staged in a directory only accessible by the current user,
+//! `$XDG_RUNTIME_DIR/<app>` or `<temp dir>/<app>-<uid>`.
+
+use super::{FileDescription, FileType, BLOCK_SIZE};
+use crate::{send_data, ClipboardFile, CliprdrError};
+use hbb_common::{config::APP_NAME, log};
+use parking_lot::Mutex;
+use std::{
+    fs::{self, DirBuilder, File, FileTimes, Permissions},
+    io::Write,
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt},
+        prelude::PermissionsExt,
+    },
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const READ_RETRY: usize = 3;
+const RECEIVE_WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const PRIVATE_DIR_MODE: u32 = 0o700;
+// The remote permissions can not add the setuid, setgid, sticky or write bits for others.
+const PERM_MASK: u32 = 0o755;
+
+lazy_static::lazy_static! {
+    static ref STAGING_ROOT: PathBuf = staging_root();
+    static ref STAGING_DIR_CLIENT: PathBuf = STAGING_ROOT.join("cliprdr-staging-client");
+    static ref STAGING_DIR_SERVER: PathBuf = STAGING_ROOT.join("cliprdr-staging-server");
+
+    static ref STAGING_TASK_CLIENT: Mutex<Option<StagingTask>> = Mutex::new(None);
+    static ref STAGING_TASK_SERVER: Mutex<Option<StagingTask>> = Mutex::new(None);
+}
+
+fn staging_root() -> PathBuf {
+    let app_name = APP_NAME.read().unwrap().clone();
+    match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() && dir.is_dir() => dir.join(app_name),
+        _ => std::env::temp_dir().join(format!("{}-{}", app_name, unsafe { libc::getuid() })),
+    }
+}
+
+// Create the directory if not exists, and make sure it is a directory owned by the current user
+// and only accessible by the current user. The temp dir is shared, it may be created by others.
+fn create_private_dir(dir: &Path) -> Result<(), CliprdrError> {
+    DirBuilder::new()
+        .mode(PRIVATE_DIR_MODE)
+        .create(dir)
+        .or_else(|e| {
+            if e.kind() == std::io::ErrorKind::AlreadyExists {
+                Ok(())
+            } else {
+                Err(file_error(dir, e))
+            }
+        })?;
+    let meta = fs::symlink_metadata(dir).map_err(|e| file_error(dir, e))?;
+    if !meta.is_dir() || meta.uid() != unsafe { libc::getuid() } {
+        return Err(CliprdrError::CommonError {
+            description: format!("{} is not a directory of the current user", dir.display()),
+        });
+    }
+    if meta.mode() & 0o777 != PRIVATE_DIR_MODE {
+        fs::set_permissions(dir, Permissions::from_mode(PRIVATE_DIR_MODE))
+            .map_err(|e| file_error(dir, e))?;
+    }
+    Ok(())
+}
+
+#[inline]
+fn task(is_client: bool) -> &'static Mutex<Option<StagingTask>> {
+    if is_client {
+        &STAGING_TASK_CLIENT
+    } else {
+        &STAGING_TASK_SERVER
+    }
+}
+
+pub fn get_exclude_paths(is_client: bool) -> PathBuf {
+    if is_client {
+        STAGING_DIR_CLIENT.clone()
+    } else {
+        STAGING_DIR_SERVER.clone()
+    }
+}
+
+struct StagingTask {
+    conn_id: i32,
+    dir: PathBuf,
+    tx: Sender<ClipboardFile>,
+    is_canceled: Arc<AtomicBool>,
+}
+
+impl Drop for StagingTask {
+    fn drop(&mut self) {
+        self.is_canceled.store(true, Ordering::SeqCst);
+        // The worker may still be writing, the remaining files are removed on its exit.
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("failed to remove staging dir {}: {}", self.dir.display(), e);
+            }
+        }
+    }
+}
+
+/// Start downloading the files of a format data response.
+/// `on_staged` is called with the urls of the top-level files and directories after all files are staged.
+/// The previous task of the same side is canceled and its files are removed.
+pub fn start(
+    is_client: bool,
+    format_data: Vec<u8>,
+    conn_id: i32,
+    on_staged: impl FnOnce(Vec<String>) + Send + 'static,
+) -> Result<(), CliprdrError> {
+    let files = FileDescription::parse_file_descriptors(format_data, conn_id)?;
+    if let Some(f) = files.iter().find(|f| !is_relative_name(&f.name)) {
+        return Err(CliprdrError::InvalidRequest {
+            description: format!("invalid file name: {}", f.name.display()),
+        });
+    }
+
+    let base = get_exclude_paths(is_client);
+    let dir = base.join(format!("{}-{}", conn_id, rand::random::<u32>()));
+    let mut lock = task(is_client).lock();
+    let _ = lock.take();
+    // Other users must not read the staged files.
+    create_private_dir(&STAGING_ROOT)?;
+    create_private_dir(&base)?;
+    create_private_dir(&dir)?;
+
+    let (tx, rx) = channel();
+    let is_canceled = Arc::new(AtomicBool::new(false));
+    *lock = Some(StagingTask {
+        conn_id,
+        dir: dir.clone(),
+        tx,
+        is_canceled: is_canceled.clone(),
+    });
+    drop(lock);
+
+    log::info!(
+        "staging {} clipboard files into {}",
+        files.len(),
+        dir.display()
+    );
+    thread::spawn(move || {
+        let worker = Worker {
+            dir,
+            files,
+            rx,
+            is_canceled,
+            stream_id: rand::random(),
+        };
+        let res = worker.run();
+        let is_canceled = worker.is_canceled.load(Ordering::SeqCst);
+        match res {
+            Ok(urls) if !is_canceled => {
+                log::info!("staged clipboard files into {}", worker.dir.display());
+                on_staged(urls);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if !is_canceled {
+                    log::error!("failed to stage clipboard files: {}", e);
+                }
+                fs::remove_dir_all(&worker.dir).ok();
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn handle_file_content_response(
+    is_client: bool,
+    clip: ClipboardFile,
+) -> Result<(), CliprdrError> {
+    let lock = task(is_client).lock();
+    lock.as_ref()
+        .ok_or(CliprdrError::CliprdrInit)?
+        .tx
+        .send(clip)
+        .map_err(|e| {
+            log::error!("failed to send file contents response to staging: {:?}", e);
+            CliprdrError::ClipboardInternalError
+        })
+}
+
+/// Remove the staged files, `conn_id` 0 means any connection.
+pub fn empty_local_files(is_client: bool, conn_id: i32) -> bool {
+    let mut lock = task(is_client).lock();
+    match lock.as_ref() {
+        Some(t) if conn_id == 0 || t.conn_id == conn_id => {
+            let _ = lock.take();
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_relative_name(name: &Path) -> bool {
+    name.components().next().is_some()
+        && name.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+struct Worker {
+    dir: PathBuf,
+    files: Vec<FileDescription>,
+    rx: Receiver<ClipboardFile>,
+    is_canceled: Arc<AtomicBool>,
+    stream_id: i32,
+}
+
+impl Worker {
+    fn run(&self) -> Result<Vec<String>, CliprdrError> {
+        for (index, file) in self.files.iter().enumerate() {
+            let path = self.dir.join(&file.name);
+            match file.kind {
+                FileType::Directory => {
+                    fs::create_dir_all(&path).map_err(|e| file_error(&path, e))?;
+                }
+                FileType::File => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).map_err(|e| file_error(parent, e))?;
+                    }
+                    self.download(index, file, &path)?;
+                }
+                FileType::Symlink => {
+                    // to-do: handle symlink
+                }
+            }
+        }
+        // Set the permissions after all files are written, a read-only directory may contain files.
+        for file in self.files.iter().rev() {
+            let path = self.dir.join(&file.name);
+            let mode = file.perm as u32 & PERM_MASK;
+            fs::set_permissions(&path, Permissions::from_mode(mode))
+                .map_err(|e| file_error(&path, e))?;
+        }
+        Ok(self
+            .files
+            .iter()
+            .filter(|f| f.name.components().count() == 1)
+            .map(|f| self.dir.join(&f.name).to_string_lossy().to_string())
+            .collect())
+    }
+
+    fn download(
+        &self,
+        index: usize,
+        desc: &FileDescription,
+        path: &Path,
+    ) -> Result<(), CliprdrError> {
+        let mut file = File::create(path).map_err(|e| file_error(path, e))?;
+        let mut offset = 0;
+        while offset < desc.size {
+            if self.is_canceled.load(Ordering::SeqCst) {
+                return Err(CliprdrError::CommonError {
+                    description: "canceled".to_owned(),
+                });
+            }
+            let size = (desc.size - offset).min(BLOCK_SIZE as u64) as u32;
+            let data = self.read_block(desc.conn_id, index, offset, size)?;
+            if data.is_empty() {
+                return Err(CliprdrError::InvalidRequest {
+                    description: format!("empty file contents, {}", desc.name.display()),
+                });
+            }
+            file.write_all(&data).map_err(|e| file_error(path, e))?;
+            offset += data.len() as u64;
+        }
+        file.flush().map_err(|e| file_error(path, e))?;
+        file.set_times(
+            FileTimes::new()
+                .set_accessed(desc.atime)
+                .set_modified(desc.last_modified),
+        )
+        .ok();
+        Ok(())
+    }
+
+    fn read_block(
+        &self,
+        conn_id: i32,
+        index: usize,
+        offset: u64,
+        size: u32,
+    ) -> Result<Vec<u8>, CliprdrError> {
+        let request = ClipboardFile::FileContentsRequest {
+            stream_id: self.stream_id,
+            list_index: index as i32,
+            dw_flags: 2,
+            n_position_low: (offset & (u32::MAX as u64)) as i32,
+            n_position_high: (offset >> 32) as i32,
+            cb_requested: size as i32,
+            have_clip_data_id: false,
+            clip_data_id: 0,
+        };
+        send_data(conn_id, request.clone())?;
+        let mut retry_times = 0;
+        loop {
+            let reply = match self.rx.recv_timeout(RECEIVE_WAIT_TIMEOUT) {
+                Ok(reply) => reply,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(CliprdrError::InvalidRequest {
+                        description: "timeout to read file contents".to_owned(),
+                    })
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(CliprdrError::ClipboardInternalError)
+                }
+            };
+            let ClipboardFile::FileContentsResponse {
+                msg_flags,
+                stream_id,
+                requested_data,
+            } = reply
+            else {
+                continue;
+            };
+            if stream_id != self.stream_id {
+                log::debug!("stream id mismatch, ignore");
+                continue;
+            }
+            if msg_flags & 1 == 0 {
+                retry_times += 1;
+                if retry_times > READ_RETRY {
+                    return Err(CliprdrError::InvalidRequest {
+                        description: "failure request".to_owned(),
+                    });
+                }
+                send_data(conn_id, request.clone())?;
+                continue;
+            }
+            return Ok(requested_data);
+        }
+    }
+}
+
+#[inline]
+fn file_error(path: &Path, err: std::io::Error) -> CliprdrError {
+    CliprdrError::FileError {
+        path: path.to_string_lossy().to_string(),
+        err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_name() {
+        assert!(is_relative_name(Path::new("a")));
+        assert!(is_relative_name(Path::new("a/b.txt")));
+        assert!(!is_relative_name(Path::new("")));
+        assert!(!is_relative_name(Path::new("/etc/passwd")));
+        assert!(!is_relative_name(Path::new("a/../../b")));
+    }
+
+    #[test]
+    fn test_private_dir() {
+        let dir = std::env::temp_dir().join(format!("staging-test-{}", rand::random::<u32>()));
+        create_private_dir(&dir).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, PRIVATE_DIR_MODE);
+        fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+        create_private_dir(&dir).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, PRIVATE_DIR_MODE);
+        fs::remove_dir(&dir).unwrap();
+
+        let file = std::env::temp_dir().join(format!("staging-test-{}", rand::random::<u32>()));
+        File::create(&file).unwrap();
+        assert!(create_private_dir(&file).is_err());
+        fs::remove_file(&file).unwrap();
+    }
+}
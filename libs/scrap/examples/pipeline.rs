@@ -0,0 +1,544 @@
+// Benchmark of the whole video pipeline: convert_to_yuv -> encode -> decode.
+// The same frames are fed to every available encoder, so the results are comparable.
+//
+// cargo run --package scrap --example pipeline --release
+// cargo run --package scrap --example pipeline --release --features hwcodec,openh264 -- --codecs=vp9,h264
+// cargo run --package scrap --example pipeline --release -- --capture --save=frames.bgra
+// cargo run --package scrap --example pipeline --release -- --input=frames.bgra --width=1920 --height=1080
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn main() {
+    bench::main();
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn main() {
+    println!("The pipeline benchmark only supports Windows and Linux.");
+}
+
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+mod bench {
+    use docopt::Docopt;
+    use hbb_common::{
+        env_logger::{init_from_env, Env, DEFAULT_FILTER_ENV},
+        log,
+        message_proto::Chroma,
+    };
+    use scrap::{
+        aom::AomEncoderConfig,
+        codec::{Decoder, Encoder, EncoderCfg, EncoderTuning},
+        convert_to_yuv, CodecFormat, Display, EncodeInput, Frame, ImageFormat, ImageRgb,
+        ImageTexture, PixelBuffer, Pixfmt, TraitCapturer, TraitPixelBuffer, VpxEncoderConfig,
+        VpxVideoCodecId,
+    };
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        time::{Duration, Instant},
+    };
+
+    const USAGE: &'static str = "
+Video pipeline benchmark.
+
+Usage:
+  pipeline [options]
+  pipeline (-h | --help)
+
+Options:
+  -h --help             Show this screen.
+  --count=COUNT         Frame count [default: 120].
+  --fps=FPS             Frame rate of the source [default: 30].
+  --quality=QUALITY     Video quality [default: 1.0].
+  --i444                I444, for VP9 and AV1.
+  --codecs=CODECS       Comma separated codecs, vp8,vp9,av1,h264,h265 [default: vp8,vp9,av1,h264,h265].
+  --tuning=JSON         Encoder tuning, same as option encoder-tuning.
+  --width=WIDTH         Width of the synthetic or input frames [default: 1920].
+  --height=HEIGHT       Height of the synthetic or input frames [default: 1080].
+  --input=FILE          Raw BGRA frames, width * height * 4 bytes each.
+  --capture             Capture the frames from the primary display.
+  --save=FILE           Save the source frames as raw BGRA.
+";
+
+    #[derive(Debug, serde::Deserialize, Clone)]
+    struct Args {
+        flag_count: usize,
+        flag_fps: u32,
+        flag_quality: f32,
+        flag_i444: bool,
+        flag_codecs: String,
+        flag_tuning: Option<String>,
+        flag_width: usize,
+        flag_height: usize,
+        flag_input: Option<String>,
+        flag_capture: bool,
+        flag_save: Option<String>,
+    }
+
+    // BGRA frames without padding.
+    struct Frames {
+        width: usize,
+        height: usize,
+        data: Vec<Vec<u8>>,
+    }
+
+    #[derive(Default)]
+    struct Report {
+        convert: Vec<Duration>,
+        encode: Vec<Duration>,
+        decode: Vec<Duration>,
+        bytes: usize,
+        psnr: Vec<f64>,
+        ssim: Vec<f64>,
+    }
+
+    pub fn main() {
+        init_from_env(Env::default().filter_or(DEFAULT_FILTER_ENV, "info"));
+        let args: Args = Docopt::new(USAGE)
+            .and_then(|d| d.deserialize())
+            .unwrap_or_else(|e| e.exit());
+        let count = args.flag_count.max(1);
+        let frames = if args.flag_capture {
+            capture(count)
+        } else if let Some(input) = &args.flag_input {
+            read(input, args.flag_width, args.flag_height, count)
+        } else {
+            synthesize(args.flag_width, args.flag_height, count)
+        };
+        if frames.data.is_empty() {
+            println!("no frames");
+            return;
+        }
+        if let Some(save) = &args.flag_save {
+            let mut file = File::create(save).unwrap();
+            for frame in frames.data.iter() {
+                file.write_all(frame).unwrap();
+            }
+            println!("saved {} frames to {}", frames.data.len(), save);
+        }
+        println!(
+            "pipeline {}x{}, {} frames, {} fps, quality: {:?}, i444: {:?}",
+            frames.width,
+            frames.height,
+            frames.data.len(),
+            args.flag_fps,
+            args.flag_quality,
+            args.flag_i444
+        );
+        println!(
+            "{:<12}{:>16}{:>16}{:>16}{:>10}{:>10}{:>8}",
+            "codec", "convert ms", "encode ms", "decode ms", "kbps", "psnr", "ssim"
+        );
+        println!(
+            "{:<12}{:>16}{:>16}{:>16}",
+            "", "avg/p95", "avg/p95", "avg/p95"
+        );
+        for name in args.flag_codecs.split(',') {
+            let name = name.trim().to_lowercase();
+            for (label, format, cfg) in encoder_configs(&name, &frames, &args) {
+                match run(format, cfg, &frames, &args) {
+                    Ok(report) => print_report(&label, &report, frames.data.len(), args.flag_fps),
+                    Err(e) => println!("{label:<12}failed: {e:?}"),
+                }
+            }
+        }
+    }
+
+    fn encoder_configs(
+        name: &str,
+        frames: &Frames,
+        args: &Args,
+    ) -> Vec<(String, CodecFormat, EncoderCfg)> {
+        let (width, height, quality) = (frames.width, frames.height, args.flag_quality);
+        let tuning =
+            |format| EncoderTuning::parse(&args.flag_tuning.clone().unwrap_or_default(), format);
+        let mut configs = vec![];
+        match name {
+            "vp8" | "vp9" => {
+                let (format, codec) = if name == "vp8" {
+                    (CodecFormat::VP8, VpxVideoCodecId::VP8)
+                } else {
+                    (CodecFormat::VP9, VpxVideoCodecId::VP9)
+                };
+                let tuning = tuning(format);
+                configs.push((
+                    name.to_uppercase(),
+                    format,
+                    EncoderCfg::VPX(VpxEncoderConfig {
+                        width: width as _,
+                        height: height as _,
+                        quality,
+                        codec,
+                        keyframe_interval: tuning.keyframe_interval,
+                        tuning,
+                        hdr: false,
+                    }),
+                ));
+            }
+            "av1" => {
+                let tuning = tuning(CodecFormat::AV1);
+                configs.push((
+                    "AV1".to_owned(),
+                    CodecFormat::AV1,
+                    EncoderCfg::AOM(AomEncoderConfig {
+                        width: width as _,
+                        height: height as _,
+                        quality,
+                        keyframe_interval: tuning.keyframe_interval,
+                        tuning,
+                        hdr: false,
+                    }),
+                ));
+            }
+            "h264" | "h265" => {
+                let format = if name == "h264" {
+                    CodecFormat::H264
+                } else {
+                    CodecFormat::H265
+                };
+                let _tuning = tuning(format);
+                #[cfg(feature = "hwcodec")]
+                if let Some(info) = scrap::hwcodec::HwRamEncoder::try_get(format) {
+                    configs.push((
+                        info.name.clone(),
+                        format,
+                        EncoderCfg::HWRAM(scrap::hwcodec::HwRamEncoderConfig {
+                            name: info.name,
+                            mc_name: None,
+                            width,
+                            height,
+                            quality,
+                            keyframe_interval: _tuning.keyframe_interval,
+                        }),
+                    ));
+                }
+                #[cfg(feature = "openh264")]
+                if format == CodecFormat::H264 {
+                    configs.push((
+                        "openh264".to_owned(),
+                        format,
+                        EncoderCfg::OpenH264(scrap::openh264::OpenH264EncoderConfig {
+                            width,
+                            height,
+                            quality,
+                            keyframe_interval: _tuning.keyframe_interval,
+                            tuning: _tuning,
+                        }),
+                    ));
+                }
+                if configs.is_empty() {
+                    println!("{name:<12}no encoder, try --features hwcodec or openh264");
+                }
+            }
+            _ => println!("{name:<12}unknown codec"),
+        }
+        configs
+    }
+
+    fn run(
+        format: CodecFormat,
+        cfg: EncoderCfg,
+        frames: &Frames,
+        args: &Args,
+    ) -> hbb_common::ResultType<Report> {
+        let i444 = args.flag_i444 && matches!(format, CodecFormat::VP9 | CodecFormat::AV1);
+        let mut encoder = Encoder::new(cfg, i444)?;
+        let mut decoder = Decoder::new(format, None);
+        if !decoder.valid() {
+            hbb_common::bail!("no {format:?} decoder");
+        }
+        let mut report = Report::default();
+        let (mut yuv, mut mid_data) = (vec![], vec![]);
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        let mut texture = ImageTexture::default();
+        let mut pixelbuffer = true;
+        let mut chroma: Option<Chroma> = None;
+        let total = frames.data.len();
+        for (i, frame) in frames.data.iter().enumerate() {
+            let pb = PixelBuffer::new(frame, Pixfmt::BGRA, frames.width, frames.height);
+            let start = Instant::now();
+            convert_to_yuv(&pb, encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
+            report.convert.push(start.elapsed());
+
+            let ms = i as i64 * 1000 / args.flag_fps.max(1) as i64;
+            let start = Instant::now();
+            let vf = match encoder.encode_to_message(EncodeInput::YUV(&yuv), ms) {
+                Ok(vf) => vf,
+                Err(e) => {
+                    // Dropped by the rate control.
+                    log::debug!("encode {i}: {e:?}");
+                    continue;
+                }
+            };
+            report.encode.push(start.elapsed());
+            let Some(union) = vf.union else {
+                continue;
+            };
+            report.bytes += encoded_len(&union);
+
+            let start = Instant::now();
+            let decoded = decoder.handle_video_frame(
+                &union,
+                &mut rgb,
+                &mut texture,
+                &mut pixelbuffer,
+                &mut chroma,
+            )?;
+            report.decode.push(start.elapsed());
+            if decoded {
+                let (psnr, ssim) = compare(frame, frames.width, frames.height, &rgb);
+                report.psnr.push(psnr);
+                report.ssim.push(ssim);
+            }
+            print!("\r{format:?} {}/{}", i + 1, total);
+            std::io::stdout().flush().ok();
+        }
+        print!("\r");
+        Ok(report)
+    }
+
+    fn encoded_len(union: &hbb_common::message_proto::video_frame::Union) -> usize {
+        use hbb_common::message_proto::video_frame::Union;
+        let frames = match union {
+            Union::Vp8s(v)
+            | Union::Vp9s(v)
+            | Union::Av1s(v)
+            | Union::H264s(v)
+            | Union::H265s(v) => v,
+            _ => return 0,
+        };
+        frames.frames.iter().map(|f| f.data.len()).sum()
+    }
+
+    fn print_report(label: &str, r: &Report, count: usize, fps: u32) {
+        let ms = |v: &Vec<Duration>| {
+            if v.is_empty() {
+                return "-".to_owned();
+            }
+            let mut sorted = v.clone();
+            sorted.sort();
+            let avg = sorted.iter().sum::<Duration>() / sorted.len() as u32;
+            let p95 = sorted[(sorted.len() * 95 / 100).min(sorted.len() - 1)];
+            format!(
+                "{:.2}/{:.2}",
+                avg.as_secs_f64() * 1000.0,
+                p95.as_secs_f64() * 1000.0
+            )
+        };
+        let avg = |v: &Vec<f64>| {
+            if v.is_empty() {
+                f64::NAN
+            } else {
+                v.iter().sum::<f64>() / v.len() as f64
+            }
+        };
+        let seconds = count as f64 / fps.max(1) as f64;
+        println!(
+            "{:<12}{:>16}{:>16}{:>16}{:>10.0}{:>10.2}{:>8.4}",
+            label,
+            ms(&r.convert),
+            ms(&r.encode),
+            ms(&r.decode),
+            r.bytes as f64 * 8.0 / 1000.0 / seconds,
+            avg(&r.psnr),
+            avg(&r.ssim),
+        );
+    }
+
+    // BT.601 luma of BGRA, the padding of the decoded image is ignored.
+    fn luma(data: &[u8], stride: usize, width: usize, height: usize) -> Vec<f64> {
+        let mut y = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                let p = &data[row * stride + col * 4..];
+                y.push(0.114 * p[0] as f64 + 0.587 * p[1] as f64 + 0.299 * p[2] as f64);
+            }
+        }
+        y
+    }
+
+    // (PSNR in dB, SSIM) of the luma, SSIM is the mean of 8x8 windows.
+    fn compare(src: &[u8], width: usize, height: usize, rgb: &ImageRgb) -> (f64, f64) {
+        let (w, h) = (width.min(rgb.w), height.min(rgb.h));
+        if w == 0 || h == 0 || rgb.raw.len() < rgb.h * rgb.w * 4 {
+            return (f64::NAN, f64::NAN);
+        }
+        let a = luma(src, width * 4, w, h);
+        let b = luma(&rgb.raw, rgb.raw.len() / rgb.h, w, h);
+        let mse = a
+            .iter()
+            .zip(b.iter())
+            .map(|(x, y)| (x - y).powi(2))
+            .sum::<f64>()
+            / a.len() as f64;
+        let psnr = if mse == 0.0 {
+            100.0
+        } else {
+            10.0 * (255.0 * 255.0 / mse).log10()
+        };
+        let (c1, c2) = ((0.01 * 255.0f64).powi(2), (0.03 * 255.0f64).powi(2));
+        let mut ssim_sum = 0.0;
+        let mut windows = 0;
+        for by in (0..h.saturating_sub(7)).step_by(8) {
+            for bx in (0..w.saturating_sub(7)).step_by(8) {
+                let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for y in by..by + 8 {
+                    for x in bx..bx + 8 {
+                        let (pa, pb) = (a[y * w + x], b[y * w + x]);
+                        sa += pa;
+                        sb += pb;
+                        saa += pa * pa;
+                        sbb += pb * pb;
+                        sab += pa * pb;
+                    }
+                }
+                let n = 64.0;
+                let (ma, mb) = (sa / n, sb / n);
+                let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+                ssim_sum += ((2.0 * ma * mb + c1) * (2.0 * cov + c2))
+                    / ((ma * ma + mb * mb + c1) * (va + vb + c2));
+                windows += 1;
+            }
+        }
+        let ssim = if windows > 0 {
+            ssim_sum / windows as f64
+        } else {
+            f64::NAN
+        };
+        (psnr, ssim)
+    }
+
+    // A desktop like scene: static windows, typing text, a scrolling area and a moving cursor.
+    fn synthesize(width: usize, height: usize, count: usize) -> Frames {
+        let mut data = Vec::with_capacity(count);
+        let mut seed = 1u32;
+        let mut rand = move || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345) & 0x7FFF_FFFF;
+            seed
+        };
+        let glyphs: Vec<u32> = (0..4096).map(|_| rand()).collect();
+        let mut base = vec![0u8; width * height * 4];
+        for y in 0..height {
+            for x in 0..width {
+                let p = &mut base[(y * width + x) * 4..][..4];
+                // Gradient wallpaper and two windows.
+                let (r, g, b) =
+                    if x > width / 10 && x < width / 2 && y > height / 8 && y < height * 3 / 4 {
+                        (250, 250, 250)
+                    } else if x > width / 2 + 20
+                        && x < width * 9 / 10
+                        && y > height / 5
+                        && y < height * 7 / 8
+                    {
+                        (40, 44, 52)
+                    } else {
+                        ((x * 255 / width) as u8, (y * 255 / height) as u8, 160)
+                    };
+                p.copy_from_slice(&[b, g, r, 255]);
+            }
+        }
+        for i in 0..count {
+            let mut frame = base.clone();
+            // Typing in the white window, 8x16 glyph cells.
+            let cols = (width * 4 / 10).max(8) / 8;
+            let typed = i * 3;
+            for cell in 0..typed {
+                let (cx, cy) = (
+                    width / 10 + 8 + (cell % cols) * 8,
+                    height / 8 + 8 + (cell / cols) * 16,
+                );
+                let glyph = glyphs[cell % glyphs.len()];
+                for gy in 0..12 {
+                    for gx in 0..6 {
+                        let (x, y) = (cx + gx, cy + gy);
+                        if x < width && y < height * 3 / 4 && glyph >> ((gy * 6 + gx) % 31) & 1 == 1
+                        {
+                            frame[(y * width + x) * 4..][..4].copy_from_slice(&[20, 20, 20, 255]);
+                        }
+                    }
+                }
+            }
+            // Scrolling lines in the dark window.
+            let (x0, x1) = (width / 2 + 40, width * 9 / 10 - 20);
+            for y in height / 5 + 10..height * 7 / 8 - 10 {
+                let line = (y + i * 4) / 16;
+                if (y + i * 4) % 16 < 10 {
+                    let len =
+                        (glyphs[line % glyphs.len()] as usize) % (x1.saturating_sub(x0)).max(1);
+                    for x in x0..(x0 + len).min(x1) {
+                        frame[(y * width + x) * 4..][..4].copy_from_slice(&[180, 200, 120, 255]);
+                    }
+                }
+            }
+            // Cursor.
+            let (mx, my) = ((i * 7) % width.max(1), (i * 5) % height.max(1));
+            for y in my..(my + 16).min(height) {
+                for x in mx..(mx + 12).min(width) {
+                    frame[(y * width + x) * 4..][..4].copy_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+            data.push(frame);
+        }
+        Frames {
+            width,
+            height,
+            data,
+        }
+    }
+
+    fn read(path: &str, width: usize, height: usize, count: usize) -> Frames {
+        let mut file = File::open(path).unwrap();
+        let size = width * height * 4;
+        let mut data = vec![];
+        while data.len() < count {
+            let mut frame = vec![0u8; size];
+            if file.read_exact(&mut frame).is_err() {
+                break;
+            }
+            data.push(frame);
+        }
+        Frames {
+            width,
+            height,
+            data,
+        }
+    }
+
+    fn capture(count: usize) -> Frames {
+        let mut displays = Display::all().unwrap();
+        let index = displays.iter().position(|d| d.is_primary()).unwrap_or(0);
+        let mut c = scrap::Capturer::new(displays.remove(index)).unwrap();
+        let (width, height) = (c.width(), c.height());
+        let mut data = vec![];
+        let start = Instant::now();
+        while data.len() < count && start.elapsed() < Duration::from_secs(count as u64) {
+            match c.frame(Duration::from_millis(30)) {
+                Ok(Frame::PixelBuffer(pb)) => {
+                    if pb.pixfmt() != Pixfmt::BGRA {
+                        println!("unsupported capture pixfmt: {:?}", pb.pixfmt());
+                        break;
+                    }
+                    let stride = pb.stride()[0];
+                    let mut frame = Vec::with_capacity(width * height * 4);
+                    for y in 0..height {
+                        frame.extend_from_slice(&pb.data()[y * stride..][..width * 4]);
+                    }
+                    data.push(frame);
+                    print!("\rcapture {}/{}", data.len(), count);
+                    std::io::stdout().flush().ok();
+                }
+                Ok(_) => {
+                    println!("texture capture is not supported");
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => log::error!("{e:?}"),
+            }
+        }
+        println!();
+        Frames {
+            width,
+            height,
+            data,
+        }
+    }
+}
@@ -105,6 +105,8 @@ fn main() -> io::Result<()> {
             quality,
             codec: vpx_codec,
             keyframe_interval: None,
+            tuning: Default::default(),
+            hdr: false,
         }),
         false,
     )
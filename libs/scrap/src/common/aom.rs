@@ -6,9 +6,10 @@
 
 include!(concat!(env!("OUT_DIR"), "/aom_ffi.rs"));
 
-use crate::codec::{base_bitrate, codec_thread_num};
-use crate::{codec::EncoderApi, EncodeFrame, STRIDE_ALIGN};
+use crate::codec::{base_bitrate, codec_thread_num, EncoderTuning};
+use crate::{codec::EncoderApi, dirty::Roi, EncodeFrame, STRIDE_ALIGN};
 use crate::{common::GoogleImage, generate_call_macro, generate_call_ptr_macro, Error, Result};
+use crate::hdr::{HighBitDepth, Primaries, Transfer};
 use crate::{EncodeInput, EncodeYuvFormat, Pixfmt};
 use hbb_common::{
     anyhow::{anyhow, Context},
@@ -47,6 +48,8 @@ pub struct AomEncoderConfig {
     pub height: u32,
     pub quality: f32,
     pub keyframe_interval: Option<usize>,
+    pub tuning: EncoderTuning,
+    pub hdr: bool, // 10-bit HDR10, 4:2:0 only
 }
 
 pub struct AomEncoder {
@@ -54,7 +57,9 @@ pub struct AomEncoder {
     width: usize,
     height: usize,
     i444: bool,
+    hdr: bool,
     yuvfmt: EncodeYuvFormat,
+    tuning: EncoderTuning,
 }
 
 // https://webrtc.googlesource.com/src/+/refs/heads/main/modules/video_coding/codecs/av1/libaom_av1_encoder.cc
@@ -63,6 +68,7 @@ mod webrtc {
 
     const kUsageProfile: u32 = AOM_USAGE_REALTIME;
     const kBitDepth: u32 = 8;
+    const kHdrBitDepth: u32 = 10;
     const kLagInFrames: u32 = 0; // No look ahead.
     pub(super) const kTimeBaseDen: i64 = 1000;
 
@@ -100,10 +106,15 @@ mod webrtc {
         // Overwrite default config with input encoder settings & RTC-relevant values.
         c.g_w = cfg.width;
         c.g_h = cfg.height;
-        c.g_threads = codec_thread_num(64) as _;
+        c.g_threads = cfg.tuning.thread_num(64) as _;
         c.g_timebase.num = 1;
         c.g_timebase.den = kTimeBaseDen as _;
-        c.g_input_bit_depth = kBitDepth;
+        if cfg.hdr {
+            c.g_bit_depth = aom_bit_depth::AOM_BITS_10;
+            c.g_input_bit_depth = kHdrBitDepth;
+        } else {
+            c.g_input_bit_depth = kBitDepth;
+        }
         if let Some(keyframe_interval) = cfg.keyframe_interval {
             c.kf_min_dist = 0;
             c.kf_max_dist = keyframe_interval as _;
@@ -113,7 +124,11 @@ mod webrtc {
         let (q_min, q_max) = AomEncoder::calc_q_values(cfg.quality);
         c.rc_min_quantizer = q_min;
         c.rc_max_quantizer = q_max;
-        c.rc_target_bitrate = AomEncoder::bitrate(cfg.width as _, cfg.height as _, cfg.quality);
+        c.rc_target_bitrate = cfg.tuning.clamp_bitrate(AomEncoder::bitrate(
+            cfg.width as _,
+            cfg.height as _,
+            cfg.quality,
+        ));
         c.rc_undershoot_pct = 50;
         c.rc_overshoot_pct = 50;
         c.rc_buf_initial_sz = 600;
@@ -127,12 +142,17 @@ mod webrtc {
         c.g_lag_in_frames = kLagInFrames; // No look ahead when lag equals 0.
 
         // https://aomedia.googlesource.com/aom/+/refs/tags/v3.6.0/av1/common/enums.h#82
-        c.g_profile = if i444 { 1 } else { 0 };
+        // The main profile supports 10-bit 4:2:0
+        c.g_profile = if i444 && !cfg.hdr { 1 } else { 0 };
 
         Ok(c)
     }
 
-    pub fn set_controls(ctx: *mut aom_codec_ctx_t, cfg: &aom_codec_enc_cfg) -> ResultType<()> {
+    pub fn set_controls(
+        ctx: *mut aom_codec_ctx_t,
+        cfg: &aom_codec_enc_cfg,
+        tuning: &EncoderTuning,
+    ) -> ResultType<()> {
         use aom_tune_content::*;
         use aome_enc_control_id::*;
         macro_rules! call_ctl {
@@ -141,7 +161,11 @@ mod webrtc {
             }};
         }
 
-        call_ctl!(ctx, AOME_SET_CPUUSED, get_cpu_speed(cfg.g_w, cfg.g_h));
+        call_ctl!(
+            ctx,
+            AOME_SET_CPUUSED,
+            tuning.speed(get_cpu_speed(cfg.g_w, cfg.g_h) as _, 10)
+        );
         call_ctl!(ctx, AV1E_SET_ENABLE_CDEF, 1);
         call_ctl!(ctx, AV1E_SET_ENABLE_TPL_MODEL, 0);
         call_ctl!(ctx, AV1E_SET_DELTAQ_MODE, 0);
@@ -194,6 +218,29 @@ mod webrtc {
         call_ctl!(ctx, AV1E_SET_ENABLE_SMOOTH_INTERINTRA, 0);
         call_ctl!(ctx, AV1E_SET_ENABLE_TX64, 0);
         call_ctl!(ctx, AV1E_SET_MAX_REFERENCE_FRAMES, 3);
+        if cfg.g_bit_depth == aom_bit_depth::AOM_BITS_10 {
+            // HDR10, see hdr.rs
+            call_ctl!(
+                ctx,
+                AV1E_SET_COLOR_PRIMARIES,
+                aom_color_primaries::AOM_CICP_CP_BT_2020 as i32
+            );
+            call_ctl!(
+                ctx,
+                AV1E_SET_TRANSFER_CHARACTERISTICS,
+                aom_transfer_characteristics::AOM_CICP_TC_SMPTE_2084 as i32
+            );
+            call_ctl!(
+                ctx,
+                AV1E_SET_MATRIX_COEFFICIENTS,
+                aom_matrix_coefficients::AOM_CICP_MC_BT_2020_NCL as i32
+            );
+            call_ctl!(
+                ctx,
+                AV1E_SET_COLOR_RANGE,
+                aom_color_range::AOM_CR_STUDIO_RANGE as i32
+            );
+        }
 
         Ok(())
     }
@@ -206,12 +253,17 @@ impl EncoderApi for AomEncoder {
     {
         match cfg {
             crate::codec::EncoderCfg::AOM(config) => {
+                let i444 = i444 && !config.hdr;
                 let i = call_aom_ptr!(aom_codec_av1_cx());
                 let c = webrtc::enc_cfg(i, config, i444)?;
 
                 let mut ctx = Default::default();
                 // Flag options: AOM_CODEC_USE_PSNR and AOM_CODEC_USE_HIGHBITDEPTH
-                let flags: aom_codec_flags_t = 0;
+                let flags: aom_codec_flags_t = if config.hdr {
+                    AOM_CODEC_USE_HIGHBITDEPTH as _
+                } else {
+                    0
+                };
                 call_aom!(aom_codec_enc_init_ver(
                     &mut ctx,
                     i,
@@ -219,13 +271,15 @@ impl EncoderApi for AomEncoder {
                     flags,
                     AOM_ENCODER_ABI_VERSION as _
                 ));
-                webrtc::set_controls(&mut ctx, &c)?;
+                webrtc::set_controls(&mut ctx, &c, &config.tuning)?;
                 Ok(Self {
                     ctx,
                     width: config.width as _,
                     height: config.height as _,
                     i444,
-                    yuvfmt: Self::get_yuvfmt(config.width, config.height, i444),
+                    hdr: config.hdr,
+                    yuvfmt: Self::get_yuvfmt(config.width, config.height, i444, config.hdr),
+                    tuning: config.tuning,
                 })
             }
             _ => Err(anyhow!("encoder type mismatch")),
@@ -261,7 +315,9 @@ impl EncoderApi for AomEncoder {
         let (q_min, q_max) = Self::calc_q_values(ratio);
         c.rc_min_quantizer = q_min;
         c.rc_max_quantizer = q_max;
-        c.rc_target_bitrate = Self::bitrate(self.width as _, self.height as _, ratio);
+        c.rc_target_bitrate = self
+            .tuning
+            .clamp_bitrate(Self::bitrate(self.width as _, self.height as _, ratio));
         call_aom!(aom_codec_enc_config_set(&mut self.ctx, &c));
         Ok(())
     }
@@ -284,19 +340,43 @@ impl EncoderApi for AomEncoder {
     }
 
     fn disable(&self) {}
+
+    fn support_roi(&self) -> bool {
+        true
+    }
+
+    // Only the active map for now, the focus is not used.
+    fn set_roi(&mut self, roi: Option<&Roi>) -> ResultType<()> {
+        // The active map is in 16x16 blocks, null to disable it.
+        let (mut active, rows, cols) = match roi {
+            Some(roi) if !roi.active.is_full() => roi.active.blocks(16),
+            _ => (vec![], (self.height + 15) / 16, (self.width + 15) / 16),
+        };
+        let mut map = aom_active_map_t {
+            active_map: if active.is_empty() {
+                ptr::null_mut()
+            } else {
+                active.as_mut_ptr()
+            },
+            rows: rows as _,
+            cols: cols as _,
+        };
+        call_aom!(aom_codec_control(
+            &mut self.ctx,
+            aome_enc_control_id::AOME_SET_ACTIVEMAP as i32,
+            &mut map as *mut aom_active_map_t
+        ));
+        Ok(())
+    }
 }
 
 impl AomEncoder {
     pub fn encode<'a>(&'a mut self, ms: i64, data: &[u8], stride_align: usize) -> Result<EncodeFrames<'a>> {
-        let bpp = if self.i444 { 24 } else { 12 };
+        let bpp = if self.i444 || self.hdr { 24 } else { 12 };
         if data.len() < self.width * self.height * bpp / 8 {
             return Err(Error::FailedCall("len not enough".to_string()));
         }
-        let fmt = if self.i444 {
-            aom_img_fmt::AOM_IMG_FMT_I444
-        } else {
-            aom_img_fmt::AOM_IMG_FMT_I420
-        };
+        let fmt = Self::img_fmt(self.i444, self.hdr);
 
         let mut image = Default::default();
         call_aom_ptr!(aom_img_wrap(
@@ -307,6 +387,9 @@ impl AomEncoder {
             stride_align as _,
             data.as_ptr() as _,
         ));
+        if self.hdr {
+            image.bit_depth = 10;
+        }
         let pts = webrtc::kTimeBaseDen / 1000 * ms;
         let duration = webrtc::kTimeBaseDen / 1000;
         call_aom!(aom_codec_encode(
@@ -369,13 +452,19 @@ impl AomEncoder {
         (q_min, q_max)
     }
 
-    fn get_yuvfmt(width: u32, height: u32, i444: bool) -> EncodeYuvFormat {
-        let mut img = Default::default();
-        let fmt = if i444 {
+    fn img_fmt(i444: bool, hdr: bool) -> aom_img_fmt_t {
+        if hdr {
+            aom_img_fmt::AOM_IMG_FMT_I42016
+        } else if i444 {
             aom_img_fmt::AOM_IMG_FMT_I444
         } else {
             aom_img_fmt::AOM_IMG_FMT_I420
-        };
+        }
+    }
+
+    fn get_yuvfmt(width: u32, height: u32, i444: bool, hdr: bool) -> EncodeYuvFormat {
+        let mut img = Default::default();
+        let fmt = Self::img_fmt(i444, hdr);
         unsafe {
             aom_img_wrap(
                 &mut img,
@@ -386,7 +475,13 @@ impl AomEncoder {
                 0x1 as _,
             );
         }
-        let pixfmt = if i444 { Pixfmt::I444 } else { Pixfmt::I420 };
+        let pixfmt = if hdr {
+            Pixfmt::I010
+        } else if i444 {
+            Pixfmt::I444
+        } else {
+            Pixfmt::I420
+        };
         EncodeYuvFormat {
             pixfmt,
             w: img.w as _,
@@ -564,10 +659,30 @@ impl GoogleImage for Image {
 
     fn chroma(&self) -> Chroma {
         match self.inner().fmt {
-            aom_img_fmt::AOM_IMG_FMT_I444 => Chroma::I444,
+            aom_img_fmt::AOM_IMG_FMT_I444 | aom_img_fmt::AOM_IMG_FMT_I44416 => Chroma::I444,
             _ => Chroma::I420,
         }
     }
+
+    fn high_bitdepth(&self) -> Option<HighBitDepth> {
+        let img = self.inner();
+        if img.fmt as u32 & AOM_IMG_FMT_HIGHBITDEPTH == 0 {
+            return None;
+        }
+        Some(HighBitDepth {
+            bit_depth: img.bit_depth as _,
+            primaries: if img.cp == aom_color_primaries::AOM_CICP_CP_BT_2020 {
+                Primaries::Bt2020
+            } else {
+                Primaries::Bt709
+            },
+            transfer: if img.tc == aom_transfer_characteristics::AOM_CICP_TC_SMPTE_2084 {
+                Transfer::Pq
+            } else {
+                Transfer::Sdr
+            },
+        })
+    }
 }
 
 impl Drop for Image {
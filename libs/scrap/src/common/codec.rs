@@ -9,11 +9,15 @@ use std::{
 use crate::hwcodec::*;
 #[cfg(feature = "mediacodec")]
 use crate::mediacodec::{MediaCodecDecoder, H264_DECODER_SUPPORT, H265_DECODER_SUPPORT};
+#[cfg(feature = "openh264")]
+use crate::openh264::{OpenH264Decoder, OpenH264Encoder, OpenH264EncoderConfig};
 #[cfg(feature = "vram")]
 use crate::vram::*;
 use crate::{
     aom::{self, AomDecoder, AomEncoder, AomEncoderConfig},
     common::GoogleImage,
+    dirty::Roi,
+    hdr::VideoColor,
     vpxcodec::{self, VpxDecoder, VpxDecoderConfig, VpxEncoder, VpxEncoderConfig, VpxVideoCodecId},
     CodecFormat, EncodeInput, EncodeYuvFormat, ImageRgb, ImageTexture,
 };
@@ -34,6 +38,8 @@ use hbb_common::{
         supported_decoding::PreferCodec, video_frame, Chroma, CodecAbility, EncodedVideoFrames,
         SupportedDecoding, SupportedEncoding, VideoFrame,
     },
+    serde_derive::Deserialize,
+    serde_json,
     sysinfo::System,
     ResultType,
 };
@@ -55,6 +61,8 @@ pub enum EncoderCfg {
     HWRAM(HwRamEncoderConfig),
     #[cfg(feature = "vram")]
     VRAM(VRamEncoderConfig),
+    #[cfg(feature = "openh264")]
+    OpenH264(OpenH264EncoderConfig),
 }
 
 pub trait EncoderApi {
@@ -80,6 +88,15 @@ pub trait EncoderApi {
     fn is_hardware(&self) -> bool;
 
     fn disable(&self);
+
+    fn support_roi(&self) -> bool {
+        false
+    }
+
+    // None to encode the whole frame.
+    fn set_roi(&mut self, _roi: Option<&Roi>) -> ResultType<()> {
+        Ok(())
+    }
 }
 
 pub struct Encoder {
@@ -116,10 +133,14 @@ pub struct Decoder {
     h264_media_codec: MediaCodecDecoder,
     #[cfg(feature = "mediacodec")]
     h265_media_codec: MediaCodecDecoder,
+    #[cfg(feature = "openh264")]
+    h264_openh264: Option<OpenH264Decoder>,
     format: CodecFormat,
     valid: bool,
     #[cfg(feature = "hwcodec")]
     i420: Vec<u8>,
+    // The color sent by the server, for the 10-bit VP9 frames
+    video_color: Option<VideoColor>,
 }
 
 #[derive(Debug, Clone)]
@@ -165,6 +186,17 @@ impl Encoder {
                     Err(e)
                 }
             },
+            #[cfg(feature = "openh264")]
+            EncoderCfg::OpenH264(_) => match OpenH264Encoder::new(config, i444) {
+                Ok(sw) => Ok(Encoder {
+                    codec: Box::new(sw),
+                }),
+                Err(e) => {
+                    log::error!("new openh264 encoder failed: {e:?}");
+                    *ENCODE_CODEC_FORMAT.lock().unwrap() = CodecFormat::VP9;
+                    Err(e)
+                }
+            },
         }
     }
 
@@ -230,8 +262,14 @@ impl Encoder {
                     HwRamEncoder::try_get(CodecFormat::H265).map_or(None, |c| Some(c.name));
             }
         }
-        let h264_useable =
+        // The software H264 is only used if preferred, it's not better than VP9 when auto.
+        #[cfg(feature = "openh264")]
+        let h264sw_encoding = crate::openh264::available();
+        #[cfg(not(feature = "openh264"))]
+        let h264sw_encoding = false;
+        let h264hw_useable =
             _all_support_h264_decoding && (h264vram_encoding || h264hw_encoding.is_some());
+        let h264_useable = h264hw_useable || _all_support_h264_decoding && h264sw_encoding;
         let h265_useable =
             _all_support_h265_decoding && (h265vram_encoding || h265hw_encoding.is_some());
         let mut format = ENCODE_CODEC_FORMAT.lock().unwrap();
@@ -275,7 +313,7 @@ impl Encoder {
         } else {
             CodecFormat::VP9
         };
-        if h264_useable {
+        if h264hw_useable {
             auto_codec = CodecFormat::H264;
         }
         if h265_useable {
@@ -295,7 +333,7 @@ impl Encoder {
             PreferCodec::VP9 => CodecFormat::VP9,
             PreferCodec::AV1 => CodecFormat::AV1,
             PreferCodec::H264 => {
-                if h264vram_encoding || h264hw_encoding.is_some() {
+                if h264vram_encoding || h264hw_encoding.is_some() || h264sw_encoding {
                     CodecFormat::H264
                 } else {
                     auto_codec
@@ -351,6 +389,10 @@ impl Encoder {
             encoding.h264 |= VRamEncoder::available(CodecFormat::H264).len() > 0;
             encoding.h265 |= VRamEncoder::available(CodecFormat::H265).len() > 0;
         }
+        #[cfg(feature = "openh264")]
+        {
+            encoding.h264 |= crate::openh264::available();
+        }
         encoding
     }
 
@@ -392,6 +434,8 @@ impl Encoder {
                     return;
                 }
             },
+            #[cfg(feature = "openh264")]
+            EncoderCfg::OpenH264(_) => CodecFormat::H264,
         };
         let current = ENCODE_CODEC_FORMAT.lock().unwrap().clone();
         if current != format {
@@ -415,6 +459,8 @@ impl Encoder {
             EncoderCfg::HWRAM(_) => false,
             #[cfg(feature = "vram")]
             EncoderCfg::VRAM(_) => false,
+            #[cfg(feature = "openh264")]
+            EncoderCfg::OpenH264(_) => false,
         };
         prefer_i444 && i444_useable && !decodings.is_empty()
     }
@@ -442,6 +488,10 @@ impl Decoder {
             .into(),
             prefer: prefer.into(),
             prefer_chroma: prefer_chroma.into(),
+            // The frames encoded at a lower resolution are scaled up to `VideoFrame.original_size`.
+            scaled_video: true,
+            // The 10-bit frames are tone mapped on the SDR displays.
+            hdr: true,
             ..Default::default()
         };
         #[cfg(feature = "hwcodec")]
@@ -485,6 +535,10 @@ impl Decoder {
                     0
                 };
         }
+        #[cfg(feature = "openh264")]
+        if crate::openh264::available() {
+            decoding.ability_h264 = 1;
+        }
         for unsupported in mark_unsupported {
             match unsupported {
                 CodecFormat::VP8 => decoding.ability_vp8 = 0,
@@ -507,6 +561,8 @@ impl Decoder {
         let (mut h264_vram, mut h265_vram) = (None, None);
         #[cfg(feature = "mediacodec")]
         let (mut h264_media_codec, mut h265_media_codec) = (None, None);
+        #[cfg(feature = "openh264")]
+        let mut h264_openh264 = None;
         let mut valid = false;
 
         match format {
@@ -560,6 +616,14 @@ impl Decoder {
                     }
                     valid = h264_media_codec.is_some();
                 }
+                #[cfg(feature = "openh264")]
+                if !valid {
+                    match OpenH264Decoder::new(format) {
+                        Ok(v) => h264_openh264 = Some(v),
+                        Err(e) => log::error!("create H264 openh264 decoder failed: {}", e),
+                    }
+                    valid = h264_openh264.is_some();
+                }
             }
             CodecFormat::H265 => {
                 #[cfg(feature = "vram")]
@@ -612,10 +676,13 @@ impl Decoder {
             h264_media_codec,
             #[cfg(feature = "mediacodec")]
             h265_media_codec,
+            #[cfg(feature = "openh264")]
+            h264_openh264,
             format,
             valid,
             #[cfg(feature = "hwcodec")]
             i420: vec![],
+            video_color: None,
         }
     }
 
@@ -623,6 +690,14 @@ impl Decoder {
         self.format
     }
 
+    pub fn video_color(&self) -> Option<VideoColor> {
+        self.video_color
+    }
+
+    pub fn set_video_color(&mut self, video_color: Option<VideoColor>) {
+        self.video_color = video_color;
+    }
+
     pub fn valid(&self) -> bool {
         self.valid
     }
@@ -639,14 +714,14 @@ impl Decoder {
         match frame {
             video_frame::Union::Vp8s(vp8s) => {
                 if let Some(vp8) = &mut self.vp8 {
-                    Decoder::handle_vpxs_video_frame(vp8, vp8s, rgb, chroma)
+                    Decoder::handle_vpxs_video_frame(vp8, vp8s, rgb, chroma, None)
                 } else {
                     bail!("vp8 decoder not available");
                 }
             }
             video_frame::Union::Vp9s(vp9s) => {
                 if let Some(vp9) = &mut self.vp9 {
-                    Decoder::handle_vpxs_video_frame(vp9, vp9s, rgb, chroma)
+                    Decoder::handle_vpxs_video_frame(vp9, vp9s, rgb, chroma, self.video_color)
                 } else {
                     bail!("vp9 decoder not available");
                 }
@@ -658,7 +733,7 @@ impl Decoder {
                     bail!("av1 decoder not available");
                 }
             }
-            #[cfg(any(feature = "hwcodec", feature = "vram"))]
+            #[cfg(any(feature = "hwcodec", feature = "vram", feature = "openh264"))]
             video_frame::Union::H264s(h264s) => {
                 *chroma = Some(Chroma::I420);
                 #[cfg(feature = "vram")]
@@ -670,6 +745,10 @@ impl Decoder {
                 if let Some(decoder) = &mut self.h264_ram {
                     return Decoder::handle_hwram_video_frame(decoder, h264s, rgb, &mut self.i420);
                 }
+                #[cfg(feature = "openh264")]
+                if let Some(decoder) = &mut self.h264_openh264 {
+                    return Decoder::handle_openh264_video_frame(decoder, h264s, rgb);
+                }
                 Err(anyhow!("don't support h264!"))
             }
             #[cfg(any(feature = "hwcodec", feature = "vram"))]
@@ -714,6 +793,7 @@ impl Decoder {
         vpxs: &EncodedVideoFrames,
         rgb: &mut ImageRgb,
         chroma: &mut Option<Chroma>,
+        video_color: Option<VideoColor>,
     ) -> ResultType<bool> {
         let mut last_frame = vpxcodec::Image::new();
         for vpx in vpxs.frames.iter() {
@@ -730,7 +810,7 @@ impl Decoder {
             Ok(false)
         } else {
             *chroma = Some(last_frame.chroma());
-            last_frame.to(rgb);
+            last_frame.to_with_color(rgb, video_color);
             Ok(true)
         }
     }
@@ -802,6 +882,23 @@ impl Decoder {
         return Ok(ret);
     }
 
+    // rgb [in/out] fmt and stride must be set in ImageRgb
+    #[cfg(feature = "openh264")]
+    fn handle_openh264_video_frame(
+        decoder: &mut OpenH264Decoder,
+        frames: &EncodedVideoFrames,
+        rgb: &mut ImageRgb,
+    ) -> ResultType<bool> {
+        let mut ret = false;
+        for h264 in frames.frames.iter() {
+            if let Some(image) = decoder.decode(&h264.data)? {
+                image.to(rgb);
+                ret = true;
+            }
+        }
+        Ok(ret)
+    }
+
     // rgb [in/out] fmt and stride must be set in ImageRgb
     #[cfg(feature = "mediacodec")]
     fn handle_mediacodec_video_frame(
@@ -884,6 +981,85 @@ pub fn enable_directx_capture() -> bool {
     option2bool(OPTION, &Config::get_option(OPTION))
 }
 
+// Skip the static blocks and prefer the cursor area, for the encoders supporting it.
+pub const OPTION_ENABLE_ROI_ENCODING: &str = "enable-roi-encoding";
+
+pub fn enable_roi_encoding() -> bool {
+    Config::get_option(OPTION_ENABLE_ROI_ENCODING) != "N"
+}
+
+// Json value of option `encoder-tuning`, caps the cost of the software encoders per codec, e.g.
+// {
+//   "vp9": { "speed": 8, "keyframe_interval": 300, "max_threads": 2, "min_kbps": 300, "max_kbps": 3000 },
+//   "av1": { "speed": 10, "max_threads": 2 }
+// }
+// Codec names are "vp8", "vp9", "av1", "h264" and "h265". The unset fields use the defaults.
+// "speed" is cpu-used, VP8: 0-16, VP9: 0-9, AV1: 0-10, higher is faster with lower quality.
+// "speed", "max_threads" and the bitrate bounds are applied to libvpx, libaom and OpenH264 except
+// that OpenH264 has no speed and threads setting.
+// "keyframe_interval" is in frames and also applied to the hardware encoders, no periodic
+// keyframes by default except recording.
+pub const OPTION_ENCODER_TUNING: &str = "encoder-tuning";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct EncoderTuning {
+    pub speed: Option<i32>,
+    pub keyframe_interval: Option<usize>,
+    pub max_threads: Option<usize>,
+    pub min_kbps: Option<u32>,
+    pub max_kbps: Option<u32>,
+}
+
+impl EncoderTuning {
+    pub fn get(format: CodecFormat) -> Self {
+        Self::parse(&Config::get_option(OPTION_ENCODER_TUNING), format)
+    }
+
+    pub fn parse(s: &str, format: CodecFormat) -> Self {
+        if s.trim().is_empty() {
+            return Self::default();
+        }
+        let name = match format {
+            CodecFormat::VP8 => "vp8",
+            CodecFormat::VP9 => "vp9",
+            CodecFormat::AV1 => "av1",
+            CodecFormat::H264 => "h264",
+            CodecFormat::H265 => "h265",
+            CodecFormat::Unknown => return Self::default(),
+        };
+        match serde_json::from_str::<HashMap<String, Self>>(s) {
+            Ok(mut tunings) => tunings.remove(name).unwrap_or_default(),
+            Err(e) => {
+                log::error!("Failed to parse encoder tuning: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    #[inline]
+    pub fn speed(&self, default: i32, max: i32) -> i32 {
+        self.speed.map_or(default, |s| s.clamp(0, max))
+    }
+
+    #[inline]
+    pub fn thread_num(&self, limit: usize) -> usize {
+        codec_thread_num(self.max_threads.map_or(limit, |t| t.clamp(1, limit)))
+    }
+
+    // kbps
+    pub fn clamp_bitrate(&self, bitrate: u32) -> u32 {
+        let mut bitrate = bitrate;
+        if let Some(max) = self.max_kbps.filter(|v| *v > 0) {
+            bitrate = bitrate.min(max);
+        }
+        if let Some(min) = self.min_kbps {
+            bitrate = bitrate.max(min);
+        }
+        bitrate
+    }
+}
+
 #[cfg(windows)]
 pub fn allow_d3d_render() -> bool {
     use hbb_common::config::keys::OPTION_ALLOW_D3D_RENDER as OPTION;
@@ -1107,6 +1283,8 @@ pub fn test_av1() {
                     height,
                     quality,
                     keyframe_interval,
+                    tuning: Default::default(),
+                    hdr: false,
                 }),
                 i444,
             ) else {
@@ -1155,3 +1333,27 @@ pub fn test_av1() {
         });
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoder_tuning() {
+        let s = r#"{"vp9": {"speed": 12, "max_threads": 2, "min_kbps": 300, "max_kbps": 3000}}"#;
+        let tuning = EncoderTuning::parse(s, CodecFormat::VP9);
+        assert_eq!(tuning.speed(7, 9), 9);
+        assert_eq!(tuning.keyframe_interval, None);
+        assert_eq!(tuning.clamp_bitrate(100), 300);
+        assert_eq!(tuning.clamp_bitrate(1000), 1000);
+        assert_eq!(tuning.clamp_bitrate(5000), 3000);
+        let tuning = EncoderTuning::parse(s, CodecFormat::AV1);
+        assert_eq!(tuning, EncoderTuning::default());
+        assert_eq!(tuning.speed(10, 10), 10);
+        assert_eq!(tuning.clamp_bitrate(5000), 5000);
+        assert_eq!(
+            EncoderTuning::parse("{\"vp9\": 1}", CodecFormat::VP9),
+            EncoderTuning::default()
+        );
+    }
+}
@@ -15,7 +15,7 @@ generate_call_macro!(call_yuv, false);
 
 #[cfg(not(target_os = "ios"))]
 pub fn convert_to_yuv(
-    captured: &PixelBuffer,
+    captured: &impl TraitPixelBuffer,
     dst_fmt: EncodeYuvFormat,
     dst: &mut Vec<u8>,
     mid_data: &mut Vec<u8>,
@@ -35,6 +35,7 @@ pub fn convert_to_yuv(
     if src_pixfmt == crate::Pixfmt::BGRA
         || src_pixfmt == crate::Pixfmt::RGBA
         || src_pixfmt == crate::Pixfmt::RGB565LE
+        || src_pixfmt == crate::Pixfmt::RGBAF16
     {
         // stride is calculated, not real, so we need to check it
         if src_stride[0] < src_width * src_pixfmt.bytes_per_pixel() {
@@ -191,6 +192,11 @@ pub fn convert_to_yuv(
                 src_height as _,
             ));
         }
+        (crate::Pixfmt::RGBAF16, crate::Pixfmt::I010)
+        | (crate::Pixfmt::BGRA, crate::Pixfmt::I010)
+        | (crate::Pixfmt::RGBA, crate::Pixfmt::I010) => {
+            crate::hdr::convert_to_i010(captured, &dst_fmt, dst)?;
+        }
         _ => {
             bail!(unsupported);
         }
@@ -228,6 +234,10 @@ pub fn convert(captured: &PixelBuffer, pixfmt: crate::Pixfmt, dst: &mut Vec<u8>)
                 src_height as _,
             ));
         }
+        (crate::Pixfmt::RGBAF16, crate::Pixfmt::RGBA)
+        | (crate::Pixfmt::RGBAF16, crate::Pixfmt::BGRA) => {
+            crate::hdr::scrgb_to_rgba(captured, pixfmt == crate::Pixfmt::BGRA, dst)?;
+        }
         _ => {
             bail!(unsupported);
         }
@@ -0,0 +1,353 @@
+// Dirty regions of the captured frames.
+// The encoders skip the static blocks, and spend more bits around the cursor and the typing area.
+
+use crate::{Pixfmt, TraitPixelBuffer};
+
+pub const TILE_SIZE: usize = 16;
+// Encode the whole frame periodically, so that the blocks encoded at low quality
+// before they became static are refined.
+const FULL_REFRESH_INTERVAL: usize = 60;
+// Small changes are usually typing or caret blinking.
+const SMALL_DIRTY_RATIO: f32 = 0.02;
+const CURSOR_FOCUS_RADIUS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+// Dirty tiles of `TILE_SIZE` pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirtyMap {
+    width: usize,
+    height: usize,
+    cols: usize,
+    rows: usize,
+    tiles: Vec<bool>,
+}
+
+impl DirtyMap {
+    pub fn new(width: usize, height: usize) -> Self {
+        let cols = (width + TILE_SIZE - 1) / TILE_SIZE;
+        let rows = (height + TILE_SIZE - 1) / TILE_SIZE;
+        Self {
+            width,
+            height,
+            cols,
+            rows,
+            tiles: vec![false; cols * rows],
+        }
+    }
+
+    pub fn full(width: usize, height: usize) -> Self {
+        let mut map = Self::new(width, height);
+        map.tiles.fill(true);
+        map
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn mark(&mut self, rect: &DirtyRect) {
+        let x1 = (rect.x + rect.w).min(self.width);
+        let y1 = (rect.y + rect.h).min(self.height);
+        if rect.x >= x1 || rect.y >= y1 {
+            return;
+        }
+        for row in rect.y / TILE_SIZE..=(y1 - 1) / TILE_SIZE {
+            for col in rect.x / TILE_SIZE..=(x1 - 1) / TILE_SIZE {
+                self.tiles[row * self.cols + col] = true;
+            }
+        }
+    }
+
+    // The whole frame is dirty if the sizes are different.
+    pub fn merge(&mut self, other: &DirtyMap) {
+        if self.width != other.width || self.height != other.height {
+            *self = Self::full(other.width, other.height);
+            return;
+        }
+        for (a, b) in self.tiles.iter_mut().zip(other.tiles.iter()) {
+            *a |= *b;
+        }
+    }
+
+    pub fn is_rect_dirty(&self, rect: &DirtyRect) -> bool {
+        let x1 = (rect.x + rect.w).min(self.width);
+        let y1 = (rect.y + rect.h).min(self.height);
+        if rect.x >= x1 || rect.y >= y1 {
+            return false;
+        }
+        (rect.y / TILE_SIZE..=(y1 - 1) / TILE_SIZE).any(|row| {
+            (rect.x / TILE_SIZE..=(x1 - 1) / TILE_SIZE).any(|col| self.tiles[row * self.cols + col])
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.tiles.iter().filter(|x| **x).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.tiles.iter().any(|x| *x)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.tiles.iter().all(|x| *x)
+    }
+
+    pub fn ratio(&self) -> f32 {
+        if self.tiles.is_empty() {
+            return 0.0;
+        }
+        self.count() as f32 / self.tiles.len() as f32
+    }
+
+    // The map in blocks of `block_size` pixels, row major, 1 for dirty.
+    // Returns (map, rows, cols).
+    pub fn blocks(&self, block_size: usize) -> (Vec<u8>, usize, usize) {
+        let cols = (self.width + block_size - 1) / block_size;
+        let rows = (self.height + block_size - 1) / block_size;
+        let mut map = vec![0u8; cols * rows];
+        for row in 0..rows {
+            let y0 = row * block_size / TILE_SIZE;
+            let y1 = (((row + 1) * block_size).min(self.height) - 1) / TILE_SIZE;
+            for col in 0..cols {
+                let x0 = col * block_size / TILE_SIZE;
+                let x1 = (((col + 1) * block_size).min(self.width) - 1) / TILE_SIZE;
+                let dirty = (y0..=y1).any(|y| (x0..=x1).any(|x| self.tiles[y * self.cols + x]));
+                map[row * cols + col] = dirty as u8;
+            }
+        }
+        (map, rows, cols)
+    }
+}
+
+// Compare the tiles with the previous frame, for the capturers which do not report the changes.
+#[derive(Default)]
+pub struct TileDiffer {
+    prev: Vec<u8>,
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl TileDiffer {
+    pub fn diff(
+        &mut self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        bytes_per_pixel: usize,
+    ) -> DirtyMap {
+        let mut map = DirtyMap::new(width, height);
+        if self.prev.len() != data.len()
+            || self.width != width
+            || self.height != height
+            || self.stride != stride
+            || data.len() < stride * height
+        {
+            map.tiles.fill(true);
+        } else {
+            for y in 0..height {
+                let row = y / TILE_SIZE;
+                let offset = y * stride;
+                for col in 0..map.cols {
+                    let i = row * map.cols + col;
+                    if map.tiles[i] {
+                        continue;
+                    }
+                    let x0 = offset + col * TILE_SIZE * bytes_per_pixel;
+                    let x1 = offset + ((col + 1) * TILE_SIZE).min(width) * bytes_per_pixel;
+                    if data[x0..x1] != self.prev[x0..x1] {
+                        map.tiles[i] = true;
+                    }
+                }
+            }
+        }
+        self.prev.resize(data.len(), 0);
+        self.prev.copy_from_slice(data);
+        self.width = width;
+        self.height = height;
+        self.stride = stride;
+        map
+    }
+
+    pub fn reset(&mut self) {
+        if !self.prev.is_empty() {
+            *self = Default::default();
+        }
+    }
+}
+
+// The hints passed to the encoders.
+#[derive(Debug, Clone)]
+pub struct Roi {
+    // The blocks to encode, the others are copied from the previous frame.
+    pub active: DirtyMap,
+    // The blocks to encode at higher quality.
+    pub focus: Option<DirtyMap>,
+}
+
+// The changes of the captured frames, reported by the capturer or compared with the previous frame.
+#[derive(Default)]
+pub struct DirtyTracker {
+    differ: TileDiffer,
+}
+
+impl DirtyTracker {
+    pub fn update<P: TraitPixelBuffer>(&mut self, buf: &P) -> DirtyMap {
+        let (width, height) = (buf.width(), buf.height());
+        match buf.dirty_rects() {
+            Some(rects) => {
+                self.differ.reset();
+                let mut map = DirtyMap::new(width, height);
+                for rect in rects.iter() {
+                    map.mark(rect);
+                }
+                map
+            }
+            None => match buf.pixfmt() {
+                Pixfmt::BGRA | Pixfmt::RGBA | Pixfmt::RGB565LE | Pixfmt::RGBAF16 => {
+                    self.differ.diff(
+                        buf.data(),
+                        width,
+                        height,
+                        buf.stride().first().copied().unwrap_or_default(),
+                        buf.pixfmt().bytes_per_pixel(),
+                    )
+                }
+                _ => DirtyMap::full(width, height),
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RoiTracker {
+    // The changes not encoded yet, e.g. the frame is dropped by the rate control.
+    pending: Option<DirtyMap>,
+    focus: Option<DirtyMap>,
+    frames: usize,
+}
+
+impl RoiTracker {
+    // `cursor` is the cursor position in the frame.
+    pub fn update(&mut self, dirty: &DirtyMap, cursor: Option<(usize, usize)>) {
+        let (width, height) = (dirty.width(), dirty.height());
+        let mut focus = if !dirty.is_empty() && dirty.ratio() < SMALL_DIRTY_RATIO {
+            Some(dirty.clone())
+        } else {
+            None
+        };
+        if let Some((x, y)) = cursor.filter(|(x, y)| *x < width && *y < height) {
+            let rect = DirtyRect {
+                x: x.saturating_sub(CURSOR_FOCUS_RADIUS),
+                y: y.saturating_sub(CURSOR_FOCUS_RADIUS),
+                w: CURSOR_FOCUS_RADIUS * 2,
+                h: CURSOR_FOCUS_RADIUS * 2,
+            };
+            focus
+                .get_or_insert_with(|| DirtyMap::new(width, height))
+                .mark(&rect);
+        }
+        self.focus = focus;
+        match self.pending.as_mut() {
+            Some(pending) => pending.merge(dirty),
+            None => self.pending = Some(dirty.clone()),
+        }
+    }
+
+    // None to encode the whole frame.
+    pub fn roi(&mut self) -> Option<Roi> {
+        self.frames += 1;
+        if self.frames % FULL_REFRESH_INTERVAL == 0 {
+            return None;
+        }
+        let active = self.pending.clone()?;
+        if active.is_full() && self.focus.is_none() {
+            return None;
+        }
+        Some(Roi {
+            active,
+            focus: self.focus.clone(),
+        })
+    }
+
+    pub fn encoded(&mut self, ok: bool) {
+        if ok {
+            self.pending = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_map() {
+        let mut map = DirtyMap::new(100, 40);
+        assert_eq!((map.cols, map.rows), (7, 3));
+        assert!(map.is_empty());
+        map.mark(&DirtyRect {
+            x: 15,
+            y: 0,
+            w: 2,
+            h: 1,
+        });
+        assert_eq!(map.count(), 2);
+        map.mark(&DirtyRect {
+            x: 99,
+            y: 39,
+            w: 100,
+            h: 100,
+        });
+        assert_eq!(map.count(), 3);
+
+        let (blocks, rows, cols) = map.blocks(8);
+        assert_eq!((rows, cols), (5, 13));
+        assert_eq!(&blocks[..4], &[0, 1, 1, 1]);
+        assert_eq!(blocks[rows * cols - 1], 1);
+        let (blocks, rows, cols) = map.blocks(32);
+        assert_eq!((rows, cols), (2, 4));
+        assert_eq!(blocks, vec![1, 0, 0, 0, 0, 0, 0, 1]);
+        assert!(map.is_rect_dirty(&DirtyRect {
+            x: 0,
+            y: 0,
+            w: 20,
+            h: 20,
+        }));
+        assert!(!map.is_rect_dirty(&DirtyRect {
+            x: 32,
+            y: 0,
+            w: 64,
+            h: 40,
+        }));
+
+        map.merge(&DirtyMap::new(10, 10));
+        assert!(map.is_full());
+    }
+
+    #[test]
+    fn test_tile_differ() {
+        let (width, height, bpp) = (40, 20, 4);
+        let stride = width * bpp;
+        let mut data = vec![0u8; stride * height];
+        let mut differ = TileDiffer::default();
+        assert!(differ.diff(&data, width, height, stride, bpp).is_full());
+        assert!(differ.diff(&data, width, height, stride, bpp).is_empty());
+        data[17 * stride + 33 * bpp] = 1;
+        let map = differ.diff(&data, width, height, stride, bpp);
+        assert_eq!(map.count(), 1);
+        assert!(map.tiles[map.cols + 2]);
+    }
+}
@@ -0,0 +1,664 @@
+// 10-bit HDR10 capturing and encoding.
+// The HDR displays are captured in scRGB on Windows, which is linear BT.709 in half float and 1.0 is 80 nits.
+// The frames are converted to I010 (10-bit 4:2:0, BT.2020, limited range) with the PQ transfer, and
+// encoded with VP9 profile 2 or AV1 main.
+// AV1 has the primaries, transfer and matrix in the bitstream, while VP9 has only the color space,
+// so the color is set on the video frames too, used by the client for VP9.
+// The decoded frames are tone mapped to 8-bit sRGB as the clients render SDR, with the SDR white at 203 nits
+// (BT.2408) and the highlights compressed.
+
+use crate::{
+    generate_call_macro, EncodeYuvFormat, GoogleImage, ImageFormat, ImageRgb, Pixfmt,
+    TraitPixelBuffer,
+};
+use hbb_common::{
+    bail, log,
+    message_proto::{self, video_color, Chroma},
+    ResultType,
+};
+use lazy_static::lazy_static;
+
+generate_call_macro!(call_yuv, false);
+
+const SCRGB_WHITE_NITS: f32 = 80.0;
+const SDR_WHITE_NITS: f32 = 203.0;
+const PQ_MAX_NITS: f32 = 10000.0;
+
+// SMPTE ST 2084
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+// Linear primaries conversion, BT.2087
+const BT709_TO_BT2020: [[f32; 3]; 3] = [
+    [0.627404, 0.329283, 0.043313],
+    [0.069097, 0.919541, 0.011362],
+    [0.016391, 0.088013, 0.895595],
+];
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.660491, -0.587641, -0.072850],
+    [-0.124550, 1.132900, -0.008349],
+    [-0.018151, -0.100579, 1.118730],
+];
+
+// (Kr, Kb) of the Y'CbCr matrix
+const BT2020_K: (f32, f32) = (0.2627, 0.0593);
+
+// The start of the highlights compression, relative to the SDR white
+const TONE_MAP_KNEE: f32 = 0.75;
+
+const LUT_MANTISSA_BITS: u32 = 8;
+
+// The fixed point bits of the Y'CbCr coefficients
+const COEF_BITS: u32 = 16;
+
+lazy_static! {
+    // Linear relative to 10000 nits -> 10-bit PQ
+    static ref PQ_OETF: LogLut<u16> =
+        LogLut::new(-30, 0, |y| (pq_oetf(y) * 1023.0).round() as u16);
+    // 10-bit PQ -> linear relative to the SDR white
+    static ref PQ_EOTF: Vec<f32> = (0..1024)
+        .map(|i| pq_eotf(i as f32 / 1023.0) * PQ_MAX_NITS / SDR_WHITE_NITS)
+        .collect();
+    // Half float scRGB -> linear relative to 10000 nits
+    static ref SCRGB_EOTF: Vec<f32> = (0..=u16::MAX)
+        .map(|h| f16_to_f32(h) * SCRGB_WHITE_NITS / PQ_MAX_NITS)
+        .collect();
+    // 8-bit sRGB -> linear relative to 10000 nits, the SDR frames are placed at the SDR white
+    static ref SRGB_EOTF: Vec<f32> = (0..256)
+        .map(|i| srgb_eotf(i as f32 / 255.0) * SDR_WHITE_NITS / PQ_MAX_NITS)
+        .collect();
+    // Linear BT.709 relative to the SDR white -> tone mapped 8-bit sRGB
+    static ref TONE_MAP_SRGB: LogLut<u8> =
+        LogLut::new(-16, 6, |x| (srgb_oetf(tone_map(x)) * 255.0).round() as u8);
+}
+
+// The color primaries, the values are the CICP code points of ITU-T H.273.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Primaries {
+    #[default]
+    Bt709 = 1,
+    Bt2020 = 9,
+}
+
+// The transfer of the 16-bit images, the values are the CICP code points of ITU-T H.273.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    #[default]
+    Sdr = 1,
+    Pq = 16,
+}
+
+// The decoded image is 16-bit with `bit_depth` bits used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighBitDepth {
+    pub bit_depth: usize,
+    pub primaries: Primaries,
+    pub transfer: Transfer,
+}
+
+// The color of the 10-bit frames, set on the video frames as VP9 has no transfer in the bitstream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VideoColor {
+    pub primaries: Primaries,
+    pub transfer: Transfer,
+}
+
+// The unknown values are taken as the default.
+impl From<&message_proto::VideoColor> for VideoColor {
+    fn from(color: &message_proto::VideoColor) -> Self {
+        Self {
+            primaries: match color.primaries.enum_value_or_default() {
+                video_color::Primaries::BT709 => Primaries::Bt709,
+                video_color::Primaries::BT2020 => Primaries::Bt2020,
+            },
+            transfer: match color.transfer.enum_value_or_default() {
+                video_color::Transfer::SDR => Transfer::Sdr,
+                video_color::Transfer::PQ => Transfer::Pq,
+            },
+        }
+    }
+}
+
+impl From<VideoColor> for message_proto::VideoColor {
+    fn from(color: VideoColor) -> Self {
+        Self {
+            primaries: match color.primaries {
+                Primaries::Bt709 => video_color::Primaries::BT709,
+                Primaries::Bt2020 => video_color::Primaries::BT2020,
+            }
+            .into(),
+            transfer: match color.transfer {
+                Transfer::Sdr => video_color::Transfer::SDR,
+                Transfer::Pq => video_color::Transfer::PQ,
+            }
+            .into(),
+            ..Default::default()
+        }
+    }
+}
+
+// Lookup table of a function on [2^min_exp, 2^max_exp), indexed by the exponent and the high mantissa bits,
+// so the relative precision is the same in the whole range. The lower values are mapped to f(0).
+struct LogLut<T> {
+    min: f32,
+    max: f32,
+    min_exp: i32,
+    zero: T,
+    table: Vec<T>,
+}
+
+impl<T: Copy> LogLut<T> {
+    fn new(min_exp: i32, max_exp: i32, f: impl Fn(f32) -> T) -> Self {
+        let n = ((max_exp - min_exp) as usize) << LUT_MANTISSA_BITS;
+        let table = (0..n)
+            .map(|i| {
+                let exp = min_exp + (i >> LUT_MANTISSA_BITS) as i32;
+                let mantissa = (i & ((1 << LUT_MANTISSA_BITS) - 1)) as f32 + 0.5;
+                f(2f32.powi(exp) * (1.0 + mantissa / (1 << LUT_MANTISSA_BITS) as f32))
+            })
+            .collect();
+        Self {
+            min: 2f32.powi(min_exp),
+            max: 2f32.powi(max_exp),
+            min_exp,
+            zero: f(0.0),
+            table,
+        }
+    }
+
+    #[inline]
+    fn get(&self, x: f32) -> T {
+        if x.is_nan() || x < self.min {
+            return self.zero;
+        }
+        if x >= self.max {
+            return self.table[self.table.len() - 1];
+        }
+        let bits = x.to_bits();
+        let exp = ((bits >> 23) & 0xff) as i32 - 127;
+        let mantissa = (bits >> (23 - LUT_MANTISSA_BITS)) & ((1 << LUT_MANTISSA_BITS) - 1);
+        self.table[(((exp - self.min_exp) as usize) << LUT_MANTISSA_BITS) | mantissa as usize]
+    }
+}
+
+fn pq_oetf(y: f32) -> f32 {
+    let p = y.max(0.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * p) / (1.0 + PQ_C3 * p)).powf(PQ_M2)
+}
+
+fn pq_eotf(e: f32) -> f32 {
+    let p = e.clamp(0.0, 1.0).powf(1.0 / PQ_M2);
+    ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1)
+}
+
+fn srgb_oetf(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_eotf(e: f32) -> f32 {
+    if e <= 0.04045 {
+        e / 12.92
+    } else {
+        ((e + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Identity below the knee, the highlights are compressed into (knee, 1) with a continuous slope.
+fn tone_map(x: f32) -> f32 {
+    if x <= TONE_MAP_KNEE {
+        x
+    } else {
+        let a = 1.0 - TONE_MAP_KNEE;
+        let d = x - TONE_MAP_KNEE;
+        TONE_MAP_KNEE + a * d / (d + a)
+    }
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let mantissa = (h & 0x3ff) as u32;
+    match exp {
+        0 => sign * mantissa as f32 / (1 << 24) as f32,
+        0x1f if mantissa == 0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits(
+            ((h as u32 & 0x8000) << 16) | ((exp + 127 - 15) << 23) | (mantissa << 13),
+        ),
+    }
+}
+
+#[inline]
+fn mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+// The 10-bit PQ of the pixels of a row in BT.2020,
+// the 8-bit frames, e.g. captured by GDI, are placed at the SDR white.
+fn pq_row(src: &[u8], pixfmt: Pixfmt, dst: &mut [[i32; 3]]) {
+    let (pq, scrgb, srgb) = (&*PQ_OETF, &*SCRGB_EOTF, &*SRGB_EOTF);
+    let bpp = pixfmt.bytes_per_pixel();
+    for (p, out) in src.chunks_exact(bpp).zip(dst.iter_mut()) {
+        let linear = match pixfmt {
+            Pixfmt::RGBAF16 => {
+                [0, 1, 2].map(|i| scrgb[u16::from_le_bytes([p[i * 2], p[i * 2 + 1]]) as usize])
+            }
+            Pixfmt::BGRA => [2, 1, 0].map(|i| srgb[p[i] as usize]),
+            _ => [0, 1, 2].map(|i| srgb[p[i] as usize]),
+        };
+        *out = mul(&BT709_TO_BT2020, linear).map(|v| pq.get(v) as i32);
+    }
+}
+
+#[inline]
+fn put_u16(plane: &mut [u8], offset: usize, v: i32) {
+    plane[offset..offset + 2].copy_from_slice(&(v.clamp(0, 1023) as u16).to_le_bytes());
+}
+
+// Converts the captured frame to I010 in BT.2020 with PQ, limited range.
+// The rows go through the lookup tables, and the Y'CbCr matrix is in fixed point.
+pub fn convert_to_i010(
+    captured: &impl TraitPixelBuffer,
+    dst_fmt: &EncodeYuvFormat,
+    dst: &mut Vec<u8>,
+) -> ResultType<()> {
+    let pixfmt = captured.pixfmt();
+    if !matches!(pixfmt, Pixfmt::RGBAF16 | Pixfmt::BGRA | Pixfmt::RGBA) {
+        bail!("unsupported pixfmt for I010: {pixfmt:?}");
+    }
+    if dst_fmt.pixfmt != Pixfmt::I010 || dst_fmt.stride.len() < 3 {
+        bail!("unsupported yuv format: {:?}", dst_fmt.pixfmt);
+    }
+    let (width, height) = (captured.width(), captured.height());
+    let src = captured.data();
+    let src_stride = captured.stride().first().copied().unwrap_or_default();
+    let bpp = pixfmt.bytes_per_pixel();
+    if width == 0 || height == 0 || width > dst_fmt.w || height > dst_fmt.h {
+        bail!(
+            "invalid size: ({width}, {height}) -> ({}, {})",
+            dst_fmt.w,
+            dst_fmt.h
+        );
+    }
+    if src_stride < width * bpp || src.len() < src_stride * height {
+        bail!("wrong src len, {} < {} * {}", src.len(), src_stride, height);
+    }
+    let (stride_y, stride_u, stride_v) = (dst_fmt.stride[0], dst_fmt.stride[1], dst_fmt.stride[2]);
+    dst.resize(dst_fmt.v + stride_v * ((dst_fmt.h + 1) / 2), 0);
+    let (plane_y, plane_uv) = dst.split_at_mut(dst_fmt.u);
+    let (plane_u, plane_v) = plane_uv.split_at_mut(dst_fmt.v - dst_fmt.u);
+    // The full range 10-bit R'G'B' -> 876 levels of Y' and 896 levels of Cb and Cr
+    let (kr, kb) = BT2020_K;
+    let kg = 1.0 - kr - kb;
+    let fixed =
+        |v: f32, levels: f32| (v * levels / 1023.0 * (1 << COEF_BITS) as f32).round() as i32;
+    let coef_y = [kr, kg, kb].map(|k| fixed(k, 876.0));
+    let coef_u = [-kr, -kg, 1.0 - kb].map(|k| fixed(k / (2.0 * (1.0 - kb)), 896.0));
+    let coef_v = [1.0 - kr, -kg, -kb].map(|k| fixed(k / (2.0 * (1.0 - kr)), 896.0));
+    let dot = |c: &[i32; 3], p: [i32; 3]| c[0] * p[0] + c[1] * p[1] + c[2] * p[2];
+    let half = 1 << (COEF_BITS - 1);
+    let mut rows = [vec![[0; 3]; width], vec![[0; 3]; width]];
+    for by in 0..(height + 1) / 2 {
+        for (dy, row) in rows.iter_mut().enumerate() {
+            // The last row is repeated for the odd height
+            let y = (by * 2 + dy).min(height - 1);
+            pq_row(
+                &src[y * src_stride..y * src_stride + width * bpp],
+                pixfmt,
+                row,
+            );
+            let dst_row = &mut plane_y[y * stride_y..];
+            for (x, p) in row.iter().enumerate() {
+                put_u16(
+                    dst_row,
+                    x * 2,
+                    64 + ((dot(&coef_y, *p) + half) >> COEF_BITS),
+                );
+            }
+        }
+        for bx in 0..(width + 1) / 2 {
+            // The last column is repeated for the odd width
+            let (x0, x1) = (bx * 2, (bx * 2 + 1).min(width - 1));
+            let sum = [0, 1, 2]
+                .map(|i| rows[0][x0][i] + rows[0][x1][i] + rows[1][x0][i] + rows[1][x1][i]);
+            // Averages the 4 pixels
+            let (shift, round) = (COEF_BITS + 2, 1 << (COEF_BITS + 1));
+            put_u16(
+                plane_u,
+                by * stride_u + bx * 2,
+                512 + ((dot(&coef_u, sum) + round) >> shift),
+            );
+            put_u16(
+                plane_v,
+                by * stride_v + bx * 2,
+                512 + ((dot(&coef_v, sum) + round) >> shift),
+            );
+        }
+    }
+    Ok(())
+}
+
+// Tone maps the 10-bit PQ R'G'B' of a row of AR30 to 8-bit sRGB.
+fn tone_map_row(ar30: &[u8], primaries: Primaries, dst: &mut [u8], index: [usize; 3], bpp: usize) {
+    let (eotf, tone) = (&*PQ_EOTF, &*TONE_MAP_SRGB);
+    for (p, out) in ar30.chunks_exact(4).zip(dst.chunks_exact_mut(bpp)) {
+        let v = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+        let linear = [20, 10, 0].map(|shift| eotf[((v >> shift) & 0x3ff) as usize]);
+        let linear = match primaries {
+            Primaries::Bt2020 => mul(&BT2020_TO_BT709, linear),
+            Primaries::Bt709 => linear,
+        };
+        for (i, v) in index.iter().zip(linear) {
+            out[*i] = tone.get(v);
+        }
+        if bpp == 4 {
+            out[3] = 255;
+        }
+    }
+}
+
+// Converts the decoded 10-bit image to 8-bit rgb with libyuv, PQ is tone mapped from AR30.
+// The SDR frames in BT.2020 are not converted to BT.709, as the 8-bit frames.
+pub fn to_rgb(
+    image: &(impl GoogleImage + ?Sized),
+    color: HighBitDepth,
+    rgb: &mut ImageRgb,
+    bytes_per_row: usize,
+) -> ResultType<()> {
+    // The server encodes 10-bit only
+    if color.bit_depth != 10 {
+        bail!("unsupported bit depth: {}", color.bit_depth);
+    }
+    let (width, height) = (image.width(), image.height());
+    let stride = image.stride();
+    let planes = image.planes();
+    if stride.len() < 3 || planes.len() < 3 {
+        bail!("invalid planes");
+    }
+    if stride[..3].iter().any(|s| *s <= 0) || planes[..3].iter().any(|p| p.is_null()) {
+        bail!("invalid stride or plane");
+    }
+    let i444 = image.chroma() == Chroma::I444;
+    let (index, bpp) = match rgb.fmt() {
+        ImageFormat::ARGB => ([2, 1, 0], 4),
+        ImageFormat::ABGR => ([0, 1, 2], 4),
+        ImageFormat::Raw => ([0, 1, 2], 3),
+    };
+    if rgb.raw.len() < bytes_per_row * height || bytes_per_row < width * bpp {
+        bail!("rgb buffer too small");
+    }
+    let pq = color.transfer == Transfer::Pq;
+    // The SDR rows of ARGB and ABGR are written by libyuv directly, ABGR with U and V swapped
+    let direct = !pq && bpp == 4;
+    let swap = direct && rgb.fmt() == ImageFormat::ABGR;
+    let f = match (i444, pq) {
+        (false, false) => super::I010ToARGBMatrix,
+        (true, false) => super::I410ToARGBMatrix,
+        (false, true) => super::I010ToAR30Matrix,
+        (true, true) => super::I410ToAR30Matrix,
+    };
+    let constants = unsafe {
+        match (color.primaries, swap) {
+            (Primaries::Bt709, false) => &super::kYuvH709Constants,
+            (Primaries::Bt709, true) => &super::kYvuH709Constants,
+            (Primaries::Bt2020, false) => &super::kYuv2020Constants,
+            (Primaries::Bt2020, true) => &super::kYvu2020Constants,
+        }
+    };
+    let (u, v) = if swap { (2, 1) } else { (1, 2) };
+    // The 16-bit strides of libyuv are in samples
+    let plane =
+        |i: usize, row: usize| unsafe { planes[i].add(row * stride[i] as usize) as *const u16 };
+    let mut tmp = vec![0u8; if direct { 0 } else { width * 4 }];
+    for y in 0..height {
+        let cy = if i444 { y } else { y / 2 };
+        let dst = &mut rgb.raw[y * bytes_per_row..y * bytes_per_row + width * bpp];
+        call_yuv!(f(
+            plane(0, y),
+            stride[0] / 2,
+            plane(u, cy),
+            stride[u] / 2,
+            plane(v, cy),
+            stride[v] / 2,
+            if direct {
+                dst.as_mut_ptr()
+            } else {
+                tmp.as_mut_ptr()
+            },
+            (width * 4) as _,
+            constants,
+            width as _,
+            1,
+        ));
+        if pq {
+            tone_map_row(&tmp, color.primaries, dst, index, bpp);
+        } else if !direct {
+            // ARGB is BGRA in memory
+            for (p, out) in tmp.chunks_exact(4).zip(dst.chunks_exact_mut(3)) {
+                out.copy_from_slice(&[p[2], p[1], p[0]]);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Tone maps the captured scRGB frame to 8-bit, e.g. for the screenshots.
+pub fn scrgb_to_rgba(
+    captured: &impl TraitPixelBuffer,
+    bgra: bool,
+    dst: &mut Vec<u8>,
+) -> ResultType<()> {
+    if captured.pixfmt() != Pixfmt::RGBAF16 {
+        bail!("not scRGB: {:?}", captured.pixfmt());
+    }
+    let (width, height) = (captured.width(), captured.height());
+    let src = captured.data();
+    let src_stride = captured.stride().first().copied().unwrap_or_default();
+    if src_stride < width * 8 || src.len() < src_stride * height {
+        bail!("wrong src len, {} < {} * {}", src.len(), src_stride, height);
+    }
+    dst.resize(width * height * 4, 0);
+    for y in 0..height {
+        scrgb_row_to_rgba(
+            &src[y * src_stride..y * src_stride + width * 8],
+            bgra,
+            &mut dst[y * width * 4..(y + 1) * width * 4],
+        );
+    }
+    Ok(())
+}
+
+// Tone maps the scRGB pixels of a row to 8-bit, the same as the decoded HDR frames.
+pub fn scrgb_row_to_rgba(src: &[u8], bgra: bool, dst: &mut [u8]) {
+    let tone = &*TONE_MAP_SRGB;
+    for (p, out) in src.chunks_exact(8).zip(dst.chunks_exact_mut(4)) {
+        let [r, g, b] = [0, 1, 2].map(|i| {
+            tone.get(
+                f16_to_f32(u16::from_le_bytes([p[i * 2], p[i * 2 + 1]])) * SCRGB_WHITE_NITS
+                    / SDR_WHITE_NITS,
+            )
+        });
+        out.copy_from_slice(&if bgra { [b, g, r, 255] } else { [r, g, b, 255] });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hbb_common::protobuf::EnumOrUnknown;
+
+    struct TestBuffer {
+        data: Vec<u8>,
+        width: usize,
+        height: usize,
+    }
+
+    impl TraitPixelBuffer for TestBuffer {
+        fn data(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn width(&self) -> usize {
+            self.width
+        }
+
+        fn height(&self) -> usize {
+            self.height
+        }
+
+        fn stride(&self) -> Vec<usize> {
+            vec![self.width * 8]
+        }
+
+        fn pixfmt(&self) -> Pixfmt {
+            Pixfmt::RGBAF16
+        }
+    }
+
+    struct TestImage {
+        data: Vec<u8>,
+        fmt: EncodeYuvFormat,
+    }
+
+    impl GoogleImage for TestImage {
+        fn width(&self) -> usize {
+            self.fmt.w
+        }
+
+        fn height(&self) -> usize {
+            self.fmt.h
+        }
+
+        fn stride(&self) -> Vec<i32> {
+            self.fmt.stride.iter().map(|s| *s as _).collect()
+        }
+
+        fn planes(&self) -> Vec<*mut u8> {
+            [0, self.fmt.u, self.fmt.v]
+                .map(|offset| self.data[offset..].as_ptr() as *mut u8)
+                .to_vec()
+        }
+
+        fn chroma(&self) -> Chroma {
+            Chroma::I420
+        }
+    }
+
+    #[test]
+    fn test_f16_to_f32() {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn test_pq() {
+        // 100 nits
+        assert!((pq_oetf(0.01) - 0.5081).abs() < 0.001);
+        for nits in [0.01, 1.0, 80.0, 203.0, 1000.0, 10000.0] {
+            let y = nits / PQ_MAX_NITS;
+            assert!((pq_eotf(pq_oetf(y)) - y).abs() < y * 0.001);
+            assert!(
+                (PQ_OETF.get(y) as f32 - pq_oetf(y) * 1023.0).abs() <= 1.0,
+                "{nits}"
+            );
+        }
+        assert_eq!(PQ_OETF.get(0.0), 0);
+    }
+
+    #[test]
+    fn test_video_color() {
+        let color = VideoColor {
+            primaries: Primaries::Bt2020,
+            transfer: Transfer::Pq,
+        };
+        let msg = message_proto::VideoColor::from(color);
+        assert_eq!(VideoColor::from(&msg), color);
+        // The unknown values
+        let msg = message_proto::VideoColor {
+            primaries: EnumOrUnknown::from_i32(9),
+            transfer: EnumOrUnknown::from_i32(16),
+            ..Default::default()
+        };
+        assert_eq!(VideoColor::from(&msg), VideoColor::default());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // SDR white, black and 1000 nits
+        let (width, height) = (6, 2);
+        let colors: [f32; 3] = [
+            SDR_WHITE_NITS / SCRGB_WHITE_NITS,
+            0.0,
+            1000.0 / SCRGB_WHITE_NITS,
+        ];
+        let mut data = vec![];
+        for _ in 0..height {
+            for x in 0..width {
+                let bits = half(colors[x / 2]);
+                for _ in 0..4 {
+                    data.extend(bits.to_le_bytes());
+                }
+            }
+        }
+        let buf = TestBuffer {
+            data,
+            width,
+            height,
+        };
+        let fmt = EncodeYuvFormat {
+            pixfmt: Pixfmt::I010,
+            w: width,
+            h: height,
+            stride: vec![width * 2, width, width],
+            u: width * 2 * height,
+            v: width * 2 * height + width * height / 2,
+        };
+        let mut yuv = vec![];
+        convert_to_i010(&buf, &fmt, &mut yuv).unwrap();
+        let image = TestImage { data: yuv, fmt };
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        rgb.raw.resize(width * height * 4, 0);
+        let color = HighBitDepth {
+            bit_depth: 10,
+            primaries: Primaries::Bt2020,
+            transfer: Transfer::Pq,
+        };
+        to_rgb(&image, color, &mut rgb, width * 4).unwrap();
+        let white = (srgb_oetf(tone_map(1.0)) * 255.0).round() as i32;
+        let pixel = |x: usize| &rgb.raw[x * 4..x * 4 + 4];
+        // libyuv converts in fixed point
+        for c in &pixel(0)[..3] {
+            assert!((*c as i32 - white).abs() <= 3, "{c} != {white}");
+        }
+        assert_eq!(&pixel(2)[..3], &[0, 0, 0]);
+        // The highlights are brighter than the SDR white but not clipped
+        assert!(pixel(4)[0] as i32 > white && pixel(4)[0] < 255);
+        assert_eq!(pixel(4)[3], 255);
+
+        let mut rgba = vec![];
+        scrgb_to_rgba(&buf, false, &mut rgba).unwrap();
+        assert!((rgba[0] as i32 - white).abs() <= 1);
+    }
+
+    // Positive normal values only
+    fn half(v: f32) -> u16 {
+        if v == 0.0 {
+            return 0;
+        }
+        let bits = v.to_bits();
+        let exp = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        ((exp as u16) << 10) | ((bits >> 13) & 0x3ff) as u16
+    }
+}
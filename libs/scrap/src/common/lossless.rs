@@ -0,0 +1,598 @@
+// Lossless refinement of the static text tiles.
+// The video codecs blur small fonts at low bitrates, so the tiles which stop changing are sent again
+// as compressed palette images, and the client draws them over the decoded video frames.
+
+use crate::{
+    dirty::{DirtyMap, DirtyRect},
+    ImageFormat, ImageRgb, Pixfmt, TraitPixelBuffer,
+};
+use hbb_common::{
+    bail,
+    compress::{compress, decompress},
+    message_proto::{LosslessTile, LosslessTiles},
+    ResultType,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+pub const TILE_SIZE: usize = 64;
+// The tiles unchanged for this duration are refined.
+const REFINE_DELAY: Duration = Duration::from_millis(500);
+const REFINE_INTERVAL: Duration = Duration::from_millis(200);
+// Raw bytes of the tiles in one update, the others are sent in the next updates.
+const MAX_UPDATE_BYTES: usize = 1024 * 1024;
+// Text and UI use a few colors, photos and videos use more.
+const MAX_COLORS: usize = 256;
+// Ratio of the neighbor pixels with different colors, flat areas are fine with the video codecs.
+const MIN_EDGE_RATIO: f32 = 0.04;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteImage {
+    // RGBA
+    pub colors: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+    // None if the tile is changed and should be removed.
+    pub image: Option<PaletteImage>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TileUpdate {
+    pub display: usize,
+    pub width: usize,
+    pub height: usize,
+    // Remove all the tiles before applying the update.
+    pub reset: bool,
+    pub tiles: Vec<Tile>,
+}
+
+impl TileUpdate {
+    pub fn to_message(&self) -> LosslessTiles {
+        let tiles = self
+            .tiles
+            .iter()
+            .map(|tile| {
+                let mut t = LosslessTile {
+                    x: tile.x as _,
+                    y: tile.y as _,
+                    w: tile.w as _,
+                    h: tile.h as _,
+                    ..Default::default()
+                };
+                // The removed tiles have no colors.
+                if let Some(image) = tile.image.as_ref() {
+                    t.colors = image.colors.concat().into();
+                    t.indices = compress(&image.indices).into();
+                }
+                t
+            })
+            .collect();
+        LosslessTiles {
+            display: self.display as _,
+            width: self.width as _,
+            height: self.height as _,
+            reset: self.reset,
+            tiles,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_message(msg: &LosslessTiles) -> ResultType<Self> {
+        if msg.display < 0 || msg.width < 0 || msg.height < 0 {
+            bail!("Invalid tile update");
+        }
+        let valid_size = |v: i32| (0..=TILE_SIZE as i32).contains(&v);
+        let mut tiles = Vec::with_capacity(msg.tiles.len());
+        for t in msg.tiles.iter() {
+            if t.x < 0 || t.y < 0 || !valid_size(t.w) || !valid_size(t.h) {
+                bail!("Invalid tile");
+            }
+            let (w, h) = (t.w as usize, t.h as usize);
+            let image = if t.colors.is_empty() {
+                None
+            } else {
+                let n = t.colors.len() / 4;
+                if t.colors.len() % 4 != 0 || n > MAX_COLORS {
+                    bail!("Invalid palette size {}", n);
+                }
+                let colors = t
+                    .colors
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], c[3]])
+                    .collect::<Vec<_>>();
+                let indices = decompress(&t.indices);
+                if indices.len() != w * h || indices.iter().any(|i| *i as usize >= n) {
+                    bail!("Invalid palette index");
+                }
+                Some(PaletteImage { colors, indices })
+            };
+            tiles.push(Tile {
+                x: t.x as _,
+                y: t.y as _,
+                w,
+                h,
+                image,
+            });
+        }
+        Ok(Self {
+            display: msg.display as _,
+            width: msg.width as _,
+            height: msg.height as _,
+            reset: msg.reset,
+            tiles,
+        })
+    }
+}
+
+// None if the tile does not look like text or UI.
+fn palette_image(data: &[u8], stride: usize, rect: &DirtyRect, bgra: bool) -> Option<PaletteImage> {
+    let mut colors = Vec::new();
+    let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(rect.w * rect.h);
+    let mut edges = 0;
+    for y in rect.y..rect.y + rect.h {
+        let row = &data[y * stride + rect.x * 4..y * stride + (rect.x + rect.w) * 4];
+        let mut last = None;
+        for px in row.chunks_exact(4) {
+            let rgb = if bgra {
+                [px[2], px[1], px[0]]
+            } else {
+                [px[0], px[1], px[2]]
+            };
+            let index = match lookup.get(&rgb) {
+                Some(index) => *index,
+                None => {
+                    if colors.len() >= MAX_COLORS {
+                        return None;
+                    }
+                    let index = colors.len() as u8;
+                    lookup.insert(rgb, index);
+                    colors.push([rgb[0], rgb[1], rgb[2], 0xFF]);
+                    index
+                }
+            };
+            if last.is_some_and(|last| last != index) {
+                edges += 1;
+            }
+            last = Some(index);
+            indices.push(index);
+        }
+    }
+    if colors.len() < 2 || (edges as f32) < MIN_EDGE_RATIO * (rect.w * rect.h) as f32 {
+        return None;
+    }
+    Some(PaletteImage { colors, indices })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TileState {
+    Changed(Instant),
+    Refined,
+    Skipped,
+}
+
+// Server side, one per display.
+pub struct TileRefiner {
+    display: usize,
+    width: usize,
+    height: usize,
+    bgra: bool,
+    cols: usize,
+    // The captured pixels, BGRA or RGBA without padding.
+    frame: Vec<u8>,
+    states: Vec<TileState>,
+    last_refine: Option<Instant>,
+}
+
+impl TileRefiner {
+    pub fn new(display: usize) -> Self {
+        Self {
+            display,
+            width: 0,
+            height: 0,
+            bgra: true,
+            cols: 0,
+            frame: Vec::new(),
+            states: Vec::new(),
+            last_refine: None,
+        }
+    }
+
+    fn rect(&self, i: usize) -> DirtyRect {
+        let (x, y) = ((i % self.cols) * TILE_SIZE, (i / self.cols) * TILE_SIZE);
+        DirtyRect {
+            x,
+            y,
+            w: TILE_SIZE.min(self.width - x),
+            h: TILE_SIZE.min(self.height - y),
+        }
+    }
+
+    // Returns the refined tiles which are changed, it should be sent before the frame.
+    pub fn update<P: TraitPixelBuffer>(&mut self, buf: &P, dirty: &DirtyMap) -> Option<TileUpdate> {
+        let (bgra, bpp) = match buf.pixfmt() {
+            Pixfmt::BGRA => (true, 4),
+            Pixfmt::RGBA => (false, 4),
+            // Tone mapped to 8-bit, the same as the client does with the HDR frames.
+            Pixfmt::RGBAF16 => (true, 8),
+            _ => return self.clear(),
+        };
+        let (width, height) = (buf.width(), buf.height());
+        let stride = buf.stride().first().copied().unwrap_or_default();
+        if width == 0 || height == 0 || stride < width * bpp || buf.data().len() < stride * height {
+            return self.clear();
+        }
+        let now = Instant::now();
+        let mut update = TileUpdate {
+            display: self.display,
+            width,
+            height,
+            ..Default::default()
+        };
+        let all = (width, height, bgra) != (self.width, self.height, self.bgra)
+            || (dirty.width(), dirty.height()) != (width, height);
+        if all {
+            self.width = width;
+            self.height = height;
+            self.bgra = bgra;
+            self.cols = (width + TILE_SIZE - 1) / TILE_SIZE;
+            let rows = (height + TILE_SIZE - 1) / TILE_SIZE;
+            update.reset =
+                self.states.iter().any(|s| *s == TileState::Refined) || self.frame.is_empty();
+            self.states = vec![TileState::Changed(now); self.cols * rows];
+            self.frame.resize(width * height * 4, 0);
+        }
+        for i in 0..self.states.len() {
+            let rect = self.rect(i);
+            if !all && !dirty.is_rect_dirty(&rect) {
+                continue;
+            }
+            for y in rect.y..rect.y + rect.h {
+                let src = y * stride + rect.x * bpp;
+                let src = &buf.data()[src..src + rect.w * bpp];
+                let dst = (y * width + rect.x) * 4;
+                let dst = &mut self.frame[dst..dst + rect.w * 4];
+                if bpp == 8 {
+                    crate::hdr::scrgb_row_to_rgba(src, bgra, dst);
+                } else {
+                    dst.copy_from_slice(src);
+                }
+            }
+            if !all && self.states[i] == TileState::Refined {
+                update.tiles.push(Tile {
+                    x: rect.x,
+                    y: rect.y,
+                    w: rect.w,
+                    h: rect.h,
+                    image: None,
+                });
+            }
+            self.states[i] = TileState::Changed(now);
+        }
+        if update.reset || !update.tiles.is_empty() {
+            Some(update)
+        } else {
+            None
+        }
+    }
+
+    // Removes all the tiles, e.g. after a key frame, which may show the changes not reported by
+    // the capturer. The tiles are refined again from the next captured frame.
+    pub fn clear(&mut self) -> Option<TileUpdate> {
+        let refined = self.states.iter().any(|s| *s == TileState::Refined);
+        let update = TileUpdate {
+            display: self.display,
+            width: self.width,
+            height: self.height,
+            reset: true,
+            tiles: Vec::new(),
+        };
+        *self = Self::new(self.display);
+        if refined {
+            Some(update)
+        } else {
+            None
+        }
+    }
+
+    // The tiles unchanged for a while, call it periodically.
+    pub fn refine(&mut self) -> Option<TileUpdate> {
+        let now = Instant::now();
+        if self.frame.is_empty()
+            || self
+                .last_refine
+                .is_some_and(|last| now.duration_since(last) < REFINE_INTERVAL)
+        {
+            return None;
+        }
+        self.last_refine = Some(now);
+        let mut tiles = Vec::new();
+        let mut bytes = 0;
+        for i in 0..self.states.len() {
+            let TileState::Changed(changed) = self.states[i] else {
+                continue;
+            };
+            if now.duration_since(changed) < REFINE_DELAY {
+                continue;
+            }
+            let rect = self.rect(i);
+            match palette_image(&self.frame, self.width * 4, &rect, self.bgra) {
+                Some(image) => {
+                    bytes += image.indices.len() + image.colors.len() * 4;
+                    tiles.push(Tile {
+                        x: rect.x,
+                        y: rect.y,
+                        w: rect.w,
+                        h: rect.h,
+                        image: Some(image),
+                    });
+                    self.states[i] = TileState::Refined;
+                }
+                None => self.states[i] = TileState::Skipped,
+            }
+            if bytes >= MAX_UPDATE_BYTES {
+                break;
+            }
+        }
+        if tiles.is_empty() {
+            return None;
+        }
+        Some(TileUpdate {
+            display: self.display,
+            width: self.width,
+            height: self.height,
+            reset: false,
+            tiles,
+        })
+    }
+}
+
+// Client side, draws the tiles over the decoded frames.
+#[derive(Default)]
+pub struct TileCompositor {
+    width: usize,
+    height: usize,
+    tiles: HashMap<(usize, usize), Tile>,
+    // The last decoded frame, to draw the tiles again when they are updated without a new frame.
+    base: Vec<u8>,
+    enabled: bool,
+}
+
+impl TileCompositor {
+    // Returns true if `rgb` is changed.
+    pub fn update(&mut self, update: TileUpdate, rgb: &mut ImageRgb) -> bool {
+        self.enabled = true;
+        let mut changed = false;
+        if update.reset || (update.width, update.height) != (self.width, self.height) {
+            changed = !self.tiles.is_empty();
+            self.tiles.clear();
+            self.width = update.width;
+            self.height = update.height;
+        }
+        for tile in update.tiles {
+            if tile.x + tile.w > self.width || tile.y + tile.h > self.height {
+                continue;
+            }
+            changed = true;
+            if tile.image.is_some() {
+                self.tiles.insert((tile.x, tile.y), tile);
+            } else {
+                self.tiles.remove(&(tile.x, tile.y));
+            }
+        }
+        changed && self.redraw(rgb)
+    }
+
+    // Removes all the tiles, returns true if `rgb` is changed.
+    pub fn disable(&mut self, rgb: &mut ImageRgb) -> bool {
+        let changed = !self.tiles.is_empty();
+        self.tiles.clear();
+        let redrawn = changed && self.redraw(rgb);
+        *self = Default::default();
+        redrawn
+    }
+
+    // Call it after a frame is decoded into `rgb`.
+    pub fn composite(&mut self, rgb: &mut ImageRgb) {
+        if !self.enabled {
+            return;
+        }
+        self.base.resize(rgb.raw.len(), 0);
+        self.base.copy_from_slice(&rgb.raw);
+        if self.matches(rgb) {
+            self.draw(rgb);
+        }
+    }
+
+    // Call it after a frame is decoded into a texture, which the tiles can not be drawn on.
+    pub fn skip_frame(&mut self) {
+        self.base.clear();
+    }
+
+    // `rgb.raw` may be swapped out by the renderer, so the last frame is restored from `base`.
+    fn redraw(&self, rgb: &mut ImageRgb) -> bool {
+        if self.base.is_empty() || rgb.h == 0 || self.base.len() % rgb.h != 0 {
+            return false;
+        }
+        rgb.raw.resize(self.base.len(), 0);
+        rgb.raw.copy_from_slice(&self.base);
+        if self.matches(rgb) {
+            self.draw(rgb);
+        }
+        true
+    }
+
+    #[inline]
+    fn matches(&self, rgb: &ImageRgb) -> bool {
+        rgb.w == self.width && rgb.h == self.height && rgb.h > 0
+    }
+
+    fn draw(&self, rgb: &mut ImageRgb) {
+        let stride = rgb.raw.len() / rgb.h;
+        // ARGB is BGRA in memory, ABGR is RGBA, Raw is RGB.
+        let (bpp, order): (usize, [usize; 4]) = match rgb.fmt() {
+            ImageFormat::ARGB => (4, [2, 1, 0, 3]),
+            ImageFormat::ABGR => (4, [0, 1, 2, 3]),
+            ImageFormat::Raw => (3, [0, 1, 2, 3]),
+        };
+        if stride < rgb.w * bpp {
+            return;
+        }
+        for tile in self.tiles.values() {
+            let Some(image) = tile.image.as_ref() else {
+                continue;
+            };
+            for row in 0..tile.h {
+                let offset = (tile.y + row) * stride + tile.x * bpp;
+                let dst = &mut rgb.raw[offset..offset + tile.w * bpp];
+                let indices = &image.indices[row * tile.w..(row + 1) * tile.w];
+                for (px, index) in dst.chunks_exact_mut(bpp).zip(indices) {
+                    let color = image.colors[*index as usize];
+                    for (i, v) in px.iter_mut().enumerate() {
+                        *v = color[order[i]];
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_tile(size: usize) -> Vec<u8> {
+        let mut data = vec![0xFF; size * size * 4];
+        for y in (0..size).step_by(4) {
+            for x in (0..size).step_by(3) {
+                let i = (y * size + x) * 4;
+                data[i..i + 3].copy_from_slice(&[0, 0, 0]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_palette_image() {
+        let rect = DirtyRect {
+            x: 0,
+            y: 0,
+            w: 16,
+            h: 16,
+        };
+        assert!(palette_image(&vec![0xFF; 16 * 16 * 4], 16 * 4, &rect, true).is_none());
+        let image = palette_image(&text_tile(16), 16 * 4, &rect, true).unwrap();
+        assert_eq!(image.colors.len(), 2);
+        assert_eq!(image.indices.len(), 16 * 16);
+
+        let noise = (0..16 * 16 * 4).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        assert!(palette_image(&noise, 16 * 4, &rect, true).is_none());
+    }
+
+    #[test]
+    fn test_message() {
+        let rect = DirtyRect {
+            x: 0,
+            y: 0,
+            w: 16,
+            h: 16,
+        };
+        let update = TileUpdate {
+            display: 1,
+            width: 100,
+            height: 80,
+            reset: true,
+            tiles: vec![
+                Tile {
+                    x: 64,
+                    y: 0,
+                    w: 16,
+                    h: 16,
+                    image: palette_image(&text_tile(16), 16 * 4, &rect, false),
+                },
+                Tile {
+                    x: 0,
+                    y: 64,
+                    w: 64,
+                    h: 16,
+                    image: None,
+                },
+            ],
+        };
+        let mut msg = update.to_message();
+        assert_eq!(TileUpdate::from_message(&msg).unwrap(), update);
+        // The palette has 2 colors.
+        msg.tiles[0].indices = compress(&[2; 16 * 16]).into();
+        assert!(TileUpdate::from_message(&msg).is_err());
+    }
+
+    #[test]
+    fn test_refiner_clear() {
+        let mut refiner = TileRefiner::new(1);
+        assert!(refiner.clear().is_none());
+        refiner.width = 100;
+        refiner.height = 80;
+        refiner.states = vec![TileState::Skipped, TileState::Refined];
+        let update = refiner.clear().unwrap();
+        assert!(update.reset && update.tiles.is_empty());
+        assert_eq!((update.display, update.width, update.height), (1, 100, 80));
+        assert!(refiner.states.is_empty() && refiner.frame.is_empty());
+    }
+
+    #[test]
+    fn test_compositor() {
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        rgb.w = 4;
+        rgb.h = 2;
+        rgb.raw = vec![0; 4 * 2 * 4];
+        let mut compositor = TileCompositor::default();
+        let tile = Tile {
+            x: 2,
+            y: 0,
+            w: 2,
+            h: 1,
+            image: Some(PaletteImage {
+                colors: vec![[1, 2, 3, 0xFF]],
+                indices: vec![0, 0],
+            }),
+        };
+        let update = TileUpdate {
+            display: 0,
+            width: 4,
+            height: 2,
+            reset: false,
+            tiles: vec![tile.clone()],
+        };
+        // No decoded frame yet.
+        assert!(!compositor.update(update.clone(), &mut rgb));
+        compositor.composite(&mut rgb);
+        assert_eq!(&rgb.raw[8..12], &[3, 2, 1, 0xFF]);
+        assert_eq!(&rgb.raw[..4], &[0, 0, 0, 0]);
+
+        let removed = TileUpdate {
+            tiles: vec![Tile {
+                image: None,
+                ..tile
+            }],
+            ..update
+        };
+        assert!(compositor.update(removed, &mut rgb));
+        assert!(rgb.raw.iter().all(|v| *v == 0));
+
+        assert!(compositor.update(update, &mut rgb));
+        assert_eq!(&rgb.raw[12..16], &[3, 2, 1, 0xFF]);
+        assert!(compositor.disable(&mut rgb));
+        assert!(rgb.raw.iter().all(|v| *v == 0));
+        compositor.composite(&mut rgb);
+        assert!(compositor.base.is_empty());
+    }
+}
@@ -37,10 +37,16 @@ cfg_if! {
 
 pub mod codec;
 pub mod convert;
+pub mod dirty;
+pub mod hdr;
+pub mod lossless;
+pub mod scale;
 #[cfg(feature = "hwcodec")]
 pub mod hwcodec;
 #[cfg(feature = "mediacodec")]
 pub mod mediacodec;
+#[cfg(feature = "openh264")]
+pub mod openh264;
 pub mod vpxcodec;
 #[cfg(feature = "vram")]
 pub mod vram;
@@ -136,6 +142,11 @@ pub trait TraitCapturer {
     #[cfg(windows)]
     fn set_gdi(&mut self) -> bool;
 
+    // Capture 16-bit float frames of an HDR display, return whether it is enabled.
+    fn set_hdr(&mut self, _hdr: bool) -> bool {
+        false
+    }
+
     #[cfg(feature = "vram")]
     fn device(&self) -> AdapterDevice;
 
@@ -170,6 +181,11 @@ pub trait TraitPixelBuffer {
     fn stride(&self) -> Vec<usize>;
 
     fn pixfmt(&self) -> Pixfmt;
+
+    // The areas changed since the last frame, None if unknown.
+    fn dirty_rects(&self) -> Option<Vec<dirty::DirtyRect>> {
+        None
+    }
 }
 
 #[cfg(not(any(target_os = "ios")))]
@@ -195,12 +211,32 @@ impl Frame<'_> {
     ) -> ResultType<EncodeInput<'a>> {
         match self {
             Frame::PixelBuffer(pixelbuffer) => {
-                convert_to_yuv(&pixelbuffer, yuvfmt, yuv, mid_data)?;
+                convert_to_yuv(pixelbuffer, yuvfmt, yuv, mid_data)?;
                 Ok(EncodeInput::YUV(yuv))
             }
             Frame::Texture(texture) => Ok(EncodeInput::Texture(*texture)),
         }
     }
+
+    // Scale the pixel buffer down to `size` before converting, see scale.rs
+    pub fn to_scaled<'a>(
+        &'a self,
+        size: (usize, usize),
+        yuvfmt: EncodeYuvFormat,
+        yuv: &'a mut Vec<u8>,
+        mid_data: &mut Vec<u8>,
+        scaled: &mut Vec<u8>,
+    ) -> ResultType<EncodeInput<'a>> {
+        match self {
+            Frame::PixelBuffer(pixelbuffer) => {
+                let scaled =
+                    scale::scale_pixelbuffer(pixelbuffer, size.0, size.1, scaled, mid_data)?;
+                convert_to_yuv(&scaled, yuvfmt, yuv, mid_data)?;
+                Ok(EncodeInput::YUV(yuv))
+            }
+            Frame::Texture(_) => bail!("scaling texture is not supported"),
+        }
+    }
 }
 
 pub enum EncodeInput<'a> {
@@ -229,9 +265,11 @@ pub enum Pixfmt {
     BGRA,
     RGBA,
     RGB565LE,
+    RGBAF16, // scRGB, see hdr.rs
     I420,
     NV12,
     I444,
+    I010, // 10-bit I420 in 16-bit little endian
 }
 
 impl Pixfmt {
@@ -239,8 +277,9 @@ impl Pixfmt {
         match self {
             Pixfmt::BGRA | Pixfmt::RGBA => 32,
             Pixfmt::RGB565LE => 16,
+            Pixfmt::RGBAF16 => 64,
             Pixfmt::I420 | Pixfmt::NV12 => 12,
-            Pixfmt::I444 => 24,
+            Pixfmt::I444 | Pixfmt::I010 => 24,
         }
     }
 
@@ -427,6 +466,10 @@ pub trait GoogleImage {
     fn stride(&self) -> Vec<i32>;
     fn planes(&self) -> Vec<*mut u8>;
     fn chroma(&self) -> Chroma;
+    // Some if the planes are 16-bit
+    fn high_bitdepth(&self) -> Option<hdr::HighBitDepth> {
+        None
+    }
     fn get_bytes_per_row(w: usize, fmt: ImageFormat, align: usize) -> usize {
         let bytes_per_pixel = match fmt {
             ImageFormat::Raw => 3,
@@ -438,10 +481,24 @@ pub trait GoogleImage {
     }
     // rgb [in/out] fmt and stride must be set in ImageRgb
     fn to(&self, rgb: &mut ImageRgb) {
+        self.to_with_color(rgb, None)
+    }
+    // The color of the 16-bit planes overrides the one of the bitstream, see hdr.rs
+    fn to_with_color(&self, rgb: &mut ImageRgb, video_color: Option<hdr::VideoColor>) {
         rgb.w = self.width();
         rgb.h = self.height();
         let bytes_per_row = Self::get_bytes_per_row(rgb.w, rgb.fmt, rgb.align());
         rgb.raw.resize(rgb.h * bytes_per_row, 0);
+        if let Some(mut color) = self.high_bitdepth() {
+            if let Some(video_color) = video_color {
+                color.primaries = video_color.primaries;
+                color.transfer = video_color.transfer;
+            }
+            if let Err(e) = hdr::to_rgb(self, color, rgb, bytes_per_row) {
+                log::error!("Failed to convert {color:?}: {e:?}");
+            }
+            return;
+        }
         let stride = self.stride();
         let planes = self.planes();
         unsafe {
@@ -0,0 +1,324 @@
+// Software H264 with OpenH264, used when no hardware H264 codec is available.
+// https://github.com/cisco/openh264
+//
+// Only Cisco's prebuilt library is covered by Cisco's H.264 patent license, so it's not built
+// from source or shipped with RustDesk. The library downloaded from Cisco is loaded at runtime
+// from the directory of the executable, the hash of it is checked by `openh264-sys2`.
+// http://www.openh264.org/BINARY_LICENSE.txt
+
+use crate::{
+    codec::{base_bitrate, EncoderApi, EncoderCfg, EncoderTuning},
+    CodecFormat, EncodeInput, EncodeYuvFormat, GoogleImage, Pixfmt, STRIDE_ALIGN,
+};
+use hbb_common::{
+    anyhow::anyhow,
+    bytes::Bytes,
+    log,
+    message_proto::{Chroma, EncodedVideoFrame, EncodedVideoFrames, VideoFrame},
+    ResultType,
+};
+use openh264::{
+    decoder::{DecodedYUV, Decoder, DecoderConfig},
+    encoder::{Encoder, EncoderConfig, FrameType, RateControlMode},
+    formats::YUVSource,
+    OpenH264API,
+};
+use openh264_sys2::{SBitrateInfo, ENCODER_OPTION_BITRATE, SPATIAL_LAYER_ALL};
+use std::path::PathBuf;
+
+const DEFAULT_FPS: f32 = 30.0;
+// H264 needs more bits than VP9 for the same quality.
+const BITRATE_FACTOR: f32 = 1.5;
+
+lazy_static::lazy_static! {
+    static ref LIBRARY_PATH: Option<PathBuf> = library_path();
+}
+
+// The version must be one of the hashes known by `openh264-sys2`.
+fn library_name() -> Option<&'static str> {
+    use std::env::consts::{ARCH, OS};
+    Some(match (OS, ARCH) {
+        ("windows", "x86_64") => "openh264-2.4.0-win64.dll",
+        ("windows", "x86") => "openh264-2.4.0-win32.dll",
+        ("linux", "x86_64") => "libopenh264-2.4.0-linux64.7.so",
+        ("linux", "aarch64") => "libopenh264-2.4.0-linux-arm64.7.so",
+        ("linux", "arm") => "libopenh264-2.4.0-linux-arm.7.so",
+        ("macos", "x86_64") => "libopenh264-2.4.0-mac-x64.dylib",
+        ("macos", "aarch64") => "libopenh264-2.4.0-mac-arm64.dylib",
+        _ => return None,
+    })
+}
+
+fn library_path() -> Option<PathBuf> {
+    let name = library_name()?;
+    let path = std::env::current_exe().ok()?.parent()?.join(name);
+    if path.exists() {
+        log::info!("found openh264 library: {path:?}");
+        Some(path)
+    } else {
+        log::info!("openh264 library not found: {path:?}");
+        None
+    }
+}
+
+// Whether Cisco's library is installed.
+pub fn available() -> bool {
+    LIBRARY_PATH.is_some()
+}
+
+fn load_api() -> ResultType<OpenH264API> {
+    let Some(path) = LIBRARY_PATH.as_ref() else {
+        return Err(anyhow!("openh264 library not found"));
+    };
+    OpenH264API::from_blob_path(path).map_err(|e| anyhow!("Failed to load {path:?}: {e}"))
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenH264EncoderConfig {
+    pub width: usize,
+    pub height: usize,
+    pub quality: f32,
+    pub keyframe_interval: Option<usize>,
+    pub tuning: EncoderTuning,
+}
+
+pub struct OpenH264Encoder {
+    encoder: Encoder,
+    yuvfmt: EncodeYuvFormat,
+    bitrate: u32, // kbps
+    config: OpenH264EncoderConfig,
+    frame_count: usize,
+}
+
+// The I420 data converted with `yuvfmt`.
+struct YuvInput<'a> {
+    data: &'a [u8],
+    fmt: &'a EncodeYuvFormat,
+}
+
+impl YUVSource for YuvInput<'_> {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.fmt.w, self.fmt.h)
+    }
+
+    fn strides(&self) -> (usize, usize, usize) {
+        (self.fmt.stride[0], self.fmt.stride[1], self.fmt.stride[2])
+    }
+
+    fn y(&self) -> &[u8] {
+        &self.data[..self.fmt.u]
+    }
+
+    fn u(&self) -> &[u8] {
+        &self.data[self.fmt.u..self.fmt.v]
+    }
+
+    fn v(&self) -> &[u8] {
+        &self.data[self.fmt.v..self.fmt.v + self.fmt.stride[2] * self.fmt.h / 2]
+    }
+}
+
+impl EncoderApi for OpenH264Encoder {
+    fn new(cfg: EncoderCfg, _i444: bool) -> ResultType<Self>
+    where
+        Self: Sized,
+    {
+        match cfg {
+            EncoderCfg::OpenH264(config) => {
+                // The width and height of I420 must be even.
+                let yuvfmt = Self::get_yuvfmt((config.width + 1) & !1, (config.height + 1) & !1);
+                let bitrate = config.tuning.clamp_bitrate(Self::bitrate(
+                    yuvfmt.w as _,
+                    yuvfmt.h as _,
+                    config.quality,
+                ));
+                let encoder_config = EncoderConfig::new()
+                    .set_bitrate_bps(bitrate * 1000)
+                    .max_frame_rate(DEFAULT_FPS)
+                    .rate_control_mode(RateControlMode::Bitrate)
+                    // The frames are dropped by the video service.
+                    .enable_skip_frame(false);
+                let encoder = Encoder::with_api_config(load_api()?, encoder_config)
+                    .map_err(|e| anyhow!("Failed to create openh264 encoder: {e}"))?;
+                Ok(Self {
+                    encoder,
+                    yuvfmt,
+                    bitrate,
+                    config,
+                    frame_count: 0,
+                })
+            }
+            _ => Err(anyhow!("encoder type mismatch")),
+        }
+    }
+
+    fn encode_to_message(&mut self, input: EncodeInput, ms: i64) -> ResultType<VideoFrame> {
+        let yuv = input.yuv()?;
+        if yuv.len() < self.yuvfmt.v + self.yuvfmt.stride[2] * self.yuvfmt.h / 2 {
+            return Err(anyhow!("len not enough"));
+        }
+        if let Some(interval) = self.config.keyframe_interval {
+            if self.frame_count > 0 && self.frame_count % interval.max(1) == 0 {
+                self.encoder.force_intra_frame();
+            }
+        }
+        self.frame_count += 1;
+        let input = YuvInput {
+            data: yuv,
+            fmt: &self.yuvfmt,
+        };
+        let bitstream = self
+            .encoder
+            .encode(&input)
+            .map_err(|e| anyhow!("Failed to encode: {e}"))?;
+        let key = bitstream.frame_type() == FrameType::IDR;
+        let data = bitstream.to_vec();
+        if data.is_empty() {
+            return Err(anyhow!("no valid frame"));
+        }
+        let mut vf = VideoFrame::new();
+        vf.set_h264s(EncodedVideoFrames {
+            frames: vec![EncodedVideoFrame {
+                data: Bytes::from(data),
+                pts: ms,
+                key,
+                ..Default::default()
+            }]
+            .into(),
+            ..Default::default()
+        });
+        Ok(vf)
+    }
+
+    fn yuvfmt(&self) -> EncodeYuvFormat {
+        self.yuvfmt.clone()
+    }
+
+    #[cfg(feature = "vram")]
+    fn input_texture(&self) -> bool {
+        false
+    }
+
+    fn set_quality(&mut self, ratio: f32) -> ResultType<()> {
+        let bitrate = self.config.tuning.clamp_bitrate(Self::bitrate(
+            self.yuvfmt.w as _,
+            self.yuvfmt.h as _,
+            ratio,
+        ));
+        if bitrate > 0 && bitrate != self.bitrate {
+            let mut info = SBitrateInfo {
+                iLayer: SPATIAL_LAYER_ALL,
+                iBitrate: (bitrate * 1000) as _,
+            };
+            let ret = unsafe {
+                self.encoder
+                    .raw_api()
+                    .set_option(ENCODER_OPTION_BITRATE, &mut info as *mut _ as _)
+            };
+            if ret != 0 {
+                return Err(anyhow!("Failed to set openh264 bitrate, ret: {ret}"));
+            }
+            self.bitrate = bitrate;
+        }
+        self.config.quality = ratio;
+        Ok(())
+    }
+
+    fn bitrate(&self) -> u32 {
+        self.bitrate
+    }
+
+    fn support_changing_quality(&self) -> bool {
+        true
+    }
+
+    fn latency_free(&self) -> bool {
+        true
+    }
+
+    fn is_hardware(&self) -> bool {
+        false
+    }
+
+    fn disable(&self) {}
+}
+
+impl OpenH264Encoder {
+    #[inline]
+    fn bitrate(width: u32, height: u32, ratio: f32) -> u32 {
+        (base_bitrate(width, height) as f32 * ratio * BITRATE_FACTOR) as u32
+    }
+
+    fn get_yuvfmt(width: usize, height: usize) -> EncodeYuvFormat {
+        let align = |x: usize| (x + STRIDE_ALIGN - 1) / STRIDE_ALIGN * STRIDE_ALIGN;
+        let stride_y = align(width);
+        let stride_uv = align(width / 2);
+        let u = stride_y * height;
+        EncodeYuvFormat {
+            pixfmt: Pixfmt::I420,
+            w: width,
+            h: height,
+            stride: vec![stride_y, stride_uv, stride_uv],
+            u,
+            v: u + stride_uv * height / 2,
+        }
+    }
+}
+
+pub struct OpenH264Decoder {
+    decoder: Decoder,
+}
+
+impl OpenH264Decoder {
+    pub fn new(format: CodecFormat) -> ResultType<Self> {
+        if format != CodecFormat::H264 {
+            return Err(anyhow!("unsupported format: {:?}", format));
+        }
+        let decoder = Decoder::with_api_config(load_api()?, DecoderConfig::new())
+            .map_err(|e| anyhow!("Failed to create decoder: {e}"))?;
+        log::info!("create openh264 decoder");
+        Ok(Self { decoder })
+    }
+
+    // None if more data is needed.
+    pub fn decode<'a>(&'a mut self, data: &[u8]) -> ResultType<Option<Image<'a>>> {
+        self.decoder
+            .decode(data)
+            .map(|yuv| yuv.map(Image))
+            .map_err(|e| anyhow!("Failed to decode: {e}"))
+    }
+}
+
+pub struct Image<'a>(DecodedYUV<'a>);
+
+impl GoogleImage for Image<'_> {
+    #[inline]
+    fn width(&self) -> usize {
+        self.0.dimensions().0
+    }
+
+    #[inline]
+    fn height(&self) -> usize {
+        self.0.dimensions().1
+    }
+
+    #[inline]
+    fn stride(&self) -> Vec<i32> {
+        let (y, u, v) = self.0.strides();
+        vec![y as _, u as _, v as _]
+    }
+
+    // The planes are only read when converting to rgb.
+    #[inline]
+    fn planes(&self) -> Vec<*mut u8> {
+        vec![
+            self.0.y().as_ptr() as _,
+            self.0.u().as_ptr() as _,
+            self.0.v().as_ptr() as _,
+        ]
+    }
+
+    fn chroma(&self) -> Chroma {
+        Chroma::I420
+    }
+}
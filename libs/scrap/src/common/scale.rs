@@ -0,0 +1,182 @@
+// Encoding at a lower resolution when the bandwidth is too low for the full resolution.
+// The captured frames are scaled down before being converted to yuv, and the client scales the
+// decoded frames up to the original size, so the display geometry and mouse coordinates are not changed.
+
+use crate::{
+    generate_call_macro, ARGBScale, FilterMode, ImageFormat, ImageRgb, Pixfmt, TraitPixelBuffer,
+};
+use hbb_common::{bail, ResultType};
+
+generate_call_macro!(call_yuv, false);
+
+// The encoding size, which is even for the yuv formats.
+pub fn scaled_size(width: usize, height: usize, scale: f32) -> (usize, usize) {
+    let scale = scale.clamp(0.1, 1.0);
+    let f = |v: usize| ((v as f32 * scale).round() as usize & !1).max(2);
+    (f(width), f(height))
+}
+
+// The captured frame scaled down, always 4 bytes per pixel.
+pub struct ScaledPixelBuffer<'a> {
+    data: &'a [u8],
+    pixfmt: Pixfmt,
+    width: usize,
+    height: usize,
+}
+
+impl TraitPixelBuffer for ScaledPixelBuffer<'_> {
+    fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn stride(&self) -> Vec<usize> {
+        vec![self.width * 4]
+    }
+
+    fn pixfmt(&self) -> Pixfmt {
+        self.pixfmt
+    }
+}
+
+pub fn scale_pixelbuffer<'a>(
+    captured: &impl TraitPixelBuffer,
+    width: usize,
+    height: usize,
+    dst: &'a mut Vec<u8>,
+    mid_data: &mut Vec<u8>,
+) -> ResultType<ScaledPixelBuffer<'a>> {
+    let src_width = captured.width();
+    let src_height = captured.height();
+    let (src, src_stride, pixfmt) = match captured.pixfmt() {
+        Pixfmt::BGRA | Pixfmt::RGBA => (captured.data(), captured.stride()[0], captured.pixfmt()),
+        Pixfmt::RGB565LE => {
+            let mid_stride = src_width * 4;
+            mid_data.resize(mid_stride * src_height, 0);
+            call_yuv!(crate::RGB565ToARGB(
+                captured.data().as_ptr(),
+                captured.stride()[0] as _,
+                mid_data.as_mut_ptr(),
+                mid_stride as _,
+                src_width as _,
+                src_height as _,
+            ));
+            (&mid_data[..], mid_stride, Pixfmt::BGRA)
+        }
+        pixfmt => bail!("unsupported pixfmt for scaling: {pixfmt:?}"),
+    };
+    if src.len() < src_stride * src_height {
+        bail!(
+            "wrong src len, {} < {} * {}",
+            src.len(),
+            src_stride,
+            src_height
+        );
+    }
+    dst.resize(width * height * 4, 0);
+    call_yuv!(ARGBScale(
+        src.as_ptr(),
+        src_stride as _,
+        src_width as _,
+        src_height as _,
+        dst.as_mut_ptr(),
+        (width * 4) as _,
+        width as _,
+        height as _,
+        FilterMode::kFilterBox,
+    ));
+    Ok(ScaledPixelBuffer {
+        data: dst,
+        pixfmt,
+        width,
+        height,
+    })
+}
+
+// Scale the decoded image up to the original size, `tmp` is reused between the frames.
+// The padding of the odd sizes is cropped instead, e.g. OpenH264 only encodes the even sizes.
+pub fn scale_rgb(
+    rgb: &mut ImageRgb,
+    width: usize,
+    height: usize,
+    tmp: &mut Vec<u8>,
+) -> ResultType<()> {
+    if rgb.w == width && rgb.h == height {
+        return Ok(());
+    }
+    if matches!(rgb.fmt(), ImageFormat::Raw) {
+        bail!("unsupported format for scaling: {:?}", rgb.fmt());
+    }
+    if rgb.w == 0 || rgb.h == 0 || width == 0 || height == 0 {
+        bail!(
+            "invalid size: ({}, {}) -> ({width}, {height})",
+            rgb.w,
+            rgb.h
+        );
+    }
+    let src_stride = rgb.raw.len() / rgb.h;
+    let align = rgb.align().max(1);
+    let dst_stride = (width * 4 + align - 1) / align * align;
+    tmp.resize(dst_stride * height, 0);
+    if (rgb.w == width || rgb.w == width + 1) && (rgb.h == height || rgb.h == height + 1) {
+        for (src, dst) in rgb
+            .raw
+            .chunks(src_stride)
+            .zip(tmp.chunks_mut(dst_stride))
+            .take(height)
+        {
+            dst[..width * 4].copy_from_slice(&src[..width * 4]);
+        }
+        std::mem::swap(&mut rgb.raw, tmp);
+        rgb.w = width;
+        rgb.h = height;
+        return Ok(());
+    }
+    call_yuv!(ARGBScale(
+        rgb.raw.as_ptr(),
+        src_stride as _,
+        rgb.w as _,
+        rgb.h as _,
+        tmp.as_mut_ptr(),
+        dst_stride as _,
+        width as _,
+        height as _,
+        FilterMode::kFilterBilinear,
+    ));
+    std::mem::swap(&mut rgb.raw, tmp);
+    rgb.w = width;
+    rgb.h = height;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!(scaled_size(3840, 2160, 1.0), (3840, 2160));
+        assert_eq!(scaled_size(3840, 2160, 0.5), (1920, 1080));
+        assert_eq!(scaled_size(1366, 768, 2.0 / 3.0), (910, 512));
+        assert_eq!(scaled_size(1, 1, 0.5), (2, 2));
+    }
+
+    #[test]
+    fn test_crop_padding() {
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        rgb.w = 4;
+        rgb.h = 2;
+        rgb.raw = (0..32).collect();
+        let mut tmp = Vec::new();
+        scale_rgb(&mut rgb, 3, 1, &mut tmp).unwrap();
+        assert_eq!((rgb.w, rgb.h), (3, 1));
+        assert_eq!(rgb.raw, (0..12).collect::<Vec<u8>>());
+    }
+}
@@ -8,7 +8,9 @@ use hbb_common::log;
 use hbb_common::message_proto::{Chroma, EncodedVideoFrame, EncodedVideoFrames, VideoFrame};
 use hbb_common::ResultType;
 
-use crate::codec::{base_bitrate, codec_thread_num, EncoderApi};
+use crate::codec::{base_bitrate, codec_thread_num, EncoderApi, EncoderTuning};
+use crate::dirty::Roi;
+use crate::hdr::{HighBitDepth, Primaries, Transfer};
 use crate::{EncodeInput, EncodeYuvFormat, GoogleImage, Pixfmt, STRIDE_ALIGN};
 
 use super::vpx::{vp8e_enc_control_id::*, vpx_codec_err_t::*, *};
@@ -20,6 +22,9 @@ use std::{ptr, slice};
 generate_call_macro!(call_vpx, false);
 generate_call_ptr_macro!(call_vpx_ptr);
 
+// Lower quantizer for the cursor and typing area, range [-63, 63].
+const ROI_FOCUS_DELTA_Q: c_int = -15;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum VpxVideoCodecId {
     VP8,
@@ -38,7 +43,9 @@ pub struct VpxEncoder {
     height: usize,
     id: VpxVideoCodecId,
     i444: bool,
+    hdr: bool,
     yuvfmt: EncodeYuvFormat,
+    tuning: EncoderTuning,
 }
 
 pub struct VpxDecoder {
@@ -52,6 +59,9 @@ impl EncoderApi for VpxEncoder {
     {
         match cfg {
             crate::codec::EncoderCfg::VPX(config) => {
+                // 10-bit is VP9 profile 2, 4:2:0 only
+                let hdr = config.hdr && config.codec == VpxVideoCodecId::VP9;
+                let i444 = i444 && !hdr;
                 let i = match config.codec {
                     VpxVideoCodecId::VP8 => call_vpx_ptr!(vpx_codec_vp8_cx()),
                     VpxVideoCodecId::VP9 => call_vpx_ptr!(vpx_codec_vp9_cx()),
@@ -71,7 +81,7 @@ impl EncoderApi for VpxEncoder {
                 // When the data buffer falls below this percentage of fullness, a dropped frame is indicated. Set the threshold to zero (0) to disable this feature.
                 // In dynamic scenes, low bitrate gets low fps while high bitrate gets high fps.
                 c.rc_dropframe_thresh = 25;
-                c.g_threads = codec_thread_num(64) as _;
+                c.g_threads = config.tuning.thread_num(64) as _;
                 c.g_error_resilient = VPX_ERROR_RESILIENT_DEFAULT;
                 // https://developers.google.com/media/vp9/bitrate-modes/
                 // Constant Bitrate mode (CBR) is recommended for live streaming with VP9.
@@ -86,15 +96,24 @@ impl EncoderApi for VpxEncoder {
                 let (q_min, q_max) = Self::calc_q_values(config.quality);
                 c.rc_min_quantizer = q_min;
                 c.rc_max_quantizer = q_max;
-                c.rc_target_bitrate =
-                    Self::bitrate(config.width as _, config.height as _, config.quality);
+                c.rc_target_bitrate = config.tuning.clamp_bitrate(Self::bitrate(
+                    config.width as _,
+                    config.height as _,
+                    config.quality,
+                ));
                 // https://chromium.googlesource.com/webm/libvpx/+/refs/heads/main/vp9/common/vp9_enums.h#29
                 // https://chromium.googlesource.com/webm/libvpx/+/refs/heads/main/vp8/vp8_cx_iface.c#282
-                c.g_profile = if i444 && config.codec == VpxVideoCodecId::VP9 {
+                c.g_profile = if hdr {
+                    2
+                } else if i444 && config.codec == VpxVideoCodecId::VP9 {
                     1
                 } else {
                     0
                 };
+                if hdr {
+                    c.g_bit_depth = vpx_bit_depth::VPX_BITS_10;
+                    c.g_input_bit_depth = 10;
+                }
 
                 /*
                 The VPX encoder supports two-pass encoding for rate control purposes.
@@ -105,11 +124,12 @@ impl EncoderApi for VpxEncoder {
                 */
 
                 let mut ctx = Default::default();
+                let flags = if hdr { VPX_CODEC_USE_HIGHBITDEPTH } else { 0 };
                 call_vpx!(vpx_codec_enc_init_ver(
                     &mut ctx,
                     i,
                     &c,
-                    0,
+                    flags as _,
                     VPX_ENCODER_ABI_VERSION as _
                 ));
 
@@ -129,7 +149,11 @@ impl EncoderApi for VpxEncoder {
                     Higher numbers (7 or 8) will be lower quality but more manageable for lower latency
                     use cases and also for lower CPU power devices such as mobile.
                     */
-                    call_vpx!(vpx_codec_control_(&mut ctx, VP8E_SET_CPUUSED as _, 7,));
+                    call_vpx!(vpx_codec_control_(
+                        &mut ctx,
+                        VP8E_SET_CPUUSED as _,
+                        config.tuning.speed(7, 9),
+                    ));
                     // set row level multi-threading
                     /*
                     as some people in comments and below have already commented,
@@ -152,10 +176,22 @@ impl EncoderApi for VpxEncoder {
                         VP9E_SET_TILE_COLUMNS as _,
                         4 as c_int
                     ));
+                    // VP9 has no transfer in the bitstream, it's sent in a message, see hdr.rs
+                    if hdr {
+                        call_vpx!(vpx_codec_control_(
+                            &mut ctx,
+                            VP9E_SET_COLOR_SPACE as _,
+                            vpx_color_space::VPX_CS_BT_2020 as c_int
+                        ));
+                    }
                 } else if config.codec == VpxVideoCodecId::VP8 {
                     // https://github.com/webmproject/libvpx/blob/972149cafeb71d6f08df89e91a0130d6a38c4b15/vpx/vp8cx.h#L172
                     // https://groups.google.com/a/webmproject.org/g/webm-discuss/c/DJhSrmfQ61M
-                    call_vpx!(vpx_codec_control_(&mut ctx, VP8E_SET_CPUUSED as _, 12,));
+                    call_vpx!(vpx_codec_control_(
+                        &mut ctx,
+                        VP8E_SET_CPUUSED as _,
+                        config.tuning.speed(12, 16),
+                    ));
                 }
 
                 Ok(Self {
@@ -164,7 +200,9 @@ impl EncoderApi for VpxEncoder {
                     height: config.height as _,
                     id: config.codec,
                     i444,
-                    yuvfmt: Self::get_yuvfmt(config.width, config.height, i444),
+                    hdr,
+                    yuvfmt: Self::get_yuvfmt(config.width, config.height, i444, hdr),
+                    tuning: config.tuning,
                 })
             }
             _ => Err(anyhow!("encoder type mismatch")),
@@ -205,7 +243,9 @@ impl EncoderApi for VpxEncoder {
         let (q_min, q_max) = Self::calc_q_values(ratio);
         c.rc_min_quantizer = q_min;
         c.rc_max_quantizer = q_max;
-        c.rc_target_bitrate = Self::bitrate(self.width as _, self.height as _, ratio);
+        c.rc_target_bitrate = self
+            .tuning
+            .clamp_bitrate(Self::bitrate(self.width as _, self.height as _, ratio));
         call_vpx!(vpx_codec_enc_config_set(&mut self.ctx, &c));
         Ok(())
     }
@@ -228,19 +268,69 @@ impl EncoderApi for VpxEncoder {
     }
 
     fn disable(&self) {}
+
+    fn support_roi(&self) -> bool {
+        true
+    }
+
+    fn set_roi(&mut self, roi: Option<&Roi>) -> ResultType<()> {
+        // The active map is in 16x16 macroblocks, null to disable it.
+        let (mut active, rows, cols) = match roi {
+            Some(roi) if !roi.active.is_full() => roi.active.blocks(16),
+            _ => (vec![], (self.height + 15) / 16, (self.width + 15) / 16),
+        };
+        let mut map = vpx_active_map_t {
+            active_map: if active.is_empty() {
+                ptr::null_mut()
+            } else {
+                active.as_mut_ptr()
+            },
+            rows: rows as _,
+            cols: cols as _,
+        };
+        call_vpx!(vpx_codec_control_(
+            &mut self.ctx,
+            VP8E_SET_ACTIVEMAP as _,
+            &mut map as *mut vpx_active_map_t
+        ));
+        if self.id == VpxVideoCodecId::VP9 {
+            // The roi map is in 8x8 blocks, segment 1 is the focus.
+            // It is disabled if all the features are unset.
+            let focus = roi.and_then(|roi| roi.focus.as_ref());
+            let (mut segments, rows, cols) = match focus {
+                Some(focus) => focus.blocks(8),
+                None => {
+                    let (rows, cols) = ((self.height + 7) / 8, (self.width + 7) / 8);
+                    (vec![0; rows * cols], rows, cols)
+                }
+            };
+            let mut map: vpx_roi_map_t = unsafe { std::mem::zeroed() };
+            map.roi_map = segments.as_mut_ptr();
+            map.rows = rows as _;
+            map.cols = cols as _;
+            // -1 for no reference frame feature, 0 is intra only.
+            map.ref_frame = [-1; 8];
+            if focus.is_some() {
+                map.enabled = 1;
+                map.delta_q[1] = ROI_FOCUS_DELTA_Q;
+            }
+            call_vpx!(vpx_codec_control_(
+                &mut self.ctx,
+                VP9E_SET_ROI_MAP as _,
+                &mut map as *mut vpx_roi_map_t
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl VpxEncoder {
     pub fn encode<'a>(&'a mut self, pts: i64, data: &[u8], stride_align: usize) -> Result<EncodeFrames<'a>> {
-        let bpp = if self.i444 { 24 } else { 12 };
+        let bpp = if self.i444 || self.hdr { 24 } else { 12 };
         if data.len() < self.width * self.height * bpp / 8 {
             return Err(Error::FailedCall("len not enough".to_string()));
         }
-        let fmt = if self.i444 {
-            vpx_img_fmt::VPX_IMG_FMT_I444
-        } else {
-            vpx_img_fmt::VPX_IMG_FMT_I420
-        };
+        let fmt = Self::img_fmt(self.i444, self.hdr);
 
         let mut image = Default::default();
         call_vpx_ptr!(vpx_img_wrap(
@@ -251,6 +341,9 @@ impl VpxEncoder {
             stride_align as _,
             data.as_ptr() as _,
         ));
+        if self.hdr {
+            image.bit_depth = 10;
+        }
 
         call_vpx!(vpx_codec_encode(
             &mut self.ctx,
@@ -336,13 +429,19 @@ impl VpxEncoder {
         (q_min, q_max)
     }
 
-    fn get_yuvfmt(width: u32, height: u32, i444: bool) -> EncodeYuvFormat {
-        let mut img = Default::default();
-        let fmt = if i444 {
+    fn img_fmt(i444: bool, hdr: bool) -> vpx_img_fmt_t {
+        if hdr {
+            vpx_img_fmt::VPX_IMG_FMT_I42016
+        } else if i444 {
             vpx_img_fmt::VPX_IMG_FMT_I444
         } else {
             vpx_img_fmt::VPX_IMG_FMT_I420
-        };
+        }
+    }
+
+    fn get_yuvfmt(width: u32, height: u32, i444: bool, hdr: bool) -> EncodeYuvFormat {
+        let mut img = Default::default();
+        let fmt = Self::img_fmt(i444, hdr);
         unsafe {
             vpx_img_wrap(
                 &mut img,
@@ -353,7 +452,13 @@ impl VpxEncoder {
                 0x1 as _,
             );
         }
-        let pixfmt = if i444 { Pixfmt::I444 } else { Pixfmt::I420 };
+        let pixfmt = if hdr {
+            Pixfmt::I010
+        } else if i444 {
+            Pixfmt::I444
+        } else {
+            Pixfmt::I420
+        };
         EncodeYuvFormat {
             pixfmt,
             w: img.w as _,
@@ -398,6 +503,10 @@ pub struct VpxEncoderConfig {
     pub codec: VpxVideoCodecId,
     /// keyframe interval
     pub keyframe_interval: Option<usize>,
+    /// The admin tuning
+    pub tuning: EncoderTuning,
+    /// 10-bit HDR10, VP9 only
+    pub hdr: bool,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -580,10 +689,27 @@ impl GoogleImage for Image {
 
     fn chroma(&self) -> Chroma {
         match self.inner().fmt {
-            vpx_img_fmt::VPX_IMG_FMT_I444 => Chroma::I444,
+            vpx_img_fmt::VPX_IMG_FMT_I444 | vpx_img_fmt::VPX_IMG_FMT_I44416 => Chroma::I444,
             _ => Chroma::I420,
         }
     }
+
+    fn high_bitdepth(&self) -> Option<HighBitDepth> {
+        let img = self.inner();
+        if img.fmt as u32 & VPX_IMG_FMT_HIGHBITDEPTH == 0 {
+            return None;
+        }
+        // The transfer is unknown, `to_with_color` takes the one sent by the server
+        Some(HighBitDepth {
+            bit_depth: img.bit_depth as _,
+            primaries: if img.cs == vpx_color_space::VPX_CS_BT_2020 {
+                Primaries::Bt2020
+            } else {
+                Primaries::Bt709
+            },
+            transfer: Transfer::Sdr,
+        })
+    }
 }
 
 impl Drop for Image {
@@ -1,4 +1,4 @@
-use crate::{common::TraitCapturer, x11, Frame, Pixfmt, TraitPixelBuffer};
+use crate::{common::TraitCapturer, dirty::DirtyRect, x11, Frame, Pixfmt, TraitPixelBuffer};
 use std::{io, time::Duration};
 
 pub struct Capturer(x11::Capturer);
@@ -24,12 +24,11 @@ impl TraitCapturer for Capturer {
         let width = self.width();
         let height = self.height();
         let pixfmt = self.0.display().pixfmt();
-        Ok(Frame::PixelBuffer(PixelBuffer::new(
-            self.0.frame()?,
-            pixfmt,
-            width,
-            height,
-        )))
+        let (data, dirty_rects) = self.0.frame()?;
+        Ok(Frame::PixelBuffer(PixelBuffer {
+            dirty_rects,
+            ..PixelBuffer::new(data, pixfmt, width, height)
+        }))
     }
 }
 
@@ -39,6 +38,7 @@ pub struct PixelBuffer<'a> {
     width: usize,
     height: usize,
     stride: Vec<usize>,
+    dirty_rects: Option<Vec<DirtyRect>>,
 }
 
 impl<'a> PixelBuffer<'a> {
@@ -52,6 +52,7 @@ impl<'a> PixelBuffer<'a> {
             width,
             height,
             stride,
+            dirty_rects: None,
         }
     }
 }
@@ -76,6 +77,10 @@ impl<'a> TraitPixelBuffer for PixelBuffer<'a> {
     fn pixfmt(&self) -> crate::Pixfmt {
         self.pixfmt
     }
+
+    fn dirty_rects(&self) -> Option<Vec<DirtyRect>> {
+        self.dirty_rects.clone()
+    }
 }
 
 pub struct Display(x11::Display);
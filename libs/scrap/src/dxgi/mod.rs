@@ -7,6 +7,9 @@ use winapi::{
     shared::{
         dxgi::*,
         dxgi1_2::*,
+        dxgi1_5::IDXGIOutput5,
+        dxgi1_6::{IDXGIOutput6, DXGI_OUTPUT_DESC1},
+        dxgiformat::DXGI_FORMAT_R16G16B16A16_FLOAT,
         dxgitype::*,
         minwindef::{DWORD, FALSE, TRUE, UINT},
         ntdef::LONG,
@@ -18,11 +21,12 @@ use winapi::{
         d3d11::*, d3dcommon::D3D_DRIVER_TYPE_UNKNOWN, unknwnbase::IUnknown, wingdi::*,
         winnt::HRESULT, winuser::*,
     },
+    Interface,
 };
 
 use crate::RotationMode::*;
 
-use crate::{AdapterDevice, Frame, PixelBuffer};
+use crate::{AdapterDevice, Frame, PixelBuffer, Pixfmt};
 use std::ffi::c_void;
 
 pub struct ComPtr<T>(*mut T);
@@ -58,6 +62,7 @@ pub struct Capturer {
     output_texture: bool,
     adapter_desc1: DXGI_ADAPTER_DESC1,
     rotate: Rotate,
+    hdr: bool,
 }
 
 impl Capturer {
@@ -174,6 +179,7 @@ impl Capturer {
             output_texture: false,
             adapter_desc1,
             rotate,
+            hdr: false,
         })
     }
 
@@ -324,6 +330,76 @@ impl Capturer {
         self.gdi_capturer.take();
     }
 
+    // Duplicate in 16-bit float scRGB if the display is in HDR mode, see hdr.rs
+    pub fn set_hdr(&mut self, hdr: bool) -> bool {
+        let hdr = hdr
+            && !self.is_gdi()
+            && !self.output_texture
+            && !self.duplication.is_null()
+            && matches!(
+                self.display.rotation(),
+                DXGI_MODE_ROTATION_IDENTITY | DXGI_MODE_ROTATION_UNSPECIFIED
+            )
+            && self.display.is_hdr();
+        if hdr == self.hdr {
+            return hdr;
+        }
+        unsafe {
+            self.unmap();
+            self.surface = ComPtr(ptr::null_mut());
+            // Only one duplication of an output is allowed
+            self.duplication = ComPtr(ptr::null_mut());
+            let duplication = match self.duplicate(hdr) {
+                Ok(duplication) => {
+                    self.hdr = hdr;
+                    duplication
+                }
+                Err(e) => {
+                    println!("Failed to duplicate output, hdr: {hdr}, {e:?}");
+                    self.hdr = false;
+                    match self.duplicate(false) {
+                        Ok(duplication) => duplication,
+                        Err(_) => return false,
+                    }
+                }
+            };
+            #[allow(invalid_value)]
+            let mut desc = mem::MaybeUninit::uninit().assume_init();
+            (*duplication.0).GetDesc(&mut desc);
+            self.fastlane = desc.DesktopImageInSystemMemory == TRUE;
+            self.duplication = duplication;
+        }
+        self.hdr
+    }
+
+    unsafe fn duplicate(&self, hdr: bool) -> io::Result<ComPtr<IDXGIOutputDuplication>> {
+        let mut duplication = ptr::null_mut();
+        if hdr {
+            let mut output5: *mut IDXGIOutput5 = ptr::null_mut();
+            (*self.display.inner.0).QueryInterface(
+                &IDXGIOutput5::uuidof(),
+                &mut output5 as *mut *mut _ as *mut *mut _,
+            );
+            if output5.is_null() {
+                return Err(io::ErrorKind::Unsupported.into());
+            }
+            let output5 = ComPtr(output5);
+            let formats = [DXGI_FORMAT_R16G16B16A16_FLOAT];
+            wrap_hresult((*output5.0).DuplicateOutput1(
+                self.device.0 as *mut _,
+                0,
+                formats.len() as _,
+                formats.as_ptr(),
+                &mut duplication,
+            ))?;
+        } else {
+            wrap_hresult(
+                (*self.display.inner.0).DuplicateOutput(self.device.0 as *mut _, &mut duplication),
+            )?;
+        }
+        Ok(ComPtr(duplication))
+    }
+
     #[cfg(feature = "vram")]
     pub fn set_output_texture(&mut self, texture: bool) {
         self.output_texture = texture;
@@ -396,8 +472,15 @@ impl Capturer {
         } else {
             let width = self.width;
             let height = self.height;
-            Ok(Frame::PixelBuffer(PixelBuffer::with_BGRA(
+            // GDI is always BGRA
+            let pixfmt = if self.hdr && !self.is_gdi() {
+                Pixfmt::RGBAF16
+            } else {
+                Pixfmt::BGRA
+            };
+            Ok(Frame::PixelBuffer(PixelBuffer::new(
                 self.get_pixelbuffer(timeout)?,
+                pixfmt,
                 width,
                 height,
             )))
@@ -809,6 +892,27 @@ impl Display {
         self.desc.Rotation
     }
 
+    // The display is in HDR mode, whose desktop is composed in BT.2020 PQ
+    pub fn is_hdr(&self) -> bool {
+        if self.inner.is_null() {
+            return false;
+        }
+        unsafe {
+            let mut output6: *mut IDXGIOutput6 = ptr::null_mut();
+            (*self.inner.0).QueryInterface(
+                &IDXGIOutput6::uuidof(),
+                &mut output6 as *mut *mut _ as *mut *mut _,
+            );
+            if output6.is_null() {
+                return false;
+            }
+            let output6 = ComPtr(output6);
+            let mut desc: DXGI_OUTPUT_DESC1 = mem::zeroed();
+            wrap_hresult((*output6.0).GetDesc1(&mut desc)).is_ok()
+                && desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020
+        }
+    }
+
     fn create_gdi(&self) -> Option<CapturerGDI> {
         if let Ok(res) = CapturerGDI::new(self.name(), self.width(), self.height()) {
             Some(res)
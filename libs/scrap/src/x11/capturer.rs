@@ -1,8 +1,105 @@
 use super::ffi::*;
 use super::Display;
+use crate::dirty::DirtyRect;
 use hbb_common::libc;
 use std::{io, ptr, slice};
 
+// Too many small rectangles cost more than diffing the frame.
+const MAX_DAMAGE_RECTS: usize = 256;
+
+// The areas of the root window changed since the last frame, reported by XDamage.
+struct Damage {
+    id: xcb_damage_damage_t,
+    first_event: u8,
+    // None before the first frame, the whole frame is dirty.
+    rects: Option<Vec<DirtyRect>>,
+}
+
+impl Damage {
+    fn new(display: &Display) -> Option<Damage> {
+        let server = display.server().raw();
+        unsafe {
+            let ext = xcb_get_extension_data(server, ptr::addr_of_mut!(xcb_damage_id));
+            if ext.is_null() || (*ext).present == 0 {
+                return None;
+            }
+            let first_event = (*ext).first_event;
+            // The version must be negotiated before using the extension.
+            let reply = xcb_damage_query_version_reply(
+                server,
+                xcb_damage_query_version(server, 1, 1),
+                ptr::null_mut(),
+            );
+            if reply.is_null() {
+                return None;
+            }
+            libc::free(reply as *mut _);
+            let id = xcb_generate_id(server);
+            xcb_damage_create(
+                server,
+                id,
+                display.root(),
+                XCB_DAMAGE_REPORT_LEVEL_RAW_RECTANGLES,
+            );
+            xcb_flush(server);
+            Some(Damage {
+                id,
+                first_event,
+                rects: None,
+            })
+        }
+    }
+
+    fn poll(&mut self, display: &Display) {
+        let server = display.server().raw();
+        let rect = display.rect();
+        loop {
+            let event = unsafe { xcb_poll_for_event(server) };
+            if event.is_null() {
+                break;
+            }
+            unsafe {
+                if (*event).response_type & 0x7f == self.first_event + XCB_DAMAGE_NOTIFY {
+                    let notify = &*(event as *const xcb_damage_notify_event_t);
+                    if notify.damage == self.id {
+                        self.add(notify.area, rect);
+                    }
+                }
+                libc::free(event as *mut _);
+            }
+        }
+    }
+
+    // `area` is in the root window, `rect` is the display in the root window.
+    fn add(&mut self, area: xcb_rectangle_t, rect: super::Rect) {
+        let Some(rects) = self.rects.as_mut() else {
+            return;
+        };
+        let x0 = (area.x as i32).max(rect.x as i32);
+        let y0 = (area.y as i32).max(rect.y as i32);
+        let x1 = (area.x as i32 + area.width as i32).min(rect.x as i32 + rect.w as i32);
+        let y1 = (area.y as i32 + area.height as i32).min(rect.y as i32 + rect.h as i32);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        if rects.len() >= MAX_DAMAGE_RECTS {
+            self.rects = None;
+            return;
+        }
+        rects.push(DirtyRect {
+            x: (x0 - rect.x as i32) as _,
+            y: (y0 - rect.y as i32) as _,
+            w: (x1 - x0) as _,
+            h: (y1 - y0) as _,
+        });
+    }
+
+    // None if the changed areas are unknown.
+    fn take(&mut self) -> Option<Vec<DirtyRect>> {
+        self.rects.replace(Vec::new())
+    }
+}
+
 pub struct Capturer {
     display: Display,
     shmid: i32,
@@ -11,6 +108,7 @@ pub struct Capturer {
 
     size: usize,
     saved_raw_data: Vec<u8>, // for faster compare and copy
+    damage: Option<Damage>,
 }
 
 impl Capturer {
@@ -57,6 +155,11 @@ impl Capturer {
             );
         }
 
+        let damage = Damage::new(&display);
+        if damage.is_none() {
+            hbb_common::log::info!("XDamage is not available, diff the frames instead");
+        }
+
         let c = Capturer {
             display,
             shmid,
@@ -64,6 +167,7 @@ impl Capturer {
             buffer,
             size,
             saved_raw_data: Vec::new(),
+            damage,
         };
         Ok(c)
     }
@@ -93,17 +197,43 @@ impl Capturer {
         }
     }
 
-    pub fn frame<'b>(&'b mut self) -> std::io::Result<&'b [u8]> {
+    // The frame and the areas changed since the last frame, if they are known.
+    pub fn frame<'b>(&'b mut self) -> std::io::Result<(&'b [u8], Option<Vec<DirtyRect>>)> {
+        // Take the changes before capturing. The changes made after polling are in this image,
+        // and are reported again with the next frame.
+        let dirty_rects = match self.damage.as_mut() {
+            Some(damage) => {
+                damage.poll(&self.display);
+                damage.take()
+            }
+            None => None,
+        };
         self.get_image();
         let result = unsafe { slice::from_raw_parts(self.buffer, self.size) };
-        crate::would_block_if_equal(&mut self.saved_raw_data, result)?;
-        Ok(result)
+        match dirty_rects {
+            // The changes are known, no need to compare the whole frame.
+            Some(rects) => {
+                if rects.is_empty() {
+                    return Err(std::io::ErrorKind::WouldBlock.into());
+                }
+                // The saved frame is outdated, don't compare the next frame with it.
+                self.saved_raw_data.clear();
+                Ok((result, Some(rects)))
+            }
+            None => {
+                crate::would_block_if_equal(&mut self.saved_raw_data, result)?;
+                Ok((result, None))
+            }
+        }
     }
 }
 
 impl Drop for Capturer {
     fn drop(&mut self) {
         unsafe {
+            if let Some(damage) = self.damage.as_ref() {
+                xcb_damage_destroy(self.display.server().raw(), damage.id);
+            }
             // Detach segment from XCB.
             xcb_shm_detach(self.display.server().raw(), self.xcbid);
             // Detach segment from our space.
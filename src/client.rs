@@ -75,6 +75,7 @@ use hbb_common::{
 pub use helper::*;
 use scrap::{
     codec::Decoder,
+    lossless::{TileCompositor, TileUpdate},
     record::{Recorder, RecorderContext},
     CodecFormat, ImageFormat, ImageRgb, ImageTexture,
 };
@@ -89,9 +90,12 @@ use crate::ui_session_interface::SessionPermissionConfig;
 
 pub use super::lang::*;
 
+pub mod clipboard_history;
 pub mod file_trait;
 pub mod helper;
 pub mod io_loop;
+pub mod keystrokes;
+pub mod macros;
 pub mod screenshot;
 
 pub const MILLI1: Duration = Duration::from_millis(1);
@@ -1546,6 +1550,8 @@ pub struct VideoHandler {
     _display: usize, // useful for debug
     fail_counter: usize,
     first_frame: bool,
+    lossless_tiles: TileCompositor,
+    scaled_rgb: Vec<u8>,
 }
 
 impl VideoHandler {
@@ -1578,6 +1584,8 @@ impl VideoHandler {
             _display,
             fail_counter: 0,
             first_frame: true,
+            lossless_tiles: Default::default(),
+            scaled_rgb: Vec::new(),
         }
     }
 
@@ -1635,6 +1643,11 @@ impl VideoHandler {
         }
     }
 
+    /// Scale the frame encoded at a lower resolution up to the original size.
+    pub fn scale_up(&mut self, width: usize, height: usize) -> ResultType<()> {
+        scrap::scale::scale_rgb(&mut self.rgb, width, height, &mut self.scaled_rgb)
+    }
+
     /// Reset the decoder, change format if it is Some
     pub fn reset(&mut self, format: Option<CodecFormat>) {
         log::info!(
@@ -1645,7 +1658,9 @@ impl VideoHandler {
         self.rgb.set_align(crate::get_dst_align_rgba());
         let luid = Self::get_adapter_luid();
         let format = format.unwrap_or(self.decoder.format());
+        let video_color = self.decoder.video_color();
         self.decoder = Decoder::new(format, luid);
+        self.decoder.set_video_color(video_color);
         self.fail_counter = 0;
         self.first_frame = true;
     }
@@ -2812,6 +2827,8 @@ pub enum MediaData {
     AudioFormat(AudioFormat),
     Reset,
     RecordScreen(bool),
+    LosslessTiles(Box<TileUpdate>),
+    DisableLosslessTiles,
 }
 
 pub type MediaSender = mpsc::Sender<MediaData>;
@@ -2873,6 +2890,11 @@ pub fn start_video_thread<F, T>(
                         let display = vf.display as usize;
                         let start = std::time::Instant::now();
                         let format = CodecFormat::from(&vf);
+                        let original_size = vf
+                            .original_size
+                            .as_ref()
+                            .map(|s| (s.width as usize, s.height as usize));
+                        let video_color = vf.color.as_ref().map(scrap::hdr::VideoColor::from);
                         if video_handler.is_none() {
                             let mut handler = VideoHandler::new(format, display);
                             let record_state = session.lc.read().unwrap().record_state;
@@ -2884,11 +2906,22 @@ pub fn start_video_thread<F, T>(
                             video_handler = Some(handler);
                         }
                         if let Some(handler) = video_handler.as_mut() {
+                            handler.decoder.set_video_color(video_color);
                             let mut pixelbuffer = true;
                             let mut tmp_chroma = None;
                             let format_changed = handler.decoder.format() != format;
                             match handler.handle_frame(vf, &mut pixelbuffer, &mut tmp_chroma) {
                                 Ok(true) => {
+                                    if pixelbuffer {
+                                        if let Some((width, height)) = original_size {
+                                            if let Err(e) = handler.scale_up(width, height) {
+                                                log::error!("Failed to scale up video frame: {e:?}");
+                                            }
+                                        }
+                                        handler.lossless_tiles.composite(&mut handler.rgb);
+                                    } else {
+                                        handler.lossless_tiles.skip_frame();
+                                    }
                                     video_callback(
                                         display,
                                         &mut handler.rgb,
@@ -2963,6 +2996,24 @@ pub fn start_video_thread<F, T>(
                             handler.record_screen(start, id, display, is_view_camera);
                         }
                     }
+                    MediaData::LosslessTiles(_) | MediaData::DisableLosslessTiles => {
+                        if let Some(handler) = video_handler.as_mut() {
+                            let changed = match data {
+                                MediaData::LosslessTiles(update) => {
+                                    handler.lossless_tiles.update(*update, &mut handler.rgb)
+                                }
+                                _ => handler.lossless_tiles.disable(&mut handler.rgb),
+                            };
+                            if changed {
+                                video_callback(
+                                    display,
+                                    &mut handler.rgb,
+                                    handler.texture.texture,
+                                    true,
+                                );
+                            }
+                        }
+                    }
                     _ => {}
                 }
             } else {
@@ -3771,6 +3822,7 @@ pub enum Data {
     AddJob((i32, JobType, String, String, i32, bool, bool)),
     ResumeJob((i32, bool)),
     RecordScreen(bool),
+    LosslessTiles(bool),
     ElevateDirect,
     ElevateWithLogon(String, String),
     NewVoiceCall,
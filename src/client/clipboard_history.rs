@@ -0,0 +1,259 @@
+use hbb_common::{
+    compress::decompress,
+    get_time,
+    message_proto::{Clipboard, ClipboardFormat},
+};
+use serde_json::{json, Value};
+use std::{collections::VecDeque, sync::Mutex};
+
+// The clipboards received from or sent to the remote side in a session.
+// Entries with the same content are merged, the latest one is kept.
+const MAX_ENTRIES: usize = 20;
+// The sum of the (compressed) content sizes of all entries.
+const MAX_TOTAL_SIZE: usize = 32 * 1024 * 1024;
+const TEXT_PREVIEW_CHARS: usize = 200;
+#[cfg(any(target_os = "macos", target_os = "linux", target_os = "windows"))]
+const THUMBNAIL_SIZE: u32 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Clone)]
+struct Entry {
+    id: u64,
+    time: i64,
+    direction: Direction,
+    clipboards: Vec<Clipboard>,
+    size: usize,
+    // Generated on the first listing without holding the lock, the thumbnails are not cheap.
+    summary: Option<Value>,
+}
+
+#[derive(Default)]
+pub struct ClipboardHistory {
+    entries: VecDeque<Entry>,
+    next_id: u64,
+}
+
+fn is_same_content(a: &[Clipboard], b: &[Clipboard]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b.iter()).all(|(a, b)| {
+            a.format == b.format && a.special_name == b.special_name && a.content == b.content
+        })
+}
+
+fn content(c: &Clipboard) -> Vec<u8> {
+    if c.compress {
+        decompress(&c.content)
+    } else {
+        c.content.to_vec()
+    }
+}
+
+fn format_name(c: &Clipboard) -> String {
+    match c.format.enum_value() {
+        Ok(ClipboardFormat::Special) => c.special_name.clone(),
+        Ok(f) => format!("{:?}", f),
+        Err(v) => v.to_string(),
+    }
+}
+
+fn text_preview(clipboards: &[Clipboard]) -> Option<String> {
+    let c = clipboards
+        .iter()
+        .find(|c| c.format.enum_value() == Ok(ClipboardFormat::Text))?;
+    let text = String::from_utf8(content(c)).ok()?;
+    Some(text.chars().take(TEXT_PREVIEW_CHARS).collect())
+}
+
+// Base64 encoded png.
+#[cfg(any(target_os = "macos", target_os = "linux", target_os = "windows"))]
+fn thumbnail(clipboards: &[Clipboard]) -> Option<String> {
+    let img = clipboards
+        .iter()
+        .find_map(|c| match c.format.enum_value() {
+            Ok(ClipboardFormat::ImagePng) => {
+                image::load_from_memory_with_format(&content(c), image::ImageFormat::Png).ok()
+            }
+            Ok(ClipboardFormat::ImageRgba) => {
+                image::RgbaImage::from_raw(c.width as _, c.height as _, content(c))
+                    .map(image::DynamicImage::ImageRgba8)
+            }
+            _ => None,
+        })?;
+    let mut png = Vec::new();
+    img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .ok()?;
+    Some(crate::encode64(png))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn thumbnail(_clipboards: &[Clipboard]) -> Option<String> {
+    None
+}
+
+impl Entry {
+    fn make_summary(&self) -> Value {
+        let mut v = json!({
+            "id": self.id,
+            "time": self.time,
+            "direction": match self.direction {
+                Direction::Sent => "sent",
+                Direction::Received => "received",
+            },
+            "formats": self.clipboards.iter().map(format_name).collect::<Vec<_>>(),
+            "size": self.size,
+        });
+        if let Some(text) = text_preview(&self.clipboards) {
+            v["text"] = json!(text);
+        }
+        if let Some(thumbnail) = thumbnail(&self.clipboards) {
+            v["thumbnail"] = json!(thumbnail);
+        }
+        v
+    }
+}
+
+impl ClipboardHistory {
+    pub fn push(&mut self, direction: Direction, clipboards: &[Clipboard]) {
+        let size: usize = clipboards.iter().map(|c| c.content.len()).sum();
+        if clipboards.is_empty() || size > MAX_TOTAL_SIZE {
+            return;
+        }
+        self.entries
+            .retain(|e| !is_same_content(&e.clipboards, clipboards));
+        self.next_id += 1;
+        self.entries.push_back(Entry {
+            id: self.next_id,
+            time: get_time(),
+            direction,
+            clipboards: clipboards.to_vec(),
+            size,
+            summary: None,
+        });
+        let mut total: usize = self.entries.iter().map(|e| e.size).sum();
+        while self.entries.len() > MAX_ENTRIES || total > MAX_TOTAL_SIZE {
+            match self.entries.pop_front() {
+                Some(e) => total -= e.size,
+                None => break,
+            }
+        }
+    }
+
+    // The newest first.
+    // The contents are cheap to clone, the summaries are generated after the lock is released.
+    pub fn list(history: &Mutex<Self>) -> Value {
+        let pending: Vec<Entry> = history
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .filter(|e| e.summary.is_none())
+            .cloned()
+            .collect();
+        let summaries: Vec<(u64, Value)> =
+            pending.iter().map(|e| (e.id, e.make_summary())).collect();
+        let mut lock = history.lock().unwrap();
+        for (id, summary) in summaries {
+            if let Some(e) = lock.entries.iter_mut().find(|e| e.id == id) {
+                e.summary = Some(summary);
+            }
+        }
+        // The entries pushed in the meantime.
+        Value::Array(
+            lock.entries
+                .iter_mut()
+                .rev()
+                .map(|e| {
+                    if e.summary.is_none() {
+                        e.summary = Some(e.make_summary());
+                    }
+                    e.summary.clone().unwrap_or_default()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, id: u64) -> Option<Vec<Clipboard>> {
+        self.entries
+            .iter()
+            .find(|e| e.id == id)
+            .map(|e| e.clipboards.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Clipboard {
+        Clipboard {
+            content: s.as_bytes().to_vec().into(),
+            format: ClipboardFormat::Text.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_push_and_list() {
+        let history = Mutex::new(ClipboardHistory::default());
+        history.lock().unwrap().push(Direction::Sent, &[text("a")]);
+        history
+            .lock()
+            .unwrap()
+            .push(Direction::Received, &[text("b")]);
+        history.lock().unwrap().push(Direction::Received, &[]);
+        let list = ClipboardHistory::list(&history);
+        assert_eq!(list.as_array().unwrap().len(), 2);
+        assert_eq!(list[0]["text"], "b");
+        assert_eq!(list[0]["direction"], "received");
+        assert_eq!(list[1]["text"], "a");
+        assert_eq!(list[1]["formats"][0], "Text");
+
+        // The same content is moved to the newest.
+        history
+            .lock()
+            .unwrap()
+            .push(Direction::Received, &[text("a")]);
+        let list = ClipboardHistory::list(&history);
+        assert_eq!(list.as_array().unwrap().len(), 2);
+        assert_eq!(list[0]["text"], "a");
+        assert_eq!(list[0]["id"], 3);
+        let history = history.lock().unwrap();
+        assert!(history.get(1).is_none());
+        assert_eq!(history.get(3).unwrap()[0].content.as_ref(), b"a");
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut history = ClipboardHistory::default();
+        for i in 0..MAX_ENTRIES + 5 {
+            history.push(Direction::Sent, &[text(&i.to_string())]);
+        }
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.entries[0].id, 6);
+
+        let big = Clipboard {
+            content: vec![0u8; MAX_TOTAL_SIZE / 2 + 1].into(),
+            format: ClipboardFormat::ImagePng.into(),
+            ..Default::default()
+        };
+        history.push(Direction::Received, &[big.clone()]);
+        let mut big2 = big.clone();
+        big2.width = 1;
+        big2.content = vec![1u8; MAX_TOTAL_SIZE / 2 + 1].into();
+        history.push(Direction::Received, &[big2]);
+        assert_eq!(history.entries.len(), 1);
+        let history = Mutex::new(history);
+        assert!(ClipboardHistory::list(&history)[0]
+            .get("thumbnail")
+            .is_none());
+    }
+}
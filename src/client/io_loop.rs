@@ -4,8 +4,8 @@ use crate::clipboard::{update_clipboard, ClipboardSide};
 use crate::{audio_service, clipboard::CLIPBOARD_INTERVAL, ConnInner, CLIENT_SERVER};
 use crate::{
     client::{
-        self, new_voice_call_request, Client, Data, Interface, MediaData, MediaSender,
-        QualityStatus, MILLI1, SEC30,
+        self, clipboard_history, new_voice_call_request, Client, Data, Interface, MediaData,
+        MediaSender, QualityStatus, MILLI1, SEC30,
     },
     common::get_default_sound_input,
     ui_session_interface::{InvokeUiSession, Session},
@@ -78,6 +78,9 @@ pub struct Remote<T: InvokeUiSession> {
     chroma: Arc<RwLock<Option<Chroma>>>,
     last_record_state: bool,
     sent_close_reason: bool,
+    // for the telemetry
+    frame_drops: u64,
+    last_test_delay: Option<TestDelay>,
 }
 
 #[derive(Default)]
@@ -87,6 +90,8 @@ struct ParsedPeerInfo {
     idd_impl: String,
     support_view_camera: bool,
     support_terminal: bool,
+    support_lossless_tiles: bool,
+    support_video_received_ack: bool,
 }
 
 impl ParsedPeerInfo {
@@ -127,6 +132,8 @@ impl<T: InvokeUiSession> Remote<T> {
             chroma: Default::default(),
             last_record_state: false,
             sent_close_reason: false,
+            frame_drops: 0,
+            last_test_delay: None,
         }
     }
 
@@ -286,6 +293,7 @@ impl<T: InvokeUiSession> Remote<T> {
                             fps_instant = Instant::now();
                             let mut speed = self.data_count.swap(0, Ordering::Relaxed);
                             speed = speed * 1000 / elapsed as usize;
+                            let bytes_per_second = speed;
                             let speed = format!("{:.2}kB/s", speed as f32 / 1024 as f32);
 
                             let fps = self.video_threads.iter().map(|(k, v)| {
@@ -308,6 +316,9 @@ impl<T: InvokeUiSession> Remote<T> {
                             } else {
                                 Some(self.video_format.clone())
                             };
+                            if !self.video_threads.is_empty() && crate::telemetry::enabled() {
+                                self.report_telemetry(direct, &fps, bytes_per_second);
+                            }
                             self.handler.update_quality_status(QualityStatus {
                                 speed: Some(speed),
                                 fps,
@@ -319,6 +330,9 @@ impl<T: InvokeUiSession> Remote<T> {
                     }
                 }
                 log::debug!("Exit io_loop of id={}", self.handler.get_id());
+                crate::telemetry::remove(&crate::telemetry::client_session(
+                    self.handler.lc.read().unwrap().session_id,
+                ));
                 // Stop client audio server.
                 if let Some(s) = self.stop_voice_call_sender.take() {
                     s.send(()).ok();
@@ -563,6 +577,18 @@ impl<T: InvokeUiSession> Remote<T> {
                         }
                         _ => {}
                     },
+                    Some(message::Union::Clipboard(cb)) => {
+                        self.push_clipboard_history(
+                            clipboard_history::Direction::Sent,
+                            std::slice::from_ref(cb),
+                        );
+                    }
+                    Some(message::Union::MultiClipboards(mcb)) => {
+                        self.push_clipboard_history(
+                            clipboard_history::Direction::Sent,
+                            &mcb.clipboards,
+                        );
+                    }
                     _ => {}
                 }
                 allow_err!(peer.send(&msg).await);
@@ -925,6 +951,16 @@ impl<T: InvokeUiSession> Remote<T> {
                 self.handler.lc.write().unwrap().record_state = start;
                 self.update_record_state();
             }
+            Data::LosslessTiles(on) => {
+                if !on {
+                    for (_, v) in self.video_threads.iter_mut() {
+                        v.video_sender.send(MediaData::DisableLosslessTiles).ok();
+                    }
+                }
+                if self.first_frame {
+                    self.send_lossless_tiles_msg(on, peer).await;
+                }
+            }
             Data::ElevateDirect => {
                 let mut request = ElevationRequest::new();
                 request.set_direct(true);
@@ -1121,6 +1157,55 @@ impl<T: InvokeUiSession> Remote<T> {
         }
     }
 
+    async fn send_lossless_tiles_msg(&self, on: bool, peer: &mut Stream) {
+        if !self.peer_info.support_lossless_tiles {
+            return;
+        }
+        let mut misc = Misc::new();
+        misc.set_enable_lossless_tiles(on);
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        allow_err!(peer.send(&msg_out).await);
+    }
+
+    // The server estimates the bandwidth with the arrival of the video frames.
+    async fn send_video_received_msg(&self, peer: &mut Stream) {
+        if !self.peer_info.support_video_received_ack {
+            return;
+        }
+        let mut misc = Misc::new();
+        misc.set_video_received(true);
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        allow_err!(peer.send(&msg_out).await);
+    }
+
+    fn report_telemetry(&self, direct: bool, fps: &HashMap<usize, i32>, bytes_per_second: usize) {
+        // The decoding fps is measured with the decoding time.
+        let decode_ms = self
+            .video_threads
+            .values()
+            .filter_map(|v| *v.decode_fps.read().unwrap())
+            .filter(|fps| *fps > 0)
+            .min()
+            .map(|fps| 1000.0 / fps as f32);
+        let lc = self.handler.lc.read().unwrap();
+        crate::telemetry::report(crate::telemetry::SessionStats {
+            session: crate::telemetry::client_session(lc.session_id),
+            side: "client",
+            peer_id: lc.id.clone(),
+            codec: format!("{:?}", self.video_format),
+            direct: Some(direct),
+            fps: fps.values().map(|v| (*v).max(0) as u32).sum(),
+            bitrate_kbps: (bytes_per_second * 8 / 1000) as _,
+            target_bitrate_kbps: self.last_test_delay.as_ref().map(|t| t.target_bitrate),
+            decode_ms,
+            delay_ms: self.last_test_delay.as_ref().map(|t| t.last_delay),
+            frame_drops: Some(self.frame_drops),
+            ..Default::default()
+        });
+    }
+
     fn contains_key_frame(vf: &VideoFrame) -> bool {
         use video_frame::Union::*;
         match &vf.union {
@@ -1292,8 +1377,15 @@ impl<T: InvokeUiSession> Remote<T> {
                         self.handler.adapt_size();
                         self.send_toggle_virtual_display_msg(peer).await;
                         self.send_toggle_privacy_mode_msg(peer).await;
+                        if self
+                            .handler
+                            .get_toggle_option("lossless-text-tiles".to_owned())
+                        {
+                            self.send_lossless_tiles_msg(true, peer).await;
+                        }
                     }
                     self.video_format = CodecFormat::from(&vf);
+                    self.send_video_received_msg(peer).await;
 
                     let display = vf.display as usize;
                     if !self.video_threads.contains_key(&display) {
@@ -1311,6 +1403,7 @@ impl<T: InvokeUiSession> Remote<T> {
                         let video_queue = thread.video_queue.read().unwrap();
                         if video_queue.force_push(vf).is_some() {
                             drop(video_queue);
+                            self.frame_drops += 1;
                             self.handler.refresh_video(display as _);
                         } else {
                             thread.video_sender.send(MediaData::VideoQueue).ok();
@@ -1427,6 +1520,10 @@ impl<T: InvokeUiSession> Remote<T> {
                 }
                 Some(message::Union::Clipboard(cb)) => {
                     if !self.handler.lc.read().unwrap().disable_clipboard.v {
+                        self.push_clipboard_history(
+                            clipboard_history::Direction::Received,
+                            std::slice::from_ref(&cb),
+                        );
                         #[cfg(not(any(target_os = "android", target_os = "ios")))]
                         update_clipboard(vec![cb], ClipboardSide::Client);
                         #[cfg(target_os = "ios")]
@@ -1446,6 +1543,10 @@ impl<T: InvokeUiSession> Remote<T> {
                 }
                 Some(message::Union::MultiClipboards(_mcb)) => {
                     if !self.handler.lc.read().unwrap().disable_clipboard.v {
+                        self.push_clipboard_history(
+                            clipboard_history::Direction::Received,
+                            &_mcb.clipboards,
+                        );
                         #[cfg(not(any(target_os = "android", target_os = "ios")))]
                         update_clipboard(_mcb.clipboards, ClipboardSide::Client);
                         #[cfg(target_os = "ios")]
@@ -1941,6 +2042,19 @@ impl<T: InvokeUiSession> Remote<T> {
                             );
                         }
                     }
+                    Some(misc::Union::LosslessTiles(tiles)) => {
+                        match scrap::lossless::TileUpdate::from_message(&tiles) {
+                            Ok(update) => {
+                                if let Some(thread) = self.video_threads.get_mut(&update.display) {
+                                    thread
+                                        .video_sender
+                                        .send(MediaData::LosslessTiles(Box::new(update)))
+                                        .ok();
+                                }
+                            }
+                            Err(e) => log::error!("Failed to decode lossless tiles: {e:?}"),
+                        }
+                    }
                     #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
                     Some(misc::Union::PluginRequest(p)) => {
@@ -1971,6 +2085,9 @@ impl<T: InvokeUiSession> Remote<T> {
                     _ => {}
                 },
                 Some(message::Union::TestDelay(t)) => {
+                    if !t.from_client {
+                        self.last_test_delay = Some(t.clone());
+                    }
                     self.handler.handle_test_delay(t, peer).await;
                 }
                 Some(message::Union::AudioFrame(frame)) => {
@@ -2094,6 +2211,8 @@ impl<T: InvokeUiSession> Remote<T> {
         // Check features field for terminal support
         if let Some(features) = pi.features.as_ref() {
             self.peer_info.support_terminal = features.terminal;
+            self.peer_info.support_lossless_tiles = features.lossless_tiles;
+            self.peer_info.support_video_received_ack = features.video_received_ack;
         }
 
         if let Ok(platform_additions) =
@@ -2292,6 +2411,19 @@ impl<T: InvokeUiSession> Remote<T> {
         true
     }
 
+    #[inline]
+    fn push_clipboard_history(
+        &self,
+        direction: clipboard_history::Direction,
+        clipboards: &[Clipboard],
+    ) {
+        self.handler
+            .clipboard_history
+            .lock()
+            .unwrap()
+            .push(direction, clipboards);
+    }
+
     #[cfg(all(target_os = "windows", not(feature = "flutter")))]
     fn check_clipboard_file_context(&self) {
         let enabled = *self.handler.server_file_transfer_enabled.read().unwrap()
@@ -0,0 +1,192 @@
+use hbb_common::message_proto::*;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+use rdev::Key;
+
+// Type text on the remote side key by key, for the peers without clipboard sync,
+// e.g. login screens, BIOS-over-IP consoles or VMs without guest tools.
+pub const MAX_CHARS: usize = 64 * 1024;
+pub const DEFAULT_INTERVAL_MS: u64 = 20;
+pub const MIN_INTERVAL_MS: u64 = 5;
+pub const MAX_INTERVAL_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystrokeMode {
+    // The same events as typing the text in translate mode,
+    // see `keyboard::translate_keyboard_mode`.
+    Translate,
+    // Characters, mapped to keys by the current keyboard layout of the remote side.
+    // Works on consoles that only understand key presses.
+    Layout,
+}
+
+impl KeystrokeMode {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "layout" => Self::Layout,
+            _ => Self::Translate,
+        }
+    }
+}
+
+// The event of typing `key` or `name` on this side.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn local_event(key: Key, name: Option<String>, down: bool) -> rdev::Event {
+    #[cfg(target_os = "windows")]
+    let (platform_code, position_code) = (
+        rdev::win_code_from_key(key).unwrap_or(0),
+        rdev::win_scancode_from_key(key).unwrap_or(0),
+    );
+    #[cfg(not(target_os = "windows"))]
+    let (platform_code, position_code) = {
+        let code = rdev::code_from_key(key).unwrap_or(0);
+        (code as _, code as _)
+    };
+    rdev::Event {
+        time: std::time::SystemTime::now(),
+        unicode: name.map(|name| rdev::UnicodeInfo {
+            unicode: name.encode_utf16().collect(),
+            name: Some(name),
+            is_dead: false,
+        }),
+        platform_code,
+        position_code,
+        event_type: if down {
+            rdev::EventType::KeyPress(key)
+        } else {
+            rdev::EventType::KeyRelease(key)
+        },
+        usb_hid: 0,
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        extra_data: 0,
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn translate_key_events(peer: &str, c: char) -> Vec<KeyEvent> {
+    let mut key_event = KeyEvent::new();
+    key_event.mode = KeyboardMode::Translate.into();
+    let translate = |event: rdev::Event| {
+        crate::keyboard::translate_keyboard_mode(peer, &event, key_event.clone())
+    };
+    match c {
+        '\n' | '\t' => {
+            let key = if c == '\n' { Key::Return } else { Key::Tab };
+            let mut events = translate(local_event(key, None, true));
+            events.append(&mut translate(local_event(key, None, false)));
+            events
+        }
+        // Only the press of a character is sent, as the unicode sequence.
+        _ => translate(local_event(Key::Unknown(0), Some(c.to_string()), true)),
+    }
+}
+
+// Translate mode without the local key events, see `keyboard::translate_keyboard_mode`.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn translate_key_events(_peer: &str, c: char) -> Vec<KeyEvent> {
+    match c {
+        '\n' => vec![control_key(ControlKey::Return)],
+        '\t' => vec![control_key(ControlKey::Tab)],
+        _ => vec![unicode_seq(&c.to_string())],
+    }
+}
+
+fn control_key(ck: ControlKey) -> KeyEvent {
+    let mut evt = KeyEvent::new();
+    evt.set_control_key(ck);
+    evt.press = true;
+    evt.mode = KeyboardMode::Legacy.into();
+    evt
+}
+
+fn legacy_chr(c: char, down: bool) -> KeyEvent {
+    let mut evt = KeyEvent::new();
+    evt.set_chr(c as _);
+    evt.down = down;
+    evt.mode = KeyboardMode::Legacy.into();
+    evt
+}
+
+// Unicode input, only where the remote side supports it.
+fn unicode_seq(s: &str) -> KeyEvent {
+    let mut evt = KeyEvent::new();
+    evt.set_seq(s.to_owned());
+    evt.mode = KeyboardMode::Translate.into();
+    evt
+}
+
+// The key events of each character, a character is typed after the previous one is released.
+fn text_to_key_events(peer: &str, text: &str, mode: KeystrokeMode) -> Vec<Vec<KeyEvent>> {
+    text.replace("\r\n", "\n")
+        .chars()
+        .take(MAX_CHARS)
+        .filter_map(|c| {
+            if c == '\r' || (c.is_control() && c != '\n' && c != '\t') {
+                return None;
+            }
+            let events = match (mode, c) {
+                (KeystrokeMode::Translate, _) => translate_key_events(peer, c),
+                (_, '\n') => vec![control_key(ControlKey::Return)],
+                (_, '\t') => vec![control_key(ControlKey::Tab)],
+                _ if c.is_ascii() => vec![legacy_chr(c, true), legacy_chr(c, false)],
+                _ => vec![unicode_seq(&c.to_string())],
+            };
+            if events.is_empty() {
+                None
+            } else {
+                Some(events)
+            }
+        })
+        .collect()
+}
+
+// Every key event is sent `interval_ms` after the previous one.
+pub fn text_to_messages(
+    peer: &str,
+    text: &str,
+    mode: KeystrokeMode,
+    interval_ms: u64,
+) -> Vec<(u64, Message)> {
+    let interval_ms = interval_ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS);
+    text_to_key_events(peer, text, mode)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, evt)| {
+            let mut msg = Message::new();
+            msg.set_key_event(evt);
+            (if i == 0 { 0 } else { interval_ms }, msg)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn test_text_to_key_events() {
+        let events = text_to_key_events("windows", "aB\r\né\x07", KeystrokeMode::Translate);
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].len(), 1);
+        assert_eq!(events[0][0].seq(), "a");
+        assert!(events[0][0].down);
+        assert_eq!(events[1][0].seq(), "B");
+        // The local return key, mapped to the key of the peer.
+        assert_eq!(events[2].len(), 2);
+        assert_eq!(
+            events[2][0].chr(),
+            rdev::win_scancode_from_key(Key::Return).unwrap()
+        );
+        assert!(events[2][0].down && !events[2][1].down);
+        assert_eq!(events[3][0].seq(), "é");
+
+        let events = text_to_key_events("linux", "a\n", KeystrokeMode::Layout);
+        assert_eq!(events[0][0].chr(), 'a' as u32);
+        assert_eq!(
+            events[0][0].mode.enum_value_or_default(),
+            KeyboardMode::Legacy
+        );
+        assert_eq!(events[1][0].control_key(), ControlKey::Return);
+    }
+}
@@ -0,0 +1,455 @@
+use hbb_common::{
+    bail,
+    config::{self, Config},
+    log,
+    message_proto::*,
+    protobuf::Message as _,
+    tokio::sync::mpsc::UnboundedSender,
+    ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::client::Data;
+use crate::common::input::{MOUSE_TYPE_DOWN, MOUSE_TYPE_MASK, MOUSE_TYPE_UP};
+
+// Recorded key and mouse events of the controlling side, stored per user in `macros.toml`.
+// The events are replayed as they were sent, so the mouse positions are the remote coordinates.
+const MACROS_FILE: &str = "macros.toml";
+// Long idle periods are shortened when recording.
+const MAX_DELAY_MS: u64 = 10_000;
+const MAX_EVENTS: usize = 100_000;
+pub const MIN_SPEED: f64 = 0.1;
+pub const MAX_SPEED: f64 = 10.0;
+
+lazy_static::lazy_static! {
+    // Cached, the hotkeys are looked up on every key down.
+    static ref MACROS: Mutex<Option<Vec<Macro>>> = Default::default();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EventType {
+    Key,
+    Mouse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MacroEvent {
+    // Milliseconds after the previous event.
+    delay: u64,
+    r#type: EventType,
+    // Base64 encoded protobuf message.
+    data: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    #[serde(default)]
+    pub hotkey: String,
+    #[serde(default)]
+    events: Vec<MacroEvent>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Macros {
+    #[serde(default)]
+    macros: Vec<Macro>,
+}
+
+#[inline]
+fn path() -> PathBuf {
+    Config::path(MACROS_FILE)
+}
+
+// "alt + Ctrl+A" -> "ctrl+alt+a"
+pub fn normalize_hotkey(hotkey: &str) -> String {
+    let mut modifiers = [false; 4];
+    let mut key = None;
+    for part in hotkey.split('+').map(|x| x.trim().to_lowercase()) {
+        match part.as_str() {
+            "ctrl" | "control" => modifiers[0] = true,
+            "alt" | "option" => modifiers[1] = true,
+            "shift" => modifiers[2] = true,
+            "meta" | "cmd" | "command" | "win" | "super" => modifiers[3] = true,
+            "" => {}
+            _ => key = Some(part),
+        }
+    }
+    let Some(key) = key else {
+        return "".to_owned();
+    };
+    ["ctrl", "alt", "shift", "meta"]
+        .iter()
+        .zip(modifiers.iter())
+        .filter(|(_, on)| **on)
+        .map(|(m, _)| m.to_string())
+        .chain(std::iter::once(key))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+impl Macro {
+    pub fn duration(&self) -> u64 {
+        self.events.iter().map(|e| e.delay).sum()
+    }
+
+    pub fn summary(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "hotkey": self.hotkey,
+            "events": self.events.len(),
+            "duration": self.duration(),
+        })
+    }
+
+    fn messages(&self) -> Vec<(u64, Message)> {
+        self.events
+            .iter()
+            .filter_map(|e| {
+                let bytes = crate::decode64(&e.data).ok()?;
+                let mut msg = Message::new();
+                match e.r#type {
+                    EventType::Key => msg.set_key_event(KeyEvent::parse_from_bytes(&bytes).ok()?),
+                    EventType::Mouse => {
+                        msg.set_mouse_event(MouseEvent::parse_from_bytes(&bytes).ok()?)
+                    }
+                }
+                Some((e.delay, msg))
+            })
+            .collect()
+    }
+}
+
+fn load(cache: &mut Option<Vec<Macro>>) -> &mut Vec<Macro> {
+    cache.get_or_insert_with(|| config::load_path::<Macros>(path()).macros)
+}
+
+fn store(macros: Vec<Macro>) -> ResultType<()> {
+    config::store_path(path(), Macros { macros })
+}
+
+pub fn get_macros() -> Vec<Macro> {
+    load(&mut MACROS.lock().unwrap()).clone()
+}
+
+pub fn get_macro(name: &str) -> Option<Macro> {
+    get_macros().into_iter().find(|m| m.name == name)
+}
+
+pub fn get_macro_by_hotkey(hotkey: &str) -> Option<Macro> {
+    let hotkey = normalize_hotkey(hotkey);
+    if hotkey.is_empty() {
+        return None;
+    }
+    load(&mut MACROS.lock().unwrap())
+        .iter()
+        .find(|m| normalize_hotkey(&m.hotkey) == hotkey)
+        .cloned()
+}
+
+// Replace the macro with the same name.
+pub fn save_macro(m: Macro) -> ResultType<()> {
+    if m.name.trim().is_empty() {
+        bail!("Empty macro name");
+    }
+    let mut lock = MACROS.lock().unwrap();
+    let mut macros = load(&mut lock).clone();
+    let hotkey = normalize_hotkey(&m.hotkey);
+    if !hotkey.is_empty() {
+        if let Some(other) = macros
+            .iter()
+            .find(|x| x.name != m.name && normalize_hotkey(&x.hotkey) == hotkey)
+        {
+            bail!("Hotkey {} is used by macro {}", m.hotkey, other.name);
+        }
+    }
+    macros.retain(|x| x.name != m.name);
+    macros.push(m);
+    store(macros.clone())?;
+    *lock = Some(macros);
+    Ok(())
+}
+
+pub fn remove_macro(name: &str) -> ResultType<()> {
+    let mut lock = MACROS.lock().unwrap();
+    let mut macros = load(&mut lock).clone();
+    macros.retain(|x| x.name != name);
+    store(macros.clone())?;
+    *lock = Some(macros);
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct MacroRecorder {
+    last: Option<Instant>,
+    events: Vec<MacroEvent>,
+}
+
+impl MacroRecorder {
+    pub fn push(&mut self, msg: &Message) {
+        let (r#type, bytes) = match &msg.union {
+            Some(message::Union::KeyEvent(evt)) => (EventType::Key, evt.write_to_bytes()),
+            Some(message::Union::MouseEvent(evt)) => (EventType::Mouse, evt.write_to_bytes()),
+            _ => return,
+        };
+        if self.events.len() >= MAX_EVENTS {
+            return;
+        }
+        let Ok(bytes) = bytes else {
+            return;
+        };
+        let now = Instant::now();
+        // The first event is played immediately.
+        let delay = self
+            .last
+            .map(|last| (now - last).as_millis() as u64)
+            .unwrap_or(0)
+            .min(MAX_DELAY_MS);
+        self.last = Some(now);
+        self.events.push(MacroEvent {
+            delay,
+            r#type,
+            data: crate::encode64(bytes),
+        });
+    }
+
+    pub fn finish(self, name: String, hotkey: String) -> Macro {
+        Macro {
+            name,
+            hotkey,
+            events: self.events,
+        }
+    }
+}
+
+// Release the keys and mouse buttons which are still down when the playback ends,
+// so that the remote side is not left with a stuck modifier.
+#[derive(Default)]
+struct PressedInputs {
+    keys: Vec<KeyEvent>,
+    mouse_buttons: HashSet<i32>,
+    last_mouse: Option<MouseEvent>,
+}
+
+impl PressedInputs {
+    fn update(&mut self, msg: &Message) {
+        match &msg.union {
+            Some(message::Union::KeyEvent(evt)) => {
+                if evt.press {
+                    return;
+                }
+                let mut up = evt.clone();
+                up.down = false;
+                self.keys.retain(|k| *k != up);
+                if evt.down {
+                    self.keys.push(up);
+                }
+            }
+            Some(message::Union::MouseEvent(evt)) => {
+                let buttons = evt.mask >> 3;
+                match evt.mask & MOUSE_TYPE_MASK {
+                    MOUSE_TYPE_DOWN => {
+                        self.mouse_buttons.insert(buttons);
+                    }
+                    MOUSE_TYPE_UP => {
+                        self.mouse_buttons.remove(&buttons);
+                    }
+                    _ => {}
+                }
+                self.last_mouse = Some(evt.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn release(self, sender: &UnboundedSender<Data>) {
+        for key in self.keys.into_iter().rev() {
+            let mut msg = Message::new();
+            msg.set_key_event(key);
+            sender.send(Data::Message(msg)).ok();
+        }
+        let Some(last_mouse) = self.last_mouse else {
+            return;
+        };
+        for buttons in self.mouse_buttons {
+            let mut evt = last_mouse.clone();
+            evt.mask = (buttons << 3) | MOUSE_TYPE_UP;
+            let mut msg = Message::new();
+            msg.set_mouse_event(evt);
+            sender.send(Data::Message(msg)).ok();
+        }
+    }
+}
+
+// The playback state of a session, only one playback runs at a time.
+// Every playback has an id, a stopped playback never sends again even if another one is started
+// before it wakes up.
+#[derive(Default)]
+pub struct Playback {
+    // The id of the running playback.
+    current: Mutex<Option<u64>>,
+    next_id: AtomicU64,
+}
+
+impl Playback {
+    fn start(&self) -> Option<u64> {
+        let mut lock = self.current.lock().unwrap();
+        if lock.is_some() {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        *lock = Some(id);
+        Some(id)
+    }
+
+    #[inline]
+    fn is_current(&self, id: u64) -> bool {
+        *self.current.lock().unwrap() == Some(id)
+    }
+
+    fn finish(&self, id: u64) {
+        let mut lock = self.current.lock().unwrap();
+        if *lock == Some(id) {
+            *lock = None;
+        }
+    }
+
+    #[inline]
+    pub fn stop(&self) {
+        *self.current.lock().unwrap() = None;
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.current.lock().unwrap().is_some()
+    }
+}
+
+// Returns false if a macro is already playing in the session.
+pub fn play(
+    m: Macro,
+    speed: f64,
+    sender: Arc<RwLock<Option<UnboundedSender<Data>>>>,
+    playback: Arc<Playback>,
+) -> bool {
+    let speed = if speed.is_finite() {
+        speed.clamp(MIN_SPEED, MAX_SPEED)
+    } else {
+        1.0
+    };
+    let messages = m.messages();
+    play_messages(m.name, messages, speed, sender, playback)
+}
+
+// Send the messages with the delays (in milliseconds) in a thread,
+// e.g. the keystrokes of a paste. Only one playback runs in a session.
+pub fn play_messages(
+    name: String,
+    messages: Vec<(u64, Message)>,
+    speed: f64,
+    sender: Arc<RwLock<Option<UnboundedSender<Data>>>>,
+    playback: Arc<Playback>,
+) -> bool {
+    let Some(id) = playback.start() else {
+        return false;
+    };
+    std::thread::spawn(move || {
+        log::info!("Play {} at speed {}", name, speed);
+        let mut pressed = PressedInputs::default();
+        for (delay, msg) in messages {
+            if delay > 0 {
+                std::thread::sleep(Duration::from_millis((delay as f64 / speed) as u64));
+            }
+            if !playback.is_current(id) {
+                log::info!("{} stopped", name);
+                break;
+            }
+            pressed.update(&msg);
+            match sender.read().unwrap().as_ref() {
+                Some(sender) => {
+                    sender.send(Data::Message(msg)).ok();
+                }
+                None => break,
+            }
+        }
+        if let Some(sender) = sender.read().unwrap().as_ref() {
+            pressed.release(sender);
+        }
+        playback.finish(id);
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_hotkey() {
+        assert_eq!(normalize_hotkey("alt + Ctrl+A"), "ctrl+alt+a");
+        assert_eq!(normalize_hotkey("Meta+Shift+F1"), "shift+meta+f1");
+        assert_eq!(normalize_hotkey("ctrl+alt"), "");
+        assert_eq!(normalize_hotkey(""), "");
+    }
+
+    #[test]
+    fn test_record() {
+        let mut recorder = MacroRecorder::default();
+        let mut msg = Message::new();
+        msg.set_key_event(KeyEvent {
+            down: true,
+            ..Default::default()
+        });
+        recorder.push(&msg);
+        let mut mouse = Message::new();
+        mouse.set_mouse_event(MouseEvent {
+            mask: (1 << 3) | MOUSE_TYPE_DOWN,
+            x: 10,
+            y: 20,
+            ..Default::default()
+        });
+        recorder.push(&mouse);
+        recorder.push(&Message::new());
+        let m = recorder.finish("test".to_owned(), "".to_owned());
+        assert_eq!(m.events.len(), 2);
+        assert_eq!(m.events[0].delay, 0);
+
+        let messages = m.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].1, mouse);
+
+        let mut pressed = PressedInputs::default();
+        for (_, msg) in messages.iter() {
+            pressed.update(msg);
+        }
+        assert_eq!(pressed.keys.len(), 1);
+        assert!(!pressed.keys[0].down);
+        assert!(pressed.mouse_buttons.contains(&1));
+    }
+
+    #[test]
+    fn test_playback() {
+        let playback = Playback::default();
+        let first = playback.start().unwrap();
+        assert!(playback.start().is_none());
+        playback.stop();
+        let second = playback.start().unwrap();
+        // The stopped playback does not continue or finish the new one.
+        assert!(!playback.is_current(first));
+        playback.finish(first);
+        assert!(playback.is_playing());
+        assert!(playback.is_current(second));
+        playback.finish(second);
+        assert!(!playback.is_playing());
+    }
+}
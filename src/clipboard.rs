@@ -168,7 +168,10 @@ pub fn try_empty_clipboard_files(_side: ClipboardSide, _conn_id: i32) {
             #[cfg(target_os = "linux")]
             {
                 use clipboard::platform::unix;
-                if unix::fuse::empty_local_files(_side == ClipboardSide::Client, _conn_id) {
+                let is_client = _side == ClipboardSide::Client;
+                let fuse_emptied = unix::fuse::empty_local_files(is_client, _conn_id);
+                let staging_emptied = unix::staging::empty_local_files(is_client, _conn_id);
+                if fuse_emptied || staging_emptied {
                     ctx.try_empty_clipboard_files(_side);
                 }
             }
@@ -459,13 +462,17 @@ impl ClipboardContext {
 
     #[cfg(all(feature = "unix-file-copy-paste", target_os = "linux"))]
     fn get_file_urls_set_by_rustdesk(data: Vec<ClipboardData>, side: ClipboardSide) -> Vec<String> {
-        let exclude_path =
-            clipboard::platform::unix::fuse::get_exclude_paths(side == ClipboardSide::Client);
+        let is_client = side == ClipboardSide::Client;
+        let exclude_path = clipboard::platform::unix::fuse::get_exclude_paths(is_client);
+        let staging_path = clipboard::platform::unix::staging::get_exclude_paths(is_client);
         data.into_iter()
             .filter_map(|c| match c {
                 ClipboardData::FileUrl(urls) => Some(
                     urls.into_iter()
-                        .filter(|s| s.starts_with(&*exclude_path))
+                        .filter(|s| {
+                            s.starts_with(&*exclude_path)
+                                || std::path::Path::new(s).starts_with(&staging_path)
+                        })
                         .collect::<Vec<_>>(),
                 ),
                 _ => None,
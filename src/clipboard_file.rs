@@ -231,18 +231,38 @@ pub mod unix_file_clip {
     use crate::clipboard::update_clipboard_files;
     use crate::clipboard::{try_empty_clipboard_files, ClipboardSide};
     #[cfg(target_os = "linux")]
-    use clipboard::platform::unix::fuse;
+    use clipboard::platform::unix::{fuse, staging};
     use clipboard::platform::unix::{
         get_local_format, serv_files, FILECONTENTS_FORMAT_ID, FILECONTENTS_FORMAT_NAME,
         FILEDESCRIPTORW_FORMAT_NAME, FILEDESCRIPTOR_FORMAT_ID,
     };
     use hbb_common::log;
+    #[cfg(target_os = "linux")]
+    use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{Arc, Mutex};
 
     lazy_static::lazy_static! {
         static ref CLIPBOARD_CTX: Arc<Mutex<Option<crate::clipboard::ClipboardContext>>> = Arc::new(Mutex::new(None));
     }
 
+    #[cfg(target_os = "linux")]
+    static FUSE_UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+    // FUSE is not available in some environments, e.g. containers without `/dev/fuse`.
+    // The files are staged in a temp directory instead once mounting fails.
+    #[cfg(target_os = "linux")]
+    fn is_fuse_available() -> bool {
+        if FUSE_UNAVAILABLE.load(Ordering::Relaxed) {
+            return false;
+        }
+        if fuse::init_fuse_context(true).is_ok() {
+            return true;
+        }
+        log::warn!("FUSE is not available, clipboard files will be staged in a temp directory");
+        FUSE_UNAVAILABLE.store(true, Ordering::Relaxed);
+        false
+    }
+
     pub fn get_format_list() -> ClipboardFile {
         let fd_format_name = get_local_format(FILEDESCRIPTOR_FORMAT_ID)
             .unwrap_or(FILEDESCRIPTORW_FORMAT_NAME.to_string());
@@ -336,7 +356,7 @@ pub mod unix_file_clip {
                 }
 
                 log::debug!("parsing file descriptors");
-                if fuse::init_fuse_context(true).is_ok() {
+                if is_fuse_available() {
                     match fuse::format_data_response_to_urls(
                         side == ClipboardSide::Client,
                         format_data,
@@ -349,8 +369,13 @@ pub mod unix_file_clip {
                             log::error!("failed to parse file descriptors: {:?}", e);
                         }
                     }
-                } else {
-                    // send error message to server
+                } else if let Err(e) = staging::start(
+                    side == ClipboardSide::Client,
+                    format_data,
+                    conn_id,
+                    move |files| update_clipboard_files(files, side),
+                ) {
+                    log::error!("failed to stage clipboard files: {:?}", e);
                 }
             }
             ClipboardFile::FileContentsRequest {
@@ -393,13 +418,16 @@ pub mod unix_file_clip {
                     msg_flags,
                     stream_id,
                 );
-                if fuse::init_fuse_context(true).is_ok() {
+                if is_fuse_available() {
                     hbb_common::allow_err!(fuse::handle_file_content_response(
                         side == ClipboardSide::Client,
                         clip
                     ));
                 } else {
-                    // send error message to server
+                    hbb_common::allow_err!(staging::handle_file_content_response(
+                        side == ClipboardSide::Client,
+                        clip
+                    ));
                 }
             }
             ClipboardFile::NotifyCallback {
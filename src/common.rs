@@ -1279,14 +1279,21 @@ fn parse_simple_header(header: &str) -> Vec<HeaderEntry> {
     entries
 }
 
-/// POST request via TCP proxy.
-async fn post_request_via_tcp_proxy(url: &str, body: &str, header: &str) -> ResultType<String> {
+/// POST request via TCP proxy. Returns (status_code, body_text).
+async fn post_request_via_tcp_proxy(
+    url: &str,
+    body: &str,
+    header: &str,
+) -> ResultType<(u16, String)> {
     let headers = parse_simple_header(header);
     let resp = tcp_proxy_request("POST", url, body.as_bytes(), headers).await?;
     if !resp.error.is_empty() {
         bail!("TCP proxy error: {}", resp.error);
     }
-    Ok(String::from_utf8_lossy(&resp.body).to_string())
+    Ok((
+        resp.status as u16,
+        String::from_utf8_lossy(&resp.body).to_string(),
+    ))
 }
 
 fn http_proxy_response_to_json(resp: HttpProxyResponse) -> ResultType<String> {
@@ -1349,15 +1356,16 @@ async fn post_request_http(url: &str, body: &str, header: &str) -> ResultType<(u
 
 /// Try `http_fn` first; on connection failure or 5xx, fall back to `tcp_fn`
 /// if the URL is eligible. 4xx responses are returned as-is.
+/// Returns (status_code, text) of the response used.
 async fn with_tcp_proxy_fallback<HttpFut, TcpFut>(
     url: &str,
     method: &str,
     http_fn: HttpFut,
     tcp_fn: TcpFut,
-) -> ResultType<String>
+) -> ResultType<(u16, String)>
 where
     HttpFut: Future<Output = ResultType<(u16, String)>>,
-    TcpFut: Future<Output = ResultType<String>>,
+    TcpFut: Future<Output = ResultType<(u16, String)>>,
 {
     if should_use_raw_tcp_for_api(url) {
         return tcp_fn.await;
@@ -1387,7 +1395,7 @@ where
         }
     }
 
-    http_result.map(|(_status, text)| text)
+    http_result
 }
 
 /// POST request with raw TCP proxy support.
@@ -1397,6 +1405,18 @@ where
 /// - 4xx responses are returned as-is (server is reachable, business logic error).
 /// - If fallback also fails, returns the original HTTP result (text or error).
 pub async fn post_request(url: String, body: String, header: &str) -> ResultType<String> {
+    post_request_with_status(url, body, header)
+        .await
+        .map(|(_status, text)| text)
+}
+
+/// Same as `post_request`, but returns (status_code, body_text), so that the caller can tell
+/// a rejected request from a delivered one.
+pub async fn post_request_with_status(
+    url: String,
+    body: String,
+    header: &str,
+) -> ResultType<(u16, String)> {
     with_tcp_proxy_fallback(
         &url,
         "POST",
@@ -1659,21 +1679,24 @@ pub async fn http_request_sync(
         http_request_via_tcp_proxy(&url, &method, body.as_deref(), &header),
     )
     .await
+    .map(|(_status, json)| json)
 }
 
 /// General HTTP request via TCP proxy. Header is a JSON string (used by http_request_sync).
-/// Returns a JSON string with status_code, headers, body (same format as http_request_sync).
+/// Returns (status_code, json_string), the JSON string has status_code, headers, body
+/// (same format as http_request_sync).
 async fn http_request_via_tcp_proxy(
     url: &str,
     method: &str,
     body: Option<&str>,
     header: &str,
-) -> ResultType<String> {
+) -> ResultType<(u16, String)> {
     let headers = parse_json_header_entries(header)?;
     let body_bytes = body.unwrap_or("").as_bytes();
 
     let resp = tcp_proxy_request(method, url, body_bytes, headers).await?;
-    http_proxy_response_to_json(resp)
+    let status = resp.status as u16;
+    Ok((status, http_proxy_response_to_json(resp)?))
 }
 
 #[inline]
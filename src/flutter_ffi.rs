@@ -1072,6 +1072,10 @@ pub fn main_test_if_valid_server(server: String, test_with_proxy: bool) -> Strin
     test_if_valid_server(server, test_with_proxy)
 }
 
+pub fn main_get_audit_queue_status() -> String {
+    get_audit_queue_status()
+}
+
 pub fn main_set_socks(proxy: String, username: String, password: String) {
     set_socks(proxy, username, password)
 }
@@ -2055,6 +2059,132 @@ pub fn session_get_audit_guid(session_id: SessionID) -> SyncReturn<String> {
     }
 }
 
+pub fn session_get_clipboard_history(session_id: SessionID) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.get_clipboard_history())
+    } else {
+        SyncReturn("[]".to_owned())
+    }
+}
+
+pub fn session_resend_clipboard_history(
+    session_id: SessionID,
+    history_id: i64,
+) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.resend_clipboard_history(history_id as _))
+    } else {
+        SyncReturn(false)
+    }
+}
+
+// The json of the key remapping table of the peer, empty if no remapping.
+pub fn session_get_key_remap(session_id: SessionID) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.get_key_remap().to_json())
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+// Returns the error message, empty on success.
+pub fn session_set_key_remap(session_id: SessionID, json: String) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.set_key_remap(json))
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+pub fn session_macro_start_recording(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.start_macro_recording();
+    }
+}
+
+pub fn session_macro_is_recording(session_id: SessionID) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.is_macro_recording())
+    } else {
+        SyncReturn(false)
+    }
+}
+
+// An empty name discards the recording, returns the error message.
+pub fn session_macro_stop_recording(
+    session_id: SessionID,
+    name: String,
+    hotkey: String,
+) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.stop_macro_recording(name, hotkey))
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+pub fn session_play_macro(session_id: SessionID, name: String, speed: f64) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.play_macro(&name, speed))
+    } else {
+        SyncReturn(false)
+    }
+}
+
+pub fn session_play_macro_by_hotkey(session_id: SessionID, hotkey: String) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.play_macro_by_hotkey(&hotkey))
+    } else {
+        SyncReturn(false)
+    }
+}
+
+// `mode` is "scancode" (US layout keys) or "layout" (characters mapped by the remote layout).
+pub fn session_paste_as_keystrokes(
+    session_id: SessionID,
+    text: String,
+    mode: String,
+    interval_ms: i64,
+) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.paste_as_keystrokes(&text, &mode, interval_ms.max(0) as _))
+    } else {
+        SyncReturn(false)
+    }
+}
+
+pub fn session_stop_macro(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.stop_macro();
+    }
+}
+
+pub fn session_is_macro_playing(session_id: SessionID) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.is_macro_playing())
+    } else {
+        SyncReturn(false)
+    }
+}
+
+pub fn main_get_macros() -> SyncReturn<String> {
+    SyncReturn(
+        serde_json::Value::Array(
+            crate::client::macros::get_macros()
+                .iter()
+                .map(|m| m.summary())
+                .collect(),
+        )
+        .to_string(),
+    )
+}
+
+pub fn main_remove_macro(name: String) {
+    if let Err(e) = crate::client::macros::remove_macro(&name) {
+        log::error!("Failed to remove macro {}: {}", name, e);
+    }
+}
+
 pub fn session_get_conn_session_id(session_id: SessionID) -> SyncReturn<String> {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         SyncReturn(session.lc.read().unwrap().session_id.to_string())
@@ -2584,6 +2714,30 @@ pub fn plugin_set_shared_option(_id: String, _key: String, _value: String) {
     }
 }
 
+// Set the values of the plugin ui controls, `_values` is a json map of key to value.
+// Returns the error message, empty if succeeded.
+pub fn plugin_set_ui_values(_id: String, _peer: String, _values: String) -> SyncReturn<String> {
+    #[cfg(feature = "plugin_framework")]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    {
+        let res = serde_json::from_str::<HashMap<String, String>>(&_values)
+            .map_err(hbb_common::anyhow::Error::from)
+            .and_then(|values| crate::plugin::set_ui_values(&_id, &_peer, &values));
+        SyncReturn(match res {
+            Ok(()) => "".to_owned(),
+            Err(e) => e.to_string(),
+        })
+    }
+    #[cfg(any(
+        not(feature = "plugin_framework"),
+        target_os = "android",
+        target_os = "ios"
+    ))]
+    {
+        SyncReturn("".to_owned())
+    }
+}
+
 #[inline]
 pub fn plugin_reload(_id: String) {
     #[cfg(feature = "plugin_framework")]
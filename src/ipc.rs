@@ -832,6 +832,8 @@ async fn handle(data: Data, stream: &mut Connection) {
                 } else if name == "permanent-password-storage-and-salt" {
                     let (storage, salt) = Config::get_local_permanent_password_storage_and_salt();
                     value = Some(storage + "\n" + &salt);
+                } else if name == "audit-queue-status" {
+                    value = serde_json::to_string(&crate::server::audit_queue::status()).ok();
                 } else if name == "permanent-password-set" {
                     value = Some(if Config::has_permanent_password() {
                         "Y".to_owned()
@@ -1664,6 +1666,14 @@ pub fn get_unlock_pin() -> String {
     }
 }
 
+pub fn get_audit_queue_status() -> String {
+    if let Ok(Some(v)) = get_config("audit-queue-status") {
+        v
+    } else {
+        "".to_owned()
+    }
+}
+
 #[cfg(feature = "flutter")]
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 pub fn get_trusted_devices() -> String {
@@ -20,6 +20,8 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+pub mod remap;
+
 #[cfg(windows)]
 static mut IS_ALT_GR: bool = false;
 
@@ -324,7 +326,8 @@ pub mod client {
             return;
         }
         let peer = get_peer_platform().to_lowercase();
-        for key_event in event_to_key_events(peer, &event, keyboard_mode, lock_modes) {
+        let key_remap = get_peer_key_remap();
+        for key_event in event_to_key_events(peer, &event, keyboard_mode, lock_modes, &key_remap) {
             send_key_event(&key_event);
         }
     }
@@ -340,7 +343,8 @@ pub mod client {
             return;
         }
         let peer = session.peer_platform().to_lowercase();
-        for key_event in event_to_key_events(peer, &event, keyboard_mode, lock_modes) {
+        let key_remap = session.get_key_remap();
+        for key_event in event_to_key_events(peer, &event, keyboard_mode, lock_modes, &key_remap) {
             session.send_key_event(&key_event);
         }
     }
@@ -942,9 +946,14 @@ pub fn event_to_key_events(
     event: &Event,
     keyboard_mode: KeyboardMode,
     _lock_modes: Option<i32>,
+    key_remap: &remap::KeyRemap,
 ) -> Vec<KeyEvent> {
     peer.retain(|c| !c.is_whitespace());
 
+    // Remap the key before the mode conversion, so that all modes send the target key.
+    let remapped = key_remap.apply(event);
+    let event = remapped.as_ref().unwrap_or(event);
+
     update_modifiers_state(event);
 
     match event.event_type {
@@ -1002,6 +1011,18 @@ pub fn send_key_event(key_event: &KeyEvent) {
     }
 }
 
+pub fn get_peer_key_remap() -> Arc<remap::KeyRemap> {
+    #[cfg(not(any(feature = "flutter", feature = "cli")))]
+    if let Some(session) = CUR_SESSION.lock().unwrap().as_ref() {
+        return session.get_key_remap();
+    }
+    #[cfg(feature = "flutter")]
+    if let Some(session) = flutter::get_cur_session() {
+        return session.get_key_remap();
+    }
+    Default::default()
+}
+
 pub fn get_peer_platform() -> String {
     #[cfg(not(any(feature = "flutter", feature = "cli")))]
     if let Some(session) = CUR_SESSION.lock().unwrap().as_ref() {
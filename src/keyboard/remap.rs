@@ -0,0 +1,257 @@
+use hbb_common::{bail, ResultType};
+use rdev::{Event, EventType, Key};
+use std::collections::{BTreeMap, HashMap};
+
+// Peer option, json object of the local key names to the key names sent to the peer, e.g.
+// { "MetaLeft": "ControlLeft", "ControlLeft": "MetaLeft", "CapsLock": "Escape", "AltGr": "MetaRight" }
+// The names are the names of `rdev::Key`.
+pub const OPTION_KEY_REMAP: &str = "key-remap";
+
+const KEYS: &[Key] = &[
+    Key::Alt,
+    Key::AltGr,
+    Key::Backspace,
+    Key::CapsLock,
+    Key::ControlLeft,
+    Key::ControlRight,
+    Key::Delete,
+    Key::DownArrow,
+    Key::End,
+    Key::Escape,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Home,
+    Key::LeftArrow,
+    Key::MetaLeft,
+    Key::MetaRight,
+    Key::PageDown,
+    Key::PageUp,
+    Key::Return,
+    Key::RightArrow,
+    Key::ShiftLeft,
+    Key::ShiftRight,
+    Key::Space,
+    Key::Tab,
+    Key::UpArrow,
+    Key::PrintScreen,
+    Key::ScrollLock,
+    Key::Pause,
+    Key::NumLock,
+    Key::BackQuote,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::Num0,
+    Key::Minus,
+    Key::Equal,
+    Key::KeyQ,
+    Key::KeyW,
+    Key::KeyE,
+    Key::KeyR,
+    Key::KeyT,
+    Key::KeyY,
+    Key::KeyU,
+    Key::KeyI,
+    Key::KeyO,
+    Key::KeyP,
+    Key::LeftBracket,
+    Key::RightBracket,
+    Key::KeyA,
+    Key::KeyS,
+    Key::KeyD,
+    Key::KeyF,
+    Key::KeyG,
+    Key::KeyH,
+    Key::KeyJ,
+    Key::KeyK,
+    Key::KeyL,
+    Key::SemiColon,
+    Key::Quote,
+    Key::BackSlash,
+    Key::IntlBackslash,
+    Key::KeyZ,
+    Key::KeyX,
+    Key::KeyC,
+    Key::KeyV,
+    Key::KeyB,
+    Key::KeyN,
+    Key::KeyM,
+    Key::Comma,
+    Key::Dot,
+    Key::Slash,
+    Key::Insert,
+    Key::KpReturn,
+    Key::KpMinus,
+    Key::KpPlus,
+    Key::KpMultiply,
+    Key::KpDivide,
+    Key::Kp0,
+    Key::Kp1,
+    Key::Kp2,
+    Key::Kp3,
+    Key::Kp4,
+    Key::Kp5,
+    Key::Kp6,
+    Key::Kp7,
+    Key::Kp8,
+    Key::Kp9,
+    Key::KpDelete,
+    Key::Apps,
+];
+
+fn key_from_name(name: &str) -> Option<Key> {
+    KEYS.iter()
+        .find(|k| format!("{:?}", k).eq_ignore_ascii_case(name.trim()))
+        .copied()
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KeyRemap {
+    keys: HashMap<Key, Key>,
+}
+
+impl KeyRemap {
+    // Unknown key names are errors, so that a typo is not silently ignored on import.
+    pub fn parse(s: &str) -> ResultType<Self> {
+        let mut remap = Self::default();
+        if s.trim().is_empty() {
+            return Ok(remap);
+        }
+        let table: BTreeMap<String, String> = serde_json::from_str(s)?;
+        for (from, to) in table.iter() {
+            let Some(from_key) = key_from_name(from) else {
+                bail!("Unknown key: {}", from);
+            };
+            let Some(to_key) = key_from_name(to) else {
+                bail!("Unknown key: {}", to);
+            };
+            if from_key != to_key {
+                remap.keys.insert(from_key, to_key);
+            }
+        }
+        Ok(remap)
+    }
+
+    // Invalid options are ignored, they are validated when set.
+    pub fn from_option(s: &str) -> Self {
+        Self::parse(s).unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // The normalized json, sorted by the key names.
+    pub fn to_json(&self) -> String {
+        let table: BTreeMap<String, String> = self
+            .keys
+            .iter()
+            .map(|(from, to)| (format!("{:?}", from), format!("{:?}", to)))
+            .collect();
+        if table.is_empty() {
+            return "".to_owned();
+        }
+        serde_json::to_string(&table).unwrap_or_default()
+    }
+
+    // The codes of the event are replaced by the codes of the target key on this platform,
+    // the character is dropped as it belongs to the original key.
+    // Not supported on mobile, the key codes are usb hid codes there.
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    pub fn apply(&self, _event: &Event) -> Option<Event> {
+        None
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    pub fn apply(&self, event: &Event) -> Option<Event> {
+        let (key, event_type) = match event.event_type {
+            EventType::KeyPress(key) => {
+                let to = *self.keys.get(&key)?;
+                (to, EventType::KeyPress(to))
+            }
+            EventType::KeyRelease(key) => {
+                let to = *self.keys.get(&key)?;
+                (to, EventType::KeyRelease(to))
+            }
+            _ => return None,
+        };
+        #[cfg(target_os = "windows")]
+        let (platform_code, position_code) = (
+            rdev::win_code_from_key(key)?,
+            rdev::win_scancode_from_key(key)?,
+        );
+        #[cfg(not(target_os = "windows"))]
+        let (platform_code, position_code) = {
+            let code = rdev::code_from_key(key)?;
+            (code as u32, code as u32)
+        };
+        let mut event = event.clone();
+        event.event_type = event_type;
+        event.platform_code = platform_code as _;
+        event.position_code = position_code as _;
+        event.unicode = None;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let remap =
+            KeyRemap::parse(r#"{ "capslock": "Escape", "MetaLeft": "ControlLeft", "Tab": "Tab" }"#)
+                .unwrap();
+        assert_eq!(remap.keys.len(), 2);
+        assert_eq!(remap.keys.get(&Key::CapsLock), Some(&Key::Escape));
+        assert_eq!(
+            remap.to_json(),
+            r#"{"CapsLock":"Escape","MetaLeft":"ControlLeft"}"#
+        );
+        assert!(KeyRemap::parse(r#"{ "CapsLock": "Esc" }"#).is_err());
+        assert!(KeyRemap::parse("[").is_err());
+        assert!(KeyRemap::parse("").unwrap().is_empty());
+        assert!(KeyRemap::from_option("[").is_empty());
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn test_apply() {
+        let remap = KeyRemap::parse(r#"{ "CapsLock": "Escape" }"#).unwrap();
+        let event = Event {
+            time: std::time::SystemTime::now(),
+            unicode: None,
+            platform_code: 0,
+            position_code: 0,
+            event_type: EventType::KeyPress(Key::CapsLock),
+            usb_hid: 0,
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            extra_data: 0,
+        };
+        let remapped = remap.apply(&event).unwrap();
+        assert_eq!(remapped.event_type, EventType::KeyPress(Key::Escape));
+        assert_ne!(remapped.position_code, 0);
+
+        let mut event = event;
+        event.event_type = EventType::KeyRelease(Key::KeyA);
+        assert!(remap.apply(&event).is_none());
+    }
+}
@@ -758,5 +758,25 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("wayland-keyboard-input-reset-choice-tip", "Нулиране на избора за въвеждане от клавиатура"),
         ("remember-wayland-keyboard-choice-tip", "Не питай отново за този отдалечен компютър"),
         ("Why this happens", "Защо се случва това"),
+        ("Clipboard history", ""),
+        ("Record macro", ""),
+        ("Stop recording macro", ""),
+        ("Macros", ""),
+        ("Hotkey", ""),
+        ("Discard", ""),
+        ("Play", ""),
+        ("Paste as keystrokes", ""),
+        ("paste-as-keystrokes-tip", ""),
+        ("Remote keyboard layout", ""),
+        ("Key interval (ms)", ""),
+        ("Key remapping", ""),
+        ("key-remapping-tip", ""),
+        ("Import", ""),
+        ("Export", ""),
+        ("Sharp text when idle", ""),
+        ("Audit events", ""),
+        ("Pending", ""),
+        ("Dropped", ""),
+        ("Rejected", ""),
     ].iter().cloned().collect();
 }
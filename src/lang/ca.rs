@@ -758,5 +758,25 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("wayland-keyboard-input-reset-choice-tip", "Restableix l'opció d'entrada de teclat"),
         ("remember-wayland-keyboard-choice-tip", "No tornis a preguntar-ho per a aquest equip remot"),
         ("Why this happens", "Per què passa això"),
+        ("Clipboard history", ""),
+        ("Record macro", ""),
+        ("Stop recording macro", ""),
+        ("Macros", ""),
+        ("Hotkey", ""),
+        ("Discard", ""),
+        ("Play", ""),
+        ("Paste as keystrokes", ""),
+        ("paste-as-keystrokes-tip", ""),
+        ("Remote keyboard layout", ""),
+        ("Key interval (ms)", ""),
+        ("Key remapping", ""),
+        ("key-remapping-tip", ""),
+        ("Import", ""),
+        ("Export", ""),
+        ("Sharp text when idle", ""),
+        ("Audit events", ""),
+        ("Pending", ""),
+        ("Dropped", ""),
+        ("Rejected", ""),
     ].iter().cloned().collect();
 }
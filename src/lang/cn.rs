@@ -758,5 +758,25 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("wayland-keyboard-input-reset-choice-tip", "重置键盘输入选择"),
         ("remember-wayland-keyboard-choice-tip", "以后对这台远程电脑不再询问"),
         ("Why this happens", "了解原因"),
+        ("Clipboard history", "剪贴板历史"),
+        ("Record macro", "录制宏"),
+        ("Stop recording macro", "停止录制宏"),
+        ("Macros", "宏"),
+        ("Hotkey", "快捷键"),
+        ("Discard", "丢弃"),
+        ("Play", "播放"),
+        ("Paste as keystrokes", "以按键方式粘贴"),
+        ("paste-as-keystrokes-tip", "将剪贴板文本逐键输入到远程端，适用于登录界面或不支持剪贴板的控制台。BIOS 和虚拟机控制台请使用远程键盘布局。非 ASCII 字符会在支持时以 Unicode 方式输入。"),
+        ("Remote keyboard layout", "远程键盘布局"),
+        ("Key interval (ms)", "按键间隔（毫秒）"),
+        ("Key remapping", "按键重映射"),
+        ("key-remapping-tip", "在此按下的按键会以其他按键发送到远程电脑，例如 {\"CapsLock\": \"Escape\"}。按键名称：ControlLeft、MetaLeft、Alt、AltGr、CapsLock、Escape、F1、KeyA、Num1 等。"),
+        ("Import", "导入"),
+        ("Export", "导出"),
+        ("Sharp text when idle", "空闲时清晰显示文字"),
+        ("Audit events", "审计事件"),
+        ("Pending", "待发送"),
+        ("Dropped", "已丢弃"),
+        ("Rejected", "被拒绝"),
     ].iter().cloned().collect();
 }
@@ -279,5 +279,7 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("wayland-soft-keyboard-input-label", "Soft keyboard input"),
         ("wayland-keyboard-input-reset-choice-tip", "Reset keyboard input choice"),
         ("remember-wayland-keyboard-choice-tip", "Don't ask again for this remote computer"),
+        ("paste-as-keystrokes-tip", "Type the clipboard text on the remote side key by key, e.g. on a login screen or a console without clipboard support. Use the remote keyboard layout for BIOS and virtual machine consoles. Non-ASCII characters are sent as Unicode input where supported."),
+        ("key-remapping-tip", "Keys pressed here are sent as other keys to this remote computer, e.g. {\"CapsLock\": \"Escape\"}. Key names: ControlLeft, MetaLeft, Alt, AltGr, CapsLock, Escape, F1, KeyA, Num1, etc."),
     ].iter().cloned().collect();
 }
@@ -758,5 +758,25 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("wayland-keyboard-input-reset-choice-tip", "重設鍵盤輸入選擇"),
         ("remember-wayland-keyboard-choice-tip", "不要再為此遠端電腦詢問"),
         ("Why this happens", "發生原因"),
+        ("Clipboard history", "剪貼簿歷史"),
+        ("Record macro", "錄製巨集"),
+        ("Stop recording macro", "停止錄製巨集"),
+        ("Macros", "巨集"),
+        ("Hotkey", "快速鍵"),
+        ("Discard", "捨棄"),
+        ("Play", "播放"),
+        ("Paste as keystrokes", "以按鍵方式貼上"),
+        ("paste-as-keystrokes-tip", "將剪貼簿文字逐鍵輸入到遠端，適用於登入畫面或不支援剪貼簿的主控台。BIOS 與虛擬機器主控台請使用遠端鍵盤配置。非 ASCII 字元會在支援時以 Unicode 方式輸入。"),
+        ("Remote keyboard layout", "遠端鍵盤配置"),
+        ("Key interval (ms)", "按鍵間隔（毫秒）"),
+        ("Key remapping", "按鍵重新對應"),
+        ("key-remapping-tip", "在此按下的按鍵會以其他按鍵傳送到遠端電腦，例如 {\"CapsLock\": \"Escape\"}。按鍵名稱：ControlLeft、MetaLeft、Alt、AltGr、CapsLock、Escape、F1、KeyA、Num1 等。"),
+        ("Import", "匯入"),
+        ("Export", "匯出"),
+        ("Sharp text when idle", "閒置時清晰顯示文字"),
+        ("Audit events", ""),
+        ("Pending", ""),
+        ("Dropped", ""),
+        ("Rejected", ""),
     ].iter().cloned().collect();
 }
@@ -36,6 +36,28 @@ fn path_plugins(id: &str) -> PathBuf {
     HbbConfig::path("plugins").join(id)
 }
 
+// Insert all the values and store once, the old values are restored if failed to store.
+fn update_values<T: DerefMut<Target = HashMap<String, String>>>(
+    config: &mut T,
+    values: &HashMap<String, String>,
+    store: impl FnOnce(&T) -> ResultType<()>,
+) -> ResultType<()> {
+    let old: Vec<_> = values
+        .iter()
+        .map(|(k, v)| (k.to_owned(), config.insert(k.to_owned(), v.to_owned())))
+        .collect();
+    if let Err(e) = store(config) {
+        for (k, v) in old {
+            match v {
+                Some(v) => config.insert(k, v),
+                None => config.remove(&k),
+            };
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
 pub fn remove(id: &str) {
     CONFIG_SHARED.lock().unwrap().remove(id);
     CONFIG_PEERS.lock().unwrap().remove(id);
@@ -131,6 +153,19 @@ impl SharedConfig {
             }
         }
     }
+
+    pub fn set_values(id: &str, values: &HashMap<String, String>) -> ResultType<()> {
+        Self::load_if_not_exists(id);
+        match CONFIG_SHARED.lock().unwrap().get_mut(id) {
+            Some(config) => update_values(config, values, |c| {
+                hbb_common::config::store_path(Self::path(id), c)
+            }),
+            None => {
+                // unreachable
+                bail!("No such plugin {}", id)
+            }
+        }
+    }
 }
 
 impl PeerConfig {
@@ -212,6 +247,54 @@ impl PeerConfig {
             }
         }
     }
+
+    pub fn set_values(id: &str, peer: &str, values: &HashMap<String, String>) -> ResultType<()> {
+        Self::load_if_not_exists(id, peer);
+        match CONFIG_PEERS.lock().unwrap().get_mut(id) {
+            Some(peers) => match peers.get_mut(peer) {
+                Some(config) => update_values(config, values, |c| {
+                    hbb_common::config::store_path(Self::path(id, peer), c)
+                }),
+                None => {
+                    // unreachable
+                    bail!("No such peer {}", peer)
+                }
+            },
+            None => {
+                // unreachable
+                bail!("No such plugin {}", id)
+            }
+        }
+    }
+}
+
+// Values from the plugin ui controls are validated against the `ConfigItem`s declared in the desc.
+// Nothing is stored if any of the values is invalid, all the values are stored in one update.
+fn validate_ui_values(id: &str, is_peer: bool, values: &HashMap<String, String>) -> ResultType<()> {
+    let Some(desc_conf) = super::plugins::get_desc_conf(id) else {
+        bail!("Plugin {} not found", id);
+    };
+    for (key, value) in values.iter() {
+        if let Err(e) = desc_conf.validate(is_peer, key, value) {
+            bail!("Invalid value of plugin {}, {}", id, e);
+        }
+    }
+    Ok(())
+}
+
+// Shared values are stored by the server process, which validates them.
+pub fn set_ui_values(id: &str, peer: &str, values: &HashMap<String, String>) -> ResultType<()> {
+    if peer.is_empty() {
+        return super::ipc::set_configs(id, values.clone());
+    }
+    validate_ui_values(id, true, values)?;
+    PeerConfig::set_values(id, peer, values)
+}
+
+// Handles `Plugin::Configs` in the server, the values may be sent by any ipc client.
+pub(super) fn set_shared_ui_values(id: &str, values: &HashMap<String, String>) -> ResultType<()> {
+    validate_ui_values(id, false, values)?;
+    SharedConfig::set_values(id, values)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -361,3 +444,21 @@ pub(super) extern "C" fn cb_get_conf(
     }
     ptr::null()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_values() {
+        let mut config = SharedConfig(HashMap::from([("a".to_owned(), "1".to_owned())]));
+        let values = HashMap::from([
+            ("a".to_owned(), "2".to_owned()),
+            ("b".to_owned(), "3".to_owned()),
+        ]);
+        assert!(update_values(&mut config, &values, |_| bail!("failed")).is_err());
+        assert_eq!(config.0, HashMap::from([("a".to_owned(), "1".to_owned())]));
+        assert!(update_values(&mut config, &values, |_| Ok(())).is_ok());
+        assert_eq!(config.0, values);
+    }
+}
@@ -1,4 +1,4 @@
-use hbb_common::ResultType;
+use hbb_common::{bail, ResultType};
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
 use std::collections::HashMap;
@@ -21,11 +21,69 @@ pub struct UiCheckbox {
     action: String, // The action to be triggered when the checkbox is checked or unchecked.
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiTextInput {
+    key: String,
+    text: String,
+    tooltip: String,
+    action: String, // The action to be triggered when the text is submitted.
+    #[serde(default)]
+    placeholder: String,
+    #[serde(default)]
+    multiline: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiSelectOption {
+    value: String,
+    text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiSelect {
+    key: String,
+    text: String,
+    tooltip: String,
+    action: String, // The action to be triggered when the selection is changed.
+    options: Vec<UiSelectOption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiNumber {
+    key: String,
+    text: String,
+    tooltip: String,
+    action: String, // The action to be triggered when the number is changed.
+    min: f64,
+    max: f64,
+    #[serde(default)]
+    step: f64,
+    #[serde(default)]
+    slider: bool, // Show a slider instead of a text field.
+}
+
+// A group of inputs submitted together, shown in a dialog opened by a button if `dialog` is true.
+// The fields must not be forms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiForm {
+    key: String,
+    text: String,
+    tooltip: String,
+    action: String, // The action to be triggered when the form is submitted.
+    fields: Vec<UiType>,
+    #[serde(default)]
+    dialog: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "t", content = "c")]
 pub enum UiType {
     Button(UiButton),
     Checkbox(UiCheckbox),
+    TextInput(UiTextInput),
+    Select(UiSelect),
+    Number(UiNumber),
+    Form(UiForm),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +96,45 @@ pub struct ConfigItem {
     pub key: String,
     pub default: String,
     pub description: String,
+    // Constraints of the values set from the ui, all are optional.
+    // Allowed values, e.g. the values of a select.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+}
+
+impl ConfigItem {
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        if !self.options.is_empty() && !self.options.iter().any(|x| x == value) {
+            return Err(format!(
+                "'{}' is not a valid option of '{}'",
+                value, self.key
+            ));
+        }
+        if let Some(max_len) = self.max_len {
+            if value.chars().count() > max_len {
+                return Err(format!(
+                    "The value of '{}' is longer than {}",
+                    self.key, max_len
+                ));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let v = match value.trim().parse::<f64>() {
+                Ok(v) if v.is_finite() => v,
+                _ => return Err(format!("The value of '{}' is not a number", self.key)),
+            };
+            if self.min.map_or(false, |min| v < min) || self.max.map_or(false, |max| v > max) {
+                return Err(format!("The value of '{}' is out of range", self.key));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,9 +171,48 @@ pub struct Desc {
     location: Location,
     config: Config,
     listen_events: Vec<String>,
+    // Host functions a wasm plugin may use, ignored by native plugins.
+    #[serde(default)]
+    permissions: Vec<String>,
+}
+
+impl Config {
+    // Only declared keys can be set from the ui.
+    pub fn validate(&self, is_peer: bool, key: &str, value: &str) -> Result<(), String> {
+        let items = if is_peer { &self.peer } else { &self.shared };
+        match items.iter().find(|x| x.key == key) {
+            Some(item) => item.validate(value),
+            None => Err(format!("Config '{}' is not declared", key)),
+        }
+    }
+}
+
+impl Location {
+    // The fields of a form must not be forms.
+    fn check(&self) -> Result<(), String> {
+        for item in self.ui.values().flatten() {
+            if let UiType::Form(form) = item {
+                if form.fields.iter().any(|x| matches!(x, UiType::Form(_))) {
+                    return Err(format!(
+                        "The fields of form '{}' must not be forms",
+                        form.key
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Desc {
+    // Checked before the plugin is loaded, the ui does not handle an invalid desc.
+    pub fn check(&self) -> ResultType<()> {
+        if let Err(e) = self.location.check() {
+            bail!("Invalid desc of plugin {}, {}", self.meta.id, e);
+        }
+        Ok(())
+    }
+
     pub fn from_cstr(s: *const c_char) -> ResultType<Self> {
         let s = unsafe { CStr::from_ptr(s) };
         Ok(serde_json::from_str(s.to_str()?)?)
@@ -97,4 +233,85 @@ impl Desc {
     pub fn listen_events(&self) -> &Vec<String> {
         &self.listen_events
     }
+
+    pub fn permissions(&self) -> &Vec<String> {
+        &self.permissions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(s: &str) -> ConfigItem {
+        serde_json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_validate_config_item() {
+        let plain = item(r#"{"key": "a", "default": "", "description": ""}"#);
+        assert!(plain.validate("anything").is_ok());
+
+        let select =
+            item(r#"{"key": "script", "default": "a", "description": "", "options": ["a", "b"]}"#);
+        assert!(select.validate("b").is_ok());
+        assert!(select.validate("c").is_err());
+
+        let number =
+            item(r#"{"key": "n", "default": "1", "description": "", "min": 1, "max": 10}"#);
+        assert!(number.validate("10").is_ok());
+        assert!(number.validate("0.5").is_err());
+        assert!(number.validate("NaN").is_err());
+        assert!(number.validate("x").is_err());
+
+        let text = item(r#"{"key": "ticket", "default": "", "description": "", "max_len": 3}"#);
+        assert!(text.validate("abc").is_ok());
+        assert!(text.validate("abcd").is_err());
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let config: Config = serde_json::from_str(
+            r#"{"shared": [{"key": "a", "default": "", "description": ""}], "peer": []}"#,
+        )
+        .unwrap();
+        assert!(config.validate(false, "a", "v").is_ok());
+        assert!(config.validate(true, "a", "v").is_err());
+        assert!(config.validate(false, "b", "v").is_err());
+    }
+
+    #[test]
+    fn test_ui_types() {
+        let ui: Vec<UiType> = serde_json::from_str(
+            r#"[
+                {"t": "Select", "c": {"key": "script", "text": "Script", "tooltip": "", "action": "",
+                    "options": [{"value": "a", "text": "A"}]}},
+                {"t": "Form", "c": {"key": "ticket", "text": "Ticket", "tooltip": "", "action": "submit", "dialog": true,
+                    "fields": [
+                        {"t": "TextInput", "c": {"key": "ticket_no", "text": "Number", "tooltip": "", "action": ""}},
+                        {"t": "Number", "c": {"key": "priority", "text": "Priority", "tooltip": "", "action": "",
+                            "min": 1, "max": 5, "slider": true}}
+                    ]}}
+            ]"#,
+        )
+        .unwrap();
+        assert!(matches!(&ui[1], UiType::Form(form) if form.fields.len() == 2 && form.dialog));
+        let location = Location {
+            ui: HashMap::from([("main".to_owned(), ui.clone())]),
+        };
+        assert!(location.check().is_ok());
+
+        let nested = UiType::Form(UiForm {
+            key: "nested".to_owned(),
+            text: "".to_owned(),
+            tooltip: "".to_owned(),
+            action: "".to_owned(),
+            fields: ui,
+            dialog: false,
+        });
+        let location = Location {
+            ui: HashMap::from([("main".to_owned(), vec![nested])]),
+        };
+        assert!(location.check().is_err());
+    }
 }
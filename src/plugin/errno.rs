@@ -24,6 +24,10 @@ pub const ERR_CALL_INVALID_PEER: i32 = 10204;
 pub const ERR_CALL_INVALID_ARGS: i32 = 10301;
 pub const ERR_PEER_ID_MISMATCH: i32 = 10302;
 pub const ERR_CALL_CONFIG_VALUE: i32 = 10303;
+// wasm plugin trapped, e.g. out of fuel or memory
+pub const ERR_CALL_TRAPPED: i32 = 10304;
+// wasm plugin did not return in time
+pub const ERR_CALL_TIMEOUT: i32 = 10305;
 // no handlers on calling
 pub const ERR_NOT_HANDLED: i32 = 10401;
 
@@ -37,6 +41,7 @@ pub const ERR_CALLBACK_INVALID_MSG: i32 = 20003;
 pub const ERR_CALLBACK_TARGET: i32 = 20004;
 pub const ERR_CALLBACK_TARGET_TYPE: i32 = 20005;
 pub const ERR_CALLBACK_PEER_NOT_FOUND: i32 = 20006;
+pub const ERR_CALLBACK_PERMISSION_DENIED: i32 = 20007;
 
 pub const ERR_CALLBACK_FAILED: i32 = 21001;
 
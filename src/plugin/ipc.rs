@@ -1,7 +1,8 @@
 // to-do: Interdependence(This mod and crate::ipc) is not good practice here.
 use crate::ipc::{connect, Connection, Data};
-use hbb_common::{allow_err, log, tokio, ResultType};
+use hbb_common::{allow_err, bail, log, tokio, ResultType};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum InstallStatus {
@@ -11,12 +12,17 @@ pub enum InstallStatus {
     FailedCreating,
     FailedDownloading,
     FailedInstalling,
+    FailedVerifying,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "t", content = "c")]
 pub enum Plugin {
     Config(String, String, Option<String>),
+    // Set several values of the shared config at once.
+    Configs(String, HashMap<String, String>),
+    // The reply of `Configs`, the error if the values are not stored.
+    ConfigsResult(String, Option<String>),
     ManagerConfig(String, Option<String>),
     ManagerPluginConfig(String, String, Option<String>),
     Load(String),
@@ -35,6 +41,11 @@ pub async fn set_config(id: &str, name: &str, value: String) -> ResultType<()> {
     set_config_async(id, name, value).await
 }
 
+#[tokio::main(flavor = "current_thread")]
+pub async fn set_configs(id: &str, values: HashMap<String, String>) -> ResultType<()> {
+    set_configs_async(id, values).await
+}
+
 #[tokio::main(flavor = "current_thread")]
 pub async fn get_manager_config(name: &str) -> ResultType<Option<String>> {
     get_manager_config_async(name, 1_000).await
@@ -156,6 +167,19 @@ async fn set_manager_plugin_config_async(id: &str, name: &str, value: String) ->
     Ok(())
 }
 
+async fn set_configs_async(id: &str, values: HashMap<String, String>) -> ResultType<()> {
+    let mut c = connect(1000, "").await?;
+    c.send(&Data::Plugin(Plugin::Configs(id.to_owned(), values)))
+        .await?;
+    match c.next_timeout(1000).await? {
+        Some(Data::Plugin(Plugin::ConfigsResult(id2, err))) if id == id2 => match err {
+            None => Ok(()),
+            Some(e) => bail!("{}", e),
+        },
+        _ => bail!("No reply of setting the config of plugin {}", id),
+    }
+}
+
 pub async fn load_plugin_async(id: &str) -> ResultType<()> {
     let mut c = connect(1000, "").await?;
     c.send(&Data::Plugin(Plugin::Load(id.to_owned()))).await?;
@@ -190,6 +214,19 @@ pub async fn handle_plugin(plugin: Plugin, stream: &mut Connection) {
                 allow_err!(super::SharedConfig::set(&id, &name, &value));
             }
         },
+        Plugin::Configs(id, values) => {
+            let err = super::config::set_shared_ui_values(&id, &values)
+                .err()
+                .map(|e| e.to_string());
+            if let Some(e) = &err {
+                log::error!("Failed to set the config of plugin {}, {}", id, e);
+            }
+            allow_err!(
+                stream
+                    .send(&Data::Plugin(Plugin::ConfigsResult(id, err)))
+                    .await
+            );
+        }
         Plugin::ManagerConfig(name, value) => match value {
             None => {
                 let value = super::ManagerConfig::get_option(&name);
@@ -223,6 +223,9 @@ fn elevate_uninstall(plugin_id: &str) -> ResultType<bool> {
 }
 
 pub fn install_plugin(id: &str) -> ResultType<()> {
+    if !super::signature::is_id_allowed(id) {
+        bail!("Plugin '{}' is not in the allowed list", id);
+    }
     match PLUGIN_INFO.lock().unwrap().get(id) {
         Some(plugin) => {
             let mut same_plugin_exists = false;
@@ -382,6 +385,9 @@ async fn handle_conn(mut stream: crate::ipc::Connection) {
                                     InstallStatus::FailedInstalling => {
                                         push_install_event(&id, "failed-installing");
                                     }
+                                    InstallStatus::FailedVerifying => {
+                                        push_install_event(&id, "failed-verifying");
+                                    }
                                 }
                             }
                             _ => {}
@@ -446,7 +452,10 @@ pub(super) mod install {
     use crate::hbbs_http::create_http_client;
     use crate::{
         ipc::{connect, Data},
-        plugin::ipc::{InstallStatus, Plugin},
+        plugin::{
+            ipc::{InstallStatus, Plugin},
+            signature::{verify_package, PACKAGE_FILENAME, SIGNATURE_SUFFIX},
+        },
     };
     use hbb_common::{allow_err, bail, log, tokio, ResultType};
     use std::{
@@ -471,7 +480,7 @@ pub(super) mod install {
         Ok(())
     }
 
-    fn download_to_file(url: &str, file: File) -> ResultType<()> {
+    fn download(url: &str) -> ResultType<Vec<u8>> {
         let resp = match create_http_client().get(url).send() {
             Ok(resp) => resp,
             Err(e) => {
@@ -483,8 +492,28 @@ pub(super) mod install {
             bail!("get plugin from '{}', status code: {}", url, resp.status());
         }
 
+        Ok(resp.bytes()?.to_vec())
+    }
+
+    fn download_to_file(url: &str, file: File) -> ResultType<()> {
         let mut writer = BufWriter::new(file);
-        writer.write_all(resp.bytes()?.as_ref())?;
+        writer.write_all(&download(url)?)?;
+        Ok(())
+    }
+
+    // Reject unsigned or tampered packages before anything is extracted.
+    // Returns the signature, it is kept with the package to verify the plugin on loading.
+    fn verify_file(id: &str, url: &str, filename: &Path) -> ResultType<Vec<u8>> {
+        let sig = download(&format!("{}{}", url, SIGNATURE_SUFFIX))?;
+        let data = std::fs::read(filename)?;
+        verify_package(id, &data, &sig)?;
+        Ok(sig)
+    }
+
+    fn keep_package(filename: &Path, sig: &[u8], target_dir: &Path) -> ResultType<()> {
+        let package = target_dir.join(PACKAGE_FILENAME);
+        std::fs::copy(filename, &package)?;
+        std::fs::write(format!("{}{}", package.display(), SIGNATURE_SUFFIX), sig)?;
         Ok(())
     }
 
@@ -586,9 +615,21 @@ pub(super) mod install {
             return;
         }
 
+        // verify
+        let sig = match verify_file(id, url, &filename) {
+            Ok(sig) => sig,
+            Err(e) => {
+                log::error!("Failed to verify plugin '{}', {}", id, e);
+                send_install_status(id, InstallStatus::FailedVerifying);
+                return;
+            }
+        };
+
         // install
         send_install_status(id, InstallStatus::Installing);
-        if let Err(e) = do_install_file(&filename, &plugin_dir) {
+        if let Err(e) = do_install_file(&filename, &plugin_dir)
+            .and_then(|_| keep_package(&filename, &sig, &plugin_dir))
+        {
             log::error!("Failed to install plugin: {}", e);
             send_install_status(id, InstallStatus::FailedInstalling);
             return;
@@ -18,13 +18,16 @@ pub mod native;
 pub mod native_handlers;
 mod plog;
 mod plugins;
+pub mod signature;
+mod wasm;
 
 pub use manager::{
     install::{change_uninstall_plugin, install_plugin_with_url},
     install_plugin, load_plugin_list, remove_uninstalled, uninstall_plugin,
 };
 pub use plugins::{
-    handle_client_event, handle_listen_event, handle_server_event, handle_ui_event, load_plugin,
+    handle_client_event, handle_listen_event, handle_listen_event_with_data, handle_server_event,
+    handle_ui_event, load_plugin,
     reload_plugin, sync_ui, unload_plugin,
 };
 
@@ -37,10 +40,25 @@ pub const EVENT_ON_CONN_CLIENT: &str = "on_conn_client";
 pub const EVENT_ON_CONN_SERVER: &str = "on_conn_server";
 pub const EVENT_ON_CONN_CLOSE_CLIENT: &str = "on_conn_close_client";
 pub const EVENT_ON_CONN_CLOSE_SERVER: &str = "on_conn_close_server";
+// The events below are sent on the controlled side with a `data` object, see `MsgListenEvent`.
+// data: { "direction": "send" | "receive", "id", "path", "num", "files": [[name, size]] }
+pub const EVENT_ON_FILE_TRANSFER_START: &str = "on_file_transfer_start";
+// data: { "direction": "send" | "receive", "id", "file_num", "error" }, "error" is empty if succeeded
+pub const EVENT_ON_FILE_TRANSFER_DONE: &str = "on_file_transfer_done";
+// data: { "direction": "send" | "receive", "formats": [..], "size" }
+pub const EVENT_ON_CLIPBOARD: &str = "on_clipboard";
+// data: { "terminal_id" }
+pub const EVENT_ON_TERMINAL_OPEN: &str = "on_terminal_open";
+pub const EVENT_ON_TERMINAL_CLOSE: &str = "on_terminal_close";
+// data: {}
+pub const EVENT_ON_RECORDING_START: &str = "on_recording_start";
+pub const EVENT_ON_RECORDING_STOP: &str = "on_recording_stop";
+// data: { "name", "enabled" }
+pub const EVENT_ON_PERMISSION_CHANGE: &str = "on_permission_change";
 
 static PLUGIN_SOURCE_LOCAL_DIR: &str = "plugins";
 
-pub use config::{ManagerConfig, PeerConfig, SharedConfig};
+pub use config::{set_ui_values, ManagerConfig, PeerConfig, SharedConfig};
 
 /// Common plugin return.
 ///
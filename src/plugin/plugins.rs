@@ -1,4 +1,9 @@
-use super::{desc::Desc, errno::*, *};
+use super::{
+    desc::Desc,
+    errno::*,
+    wasm::{is_wasm_path, WasmPlugin, WASM_SUFFIX},
+    *,
+};
 #[cfg(not(debug_assertions))]
 use crate::common::is_server;
 use crate::flutter;
@@ -26,6 +31,7 @@ pub const METHOD_HANDLE_LISTEN_EVENT: &[u8; 20] = b"handle_listen_event\0";
 lazy_static::lazy_static! {
     static ref PLUGIN_INFO: Arc<RwLock<HashMap<String, PluginInfo>>> = Default::default();
     static ref PLUGINS: Arc<RwLock<HashMap<String, Plugin>>> = Default::default();
+    static ref WASM_PLUGINS: Arc<RwLock<HashMap<String, Arc<WasmPlugin>>>> = Default::default();
 }
 
 pub(super) struct PluginInfo {
@@ -258,6 +264,8 @@ make_plugin!(
 #[derive(Serialize)]
 pub struct MsgListenEvent {
     pub event: String,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub data: serde_json::Value,
 }
 
 #[cfg(target_os = "windows")]
@@ -267,6 +275,18 @@ const DYLIB_SUFFIX: &str = ".so";
 #[cfg(target_os = "macos")]
 const DYLIB_SUFFIX: &str = ".dylib";
 
+// Native and wasm plugins are looked up by id, a plugin of one kind must not shadow the other.
+pub(super) fn check_id_unused<T>(
+    id: &str,
+    others: &HashMap<String, T>,
+    kind: &str,
+) -> ResultType<()> {
+    if others.contains_key(id) {
+        bail!("Plugin id {} is already used by a {} plugin", id, kind);
+    }
+    Ok(())
+}
+
 pub(super) fn load_plugins(uninstalled_ids: &HashSet<String>) -> ResultType<()> {
     let plugins_dir = super::get_plugins_dir()?;
     if !plugins_dir.exists() {
@@ -308,7 +328,9 @@ fn load_plugin_dir(dir: &Path) {
                     if path.is_file() {
                         let filename = entry.file_name();
                         let filename = filename.to_str().unwrap_or("");
-                        if filename.starts_with("plugin_") && filename.ends_with(DYLIB_SUFFIX) {
+                        if filename.starts_with("plugin_")
+                            && (filename.ends_with(DYLIB_SUFFIX) || filename.ends_with(WASM_SUFFIX))
+                        {
                             if let Some(path) = path.to_str() {
                                 if let Err(e) = load_plugin_path(path) {
                                     log::error!("Failed to load plugin {}, {}", filename, e);
@@ -332,6 +354,12 @@ fn load_plugin_dir(dir: &Path) {
 pub fn unload_plugin(id: &str) {
     log::info!("Plugin {} unloaded", id);
     PLUGINS.write().unwrap().remove(id);
+    WASM_PLUGINS.write().unwrap().remove(id);
+}
+
+#[inline]
+fn get_wasm_plugin(id: &str) -> Option<Arc<WasmPlugin>> {
+    WASM_PLUGINS.read().unwrap().get(id).cloned()
 }
 
 pub(super) fn mark_uninstalled(id: &str, uninstalled: bool) {
@@ -353,6 +381,11 @@ pub fn reload_plugin(id: &str) -> ResultType<()> {
 }
 
 fn load_plugin_path(path: &str) -> ResultType<()> {
+    // Never load a file which is not from a signed package, it may be replaced after installing.
+    super::signature::verify_installed_file(Path::new(path))?;
+    if is_wasm_path(path) {
+        return load_wasm_plugin_path(path);
+    }
     log::info!("Begin load plugin {}", path);
 
     let plugin = Plugin::new(path)?;
@@ -361,7 +394,9 @@ fn load_plugin_path(path: &str) -> ResultType<()> {
     // to-do validate plugin
     // to-do check the plugin id (make sure it does not use another plugin's id)
 
+    desc.check()?;
     let id = desc.meta().id.clone();
+    check_id_unused(&id, &WASM_PLUGINS.read().unwrap(), "wasm")?;
     let plugin_info = PluginInfo {
         path: path.to_string(),
         uninstalled: false,
@@ -403,6 +438,49 @@ fn load_plugin_path(path: &str) -> ResultType<()> {
     Ok(())
 }
 
+// Wasm plugins run in a sandbox, they can only use the host functions allowed by `Desc::permissions`.
+fn load_wasm_plugin_path(path: &str) -> ResultType<()> {
+    log::info!("Begin load wasm plugin {}", path);
+
+    let plugin = WasmPlugin::new(path)?;
+    let desc = plugin.desc().clone();
+    desc.check()?;
+    let id = desc.meta().id.clone();
+    check_id_unused(&id, &PLUGINS.read().unwrap(), "native")?;
+    PLUGIN_INFO.write().unwrap().insert(
+        id.clone(),
+        PluginInfo {
+            path: path.to_string(),
+            uninstalled: false,
+            desc: desc.clone(),
+        },
+    );
+
+    let init_info = serde_json::to_string(&InitInfo {
+        is_server: super::is_server_running(),
+    })?;
+    if let Err(e) = plugin.init(&init_info) {
+        log::error!("Failed to init plugin '{}', {}", id, e);
+    }
+
+    if super::is_server_running() {
+        super::config::ManagerConfig::add_plugin(&id)?;
+    }
+    reload_ui(&desc, None);
+    WASM_PLUGINS
+        .write()
+        .unwrap()
+        .insert(id.clone(), Arc::new(plugin));
+
+    log::info!(
+        "Wasm plugin {} loaded, permissions: {:?}, {}",
+        id,
+        desc.permissions(),
+        path
+    );
+    Ok(())
+}
+
 pub fn sync_ui(sync_to: String) {
     for plugin in PLUGIN_INFO.read().unwrap().values() {
         reload_ui(&plugin.desc, Some(&sync_to));
@@ -445,6 +523,9 @@ pub fn plugin_call_get_return(
     peer: &str,
     event: &[u8],
 ) -> ResultType<PluginReturn> {
+    if let Some(plugin) = get_wasm_plugin(id) {
+        return Ok(plugin.call_get_return(method, peer.as_bytes(), event));
+    }
     match PLUGINS.read().unwrap().get(id) {
         Some(plugin) => Ok((plugin.call)(
             method.as_ptr() as _,
@@ -466,7 +547,7 @@ pub fn handle_server_event(id: &str, peer: &str, event: &[u8]) -> ResultType<()>
     handle_event(METHOD_HANDLE_PEER, id, peer, event)
 }
 
-fn _handle_listen_event(event: String, peer: String) {
+fn _handle_listen_event(event: String, peer: String, data: serde_json::Value) {
     let mut plugins = Vec::new();
     for info in PLUGIN_INFO.read().unwrap().values() {
         if info.desc.listen_events().contains(&event.to_string()) {
@@ -480,12 +561,27 @@ fn _handle_listen_event(event: String, peer: String) {
 
     if let Ok(evt) = serde_json::to_string(&MsgListenEvent {
         event: event.clone(),
+        data,
     }) {
         let mut evt_bytes = evt.as_bytes().to_vec();
         evt_bytes.push(0);
         let mut peer: String = peer.to_owned();
         peer.push('\0');
         for id in plugins {
+            if let Some(plugin) = get_wasm_plugin(&id) {
+                let (code, out) =
+                    plugin.call(METHOD_HANDLE_LISTEN_EVENT, peer.as_bytes(), &evt_bytes);
+                if code != ERR_SUCCESS {
+                    log::error!(
+                        "Failed to handle plugin listen event, id: {}, event: {}, code: {}, msg: {}",
+                        id,
+                        event,
+                        code,
+                        String::from_utf8_lossy(&out)
+                    );
+                }
+                continue;
+            }
             match PLUGINS.read().unwrap().get(&id) {
                 Some(plugin) => {
                     let mut ret = (plugin.call)(
@@ -515,13 +611,37 @@ fn _handle_listen_event(event: String, peer: String) {
 
 #[inline]
 pub fn handle_listen_event(event: String, peer: String) {
-    std::thread::spawn(|| _handle_listen_event(event, peer));
+    handle_listen_event_with_data(event, peer, serde_json::Value::Null);
+}
+
+fn is_event_listened(event: &str) -> bool {
+    PLUGIN_INFO
+        .read()
+        .unwrap()
+        .values()
+        .any(|info| info.desc.listen_events().iter().any(|e| e == event))
+}
+
+// Some events are frequent, e.g. clipboard, do not spawn a thread if no plugin listens to it.
+pub fn handle_listen_event_with_data(event: String, peer: String, data: serde_json::Value) {
+    if !is_event_listened(&event) {
+        return;
+    }
+    std::thread::spawn(|| _handle_listen_event(event, peer, data));
 }
 
 #[inline]
 pub fn handle_client_event(id: &str, peer: &str, event: &[u8]) -> Message {
     let mut peer: String = peer.to_owned();
     peer.push('\0');
+    if let Some(plugin) = get_wasm_plugin(id) {
+        let (code, out) = plugin.call(METHOD_HANDLE_PEER, peer.as_bytes(), event);
+        return if code == ERR_SUCCESS {
+            make_plugin_request(id, out.as_ptr() as _, out.len())
+        } else {
+            handle_wasm_client_event_failure(id, code, &out)
+        };
+    }
     match PLUGINS.read().unwrap().get(id) {
         Some(plugin) => {
             let mut out = std::ptr::null_mut();
@@ -578,6 +698,39 @@ pub fn handle_client_event(id: &str, peer: &str, event: &[u8]) -> Message {
     }
 }
 
+// Same as the failure handling of native plugins, the output is owned by rust here.
+fn handle_wasm_client_event_failure(id: &str, code: i32, out: &[u8]) -> Message {
+    let msg = String::from_utf8_lossy(out);
+    if code > ERR_RUSTDESK_HANDLE_BASE && code < ERR_PLUGIN_HANDLE_BASE {
+        log::debug!(
+            "Plugin {} failed to handle client event, code: {}, msg: {}",
+            id,
+            code,
+            msg
+        );
+        let name = match PLUGIN_INFO.read().unwrap().get(id) {
+            Some(plugin) => &plugin.desc.meta().name,
+            None => "???",
+        }
+        .to_owned();
+        match code {
+            ERR_CALL_NOT_SUPPORTED_METHOD => {
+                make_plugin_failure(id, &name, "Plugin method is not supported")
+            }
+            ERR_CALL_INVALID_ARGS => make_plugin_failure(id, &name, "Plugin arguments is invalid"),
+            _ => make_plugin_failure(id, &name, &msg),
+        }
+    } else {
+        log::error!(
+            "Plugin {} failed to handle client event, code: {}, msg: {}",
+            id,
+            code,
+            msg
+        );
+        make_plugin_request(id, out.as_ptr() as _, out.len())
+    }
+}
+
 fn make_plugin_request(id: &str, content: *const c_void, len: usize) -> Message {
     let mut misc = Misc::new();
     misc.set_plugin_request(PluginRequest {
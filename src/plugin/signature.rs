@@ -0,0 +1,228 @@
+use super::desc::Desc;
+use hbb_common::{
+    bail,
+    config::Config,
+    log,
+    sodiumoxide::{
+        base64,
+        crypto::sign::{self, PublicKey, Signature},
+    },
+    ResultType,
+};
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+// A plugin package `<id>_<version>.zip` comes with a detached ed25519 signature `<id>_<version>.zip.sig`,
+// signed by the publisher over the whole package. The signature file is base64 encoded or raw bytes.
+pub(super) const SIGNATURE_SUFFIX: &str = ".sig";
+// The verified package and its signature are kept in the plugin dir,
+// the plugin files are checked against them every time they are loaded.
+pub(super) const PACKAGE_FILENAME: &str = "package.zip";
+// The desc of the plugin in the package, the id must be the one the package is installed as,
+// so a package signed for one plugin cannot be installed as another.
+pub(super) const MANIFEST_FILENAME: &str = "manifest.json";
+
+// Option `plugin-trusted-keys`, base64 encoded ed25519 public keys of the trusted publishers,
+// separated by "," or whitespace. Packages not signed by one of them are rejected.
+pub const OPTION_PLUGIN_TRUSTED_KEYS: &str = "plugin-trusted-keys";
+// Option `plugin-allowed-ids`, plugin ids separated by ",". Empty means all signed plugins are allowed.
+pub const OPTION_PLUGIN_ALLOWED_IDS: &str = "plugin-allowed-ids";
+
+fn split(s: &str) -> impl Iterator<Item = &str> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+}
+
+fn decode(s: &str) -> Option<Vec<u8>> {
+    base64::decode(s, base64::Variant::Original).ok()
+}
+
+fn parse_keys(s: &str) -> Vec<PublicKey> {
+    split(s)
+        .filter_map(|k| {
+            let key = decode(k).and_then(|b| PublicKey::from_slice(&b));
+            if key.is_none() {
+                log::warn!("Invalid plugin trusted key: {}", k);
+            }
+            key
+        })
+        .collect()
+}
+
+fn parse_signature(sig: &[u8]) -> Option<Signature> {
+    if sig.len() == sign::SIGNATUREBYTES {
+        return Signature::try_from(sig).ok();
+    }
+    let s = std::str::from_utf8(sig).ok()?;
+    Signature::try_from(&decode(s.trim())?[..]).ok()
+}
+
+fn is_id_allowed_in(allowed_ids: &str, id: &str) -> bool {
+    let mut ids = split(allowed_ids).peekable();
+    ids.peek().is_none() || ids.any(|x| x == id)
+}
+
+#[inline]
+pub fn is_id_allowed(id: &str) -> bool {
+    is_id_allowed_in(&Config::get_option(OPTION_PLUGIN_ALLOWED_IDS), id)
+}
+
+// Returns the index of the key which signed the package.
+fn verify_with_keys(keys: &[PublicKey], data: &[u8], sig: &[u8]) -> ResultType<usize> {
+    if keys.is_empty() {
+        bail!("No trusted plugin publisher keys");
+    }
+    let Some(sig) = parse_signature(sig) else {
+        bail!("Invalid plugin signature");
+    };
+    match keys
+        .iter()
+        .position(|pk| sign::verify_detached(&sig, data, pk))
+    {
+        Some(i) => Ok(i),
+        None => bail!("Plugin signature does not match any trusted publisher key"),
+    }
+}
+
+pub(super) fn verify_package(id: &str, data: &[u8], sig: &[u8]) -> ResultType<()> {
+    if !is_id_allowed(id) {
+        bail!("Plugin '{}' is not in the allowed list", id);
+    }
+    let keys = parse_keys(&Config::get_option(OPTION_PLUGIN_TRUSTED_KEYS));
+    let i = verify_with_keys(&keys, data, sig)?;
+    log::info!(
+        "Plugin '{}' signature verified, publisher key: {}",
+        id,
+        base64::encode(&keys[i], base64::Variant::Original)
+    );
+    check_manifest(data, id)
+}
+
+fn read_entry(package: &[u8], name: &str) -> ResultType<Vec<u8>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(package))?;
+    let mut entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(_) => bail!("'{}' is not in the plugin package", name),
+    };
+    let mut buf = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn check_manifest(package: &[u8], id: &str) -> ResultType<()> {
+    let desc: Desc = serde_json::from_slice(&read_entry(package, MANIFEST_FILENAME)?)?;
+    if desc.meta().id != id {
+        bail!(
+            "Plugin package of '{}' can not be installed as '{}'",
+            desc.meta().id,
+            id
+        );
+    }
+    Ok(())
+}
+
+// The file must be the same as the one in the package.
+fn check_entry(package: &[u8], name: &str, content: &[u8]) -> ResultType<()> {
+    if read_entry(package, name)? != content {
+        bail!("'{}' does not match the plugin package", name);
+    }
+    Ok(())
+}
+
+// Verify an installed plugin file before it is loaded, `<plugins dir>/<id>/<file>`.
+pub(super) fn verify_installed_file(path: &Path) -> ResultType<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|f| f.to_str())) else {
+        bail!("Invalid plugin path {}", path.display());
+    };
+    let id = dir.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+    let package = dir.join(PACKAGE_FILENAME);
+    let (Ok(data), Ok(sig)) = (
+        std::fs::read(&package),
+        std::fs::read(format!("{}{}", package.display(), SIGNATURE_SUFFIX)),
+    ) else {
+        bail!("Plugin '{}' has no signed package", id);
+    };
+    verify_package(id, &data, &sig)?;
+    check_entry(&data, name, &std::fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let (pk, sk) = sign::gen_keypair();
+        let (other_pk, _) = sign::gen_keypair();
+        let data = b"plugin package";
+        let sig = sign::sign_detached(data, &sk);
+        let sig_b64 = base64::encode(&sig, base64::Variant::Original);
+
+        let keys = parse_keys(&format!(
+            "{}, {}\ninvalid",
+            base64::encode(other_pk, base64::Variant::Original),
+            base64::encode(pk, base64::Variant::Original)
+        ));
+        assert_eq!(keys.len(), 2);
+        assert_eq!(verify_with_keys(&keys, data, sig.as_ref()).unwrap(), 1);
+        assert_eq!(
+            verify_with_keys(&keys, data, sig_b64.as_bytes()).unwrap(),
+            1
+        );
+        assert!(verify_with_keys(&keys, b"tampered package", sig.as_ref()).is_err());
+        assert!(verify_with_keys(&keys[..1], data, sig.as_ref()).is_err());
+        assert!(verify_with_keys(&[], data, sig.as_ref()).is_err());
+        assert!(verify_with_keys(&keys, data, b"").is_err());
+    }
+
+    fn package(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, Default::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_check_entry() {
+        let package = package(&[("plugin_a.wasm", b"wasm")]);
+
+        assert!(check_entry(&package, "plugin_a.wasm", b"wasm").is_ok());
+        assert!(check_entry(&package, "plugin_a.wasm", b"replaced").is_err());
+        assert!(check_entry(&package, "plugin_b.wasm", b"wasm").is_err());
+    }
+
+    #[test]
+    fn test_check_manifest() {
+        let manifest = serde_json::json!({
+            "meta": {
+                "id": "a", "name": "", "version": "", "description": "", "author": "", "home": "",
+                "license": "", "source": "",
+                "publish_info": { "published": "", "last_released": "" },
+            },
+            "need_reboot": false,
+            "location": { "ui": {} },
+            "config": { "shared": [], "peer": [] },
+            "listen_events": [],
+        })
+        .to_string();
+        let signed = package(&[(MANIFEST_FILENAME, manifest.as_bytes())]);
+        assert!(check_manifest(&signed, "a").is_ok());
+        // A package signed for "a" is installed as "b".
+        assert!(check_manifest(&signed, "b").is_err());
+        assert!(check_manifest(&package(&[("plugin_a.wasm", b"wasm")]), "a").is_err());
+        assert!(check_manifest(&package(&[(MANIFEST_FILENAME, b"{}")]), "a").is_err());
+    }
+
+    #[test]
+    fn test_allowed_ids() {
+        assert!(is_id_allowed_in("", "a"));
+        assert!(is_id_allowed_in(" a, b ", "b"));
+        assert!(!is_id_allowed_in("a,b", "c"));
+    }
+}
@@ -0,0 +1,695 @@
+use super::{desc::Desc, errno::*, *};
+use hbb_common::{anyhow::anyhow, bail, log, ResultType};
+use std::{
+    collections::HashSet,
+    ffi::CString,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    time::Duration,
+};
+use wasmi::{
+    core::Trap, Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store,
+    StoreLimits, StoreLimitsBuilder, TypedFunc, WasmParams, WasmResults,
+};
+
+// Guest ABI of a wasm plugin (`plugin_*.wasm`).
+//
+// Exports:
+// - `memory`
+// - `rustdesk_alloc(len) -> ptr`: allocate `len` bytes in the guest memory for the host
+// - `rustdesk_desc() -> i64`: the desc json, `ptr << 32 | len`
+// - `rustdesk_init(info_ptr, info_len) -> code`
+// - `rustdesk_call(method_ptr, method_len, peer_ptr, peer_len, args_ptr, args_len) -> code`
+// - `rustdesk_free(ptr, len)`, optional: free the memory allocated by `rustdesk_alloc` for the
+//   arguments, called once the call returns
+// - `rustdesk_clear()`, optional
+//
+// Imports, module `rustdesk`:
+// - `log(level, ptr, len)`, level: 0 error, 1 warn, 2 info, 3 debug, 4 trace
+// - `msg(target_ptr, target_len, peer_ptr, peer_len, content_ptr, content_len) -> code`
+// - `get_conf(peer_ptr, peer_len, key_ptr, key_len) -> i64`, allocated by `rustdesk_alloc`, 0 if not found
+// - `get_id() -> i64`, allocated by `rustdesk_alloc`
+// - `set_output(ptr, len)`: the output of `rustdesk_call`, or the error message if the code is not 0
+//
+// The host functions are only available if the desc declares the permissions,
+// see `PERMISSION_*`. Native callbacks are never exposed to wasm plugins.
+pub(super) const WASM_SUFFIX: &str = ".wasm";
+
+const PERMISSION_PEER: &str = "peer";
+const PERMISSION_UI: &str = "ui";
+const PERMISSION_CONFIG: &str = "config";
+const PERMISSION_EXT_SUPPORT: &str = "ext-support";
+const PERMISSION_LOCAL_ID: &str = "local_id";
+
+const HOST_MODULE: &str = "rustdesk";
+const EXPORT_MEMORY: &str = "memory";
+const EXPORT_ALLOC: &str = "rustdesk_alloc";
+const EXPORT_DESC: &str = "rustdesk_desc";
+const EXPORT_INIT: &str = "rustdesk_init";
+const EXPORT_CALL: &str = "rustdesk_call";
+const EXPORT_FREE: &str = "rustdesk_free";
+const EXPORT_CLEAR: &str = "rustdesk_clear";
+
+// Limits of a single plugin, a runaway plugin traps instead of blocking the caller.
+const MAX_MEMORY_SIZE: usize = 64 * 1024 * 1024;
+const FUEL_PER_CALL: u64 = 100_000_000;
+// The guest runs on its own thread, the caller stops waiting after this.
+const CALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct HostState {
+    id: String,
+    permissions: HashSet<String>,
+    limits: StoreLimits,
+    out: Vec<u8>,
+}
+
+struct Guest {
+    path: String,
+    store: Store<HostState>,
+    instance: Instance,
+    fuel_per_call: u64,
+    fuel_added: u64,
+}
+
+type Task = Box<dyn FnOnce(&mut Guest) + Send>;
+
+// The guest is owned by a worker thread, calls are queued to it.
+// Dropping the plugin closes the queue, the worker then drops the guest.
+pub(super) struct WasmPlugin {
+    path: String,
+    desc: Desc,
+    tx: Sender<Task>,
+}
+
+#[inline]
+fn trap<E: std::fmt::Display>(e: E) -> Trap {
+    Trap::new(e.to_string())
+}
+
+#[inline]
+fn pack(ptr: i32, len: usize) -> i64 {
+    ((ptr as u32 as i64) << 32) | (len as u32 as i64)
+}
+
+#[inline]
+fn unpack(v: i64) -> (usize, usize) {
+    (
+        ((v as u64) >> 32) as usize,
+        (v as u64 & 0xFFFF_FFFF) as usize,
+    )
+}
+
+#[inline]
+fn trim_nul(s: &[u8]) -> &[u8] {
+    s.strip_suffix(&[0]).unwrap_or(s)
+}
+
+fn read_bytes(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+    let memory = caller
+        .get_export(EXPORT_MEMORY)
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| trap("memory is not exported"))?;
+    let mut buf = vec![0u8; len.max(0) as usize];
+    memory
+        .read(caller, ptr as u32 as usize, &mut buf)
+        .map_err(trap)?;
+    Ok(buf)
+}
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String, Trap> {
+    String::from_utf8(read_bytes(caller, ptr, len)?).map_err(trap)
+}
+
+// Copy the data to the guest memory allocated by `rustdesk_alloc`, the guest owns the memory.
+fn write_guest(caller: &mut Caller<'_, HostState>, data: &[u8]) -> Result<i64, Trap> {
+    let alloc = caller
+        .get_export(EXPORT_ALLOC)
+        .and_then(Extern::into_func)
+        .ok_or_else(|| trap("rustdesk_alloc is not exported"))?
+        .typed::<i32, i32>(&*caller)
+        .map_err(trap)?;
+    let ptr = alloc.call(&mut *caller, data.len() as i32)?;
+    let memory = caller
+        .get_export(EXPORT_MEMORY)
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| trap("memory is not exported"))?;
+    memory
+        .write(&mut *caller, ptr as u32 as usize, data)
+        .map_err(trap)?;
+    Ok(pack(ptr, data.len()))
+}
+
+fn check_permission(caller: &Caller<'_, HostState>, permission: &str) -> Result<(), String> {
+    if caller.data().permissions.contains(permission) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Plugin {} has no permission '{}'",
+            caller.data().id,
+            permission
+        ))
+    }
+}
+
+// Reuse the native callback, so both plugin kinds behave the same.
+fn forward_msg(id: &str, target: &str, peer: &str, content: &[u8]) -> (i32, String) {
+    let (Ok(c_peer), Ok(c_target), Ok(c_id)) =
+        (CString::new(peer), CString::new(target), CString::new(id))
+    else {
+        return (ERR_CALLBACK_INVALID_ARGS, "Invalid string".to_owned());
+    };
+    let mut ret = callback_msg::cb_msg(
+        c_peer.as_ptr(),
+        c_target.as_ptr(),
+        c_id.as_ptr(),
+        content.as_ptr() as _,
+        content.len(),
+    );
+    ret.get_code_msg(id)
+}
+
+fn host_msg(
+    caller: Caller<'_, HostState>,
+    target_ptr: i32,
+    target_len: i32,
+    peer_ptr: i32,
+    peer_len: i32,
+    content_ptr: i32,
+    content_len: i32,
+) -> Result<i32, Trap> {
+    let target = read_string(&caller, target_ptr, target_len)?;
+    let peer = read_string(&caller, peer_ptr, peer_len)?;
+    let content = read_bytes(&caller, content_ptr, content_len)?;
+    let permission = match target.as_str() {
+        "peer" => PERMISSION_PEER,
+        "ui" => PERMISSION_UI,
+        "config" => PERMISSION_CONFIG,
+        "ext-support" => PERMISSION_EXT_SUPPORT,
+        _ => {
+            log::warn!(
+                "Plugin {} is not allowed to send msg to '{}'",
+                caller.data().id,
+                target
+            );
+            return Ok(ERR_CALLBACK_TARGET);
+        }
+    };
+    if let Err(e) = check_permission(&caller, permission) {
+        log::warn!("{}", e);
+        return Ok(ERR_CALLBACK_PERMISSION_DENIED);
+    }
+    let (code, msg) = forward_msg(&caller.data().id, &target, &peer, &content);
+    if code != ERR_SUCCESS {
+        log::debug!(
+            "Plugin {} failed to send msg to '{}', code: {}, msg: {}",
+            caller.data().id,
+            target,
+            code,
+            msg
+        );
+    }
+    Ok(code)
+}
+
+fn host_get_conf(
+    mut caller: Caller<'_, HostState>,
+    peer_ptr: i32,
+    peer_len: i32,
+    key_ptr: i32,
+    key_len: i32,
+) -> Result<i64, Trap> {
+    if let Err(e) = check_permission(&caller, PERMISSION_CONFIG) {
+        log::warn!("{}", e);
+        return Ok(0);
+    }
+    let peer = read_string(&caller, peer_ptr, peer_len)?;
+    let key = read_string(&caller, key_ptr, key_len)?;
+    let id = caller.data().id.clone();
+    let value = if peer.is_empty() {
+        SharedConfig::get(&id, &key)
+    } else {
+        PeerConfig::get(&id, &peer, &key)
+    };
+    match value {
+        Some(v) => write_guest(&mut caller, v.as_bytes()),
+        None => Ok(0),
+    }
+}
+
+fn host_get_id(mut caller: Caller<'_, HostState>) -> Result<i64, Trap> {
+    if let Err(e) = check_permission(&caller, PERMISSION_LOCAL_ID) {
+        log::warn!("{}", e);
+        return Ok(0);
+    }
+    write_guest(&mut caller, crate::ipc::get_id().as_bytes())
+}
+
+fn host_log(caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) -> Result<(), Trap> {
+    let msg = read_string(&caller, ptr, len)?;
+    let id = &caller.data().id;
+    match level {
+        0 => log::error!("[{}] {}", id, msg),
+        1 => log::warn!("[{}] {}", id, msg),
+        2 => log::info!("[{}] {}", id, msg),
+        3 => log::debug!("[{}] {}", id, msg),
+        _ => log::trace!("[{}] {}", id, msg),
+    }
+    Ok(())
+}
+
+fn host_set_output(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> Result<(), Trap> {
+    let out = read_bytes(&caller, ptr, len)?;
+    caller.data_mut().out = out;
+    Ok(())
+}
+
+fn make_linker(engine: &Engine) -> ResultType<Linker<HostState>> {
+    let mut linker = Linker::<HostState>::new(engine);
+    linker.func_wrap(HOST_MODULE, "log", host_log)?;
+    linker.func_wrap(HOST_MODULE, "msg", host_msg)?;
+    linker.func_wrap(HOST_MODULE, "get_conf", host_get_conf)?;
+    linker.func_wrap(HOST_MODULE, "get_id", host_get_id)?;
+    linker.func_wrap(HOST_MODULE, "set_output", host_set_output)?;
+    Ok(linker)
+}
+
+impl Guest {
+    #[inline]
+    fn new(path: &str) -> ResultType<Self> {
+        Self::with_fuel(path, FUEL_PER_CALL)
+    }
+
+    fn with_fuel(path: &str, fuel_per_call: u64) -> ResultType<Self> {
+        let bytes = std::fs::read(path)?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &bytes[..])
+            .map_err(|e| anyhow!("Failed to compile wasm plugin {}, {}", path, e))?;
+        let state = HostState {
+            id: "".to_owned(),
+            permissions: Default::default(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_SIZE)
+                .instances(1)
+                .memories(1)
+                .build(),
+            out: Vec::new(),
+        };
+        let mut store = Store::new(&engine, state);
+        store.limiter(|s| &mut s.limits);
+        store.add_fuel(fuel_per_call).map_err(wasmi::Error::from)?;
+        let instance = make_linker(&engine)?
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| anyhow!("Failed to instantiate wasm plugin {}, {}", path, e))?;
+        Ok(Self {
+            path: path.to_owned(),
+            store,
+            instance,
+            fuel_per_call,
+            fuel_added: fuel_per_call,
+        })
+    }
+
+    // Every call gets the same budget, unused fuel of the previous call is not accumulated.
+    fn refuel(&mut self) -> ResultType<()> {
+        let consumed = self.store.fuel_consumed().unwrap_or_default();
+        let remaining = self.fuel_added.saturating_sub(consumed);
+        if remaining < self.fuel_per_call {
+            let delta = self.fuel_per_call - remaining;
+            self.store.add_fuel(delta).map_err(wasmi::Error::from)?;
+            self.fuel_added += delta;
+        }
+        Ok(())
+    }
+
+    fn func<P: WasmParams, R: WasmResults>(&self, name: &str) -> ResultType<TypedFunc<P, R>> {
+        self.instance
+            .get_typed_func::<P, R>(&self.store, name)
+            .map_err(|e| anyhow!("Failed to get {} of wasm plugin {}, {}", name, self.path, e))
+    }
+
+    fn memory(&self) -> ResultType<Memory> {
+        match self.instance.get_memory(&self.store, EXPORT_MEMORY) {
+            Some(memory) => Ok(memory),
+            None => bail!("Wasm plugin {} does not export memory", self.path),
+        }
+    }
+
+    fn read(&self, ptr: usize, len: usize) -> ResultType<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.memory()?
+            .read(&self.store, ptr, &mut buf)
+            .map_err(wasmi::Error::from)?;
+        Ok(buf)
+    }
+
+    fn write(&mut self, data: &[u8]) -> ResultType<(i32, i32)> {
+        self.refuel()?;
+        let ptr = self
+            .func::<i32, i32>(EXPORT_ALLOC)?
+            .call(&mut self.store, data.len() as i32)?;
+        self.memory()?
+            .write(&mut self.store, ptr as u32 as usize, data)
+            .map_err(wasmi::Error::from)?;
+        Ok((ptr, data.len() as i32))
+    }
+
+    // Optional, the guest may use a bump allocator and reset it in `rustdesk_clear`.
+    fn free(&mut self, bufs: &[(i32, i32)]) {
+        let Ok(free) = self.func::<(i32, i32), ()>(EXPORT_FREE) else {
+            return;
+        };
+        for &(ptr, len) in bufs {
+            if let Err(e) = self
+                .refuel()
+                .and_then(|_| Ok(free.call(&mut self.store, (ptr, len))?))
+            {
+                log::error!("Failed to free memory of wasm plugin {}, {}", self.path, e);
+                return;
+            }
+        }
+    }
+
+    fn desc(&mut self) -> ResultType<Desc> {
+        self.refuel()?;
+        let v = self
+            .func::<(), i64>(EXPORT_DESC)?
+            .call(&mut self.store, ())?;
+        let (ptr, len) = unpack(v);
+        Ok(serde_json::from_slice(&self.read(ptr, len)?)?)
+    }
+
+    fn init(&mut self, info: &str) -> ResultType<i32> {
+        self.store.data_mut().out.clear();
+        let (ptr, len) = self.write(info.as_bytes())?;
+        let res = self.refuel().and_then(|_| {
+            Ok(self
+                .func::<(i32, i32), i32>(EXPORT_INIT)?
+                .call(&mut self.store, (ptr, len))?)
+        });
+        self.free(&[(ptr, len)]);
+        res
+    }
+
+    fn call(&mut self, method: &[u8], peer: &[u8], args: &[u8]) -> ResultType<i32> {
+        self.store.data_mut().out.clear();
+        let mut bufs = Vec::with_capacity(3);
+        let res = self.call_(method, peer, args, &mut bufs);
+        self.free(&bufs);
+        res
+    }
+
+    fn call_(
+        &mut self,
+        method: &[u8],
+        peer: &[u8],
+        args: &[u8],
+        bufs: &mut Vec<(i32, i32)>,
+    ) -> ResultType<i32> {
+        for data in [method, peer, args] {
+            let buf = self.write(data)?;
+            bufs.push(buf);
+        }
+        let (method_ptr, method_len) = bufs[0];
+        let (peer_ptr, peer_len) = bufs[1];
+        let (args_ptr, args_len) = bufs[2];
+        self.refuel()?;
+        Ok(self
+            .func::<(i32, i32, i32, i32, i32, i32), i32>(EXPORT_CALL)?
+            .call(
+                &mut self.store,
+                (
+                    method_ptr, method_len, peer_ptr, peer_len, args_ptr, args_len,
+                ),
+            )?)
+    }
+
+    #[inline]
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.store.data_mut().out)
+    }
+}
+
+impl Drop for Guest {
+    fn drop(&mut self) {
+        let Ok(clear) = self.func::<(), ()>(EXPORT_CLEAR) else {
+            return;
+        };
+        if self.refuel().is_ok() {
+            if let Err(e) = clear.call(&mut self.store, ()) {
+                log::error!("Failed to clear wasm plugin {}, {}", self.path, e);
+            }
+        }
+    }
+}
+
+impl WasmPlugin {
+    pub fn new(path: &str) -> ResultType<Self> {
+        let mut guest = Guest::new(path)?;
+        let desc = guest.desc()?;
+        if desc.meta().id.is_empty() {
+            bail!("Wasm plugin {} has an empty id", path);
+        }
+        let state = guest.store.data_mut();
+        state.id = desc.meta().id.clone();
+        state.permissions = desc.permissions().iter().cloned().collect();
+        let (tx, rx) = channel::<Task>();
+        std::thread::Builder::new()
+            .name(format!("wasm-plugin-{}", desc.meta().id))
+            .spawn(move || {
+                while let Ok(task) = rx.recv() {
+                    task(&mut guest);
+                }
+            })?;
+        Ok(Self {
+            path: path.to_owned(),
+            desc,
+            tx,
+        })
+    }
+
+    #[inline]
+    pub fn desc(&self) -> &Desc {
+        &self.desc
+    }
+
+    // Run `f` on the worker thread and wait for the result.
+    // A guest that does not return in time keeps running until its fuel is exhausted,
+    // the following calls are queued after it.
+    fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Guest) -> T + Send + 'static,
+    ) -> Result<T, (i32, String)> {
+        let (res_tx, res_rx): (Sender<T>, Receiver<T>) = channel();
+        let task: Task = Box::new(move |guest| {
+            res_tx.send(f(guest)).ok();
+        });
+        if self.tx.send(task).is_err() {
+            return Err((
+                ERR_CALL_TRAPPED,
+                format!("Wasm plugin {} is stopped", self.path),
+            ));
+        }
+        match res_rx.recv_timeout(CALL_TIMEOUT) {
+            Ok(v) => Ok(v),
+            Err(RecvTimeoutError::Timeout) => Err((
+                ERR_CALL_TIMEOUT,
+                format!("Wasm plugin {} call timeout", self.path),
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err((
+                ERR_CALL_TRAPPED,
+                format!("Wasm plugin {} is stopped", self.path),
+            )),
+        }
+    }
+
+    pub fn init(&self, info: &str) -> ResultType<()> {
+        let info = info.to_owned();
+        let (code, out) = match self.run(move |guest| {
+            guest
+                .init(&info)
+                .map(|code| (code, guest.take_output()))
+                .map_err(|e| e.to_string())
+        }) {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) | Err((_, e)) => bail!("Failed to init plugin {}, {}", self.path, e),
+        };
+        if code != ERR_SUCCESS {
+            bail!(
+                "Failed to init plugin {}, code: {}, msg: {}",
+                self.path,
+                code,
+                String::from_utf8_lossy(&out)
+            );
+        }
+        Ok(())
+    }
+
+    // Same semantics as `PluginFuncCallWithOutData`, method and peer may be null terminated.
+    // Returns the code and the output, the output is the error message if the code is not 0.
+    pub fn call(&self, method: &[u8], peer: &[u8], args: &[u8]) -> (i32, Vec<u8>) {
+        let method = trim_nul(method).to_vec();
+        let peer = trim_nul(peer).to_vec();
+        let args = args.to_vec();
+        let res = self.run(move |guest| match guest.call(&method, &peer, &args) {
+            Ok(code) => Ok((code, guest.take_output())),
+            Err(e) => Err(e.to_string()),
+        });
+        match res {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => {
+                log::error!("Wasm plugin {} call failed, {}", self.path, e);
+                (ERR_CALL_TRAPPED, e.into_bytes())
+            }
+            Err((code, e)) => {
+                log::error!("{}", e);
+                (code, e.into_bytes())
+            }
+        }
+    }
+
+    pub fn call_get_return(&self, method: &[u8], peer: &[u8], args: &[u8]) -> PluginReturn {
+        let (code, out) = self.call(method, peer, args);
+        if code == ERR_SUCCESS {
+            PluginReturn::success()
+        } else {
+            PluginReturn::new(code, &String::from_utf8_lossy(&out))
+        }
+    }
+}
+
+#[inline]
+pub(super) fn is_wasm_path(path: &str) -> bool {
+    path.ends_with(WASM_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::plugins;
+    use std::{collections::HashMap, sync::Arc};
+
+    const DESC_OFFSET: usize = 1024;
+
+    // `rustdesk_call` dispatches on the first byte of the method:
+    // "msg" sends to "ui", "conf" gets the config "ui", "loop" never returns,
+    // "grow" grows the memory over `MAX_MEMORY_SIZE`, "page" grows one page.
+    fn module(id: &str) -> Vec<u8> {
+        let desc = serde_json::json!({
+            "meta": {
+                "id": id, "name": "", "version": "", "description": "", "author": "", "home": "",
+                "license": "", "source": "",
+                "publish_info": { "published": "", "last_released": "" },
+            },
+            "need_reboot": false,
+            "location": { "ui": {} },
+            "config": { "shared": [], "peer": [] },
+            "listen_events": [],
+        })
+        .to_string();
+        let wat = format!(
+            r#"(module
+  (import "rustdesk" "msg" (func $msg (param i32 i32 i32 i32 i32 i32) (result i32)))
+  (import "rustdesk" "get_conf" (func $get_conf (param i32 i32 i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 4096))
+  (data (i32.const 16) "ui")
+  (data (i32.const {offset}) "{data}")
+  (func (export "rustdesk_alloc") (param $len i32) (result i32)
+    (global.get $heap)
+    (global.set $heap (i32.add (global.get $heap) (local.get $len))))
+  (func (export "rustdesk_desc") (result i64)
+    (i64.const {packed}))
+  (func (export "rustdesk_init") (param i32 i32) (result i32)
+    (i32.const 0))
+  (func (export "rustdesk_call") (param $m i32) (param i32 i32 i32 i32 i32) (result i32)
+    (local $c i32)
+    (local.set $c (i32.load8_u (local.get $m)))
+    (if (i32.eq (local.get $c) (i32.const 109))
+      (then (return (call $msg (i32.const 16) (i32.const 2) (i32.const 0) (i32.const 0)
+        (i32.const 0) (i32.const 0)))))
+    (if (i32.eq (local.get $c) (i32.const 99))
+      (then (return (i32.wrap_i64 (call $get_conf (i32.const 0) (i32.const 0) (i32.const 16)
+        (i32.const 2))))))
+    (if (i32.eq (local.get $c) (i32.const 108))
+      (then (loop $l (br $l))))
+    (if (i32.eq (local.get $c) (i32.const 103))
+      (then (return (memory.grow (i32.const {pages})))))
+    (if (i32.eq (local.get $c) (i32.const 112))
+      (then (return (memory.grow (i32.const 1)))))
+    (i32.const 0)))"#,
+            offset = DESC_OFFSET,
+            data = desc.replace('"', "\\\""),
+            packed = pack(DESC_OFFSET as _, desc.len()),
+            pages = MAX_MEMORY_SIZE / 65536,
+        );
+        wat::parse_str(wat).unwrap()
+    }
+
+    fn write_module(id: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "plugin_{}_{}{}",
+            id,
+            std::process::id(),
+            WASM_SUFFIX
+        ));
+        std::fs::write(&path, module(id)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn guest(id: &str, fuel_per_call: u64) -> Guest {
+        let path = write_module(id);
+        let mut guest = Guest::with_fuel(&path, fuel_per_call).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(guest.desc().unwrap().meta().id, id);
+        guest.store.data_mut().id = id.to_owned();
+        guest
+    }
+
+    #[test]
+    fn test_permission_denied() {
+        let mut guest = guest("test_permission", FUEL_PER_CALL);
+        assert!(guest.store.data().permissions.is_empty());
+        assert_eq!(
+            guest.call(b"msg", b"", b"").unwrap(),
+            ERR_CALLBACK_PERMISSION_DENIED
+        );
+        // Not found, the config is not read.
+        assert_eq!(guest.call(b"conf", b"", b"").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_fuel_exhausted() {
+        let mut guest = guest("test_fuel", 1_000_000);
+        assert!(guest.call(b"loop", b"", b"").is_err());
+        // Refueled for the next call.
+        assert!(guest.call(b"loop", b"", b"").is_err());
+        assert_eq!(guest.call(b"page", b"", b"").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut guest = guest("test_memory", FUEL_PER_CALL);
+        assert_eq!(guest.call(b"grow", b"", b"").unwrap(), -1);
+        assert_eq!(guest.call(b"page", b"", b"").unwrap(), 1);
+        assert_eq!(guest.call(b"grow", b"", b"").unwrap(), -1);
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let path = write_module("test_duplicate");
+        let plugin = WasmPlugin::new(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let id = plugin.desc().meta().id.clone();
+
+        // The wasm plugin is loaded after a native plugin, a native plugin needs a real library.
+        let native: HashMap<String, ()> = HashMap::from([(id.clone(), ())]);
+        assert!(plugins::check_id_unused(&id, &native, "native").is_err());
+
+        // A native plugin is loaded after the wasm plugin.
+        let wasm = HashMap::from([(id.clone(), Arc::new(plugin))]);
+        assert!(plugins::check_id_unused(&id, &wasm, "wasm").is_err());
+        assert!(plugins::check_id_unused("other", &wasm, "wasm").is_ok());
+    }
+}
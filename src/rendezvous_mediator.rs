@@ -901,6 +901,7 @@ async fn direct_server(server: ServerPtr) {
                             hbb_common::Stream::from(stream, local_addr),
                             addr,
                             false,
+                            false,
                             None, // Direct connections don't have control_permissions
                         )
                         .await
@@ -978,6 +979,7 @@ async fn udp_nat_listen(
             stream.1,
             peer_addr_v4,
             true,
+            false,
             control_permissions,
         )
         .await?;
@@ -66,7 +66,13 @@ pub mod input_service {
     pub const NAME_WINDOW_FOCUS: &'static str = "";
 }
 
+pub mod access_control;
+pub mod access_schedule;
+pub mod clipboard_dlp;
+pub mod audit_log;
+pub mod audit_queue;
 mod connection;
+pub mod ip_whitelist;
 mod login_failure_check;
 pub mod display_service;
 #[cfg(windows)]
@@ -180,6 +186,7 @@ async fn accept_connection_(
             Stream::from(stream, stream_addr),
             addr,
             secure,
+            false,
             control_permissions,
         )
         .await?;
@@ -192,6 +199,7 @@ pub async fn create_tcp_connection(
     stream: Stream,
     addr: SocketAddr,
     secure: bool,
+    relay: bool,
     control_permissions: Option<ControlPermissions>,
 ) -> ResultType<()> {
     let mut stream = stream;
@@ -265,6 +273,7 @@ pub async fn create_tcp_connection(
         stream,
         id,
         Arc::downgrade(&server),
+        relay,
         control_permissions,
     )
     .await;
@@ -334,7 +343,7 @@ async fn create_relay_connection_(
         ..Default::default()
     });
     stream.send(&msg_out).await?;
-    create_tcp_connection(server, stream, peer_addr, secure, control_permissions).await?;
+    create_tcp_connection(server, stream, peer_addr, secure, true, control_permissions).await?;
     Ok(())
 }
 
@@ -561,6 +570,8 @@ pub fn check_zombie() {
 #[cfg(any(target_os = "android", target_os = "ios"))]
 #[tokio::main]
 pub async fn start_server(_is_server: bool) {
+    audit_queue::start();
+    ip_whitelist::start();
     crate::RendezvousMediator::start_all().await;
 }
 
@@ -610,6 +621,8 @@ pub async fn start_server(is_server: bool, no_server: bool) {
         crate::platform::try_kill_broker();
         #[cfg(feature = "hwcodec")]
         scrap::hwcodec::start_check_process();
+        audit_queue::start();
+        ip_whitelist::start();
         crate::RendezvousMediator::start_all().await;
     } else {
         match crate::ipc::connect(1000, "").await {
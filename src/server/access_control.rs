@@ -0,0 +1,338 @@
+use cidr_utils::cidr::IpCidr;
+use hbb_common::{
+    bail,
+    config::{keys, Config},
+    log, ResultType,
+};
+use serde_derive::Deserialize;
+use std::{collections::HashMap, net::IpAddr, str::FromStr};
+
+// Json value of option `access-control-list`, e.g.
+// {
+//   "profiles": {
+//     "helpdesk": { "all": false, "keyboard": true },
+//     "admins": { "all": true },
+//     "vendor": { "all": false }
+//   },
+//   "rules": [
+//     { "ids": ["123456789"], "ips": ["10.0.0.0/8"], "profile": "admins" },
+//     { "ids": ["88*"], "names": ["helpdesk"], "profile": "helpdesk" },
+//     { "ips": ["2001:db8::/32"], "profile": "helpdesk" }
+//   ],
+//   "default": "vendor"
+// }
+// The first rule matching the controller selects the profile. The fields of a rule must all match,
+// and any entry of a field matches, e.g. id "123456789" from 10.0.0.0/8 above.
+//
+// Which fields can be trusted:
+// - `ips` are matched on the address of the connection, which the server sees itself.
+// - `ids` and `names` are matched on `my_id` and `my_name` of the login request. The controller
+//   reports them, they are not signed, so they identify the controller only as far as the password,
+//   2FA or the approval on this side does, which authenticate that login request. Pin them with
+//   `ips` in the same rule if the profile must not be picked by anyone who has the password.
+// A profile can only restrict the global options, so a forged id or name never gets more than them.
+//
+// A malformed list denies all permissions, it is rejected when saved from the ui.
+pub const OPTION_ACCESS_CONTROL_LIST: &str = "access-control-list";
+
+const PROFILE_KEY_ALL: &str = "all";
+const PROFILE_INVALID: &str = "invalid";
+
+// Same names as `ipc::Data::SwitchPermission`, so profiles read like the cm toggles.
+const PERMISSIONS: [(&str, &str); 12] = [
+    (keys::OPTION_ENABLE_KEYBOARD, "keyboard"),
+    (keys::OPTION_ENABLE_REMOTE_PRINTER, "remote_printer"),
+    (keys::OPTION_ENABLE_CLIPBOARD, "clipboard"),
+    (keys::OPTION_ENABLE_FILE_TRANSFER, "file"),
+    (keys::OPTION_ENABLE_AUDIO, "audio"),
+    (keys::OPTION_ENABLE_CAMERA, "camera"),
+    (keys::OPTION_ENABLE_TERMINAL, "terminal"),
+    (keys::OPTION_ENABLE_TUNNEL, "tunnel"),
+    (keys::OPTION_ENABLE_REMOTE_RESTART, "restart"),
+    (keys::OPTION_ENABLE_RECORD_SESSION, "recording"),
+    (keys::OPTION_ENABLE_BLOCK_INPUT, "block_input"),
+    (keys::OPTION_ENABLE_PRIVACY_MODE, "privacy_mode"),
+];
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct AclRule {
+    ips: Vec<String>,
+    ids: Vec<String>,
+    names: Vec<String>,
+    profile: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessControlList {
+    profiles: HashMap<String, HashMap<String, bool>>,
+    rules: Vec<AclRule>,
+    default: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PermissionProfile {
+    pub name: String,
+    all: Option<bool>,
+    permissions: HashMap<String, bool>,
+}
+
+// The controller of an incoming connection, see above for which fields can be trusted.
+pub struct Peer<'a> {
+    pub ip: &'a str,
+    pub id: &'a str,
+    pub name: &'a str,
+}
+
+fn profile_key(enable_prefix_option: &str) -> Option<&'static str> {
+    PERMISSIONS
+        .iter()
+        .find(|(option, _)| *option == enable_prefix_option)
+        .map(|(_, key)| *key)
+}
+
+fn id_matches(pattern: &str, id: &str) -> bool {
+    let pattern = pattern.trim();
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        id.starts_with(prefix)
+    } else {
+        pattern == id
+    }
+}
+
+impl AclRule {
+    fn check(&self) -> ResultType<()> {
+        if self.ips.is_empty() && self.ids.is_empty() && self.names.is_empty() {
+            bail!("rule of profile {} matches nothing", self.profile);
+        }
+        for x in self.ips.iter() {
+            if IpCidr::from_str(x.trim()).is_err() {
+                bail!("invalid address: {x}");
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, ip: Option<IpAddr>, peer: &Peer) -> bool {
+        let ip_matches = || {
+            ip.map_or(false, |ip| {
+                self.ips
+                    .iter()
+                    .any(|x| IpCidr::from_str(x.trim()).map_or(false, |cidr| cidr.contains(ip)))
+            })
+        };
+        (self.ips.is_empty() || ip_matches())
+            && (self.ids.is_empty() || self.ids.iter().any(|x| id_matches(x, peer.id)))
+            && (self.names.is_empty()
+                || (!peer.name.is_empty()
+                    && self
+                        .names
+                        .iter()
+                        .any(|x| x.trim().eq_ignore_ascii_case(peer.name))))
+    }
+}
+
+impl PermissionProfile {
+    // `None` means the profile does not restrict this permission.
+    pub fn get(&self, enable_prefix_option: &str) -> Option<bool> {
+        let key = profile_key(enable_prefix_option)?;
+        self.permissions.get(key).copied().or(self.all)
+    }
+
+    fn deny_all(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            all: Some(false),
+            permissions: Default::default(),
+        }
+    }
+}
+
+impl AccessControlList {
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.trim().is_empty() {
+            return None;
+        }
+        match Self::check(s) {
+            Ok(acl) => Some(acl),
+            Err(e) => {
+                log::error!("Failed to parse access control list: {}", e);
+                None
+            }
+        }
+    }
+
+    // Used to reject an invalid list when it is saved.
+    pub fn check(s: &str) -> ResultType<Self> {
+        let acl = serde_json::from_str::<Self>(s)?;
+        for (name, permissions) in acl.profiles.iter() {
+            if let Some(key) = permissions
+                .keys()
+                .find(|k| *k != PROFILE_KEY_ALL && !PERMISSIONS.iter().any(|(_, x)| x == k))
+            {
+                bail!("unknown permission {key} in profile {name}");
+            }
+        }
+        for rule in acl.rules.iter() {
+            rule.check()?;
+        }
+        if let Some(name) = acl
+            .rules
+            .iter()
+            .map(|r| &r.profile)
+            .chain((!acl.default.is_empty()).then_some(&acl.default))
+            .find(|name| !acl.profiles.contains_key(*name))
+        {
+            bail!("profile {name} not found");
+        }
+        Ok(acl)
+    }
+
+    // A missing profile denies all permissions.
+    fn profile(&self, name: &str) -> PermissionProfile {
+        let Some(permissions) = self.profiles.get(name) else {
+            log::error!("Access control profile {} not found", name);
+            return PermissionProfile::deny_all(name);
+        };
+        let mut permissions = permissions.clone();
+        let all = permissions.remove(PROFILE_KEY_ALL);
+        PermissionProfile {
+            name: name.to_owned(),
+            all,
+            permissions,
+        }
+    }
+
+    pub fn match_profile(&self, peer: &Peer) -> Option<PermissionProfile> {
+        // The address may have a scope id, e.g. `fe80::1%2`
+        let ip = IpAddr::from_str(peer.ip.split('%').next().unwrap_or(peer.ip).trim())
+            .ok()
+            .map(super::ip_whitelist::normalize);
+        if let Some(rule) = self.rules.iter().find(|r| r.matches(ip, peer)) {
+            return Some(self.profile(&rule.profile));
+        }
+        if self.default.is_empty() {
+            return None;
+        }
+        Some(self.profile(&self.default))
+    }
+}
+
+pub fn get_profile(peer: &Peer) -> Option<PermissionProfile> {
+    let s = Config::get_option(OPTION_ACCESS_CONTROL_LIST);
+    if s.trim().is_empty() {
+        return None;
+    }
+    match AccessControlList::parse(&s) {
+        Some(acl) => acl.match_profile(peer),
+        None => Some(PermissionProfile::deny_all(PROFILE_INVALID)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer<'a>(ip: &'a str, id: &'a str, name: &'a str) -> Peer<'a> {
+        Peer { ip, id, name }
+    }
+
+    fn ip(ip: &str) -> Peer {
+        peer(ip, "", "")
+    }
+
+    const ACL: &str = r#"{
+        "profiles": {
+            "helpdesk": { "all": false, "keyboard": true },
+            "admins": { "all": true },
+            "vendor": { "all": false }
+        },
+        "rules": [
+            { "ips": ["10.0.0.5", "192.168.1.0/24"], "profile": "admins" },
+            { "ids": ["123456789"], "ips": ["172.16.0.0/12"], "profile": "admins" },
+            { "ids": ["88*"], "names": ["Helpdesk"], "profile": "helpdesk" },
+            { "ips": ["2001:db8::/32"], "profile": "helpdesk" }
+        ],
+        "default": "vendor"
+    }"#;
+
+    #[test]
+    fn test_match_profile() {
+        let acl = AccessControlList::parse(ACL).unwrap();
+        let admins = acl.match_profile(&ip("10.0.0.5")).unwrap();
+        assert_eq!(admins.name, "admins");
+        assert_eq!(admins.get(keys::OPTION_ENABLE_TERMINAL), Some(true));
+        assert_eq!(
+            acl.match_profile(&ip("192.168.1.9")).unwrap().name,
+            "admins"
+        );
+        assert_eq!(
+            acl.match_profile(&ip("::ffff:192.168.1.9")).unwrap().name,
+            "admins"
+        );
+        assert_eq!(acl.match_profile(&ip("fe80::1%2")).unwrap().name, "vendor");
+
+        let helpdesk = acl.match_profile(&ip("2001:db8::1")).unwrap();
+        assert_eq!(helpdesk.get(keys::OPTION_ENABLE_KEYBOARD), Some(true));
+        assert_eq!(helpdesk.get(keys::OPTION_ENABLE_FILE_TRANSFER), Some(false));
+
+        let vendor = acl.match_profile(&ip("8.8.8.8")).unwrap();
+        assert_eq!(vendor.name, "vendor");
+        assert_eq!(vendor.get(keys::OPTION_ENABLE_KEYBOARD), Some(false));
+    }
+
+    #[test]
+    fn test_match_identity() {
+        let acl = AccessControlList::parse(ACL).unwrap();
+        let name = |p: Peer| acl.match_profile(&p).unwrap().name;
+        // All the fields of a rule must match
+        assert_eq!(name(peer("172.16.1.1", "123456789", "")), "admins");
+        assert_eq!(name(peer("8.8.8.8", "123456789", "")), "vendor");
+        assert_eq!(name(peer("172.16.1.1", "987654321", "")), "vendor");
+        assert_eq!(name(peer("8.8.8.8", "881234", "helpdesk")), "helpdesk");
+        assert_eq!(name(peer("8.8.8.8", "881234", "")), "vendor");
+        assert_eq!(name(peer("8.8.8.8", "771234", "helpdesk")), "vendor");
+        // Not an address
+        assert_eq!(name(peer("", "881234", "HELPDESK")), "helpdesk");
+    }
+
+    #[test]
+    fn test_partial_profile() {
+        let acl = AccessControlList::parse(
+            r#"{ "profiles": { "no_files": { "file": false } }, "default": "no_files" }"#,
+        )
+        .unwrap();
+        let profile = acl.match_profile(&ip("1.1.1.1")).unwrap();
+        assert_eq!(profile.get(keys::OPTION_ENABLE_FILE_TRANSFER), Some(false));
+        assert_eq!(profile.get(keys::OPTION_ENABLE_KEYBOARD), None);
+        assert_eq!(profile.get("enable-unknown"), None);
+    }
+
+    #[test]
+    fn test_no_default() {
+        let acl = AccessControlList::parse(r#"{ "profiles": { "a": { "all": true } } }"#).unwrap();
+        assert!(acl.match_profile(&ip("1.1.1.1")).is_none());
+        assert!(AccessControlList::parse("").is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        for s in [
+            "{",
+            r#"{ "profiles": { "a": { "keybaord": false } } }"#,
+            r#"{ "profiles": { "a": { "all": true } }, "rules": [{ "profile": "a" }] }"#,
+            r#"{ "profiles": { "a": {} }, "rules": [{ "ips": ["10.0.0.300"], "profile": "a" }] }"#,
+            r#"{ "profiles": { "a": {} }, "rules": [{ "ids": ["1"], "profile": "b" }] }"#,
+            r#"{ "profiles": { "a": {} }, "default": "b" }"#,
+        ] {
+            assert!(AccessControlList::check(s).is_err(), "{s}");
+            assert!(AccessControlList::parse(s).is_none(), "{s}");
+        }
+        // Fails closed
+        let profile = PermissionProfile::deny_all(PROFILE_INVALID);
+        for (option, _) in PERMISSIONS {
+            assert_eq!(profile.get(option), Some(false));
+        }
+    }
+}
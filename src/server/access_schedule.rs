@@ -0,0 +1,267 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use hbb_common::{bail, config::Config, log, ResultType};
+use serde_derive::Deserialize;
+
+// Json value of option `access-schedule`, e.g.
+// {
+//   "rules": [{ "days": ["mon", "tue", "wed", "thu", "fri"], "start": "08:00", "end": "18:00", "action": "allow" }],
+//   "holidays": ["2026-12-25"],
+//   "default": "click"
+// }
+// Rules are evaluated in order and the first match wins. Holidays always deny.
+pub const OPTION_ACCESS_SCHEDULE: &str = "access-schedule";
+
+pub const LOGIN_MSG_ACCESS_SCHEDULE_DENIED: &str =
+    "Access to this device is not allowed at this time";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleAction {
+    // Keep the approve mode and password settings unchanged.
+    #[default]
+    Allow,
+    // Only click-to-approve in the connection manager is accepted.
+    Click,
+    Deny,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct ScheduleRule {
+    days: Vec<String>,
+    start: String,
+    end: String,
+    action: ScheduleAction,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessSchedule {
+    rules: Vec<ScheduleRule>,
+    holidays: Vec<String>,
+    default: ScheduleAction,
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    match s.trim().to_lowercase().as_str() {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+impl ScheduleRule {
+    fn check(&self) -> ResultType<()> {
+        if let Some(d) = self.days.iter().find(|d| parse_weekday(d).is_none()) {
+            bail!("invalid day: {d}");
+        }
+        for t in [&self.start, &self.end] {
+            if !t.trim().is_empty() && parse_time(t).is_none() {
+                bail!("invalid time: {t}");
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, now: &NaiveDateTime) -> bool {
+        let start = parse_time(&self.start).unwrap_or(NaiveTime::MIN);
+        // An empty end means until the end of the day.
+        let end = if self.end.trim().is_empty() {
+            None
+        } else {
+            parse_time(&self.end)
+        };
+        let t = now.time();
+        let date = now.date();
+        // The day the window started on
+        let started: Option<NaiveDate> = match end {
+            Some(end) if start <= end => (t >= start && t < end).then_some(date),
+            // Overnight range, e.g. 22:00 - 06:00, started on the day before after midnight
+            Some(end) if t < end => date.pred_opt(),
+            Some(_) | None => (t >= start).then_some(date),
+        };
+        let Some(started) = started else {
+            return false;
+        };
+        self.days.is_empty()
+            || self
+                .days
+                .iter()
+                .any(|d| parse_weekday(d) == Some(started.weekday()))
+    }
+}
+
+impl AccessSchedule {
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.trim().is_empty() {
+            return None;
+        }
+        match Self::check(s) {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                log::error!("Failed to parse access schedule: {}", e);
+                None
+            }
+        }
+    }
+
+    // Used to reject an invalid schedule when it is saved.
+    pub fn check(s: &str) -> ResultType<Self> {
+        let schedule = serde_json::from_str::<Self>(s)?;
+        for rule in schedule.rules.iter() {
+            rule.check()?;
+        }
+        for h in schedule.holidays.iter() {
+            if NaiveDate::parse_from_str(h.trim(), "%Y-%m-%d").is_err() {
+                bail!("invalid holiday: {h}");
+            }
+        }
+        Ok(schedule)
+    }
+
+    pub fn evaluate(&self, now: &NaiveDateTime) -> ScheduleAction {
+        let today = now.date().format("%Y-%m-%d").to_string();
+        if self.holidays.iter().any(|h| h.trim() == today) {
+            return ScheduleAction::Deny;
+        }
+        self.rules
+            .iter()
+            .find(|r| r.matches(now))
+            .map(|r| r.action)
+            .unwrap_or(self.default)
+    }
+}
+
+// A malformed schedule denies all connections, it is rejected when saved from the ui.
+pub fn current_action() -> ScheduleAction {
+    let s = Config::get_option(OPTION_ACCESS_SCHEDULE);
+    if s.trim().is_empty() {
+        return ScheduleAction::Allow;
+    }
+    match AccessSchedule::parse(&s) {
+        Some(schedule) => schedule.evaluate(&Local::now().naive_local()),
+        None => ScheduleAction::Deny,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    const OFFICE_HOURS: &str = r#"{
+        "rules": [{ "days": ["mon", "tue", "wed", "thu", "fri"], "start": "08:00", "end": "18:00", "action": "allow" }],
+        "holidays": ["2026-12-25"],
+        "default": "click"
+    }"#;
+
+    #[test]
+    fn test_office_hours() {
+        let schedule = AccessSchedule::parse(OFFICE_HOURS).unwrap();
+        // 2026-10-19 is a Monday.
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 19, 8, 0)),
+            ScheduleAction::Allow
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 19, 17, 59)),
+            ScheduleAction::Allow
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 19, 18, 0)),
+            ScheduleAction::Click
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 19, 7, 59)),
+            ScheduleAction::Click
+        );
+        // Saturday
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 24, 10, 0)),
+            ScheduleAction::Click
+        );
+        // Holiday on a Friday
+        assert_eq!(
+            schedule.evaluate(&at(2026, 12, 25, 10, 0)),
+            ScheduleAction::Deny
+        );
+    }
+
+    #[test]
+    fn test_overnight_and_first_match() {
+        let schedule = AccessSchedule::parse(
+            r#"{
+                "rules": [
+                    { "start": "22:00", "end": "06:00", "action": "deny" },
+                    { "days": ["sunday"], "action": "click" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 19, 23, 0)),
+            ScheduleAction::Deny
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 20, 5, 0)),
+            ScheduleAction::Deny
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 20, 12, 0)),
+            ScheduleAction::Allow
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 25, 12, 0)),
+            ScheduleAction::Click
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 25, 23, 0)),
+            ScheduleAction::Deny
+        );
+
+        // The days of an overnight range are the days it starts on
+        let schedule = AccessSchedule::parse(
+            r#"{ "rules": [{ "days": ["fri"], "start": "22:00", "end": "06:00", "action": "deny" }] }"#,
+        )
+        .unwrap();
+        // 2026-10-23 is a Friday.
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 23, 23, 0)),
+            ScheduleAction::Deny
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 24, 5, 0)),
+            ScheduleAction::Deny
+        );
+        assert_eq!(
+            schedule.evaluate(&at(2026, 10, 23, 5, 0)),
+            ScheduleAction::Allow
+        );
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        assert!(AccessSchedule::parse("").is_none());
+        assert!(AccessSchedule::parse("{").is_none());
+        assert!(AccessSchedule::parse(r#"{"default": "sometimes"}"#).is_none());
+        assert!(AccessSchedule::parse(r#"{"rules": [{"start": "08:00", "end": "18"}]}"#).is_none());
+        assert!(AccessSchedule::parse(r#"{"rules": [{"days": ["mo"]}]}"#).is_none());
+        assert!(AccessSchedule::parse(r#"{"holidays": ["12/25"]}"#).is_none());
+    }
+}
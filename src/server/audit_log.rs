@@ -0,0 +1,185 @@
+use hbb_common::{config::Config, log};
+use serde_json::{json, Value};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// Option `local-audit-log`:
+// - "": write audit events locally only if no audit server is configured
+// - "Y": always write audit events locally
+// - "N": never write audit events locally
+pub const OPTION_LOCAL_AUDIT_LOG: &str = "local-audit-log";
+// Option `audit-syslog`, "Y" to also send the local audit events to syslog (journald on systemd).
+pub const OPTION_AUDIT_SYSLOG: &str = "audit-syslog";
+
+const AUDIT_LOG_NAME: &str = "audit";
+const AUDIT_LOG_EXT: &str = "jsonl";
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_ROTATED_FILES: usize = 5;
+
+lazy_static::lazy_static! {
+    static ref WRITER: Mutex<()> = Default::default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    Conn,
+    File,
+    Alarm,
+}
+
+impl AuditKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::Conn => "conn",
+            AuditKind::File => "file",
+            AuditKind::Alarm => "alarm",
+        }
+    }
+}
+
+#[inline]
+pub fn enabled(has_audit_server: bool) -> bool {
+    match Config::get_option(OPTION_LOCAL_AUDIT_LOG).as_str() {
+        "Y" => true,
+        "N" => false,
+        _ => !has_audit_server,
+    }
+}
+
+#[inline]
+pub fn log_dir() -> PathBuf {
+    Config::log_path().join(AUDIT_LOG_NAME)
+}
+
+fn file_path(dir: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        dir.join(format!("{}.{}", AUDIT_LOG_NAME, AUDIT_LOG_EXT))
+    } else {
+        dir.join(format!("{}.{}.{}", AUDIT_LOG_NAME, index, AUDIT_LOG_EXT))
+    }
+}
+
+// audit.jsonl -> audit.1.jsonl -> ... -> audit.{MAX_ROTATED_FILES}.jsonl, the oldest is removed.
+fn rotate(dir: &Path) {
+    let _ = fs::remove_file(file_path(dir, MAX_ROTATED_FILES));
+    for i in (0..MAX_ROTATED_FILES).rev() {
+        let from = file_path(dir, i);
+        if from.exists() {
+            if let Err(e) = fs::rename(&from, file_path(dir, i + 1)) {
+                log::error!("Failed to rotate audit log {:?}: {}", from, e);
+            }
+        }
+    }
+}
+
+fn open(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    // Audit events contain ips and file names, keep them away from other users.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn append(dir: &Path, line: &str) -> std::io::Result<()> {
+    let _lock = WRITER.lock().unwrap();
+    fs::create_dir_all(dir)?;
+    let path = file_path(dir, 0);
+    if fs::metadata(&path).map_or(false, |m| m.len() >= MAX_FILE_SIZE) {
+        rotate(dir);
+    }
+    let mut file = open(&path)?;
+    file.write_all(line.as_bytes())?;
+    file.write_all(b"\n")
+}
+
+fn make_record(kind: AuditKind, v: &Value) -> Value {
+    let mut record = json!({
+        "time": chrono::Local::now().to_rfc3339(),
+        "kind": kind.as_str(),
+    });
+    if let (Some(record), Some(v)) = (record.as_object_mut(), v.as_object()) {
+        for (k, v) in v.iter() {
+            record.insert(k.clone(), v.clone());
+        }
+    }
+    record
+}
+
+#[cfg(unix)]
+fn syslog(line: &str) {
+    use hbb_common::libc;
+    use std::ffi::CString;
+    let Ok(ident) = CString::new(crate::get_app_name()) else {
+        return;
+    };
+    let Ok(msg) = CString::new(line) else {
+        return;
+    };
+    unsafe {
+        // The ident pointer must stay valid while the log is open.
+        libc::openlog(ident.as_ptr(), libc::LOG_PID, libc::LOG_AUTHPRIV);
+        libc::syslog(
+            libc::LOG_NOTICE,
+            "%s\0".as_ptr() as *const libc::c_char,
+            msg.as_ptr(),
+        );
+        libc::closelog();
+    }
+}
+
+pub fn write(kind: AuditKind, v: &Value) {
+    let line = make_record(kind, v).to_string();
+    if let Err(e) = append(&log_dir(), &line) {
+        log::error!("Failed to write audit log: {}", e);
+    }
+    #[cfg(unix)]
+    if Config::get_option(OPTION_AUDIT_SYSLOG) == "Y" {
+        syslog(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let record = make_record(
+            AuditKind::Conn,
+            &json!({ "ip": "1.2.3.4", "action": "new" }),
+        );
+        assert_eq!(record["kind"], "conn");
+        assert_eq!(record["ip"], "1.2.3.4");
+        assert!(record["time"].is_string());
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir().join(format!("rustdesk_audit_test_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..(MAX_ROTATED_FILES + 2) {
+            fs::write(file_path(&dir, 0), format!("{}", i)).unwrap();
+            rotate(&dir);
+        }
+        assert!(!file_path(&dir, 0).exists());
+        assert_eq!(
+            fs::read_to_string(file_path(&dir, 1)).unwrap(),
+            format!("{}", MAX_ROTATED_FILES + 1)
+        );
+        assert!(file_path(&dir, MAX_ROTATED_FILES).exists());
+        assert!(!file_path(&dir, MAX_ROTATED_FILES + 1).exists());
+
+        append(&dir, "{}").unwrap();
+        assert_eq!(fs::read_to_string(file_path(&dir, 0)).unwrap(), "{}\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,350 @@
+use hbb_common::{
+    config::Config,
+    log,
+    tokio::{self, sync::Notify},
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+// Audit events are posted in order by a single worker.
+// Transient failures (connection errors, 5xx, 408 and 429) are retried with backoff without limit,
+// since the audit server may be down for hours.
+// An event rejected with another 4xx will never succeed, it is moved to the dead letters,
+// so it does not block the others.
+// The queue is saved to disk by the worker every `SAVE_INTERVAL` if changed,
+// so the events survive restarts and offline periods.
+const QUEUE_FILE: &str = "audit_queue.json";
+const MAX_QUEUE_LEN: usize = 1_000;
+const MAX_DEAD_LETTERS: usize = 100;
+const RETRY_MIN: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static::lazy_static! {
+    static ref QUEUE: Mutex<Queue> = Mutex::new(Queue::load());
+    static ref NOTIFY: Arc<Notify> = Default::default();
+}
+static WORKER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Item {
+    seq: u64,
+    url: String,
+    body: Value,
+    #[serde(default)]
+    attempts: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Queue {
+    items: VecDeque<Item>,
+    next_seq: u64,
+    dropped: u64,
+    // The latest rejected events, kept for inspection.
+    dead_letters: VecDeque<Item>,
+    // All the rejected events, including those no longer kept in `dead_letters`.
+    dead_lettered: u64,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Status {
+    pub pending: usize,
+    pub dropped: u64,
+    pub dead_letters: u64,
+}
+
+enum PostError {
+    Transient(String),
+    Rejected(String),
+}
+
+#[inline]
+fn path() -> PathBuf {
+    Config::path(QUEUE_FILE)
+}
+
+impl Queue {
+    fn load() -> Self {
+        let path = path();
+        if !path.exists() {
+            return Self::default();
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str::<Self>(&s).map_err(|e| e.to_string()))
+        {
+            Ok(queue) => {
+                if !queue.items.is_empty() {
+                    log::info!("{} audit events pending from last run", queue.items.len());
+                }
+                queue
+            }
+            Err(e) => {
+                log::error!("Failed to load audit queue {:?}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    // Serialized under the lock, written to a temporary file first without it,
+    // a crash during saving should not lose the whole queue.
+    fn take_dirty(&mut self) -> Option<String> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        serde_json::to_string(self).ok()
+    }
+
+    // The oldest events are dropped if the queue is full.
+    fn push(&mut self, url: String, body: Value) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.items.push_back(Item {
+            seq,
+            url,
+            body,
+            attempts: 0,
+        });
+        while self.items.len() > MAX_QUEUE_LEN {
+            self.items.pop_front();
+            self.dropped += 1;
+        }
+        self.dirty = true;
+    }
+
+    // The front may have been dropped while posting, so only remove the item with the same seq.
+    fn remove(&mut self, seq: u64) -> bool {
+        if self.items.front().map(|x| x.seq) == Some(seq) {
+            self.items.pop_front();
+            self.dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn fail(&mut self, seq: u64) {
+        if let Some(front) = self.items.front_mut().filter(|x| x.seq == seq) {
+            front.attempts += 1;
+            self.dirty = true;
+        }
+    }
+
+    // Moves the item to the dead letters, only the latest `MAX_DEAD_LETTERS` are kept,
+    // but all are counted.
+    fn reject(&mut self, seq: u64) -> bool {
+        if self.items.front().map(|x| x.seq) != Some(seq) {
+            return false;
+        }
+        if let Some(item) = self.items.pop_front() {
+            self.dead_letters.push_back(item);
+            self.dead_lettered += 1;
+            while self.dead_letters.len() > MAX_DEAD_LETTERS {
+                self.dead_letters.pop_front();
+            }
+            self.dirty = true;
+        }
+        true
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            pending: self.items.len(),
+            dropped: self.dropped,
+            dead_letters: self.dead_lettered,
+        }
+    }
+}
+
+fn write(s: String) {
+    let path = path();
+    let tmp = path.with_extension("tmp");
+    let res = std::fs::write(&tmp, s).and_then(|_| std::fs::rename(&tmp, &path));
+    if let Err(e) = res {
+        log::error!("Failed to save audit queue {:?}: {}", path, e);
+    }
+}
+
+fn next_backoff(current: Duration) -> Duration {
+    (current * 2).clamp(RETRY_MIN, RETRY_MAX)
+}
+
+#[inline]
+fn is_rejected(status: u16) -> bool {
+    (400..500).contains(&status) && status != 408 && status != 429
+}
+
+// Only 2xx means the event is delivered.
+async fn post(item: &Item) -> Result<(), PostError> {
+    match crate::post_request_with_status(item.url.clone(), item.body.to_string(), "").await {
+        Ok((status, _)) if (200..300).contains(&status) => Ok(()),
+        Ok((status, text)) if is_rejected(status) => {
+            Err(PostError::Rejected(format!("status {}: {}", status, text)))
+        }
+        Ok((status, text)) => Err(PostError::Transient(format!("status {}: {}", status, text))),
+        Err(e) => Err(PostError::Transient(e.to_string())),
+    }
+}
+
+async fn run() {
+    let mut backoff = RETRY_MIN;
+    loop {
+        let front = QUEUE.lock().unwrap().items.front().cloned();
+        let Some(item) = front else {
+            NOTIFY.notified().await;
+            continue;
+        };
+        match post(&item).await {
+            Ok(_) => {
+                QUEUE.lock().unwrap().remove(item.seq);
+                backoff = RETRY_MIN;
+            }
+            Err(PostError::Rejected(e)) => {
+                QUEUE.lock().unwrap().reject(item.seq);
+                log::error!("Audit event {} rejected: {}", item.seq, e);
+                backoff = RETRY_MIN;
+            }
+            Err(PostError::Transient(e)) => {
+                QUEUE.lock().unwrap().fail(item.seq);
+                log::debug!(
+                    "Failed to post audit event {} (attempt {}), retry in {:?}: {}",
+                    item.seq,
+                    item.attempts + 1,
+                    backoff,
+                    e
+                );
+                // A new event wakes the worker up, the server may be back.
+                let _ = tokio::time::timeout(backoff, NOTIFY.notified()).await;
+                backoff = next_backoff(backoff);
+            }
+        }
+    }
+}
+
+async fn run_saver() {
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        if let Some(s) = QUEUE.lock().unwrap().take_dirty() {
+            tokio::task::spawn_blocking(move || write(s)).await.ok();
+        }
+    }
+}
+
+// Must be called in a tokio runtime.
+pub fn start() {
+    if WORKER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(run());
+    tokio::spawn(run_saver());
+}
+
+pub fn push(url: String, body: Value) {
+    QUEUE.lock().unwrap().push(url, body);
+    start();
+    NOTIFY.notify_one();
+}
+
+pub fn status() -> Status {
+    QUEUE.lock().unwrap().status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_push_and_remove() {
+        let mut queue = Queue::default();
+        for i in 0..(MAX_QUEUE_LEN + 3) {
+            queue.push("url".to_owned(), json!({ "i": i }));
+        }
+        assert_eq!(queue.items.len(), MAX_QUEUE_LEN);
+        assert_eq!(queue.dropped, 3);
+        assert_eq!(queue.items.front().unwrap().body["i"], 3);
+
+        let front = queue.items.front().unwrap().seq;
+        assert!(!queue.remove(front + 1));
+        assert!(queue.remove(front));
+        assert_eq!(queue.items.front().unwrap().body["i"], 4);
+        assert_eq!(queue.items.len(), MAX_QUEUE_LEN - 1);
+    }
+
+    #[test]
+    fn test_dead_letters() {
+        let mut queue = Queue::default();
+        queue.push("url".to_owned(), json!({ "i": 0 }));
+        queue.push("url".to_owned(), json!({ "i": 1 }));
+        let front = queue.items.front().unwrap().seq;
+        // Transient failures never give up.
+        for _ in 0..100 {
+            queue.fail(front);
+        }
+        assert_eq!(queue.items.len(), 2);
+        assert_eq!(queue.items.front().unwrap().attempts, 100);
+        assert!(!queue.reject(front + 1));
+        assert!(queue.reject(front));
+        assert_eq!(queue.dead_letters.len(), 1);
+        assert_eq!(queue.items.front().unwrap().body["i"], 1);
+        assert_eq!(queue.items.front().unwrap().attempts, 0);
+
+        for i in 0..(MAX_DEAD_LETTERS + 5) {
+            queue.push("url".to_owned(), json!({ "i": i }));
+        }
+        while let Some(seq) = queue.items.front().map(|x| x.seq) {
+            queue.reject(seq);
+        }
+        assert_eq!(queue.dead_letters.len(), MAX_DEAD_LETTERS);
+        let status = queue.status();
+        assert_eq!(status.pending, 0);
+        assert_eq!(status.dead_letters, MAX_DEAD_LETTERS as u64 + 7);
+    }
+
+    #[test]
+    fn test_rejected() {
+        assert!(is_rejected(400));
+        assert!(is_rejected(403));
+        assert!(is_rejected(404));
+        assert!(!is_rejected(408));
+        assert!(!is_rejected(429));
+        assert!(!is_rejected(500));
+        assert!(!is_rejected(503));
+        assert!(!is_rejected(302));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = RETRY_MIN;
+        for _ in 0..20 {
+            backoff = next_backoff(backoff);
+        }
+        assert_eq!(backoff, RETRY_MAX);
+        assert_eq!(next_backoff(RETRY_MIN), RETRY_MIN * 2);
+    }
+
+    #[test]
+    fn test_serde() {
+        let mut queue = Queue::default();
+        queue.push("url".to_owned(), json!({ "a": 1 }));
+        let s = queue.take_dirty().unwrap();
+        assert!(queue.take_dirty().is_none());
+        let loaded: Queue = serde_json::from_str(&s).unwrap();
+        assert_eq!(loaded.items.len(), 1);
+        assert_eq!(loaded.next_seq, 1);
+        assert!(!loaded.dirty);
+    }
+}
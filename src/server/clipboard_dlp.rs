@@ -0,0 +1,416 @@
+use hbb_common::{
+    anyhow::anyhow,
+    bail,
+    compress::{compress, decompress},
+    config::Config,
+    log,
+    message_proto::{Clipboard, ClipboardFormat},
+    ResultType,
+};
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::sync::{Arc, Mutex};
+
+// Json value of option `clipboard-rules`, applied on the controlled side, e.g.
+// {
+//   "direction": "outgoing",
+//   "max_size": 1048576,
+//   "formats": { "incoming": ["text"], "outgoing": ["text", "html", "image"] },
+//   "redact": ["\\b(?:\\d[ -]?){13,16}\\b", "sk-[A-Za-z0-9]{20,}"],
+//   "redact_with": "***"
+// }
+// "incoming" is the controlling side to this device, "outgoing" is this device to the controlling side.
+// Format names are "text", "rtf", "html", "image" (or "image-rgba", "image-png", "image-svg"),
+// "special" or the name of a special format, e.g. "XML Spreadsheet". Empty formats allow all.
+// "files" is the file copy and paste, it needs the messages of both directions, so it is only
+// allowed if "direction" is "both" and both "incoming" and "outgoing" allow "files".
+// A malformed option or an invalid pattern blocks all clipboards.
+pub const OPTION_CLIPBOARD_RULES: &str = "clipboard-rules";
+
+const DEFAULT_REDACT_WITH: &str = "[REDACTED]";
+const FORMAT_FILES: &str = "files";
+
+// The rules parsed from the option value, parsed again only if the option is changed.
+enum Cached {
+    None,
+    Rules(ClipboardRules),
+    Invalid,
+}
+
+lazy_static::lazy_static! {
+    static ref RULES: Mutex<(String, Arc<Cached>)> =
+        Mutex::new((String::new(), Arc::new(Cached::None)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Incoming => "incoming",
+            Direction::Outgoing => "outgoing",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SyncDirection {
+    #[default]
+    Both,
+    Incoming,
+    Outgoing,
+    None,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+struct Formats {
+    incoming: Vec<String>,
+    outgoing: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClipboardRules {
+    direction: SyncDirection,
+    // In bytes of the uncompressed content, 0 means no limit.
+    max_size: usize,
+    formats: Formats,
+    redact: Vec<String>,
+    redact_with: String,
+    #[serde(skip)]
+    patterns: Vec<Regex>,
+}
+
+#[derive(Debug, Default)]
+pub struct Filtered {
+    pub clipboards: Vec<Clipboard>,
+    // The reasons of the dropped clipboards.
+    pub blocked: Vec<String>,
+    // The number of the redacted matches.
+    pub redacted: usize,
+}
+
+impl Filtered {
+    #[inline]
+    pub fn is_modified(&self) -> bool {
+        !self.blocked.is_empty() || self.redacted > 0
+    }
+}
+
+fn format_name(c: &Clipboard) -> String {
+    match c.format.enum_value() {
+        Ok(ClipboardFormat::Text) => "text",
+        Ok(ClipboardFormat::Rtf) => "rtf",
+        Ok(ClipboardFormat::Html) => "html",
+        Ok(ClipboardFormat::ImageRgba) => "image-rgba",
+        Ok(ClipboardFormat::ImagePng) => "image-png",
+        Ok(ClipboardFormat::ImageSvg) => "image-svg",
+        Ok(ClipboardFormat::Special) => "special",
+        _ => "unknown",
+    }
+    .to_owned()
+}
+
+fn is_format_allowed(allowed: &[String], c: &Clipboard) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    let name = format_name(c);
+    allowed.iter().any(|a| {
+        let a = a.trim();
+        a.eq_ignore_ascii_case(&name)
+            || (a.eq_ignore_ascii_case("image") && name.starts_with("image-"))
+            || (name == "special" && a == c.special_name)
+    })
+}
+
+fn is_text_format(c: &Clipboard) -> bool {
+    matches!(
+        c.format.enum_value(),
+        Ok(ClipboardFormat::Text) | Ok(ClipboardFormat::Rtf) | Ok(ClipboardFormat::Html)
+    )
+}
+
+fn is_files_allowed(allowed: &[String]) -> bool {
+    allowed.is_empty()
+        || allowed
+            .iter()
+            .any(|a| a.trim().eq_ignore_ascii_case(FORMAT_FILES))
+}
+
+impl ClipboardRules {
+    pub fn parse(s: &str) -> ResultType<Option<Self>> {
+        if s.trim().is_empty() {
+            return Ok(None);
+        }
+        let mut rules = serde_json::from_str::<Self>(s)
+            .map_err(|e| anyhow!("Failed to parse clipboard rules: {}", e))?;
+        for p in rules.redact.iter() {
+            match Regex::new(p) {
+                Ok(r) => rules.patterns.push(r),
+                Err(e) => bail!("Invalid clipboard redaction pattern '{}': {}", p, e),
+            }
+        }
+        if rules.redact_with.is_empty() {
+            rules.redact_with = DEFAULT_REDACT_WITH.to_owned();
+        }
+        Ok(Some(rules))
+    }
+
+    fn is_file_allowed(&self) -> bool {
+        self.direction == SyncDirection::Both
+            && is_files_allowed(&self.formats.incoming)
+            && is_files_allowed(&self.formats.outgoing)
+    }
+
+    fn is_direction_allowed(&self, dir: Direction) -> bool {
+        match self.direction {
+            SyncDirection::Both => true,
+            SyncDirection::Incoming => dir == Direction::Incoming,
+            SyncDirection::Outgoing => dir == Direction::Outgoing,
+            SyncDirection::None => false,
+        }
+    }
+
+    fn redact(&self, c: &mut Clipboard) -> usize {
+        let data = if c.compress {
+            decompress(&c.content)
+        } else {
+            c.content.to_vec()
+        };
+        let Ok(mut text) = String::from_utf8(data) else {
+            return 0;
+        };
+        let mut count = 0;
+        for p in self.patterns.iter() {
+            let n = p.find_iter(&text).count();
+            if n > 0 {
+                count += n;
+                text = p.replace_all(&text, self.redact_with.as_str()).into_owned();
+            }
+        }
+        if count > 0 {
+            let bytes = text.into_bytes();
+            if c.compress {
+                c.content = compress(&bytes).into();
+            } else {
+                c.content = bytes.into();
+            }
+        }
+        count
+    }
+
+    pub fn apply(&self, dir: Direction, clipboards: Vec<Clipboard>) -> Filtered {
+        let mut filtered = Filtered::default();
+        if !self.is_direction_allowed(dir) {
+            filtered.blocked = clipboards
+                .iter()
+                .map(|c| format!("{}: direction", format_name(c)))
+                .collect();
+            return filtered;
+        }
+        let allowed = match dir {
+            Direction::Incoming => &self.formats.incoming,
+            Direction::Outgoing => &self.formats.outgoing,
+        };
+        for mut c in clipboards {
+            if !is_format_allowed(allowed, &c) {
+                filtered
+                    .blocked
+                    .push(format!("{}: format", format_name(&c)));
+                continue;
+            }
+            if self.max_size > 0 {
+                let size = if c.compress {
+                    decompress(&c.content).len()
+                } else {
+                    c.content.len()
+                };
+                if size > self.max_size {
+                    filtered
+                        .blocked
+                        .push(format!("{}: size {}", format_name(&c), size));
+                    continue;
+                }
+            }
+            if !self.patterns.is_empty() && is_text_format(&c) {
+                filtered.redacted += self.redact(&mut c);
+            }
+            filtered.clipboards.push(c);
+        }
+        filtered
+    }
+}
+
+fn get_rules() -> Arc<Cached> {
+    let option = Config::get_option(OPTION_CLIPBOARD_RULES);
+    let mut lock = RULES.lock().unwrap();
+    if lock.0 != option {
+        let cached = match ClipboardRules::parse(&option) {
+            Ok(Some(rules)) => Cached::Rules(rules),
+            Ok(None) => Cached::None,
+            Err(e) => {
+                log::error!("{}, all clipboards are blocked", e);
+                Cached::Invalid
+            }
+        };
+        *lock = (option, Arc::new(cached));
+    }
+    lock.1.clone()
+}
+
+pub fn filter(dir: Direction, clipboards: Vec<Clipboard>) -> Filtered {
+    match &*get_rules() {
+        Cached::Rules(rules) => rules.apply(dir, clipboards),
+        Cached::None => Filtered {
+            clipboards,
+            ..Default::default()
+        },
+        Cached::Invalid => Filtered {
+            blocked: clipboards
+                .iter()
+                .map(|c| format!("{}: invalid rules", format_name(c)))
+                .collect(),
+            ..Default::default()
+        },
+    }
+}
+
+// The file clipboard (cliprdr) messages are dropped if it returns false.
+pub fn is_file_allowed() -> bool {
+    match &*get_rules() {
+        Cached::Rules(rules) => rules.is_file_allowed(),
+        Cached::None => true,
+        Cached::Invalid => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str, compress_content: bool) -> Clipboard {
+        Clipboard {
+            compress: compress_content,
+            content: if compress_content {
+                compress(s.as_bytes()).into()
+            } else {
+                s.as_bytes().to_vec().into()
+            },
+            format: ClipboardFormat::Text.into(),
+            ..Default::default()
+        }
+    }
+
+    fn image(size: usize) -> Clipboard {
+        Clipboard {
+            content: vec![0u8; size].into(),
+            format: ClipboardFormat::ImagePng.into(),
+            ..Default::default()
+        }
+    }
+
+    fn content(c: &Clipboard) -> String {
+        let data = if c.compress {
+            decompress(&c.content)
+        } else {
+            c.content.to_vec()
+        };
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn test_direction_and_formats() {
+        let rules = ClipboardRules::parse(
+            r#"{ "direction": "outgoing", "formats": { "outgoing": ["text"] } }"#,
+        )
+        .unwrap()
+        .unwrap();
+        let filtered = rules.apply(Direction::Incoming, vec![text("a", false)]);
+        assert!(filtered.clipboards.is_empty());
+        assert_eq!(filtered.blocked, vec!["text: direction"]);
+
+        let filtered = rules.apply(Direction::Outgoing, vec![text("a", false), image(10)]);
+        assert_eq!(filtered.clipboards.len(), 1);
+        assert_eq!(filtered.blocked, vec!["image-png: format"]);
+
+        let rules = ClipboardRules::parse(r#"{ "formats": { "incoming": ["image"] } }"#)
+            .unwrap()
+            .unwrap();
+        let filtered = rules.apply(Direction::Incoming, vec![image(10)]);
+        assert!(!filtered.is_modified());
+        let filtered = rules.apply(Direction::Outgoing, vec![image(10), text("a", false)]);
+        assert_eq!(filtered.clipboards.len(), 2);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let rules = ClipboardRules::parse(r#"{ "max_size": 100 }"#)
+            .unwrap()
+            .unwrap();
+        let filtered = rules.apply(
+            Direction::Outgoing,
+            vec![image(101), text(&"a".repeat(200), true), text("a", true)],
+        );
+        assert_eq!(filtered.clipboards.len(), 1);
+        assert_eq!(
+            filtered.blocked,
+            vec!["image-png: size 101", "text: size 200"]
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        assert!(ClipboardRules::parse(r#"{ "redact": ["sk-[A-Za-z0-9]{20,}", "("] }"#).is_err());
+        let rules = ClipboardRules::parse(
+            r#"{ "redact": ["\\b(?:\\d[ -]?){13,16}\\b", "sk-[A-Za-z0-9]{20,}"] }"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(rules.patterns.len(), 2);
+        let s = "card 4111 1111 1111 1111, key sk-abcdefghijklmnopqrstuvwxyz";
+        for compressed in [false, true] {
+            let filtered = rules.apply(Direction::Incoming, vec![text(s, compressed), image(10)]);
+            assert_eq!(filtered.redacted, 2);
+            assert!(filtered.blocked.is_empty());
+            assert_eq!(
+                content(&filtered.clipboards[0]),
+                "card [REDACTED], key [REDACTED]"
+            );
+        }
+        let filtered = rules.apply(Direction::Incoming, vec![text("nothing", false)]);
+        assert!(!filtered.is_modified());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(ClipboardRules::parse("").unwrap().is_none());
+        assert!(ClipboardRules::parse("{").is_err());
+        let rules = ClipboardRules::parse(r#"{ "direction": "none" }"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            rules
+                .apply(Direction::Outgoing, vec![image(1)])
+                .blocked
+                .len(),
+            1
+        );
+        assert!(!rules.is_file_allowed());
+    }
+
+    #[test]
+    fn test_file_allowed() {
+        let parse = |s| ClipboardRules::parse(s).unwrap().unwrap();
+        assert!(parse("{}").is_file_allowed());
+        assert!(parse(r#"{ "formats": { "incoming": ["text", "files"] } }"#).is_file_allowed());
+        assert!(!parse(r#"{ "formats": { "outgoing": ["text"] } }"#).is_file_allowed());
+        assert!(!parse(r#"{ "direction": "incoming" }"#).is_file_allowed());
+    }
+}
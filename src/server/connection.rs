@@ -1,3 +1,8 @@
+use super::access_control;
+use super::access_schedule::{self, ScheduleAction};
+use super::audit_log::{self, AuditKind};
+use super::audit_queue;
+use super::clipboard_dlp;
 #[cfg(target_os = "windows")]
 use super::login_failure_check::try_acquire_os_credential_login_gate;
 use super::login_failure_check::{
@@ -26,7 +31,6 @@ use crate::{
 };
 #[cfg(any(target_os = "android", target_os = "ios"))]
 use crate::{common::DEVICE_NAME, flutter::connection_manager::start_channel};
-use cidr_utils::cidr::IpCidr;
 #[cfg(target_os = "android")]
 use hbb_common::protobuf::EnumOrUnknown;
 use hbb_common::{
@@ -284,8 +288,13 @@ pub struct Connection {
     block_input: bool,
     privacy_mode: bool,
     control_permissions: Option<ControlPermissions>,
+    acl_profile: Option<access_control::PermissionProfile>,
     last_test_delay: Option<Instant>,
     network_delay: u32,
+    relay: bool,
+    // video frames and bytes sent since the last telemetry report
+    telemetry_video_sent: (usize, usize),
+    telemetry_instant: Instant,
     lock_after_session_end: bool,
     show_remote_cursor: bool,
     // by peer
@@ -341,9 +350,6 @@ pub struct Connection {
     multi_ui_session: bool,
     tx_from_authed: mpsc::UnboundedSender<ipc::Data>,
     printer_data: Vec<(Instant, String, Vec<u8>)>,
-    // For post requests that need to be sent sequentially.
-    // eg. post_conn_audit
-    tx_post_seq: mpsc::UnboundedSender<(String, Value)>,
     // Tracks read job IDs delegated to CM process.
     // When a read job is delegated to CM (via FS::ReadFile), the job id is added here.
     // Used to filter stale responses (FileBlockFromCM, FileReadDone, etc.) for
@@ -378,6 +384,8 @@ impl Subscriber for ConnInner {
             Some(message::Union::VideoFrame(_)) => true,
             Some(message::Union::Misc(misc)) => match &misc.union {
                 Some(misc::Union::SwitchDisplay(_)) => true,
+                // The lossless tiles are drawn over the video frames.
+                Some(misc::Union::LosslessTiles(_)) => true,
                 _ => false,
             },
             _ => false,
@@ -407,6 +415,7 @@ impl Connection {
         stream: super::Stream,
         id: i32,
         server: super::ServerPtrWeak,
+        relay: bool,
         control_permissions: Option<ControlPermissions>,
     ) {
         // Android is not supported yet, so we always set control_permissions to None.
@@ -438,11 +447,6 @@ impl Connection {
         let linux_headless_handle =
             LinuxHeadlessHandle::new(_rx_cm_stream_ready, _tx_desktop_ready);
 
-        let (tx_post_seq, rx_post_seq) = mpsc::unbounded_channel();
-        tokio::spawn(async move {
-            Self::post_seq_loop(rx_post_seq).await;
-        });
-
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         let tx_cloned = tx.clone();
         let mut conn = Self {
@@ -476,8 +480,12 @@ impl Connection {
             block_input: Self::permission(keys::OPTION_ENABLE_BLOCK_INPUT, &control_permissions),
             privacy_mode: Self::permission(keys::OPTION_ENABLE_PRIVACY_MODE, &control_permissions),
             control_permissions,
+            acl_profile: None,
             last_test_delay: None,
             network_delay: 0,
+            relay,
+            telemetry_video_sent: (0, 0),
+            telemetry_instant: Instant::now(),
             lock_after_session_end: false,
             show_remote_cursor: false,
             follow_remote_cursor: false,
@@ -529,7 +537,6 @@ impl Connection {
             retina: Retina::default(),
             tx_from_authed,
             printer_data: Vec::new(),
-            tx_post_seq,
             cm_read_job_ids: HashSet::new(),
             terminal_service_id: "".to_owned(),
             terminal_persistent: false,
@@ -654,6 +661,12 @@ impl Connection {
                         }
                         ipc::Data::SwitchPermission{name, enabled} => {
                             log::info!("Change permission {} -> {}", name, enabled);
+                            #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            conn.plugin_listen_event(
+                                crate::plugin::EVENT_ON_PERMISSION_CHANGE,
+                                json!({ "name": name, "enabled": enabled }),
+                            );
                             if &name == "keyboard" {
                                 conn.keyboard = enabled;
                                 conn.send_permission(Permission::Keyboard, enabled).await;
@@ -768,7 +781,7 @@ impl Connection {
                         }
                         #[cfg(target_os = "windows")]
                         ipc::Data::ClipboardFile(clip) => {
-                            if !conn.is_remote() {
+                            if !conn.is_remote() || !clipboard_dlp::is_file_allowed() {
                                 continue;
                             }
                             match clip {
@@ -933,10 +946,19 @@ impl Connection {
                         conn.on_close(&err.to_string(), false).await;
                         break;
                     }
+                    if let Some(message::Union::VideoFrame(_)) = &value.union {
+                        let bytes = value.compute_size() as usize;
+                        video_service::VIDEO_QOS.lock().unwrap().on_video_written(
+                            id,
+                            bytes,
+                            instant.elapsed(),
+                        );
+                        conn.telemetry_video_sent.0 += 1;
+                        conn.telemetry_video_sent.1 += bytes;
+                    }
                 },
                 Some((instant, value)) = rx.recv() => {
                     let latency = instant.elapsed().as_millis() as i64;
-                    #[allow(unused_mut)]
                     let mut msg = value;
 
                     if latency > 1000 {
@@ -948,6 +970,20 @@ impl Connection {
                             _ => {}
                         }
                     }
+                    if let Some(new_msg) = conn.filter_outgoing_clipboard(&msg) {
+                        msg = Arc::new(new_msg);
+                    }
+                    #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    match &msg.union {
+                        Some(message::Union::Clipboard(cb)) => {
+                            conn.plugin_listen_clipboard("send", std::slice::from_ref(cb))
+                        }
+                        Some(message::Union::MultiClipboards(mcb)) => {
+                            conn.plugin_listen_clipboard("send", &mcb.clipboards)
+                        }
+                        _ => {}
+                    }
                     match &msg.union {
                         Some(message::Union::Misc(m)) => {
                             match &m.union {
@@ -977,6 +1013,9 @@ impl Connection {
                             }
                         }
                         Some(message::Union::MultiClipboards(_multi_clipboards)) => {
+                            if _multi_clipboards.clipboards.is_empty() {
+                                continue;
+                            }
                             #[cfg(not(target_os = "ios"))]
                             if let Some(msg_out) = crate::clipboard::get_msg_if_not_support_multi_clip(&conn.lr.version, &conn.lr.my_platform, _multi_clipboards) {
                                 if let Err(err) = conn.stream.send(&msg_out).await {
@@ -999,7 +1038,7 @@ impl Connection {
                     match data {
                         #[cfg(all(target_os = "windows", feature = "flutter"))]
                         ipc::Data::PrinterData(data) => {
-                            if Self::permission(keys::OPTION_ENABLE_REMOTE_PRINTER, &conn.control_permissions) {
+                            if conn.peer_permission(keys::OPTION_ENABLE_REMOTE_PRINTER) {
                                 conn.send_printer_request(data).await;
                             } else {
                                 conn.send_remote_printing_disallowed().await;
@@ -1012,6 +1051,9 @@ impl Connection {
                     #[cfg(windows)]
                     conn.portable_check();
                     raii::AuthedConnID::check_wake_lock_on_setting_changed();
+                    if (conn.is_authed_remote_conn() || conn.is_authed_view_camera_conn()) && crate::telemetry::enabled() {
+                        conn.report_telemetry();
+                    }
                     if let Some((instant, minute)) = conn.auto_disconnect_timer.as_ref() {
                         if instant.elapsed().as_secs() > minute * 60 {
                             conn.send_close_reason_no_retry("Connection failed due to inactivity").await;
@@ -1034,7 +1076,7 @@ impl Connection {
                         let mut msg_out = Message::new();
                         msg_out.set_test_delay(TestDelay{
                             last_delay: conn.network_delay,
-                            target_bitrate: video_service::VIDEO_QOS.lock().unwrap().bitrate(),
+                            target_bitrate: video_service::VIDEO_QOS.lock().unwrap().user_bitrate(id),
                             ..Default::default()
                         });
                         conn.send(msg_out.into()).await;
@@ -1203,13 +1245,6 @@ impl Connection {
         log::debug!("Input thread exited");
     }
 
-    async fn post_seq_loop(mut rx: mpsc::UnboundedReceiver<(String, Value)>) {
-        while let Some((url, v)) = rx.recv().await {
-            allow_err!(Self::post_audit_async(url, v).await);
-        }
-        log::debug!("post_seq_loop exited");
-    }
-
     async fn try_port_forward_loop(
         &mut self,
         rx_from_cm: &mut mpsc::UnboundedReceiver<Data>,
@@ -1289,39 +1324,37 @@ impl Connection {
     }
 
     async fn check_whitelist(&mut self, addr: &SocketAddr) -> bool {
-        let whitelist: Vec<String> = Config::get_option("whitelist")
-            .split(",")
-            .filter(|x| !x.is_empty())
-            .map(|x| x.to_owned())
-            .collect();
-        if !whitelist.is_empty()
-            && whitelist
-                .iter()
-                .filter(|x| x == &"0.0.0.0")
-                .next()
-                .is_none()
-            && whitelist
-                .iter()
-                .filter(|x| IpCidr::from_str(x).map_or(false, |y| y.contains(addr.ip())))
-                .next()
-                .is_none()
-        {
+        let decision = super::ip_whitelist::check_address(addr.ip());
+        if !decision.allowed {
             self.send_login_error("Your ip is blocked by the peer")
                 .await;
             Self::post_alarm_audit(
                 AlarmAuditType::IpWhitelist, //"ip whitelist",
-                json!({ "ip":addr.ip() }),
+                json!({ "ip":addr.ip(), "entry": decision.entry }),
             );
             return false;
         }
         true
     }
 
+    async fn check_access_schedule(&mut self, addr: &SocketAddr) -> bool {
+        if access_schedule::current_action() == ScheduleAction::Deny {
+            self.send_login_error(access_schedule::LOGIN_MSG_ACCESS_SCHEDULE_DENIED)
+                .await;
+            Self::post_alarm_audit(AlarmAuditType::AccessSchedule, json!({ "ip":addr.ip() }));
+            return false;
+        }
+        true
+    }
+
     async fn on_open(&mut self, addr: SocketAddr) -> bool {
         log::debug!("#{} Connection opened from {}.", self.inner.id, addr);
         if !self.check_whitelist(&addr).await {
             return false;
         }
+        if !self.check_access_schedule(&addr).await {
+            return false;
+        }
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         if crate::is_server() && Config::get_option("allow-only-conn-window-open") == "Y" {
             if !crate::check_process("", !crate::platform::is_root()) {
@@ -1355,7 +1388,9 @@ impl Connection {
     }
 
     fn post_conn_audit(&self, v: Value) {
-        if self.server_audit_conn.is_empty() {
+        let has_server = !self.server_audit_conn.is_empty();
+        let write_local = audit_log::enabled(has_server);
+        if !has_server && !write_local {
             return;
         }
         let url = self.server_audit_conn.clone();
@@ -1364,7 +1399,14 @@ impl Connection {
         v["uuid"] = json!(crate::encode64(hbb_common::get_uuid()));
         v["conn_id"] = json!(self.inner.id);
         v["session_id"] = json!(self.lr.session_id);
-        allow_err!(self.tx_post_seq.send((url, v)));
+        if write_local {
+            v["peer_id"] = json!(self.lr.my_id);
+            v["name"] = json!(self.lr.my_name);
+            audit_log::write(AuditKind::Conn, &v);
+        }
+        if has_server {
+            audit_queue::push(url, v);
+        }
     }
 
     fn get_files_for_audit(job_type: fs::JobType, mut files: Vec<FileEntry>) -> Vec<(String, i64)> {
@@ -1383,6 +1425,111 @@ impl Connection {
             .collect()
     }
 
+    // Apply the clipboard rules of option `clipboard-rules` and audit the blocked or redacted clipboards.
+    fn apply_clipboard_rules(
+        &self,
+        dir: clipboard_dlp::Direction,
+        clipboards: Vec<Clipboard>,
+    ) -> clipboard_dlp::Filtered {
+        let filtered = clipboard_dlp::filter(dir, clipboards);
+        if filtered.is_modified() {
+            log::info!(
+                "Clipboard {} filtered, blocked: {:?}, redacted: {}",
+                dir.as_str(),
+                filtered.blocked,
+                filtered.redacted
+            );
+            Self::post_alarm_audit(
+                AlarmAuditType::ClipboardBlocked,
+                json!({
+                    "ip": self.ip,
+                    "id": self.lr.my_id.clone(),
+                    "name": self.lr.my_name.clone(),
+                    "direction": dir.as_str(),
+                    "blocked": filtered.blocked,
+                    "redacted": filtered.redacted,
+                }),
+            );
+        }
+        filtered
+    }
+
+    // Returns the new message if the clipboards to send are changed by the clipboard rules.
+    // The clipboards of the new message may be empty.
+    fn filter_outgoing_clipboard(&self, msg: &Message) -> Option<Message> {
+        let (clipboards, is_multi) = match &msg.union {
+            Some(message::Union::Clipboard(cb)) => (vec![cb.clone()], false),
+            Some(message::Union::MultiClipboards(mcb)) => (mcb.clipboards.clone(), true),
+            _ => return None,
+        };
+        let mut filtered =
+            self.apply_clipboard_rules(clipboard_dlp::Direction::Outgoing, clipboards);
+        if !filtered.is_modified() {
+            return None;
+        }
+        let mut new_msg = Message::new();
+        if !is_multi && filtered.clipboards.len() == 1 {
+            new_msg.set_clipboard(filtered.clipboards.remove(0));
+        } else {
+            new_msg.set_multi_clipboards(MultiClipboards {
+                clipboards: filtered.clipboards,
+                ..Default::default()
+            });
+        }
+        Some(new_msg)
+    }
+
+    // Notify the plugins subscribed to the event, see `crate::plugin::EVENT_ON_*`.
+    #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn plugin_listen_event(&self, event: &str, data: Value) {
+        crate::plugin::handle_listen_event_with_data(event.to_owned(), self.lr.my_id.clone(), data);
+    }
+
+    // Only the formats and the size are sent, plugins can read the local clipboard if needed.
+    #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn plugin_listen_clipboard(&self, direction: &str, clipboards: &[Clipboard]) {
+        if clipboards.is_empty() {
+            return;
+        }
+        let formats: Vec<String> = clipboards
+            .iter()
+            .map(|c| {
+                c.format
+                    .enum_value()
+                    .map(|f| format!("{:?}", f))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let size: usize = clipboards.iter().map(|c| c.content.len()).sum();
+        self.plugin_listen_event(
+            crate::plugin::EVENT_ON_CLIPBOARD,
+            json!({ "direction": direction, "formats": formats, "size": size }),
+        );
+    }
+
+    #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn plugin_listen_file_transfer_start(
+        &self,
+        direction: &str,
+        id: i32,
+        path: &str,
+        files: &[(String, i64)],
+    ) {
+        self.plugin_listen_event(
+            crate::plugin::EVENT_ON_FILE_TRANSFER_START,
+            json!({
+                "direction": direction,
+                "id": id,
+                "path": path,
+                "num": files.len(),
+                "files": files.iter().take(10).collect::<Vec<_>>(),
+            }),
+        );
+    }
+
     fn post_file_audit(
         &self,
         r#type: FileAuditType,
@@ -1390,7 +1537,9 @@ impl Connection {
         files: Vec<(String, i64)>,
         info: Value,
     ) {
-        if self.server_audit_file.is_empty() {
+        let has_server = !self.server_audit_file.is_empty();
+        let write_local = audit_log::enabled(has_server);
+        if !has_server && !write_local {
             return;
         }
         let url = self.server_audit_file.clone();
@@ -1413,9 +1562,15 @@ impl Connection {
             "is_file":is_file,
             "info":json!(info).to_string(),
         });
-        tokio::spawn(async move {
-            allow_err!(Self::post_audit_async(url, v).await);
-        });
+        if write_local {
+            let mut local = v.clone();
+            local["info"] = info;
+            audit_log::write(AuditKind::File, &local);
+        }
+        if !has_server {
+            return;
+        }
+        audit_queue::push(url, v);
     }
 
     pub fn post_alarm_audit(typ: AlarmAuditType, info: Value) {
@@ -1424,22 +1579,25 @@ impl Connection {
             Config::get_option("custom-rendezvous-server"),
             "alarm".to_owned(),
         );
-        if url.is_empty() {
+        let has_server = !url.is_empty();
+        let write_local = audit_log::enabled(has_server);
+        if !has_server && !write_local {
             return;
         }
         let mut v = Value::default();
         v["id"] = json!(Config::get_id());
         v["uuid"] = json!(crate::encode64(hbb_common::get_uuid()));
         v["typ"] = json!(typ as i8);
+        if write_local {
+            let mut local = v.clone();
+            local["info"] = info.clone();
+            audit_log::write(AuditKind::Alarm, &local);
+        }
+        if !has_server {
+            return;
+        }
         v["info"] = serde_json::Value::String(info.to_string());
-        tokio::spawn(async move {
-            allow_err!(Self::post_audit_async(url, v).await);
-        });
-    }
-
-    #[inline]
-    async fn post_audit_async(url: String, v: Value) -> ResultType<String> {
-        crate::post_request(url, v.to_string(), "").await
+        audit_queue::push(url, v);
     }
 
     fn normalize_port_forward_target(pf: &mut PortForward) -> (String, bool) {
@@ -1719,6 +1877,8 @@ impl Connection {
         pi.sas_enabled = sas_enabled;
         pi.features = Some(Features {
             privacy_mode: privacy_mode::is_privacy_mode_supported(),
+            lossless_tiles: true,
+            video_received_ack: true,
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             terminal,
             ..Default::default()
@@ -2262,6 +2422,25 @@ impl Connection {
     fn permission(
         enable_prefix_option: &str,
         control_permissions: &Option<ControlPermissions>,
+    ) -> bool {
+        Self::permission_with_profile(enable_prefix_option, control_permissions, None)
+    }
+
+    #[inline]
+    fn peer_permission(&self, enable_prefix_option: &str) -> bool {
+        Self::permission_with_profile(
+            enable_prefix_option,
+            &self.control_permissions,
+            self.acl_profile.as_ref(),
+        )
+    }
+
+    // The control permissions of the server decide first,
+    // then the local options, which the access control profile can only restrict.
+    fn permission_with_profile(
+        enable_prefix_option: &str,
+        control_permissions: &Option<ControlPermissions>,
+        acl_profile: Option<&access_control::PermissionProfile>,
     ) -> bool {
         use hbb_common::rendezvous_proto::control_permissions::Permission;
         if let Some(control_permissions) = control_permissions {
@@ -2289,6 +2468,35 @@ impl Connection {
             }
         }
         Self::is_permission_enabled_locally(enable_prefix_option)
+            && acl_profile
+                .and_then(|p| p.get(enable_prefix_option))
+                .unwrap_or(true)
+    }
+
+    // Applied for each login request, which is the one authenticated before the logon response.
+    fn apply_access_control_profile(&mut self) {
+        self.acl_profile = access_control::get_profile(&access_control::Peer {
+            ip: &self.ip,
+            id: &self.lr.my_id,
+            name: &self.lr.my_name,
+        });
+        let Some(profile) = self.acl_profile.as_ref() else {
+            return;
+        };
+        log::info!(
+            "Apply access control profile {} to {} ({})",
+            profile.name,
+            self.lr.my_id,
+            self.ip
+        );
+        self.keyboard = self.peer_permission(keys::OPTION_ENABLE_KEYBOARD);
+        self.clipboard = self.peer_permission(keys::OPTION_ENABLE_CLIPBOARD);
+        self.audio = self.peer_permission(keys::OPTION_ENABLE_AUDIO);
+        self.file = self.peer_permission(keys::OPTION_ENABLE_FILE_TRANSFER);
+        self.restart = self.peer_permission(keys::OPTION_ENABLE_REMOTE_RESTART);
+        self.recording = self.peer_permission(keys::OPTION_ENABLE_RECORD_SESSION);
+        self.block_input = self.peer_permission(keys::OPTION_ENABLE_BLOCK_INPUT);
+        self.privacy_mode = self.peer_permission(keys::OPTION_ENABLE_PRIVACY_MODE);
     }
 
     fn update_codec_on_login(&self) {
@@ -2318,6 +2526,9 @@ impl Connection {
         if let Some(o) = lr.option.as_ref() {
             self.options_in_login = Some(o.clone());
         }
+        if !self.authorized {
+            self.apply_access_control_profile();
+        }
         if self.require_2fa.is_some() && !lr.hwid.is_empty() && Self::enable_trusted_devices() {
             let devices = Config::get_trusted_devices();
             if let Some(device) = devices.iter().find(|d| d.hwid == lr.hwid) {
@@ -2386,10 +2597,7 @@ impl Connection {
             }
             match lr.union {
                 Some(login_request::Union::FileTransfer(ft)) => {
-                    if !Self::permission(
-                        keys::OPTION_ENABLE_FILE_TRANSFER,
-                        &self.control_permissions,
-                    ) {
+                    if !self.peer_permission(keys::OPTION_ENABLE_FILE_TRANSFER) {
                         self.send_login_error("No permission of file transfer")
                             .await;
                         sleep(1.).await;
@@ -2398,7 +2606,7 @@ impl Connection {
                     self.file_transfer = Some((ft.dir, ft.show_hidden));
                 }
                 Some(login_request::Union::ViewCamera(_vc)) => {
-                    if !Self::permission(keys::OPTION_ENABLE_CAMERA, &self.control_permissions) {
+                    if !self.peer_permission(keys::OPTION_ENABLE_CAMERA) {
                         self.send_login_error("No permission of viewing camera")
                             .await;
                         sleep(1.).await;
@@ -2407,7 +2615,7 @@ impl Connection {
                     self.view_camera = true;
                 }
                 Some(login_request::Union::Terminal(terminal)) => {
-                    if !Self::permission(keys::OPTION_ENABLE_TERMINAL, &self.control_permissions) {
+                    if !self.peer_permission(keys::OPTION_ENABLE_TERMINAL) {
                         self.send_login_error("No permission of terminal").await;
                         sleep(1.).await;
                         return false;
@@ -2428,7 +2636,7 @@ impl Connection {
                     self.terminal_service_id = terminal.service_id;
                 }
                 Some(login_request::Union::PortForward(mut pf)) => {
-                    if !Self::permission(keys::OPTION_ENABLE_TUNNEL, &self.control_permissions) {
+                    if !self.peer_permission(keys::OPTION_ENABLE_TUNNEL) {
                         self.send_login_error("No permission of IP tunneling").await;
                         sleep(1.).await;
                         return false;
@@ -2531,8 +2739,22 @@ impl Connection {
                 crate::get_builtin_option(keys::OPTION_ALLOW_LOGON_SCREEN_PASSWORD) == "Y"
                     && is_logon();
 
+            // The schedule may have changed since `on_open`, e.g. a connection opened at 17:59.
+            let schedule_action = access_schedule::current_action();
+            if schedule_action == ScheduleAction::Deny {
+                self.send_login_error(access_schedule::LOGIN_MSG_ACCESS_SCHEDULE_DENIED)
+                    .await;
+                Self::post_alarm_audit(
+                    AlarmAuditType::AccessSchedule,
+                    json!({ "ip": self.ip, "id": lr.my_id }),
+                );
+                sleep(1.).await;
+                return false;
+            }
+
             if (password::approve_mode() == ApproveMode::Click && !allow_logon_screen_password)
                 || password::approve_mode() == ApproveMode::Both && !password::has_valid_password()
+                || schedule_action == ScheduleAction::Click
             {
                 #[cfg(not(any(target_os = "android", target_os = "ios")))]
                 if should_use_terminal_os_login_scope(self.terminal, &lr.os_login.username) {
@@ -2892,42 +3114,66 @@ impl Connection {
                 }
                 Some(message::Union::Clipboard(cb)) => {
                     if self.clipboard {
-                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                        update_clipboard(vec![cb], ClipboardSide::Host);
-                        // ios as the controlled side is actually not supported for now.
-                        // The following code is only used to preserve the logic of handling text clipboard on mobile.
-                        #[cfg(target_os = "ios")]
-                        {
-                            let content = if cb.compress {
-                                hbb_common::compress::decompress(&cb.content)
-                            } else {
-                                cb.content.into()
-                            };
-                            if let Ok(content) = String::from_utf8(content) {
-                                let data =
-                                    HashMap::from([("name", "clipboard"), ("content", &content)]);
-                                if let Ok(data) = serde_json::to_string(&data) {
-                                    let _ = crate::flutter::push_global_event(
-                                        crate::flutter::APP_TYPE_MAIN,
-                                        data,
-                                    );
+                        let cb = self
+                            .apply_clipboard_rules(clipboard_dlp::Direction::Incoming, vec![cb])
+                            .clipboards
+                            .pop();
+                        if let Some(cb) = cb {
+                            #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            self.plugin_listen_clipboard("receive", std::slice::from_ref(&cb));
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            update_clipboard(vec![cb], ClipboardSide::Host);
+                            // ios as the controlled side is actually not supported for now.
+                            // The following code is only used to preserve the logic of handling text clipboard on mobile.
+                            #[cfg(target_os = "ios")]
+                            {
+                                let content = if cb.compress {
+                                    hbb_common::compress::decompress(&cb.content)
+                                } else {
+                                    cb.content.into()
+                                };
+                                if let Ok(content) = String::from_utf8(content) {
+                                    let data = HashMap::from([
+                                        ("name", "clipboard"),
+                                        ("content", &content),
+                                    ]);
+                                    if let Ok(data) = serde_json::to_string(&data) {
+                                        let _ = crate::flutter::push_global_event(
+                                            crate::flutter::APP_TYPE_MAIN,
+                                            data,
+                                        );
+                                    }
                                 }
                             }
+                            #[cfg(target_os = "android")]
+                            crate::clipboard::handle_msg_clipboard(cb);
                         }
-                        #[cfg(target_os = "android")]
-                        crate::clipboard::handle_msg_clipboard(cb);
                     }
                 }
-                Some(message::Union::MultiClipboards(_mcb)) => {
-                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                Some(message::Union::MultiClipboards(mut _mcb)) => {
+                    // Same as `Clipboard`, the rules do not evaluate or audit data not accepted.
                     if self.clipboard {
-                        update_clipboard(_mcb.clipboards, ClipboardSide::Host);
+                        _mcb.clipboards = self
+                            .apply_clipboard_rules(
+                                clipboard_dlp::Direction::Incoming,
+                                _mcb.clipboards,
+                            )
+                            .clipboards;
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        if !_mcb.clipboards.is_empty() {
+                            #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                            self.plugin_listen_clipboard("receive", &_mcb.clipboards);
+                            update_clipboard(_mcb.clipboards, ClipboardSide::Host);
+                        }
+                        #[cfg(target_os = "android")]
+                        if !_mcb.clipboards.is_empty() {
+                            crate::clipboard::handle_msg_multi_clipboards(_mcb);
+                        }
                     }
-                    #[cfg(target_os = "android")]
-                    crate::clipboard::handle_msg_multi_clipboards(_mcb);
                 }
                 #[cfg(any(target_os = "windows", feature = "unix-file-copy-paste"))]
-                Some(message::Union::Cliprdr(clip)) => {
+                Some(message::Union::Cliprdr(clip)) if clipboard_dlp::is_file_allowed() => {
                     if let Some(cliprdr::Union::Files(files)) = &clip.union {
                         self.post_file_audit(
                             FileAuditType::RemoteReceive,
@@ -3170,10 +3416,17 @@ impl Connection {
                                     total_size: r.total_size,
                                     conn_id: self.inner.id(),
                                 });
+                                let files =
+                                    Self::get_files_for_audit(fs::JobType::Generic, r.files);
+                                #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                                self.plugin_listen_file_transfer_start(
+                                    "receive", r.id, &r.path, &files,
+                                );
                                 self.post_file_audit(
                                     FileAuditType::RemoteReceive,
                                     &r.path,
-                                    Self::get_files_for_audit(fs::JobType::Generic, r.files),
+                                    files,
                                     json!({}),
                                 );
                                 self.file_transferred = true;
@@ -3272,6 +3525,12 @@ impl Connection {
                         });
                     }
                     Some(file_response::Union::Done(d)) => {
+                        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        self.plugin_listen_event(
+                            crate::plugin::EVENT_ON_FILE_TRANSFER_DONE,
+                            json!({ "direction": "receive", "id": d.id, "file_num": d.file_num, "error": "" }),
+                        );
                         self.send_fs(ipc::FS::WriteDone {
                             id: d.id,
                             file_num: d.file_num,
@@ -3286,6 +3545,12 @@ impl Connection {
                         is_resume: d.is_resume,
                     }),
                     Some(file_response::Union::Error(e)) => {
+                        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        self.plugin_listen_event(
+                            crate::plugin::EVENT_ON_FILE_TRANSFER_DONE,
+                            json!({ "direction": "receive", "id": e.id, "file_num": e.file_num, "error": e.error }),
+                        );
                         self.send_fs(ipc::FS::WriteError {
                             id: e.id,
                             file_num: e.file_num,
@@ -3332,10 +3597,16 @@ impl Connection {
                         self.update_auto_disconnect_timer();
                     }
                     Some(misc::Union::VideoReceived(_)) => {
-                        video_service::notify_video_frame_fetched_by_conn_id(
-                            self.inner.id,
-                            Some(Instant::now().into()),
-                        );
+                        if self.video_ack_required {
+                            video_service::notify_video_frame_fetched_by_conn_id(
+                                self.inner.id,
+                                Some(Instant::now().into()),
+                            );
+                        }
+                        video_service::VIDEO_QOS
+                            .lock()
+                            .unwrap()
+                            .on_video_acked(self.inner.id);
                     }
                     Some(misc::Union::RestartRemoteDevice(_)) => {
                         #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -3402,6 +3673,12 @@ impl Connection {
                     Some(misc::Union::ChangeDisplayResolution(dr)) => {
                         self.change_resolution(Some(dr.display as _), &dr.resolution)
                     }
+                    Some(misc::Union::EnableLosslessTiles(on)) => {
+                        // Capture the whole screen again for the tiles.
+                        if video_service::set_lossless_tiles(self.inner.id(), on) && on {
+                            self.refresh_video_display(None);
+                        }
+                    }
                     #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
                     Some(misc::Union::PluginRequest(p)) => {
@@ -3413,10 +3690,22 @@ impl Connection {
                         .lock()
                         .unwrap()
                         .user_auto_adjust_fps(self.inner.id(), fps),
-                    Some(misc::Union::ClientRecordStatus(status)) => video_service::VIDEO_QOS
-                        .lock()
-                        .unwrap()
-                        .user_record(self.inner.id(), status),
+                    Some(misc::Union::ClientRecordStatus(status)) => {
+                        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        self.plugin_listen_event(
+                            if status {
+                                crate::plugin::EVENT_ON_RECORDING_START
+                            } else {
+                                crate::plugin::EVENT_ON_RECORDING_STOP
+                            },
+                            json!({}),
+                        );
+                        video_service::VIDEO_QOS
+                            .lock()
+                            .unwrap()
+                            .user_record(self.inner.id(), status)
+                    }
                     #[cfg(windows)]
                     Some(misc::Union::SelectedSid(sid)) => {
                         if let Some(current_process_sid) =
@@ -4705,12 +4994,11 @@ impl Connection {
                     .await;
 
                 // Post audit for file transfer
-                self.post_file_audit(
-                    FileAuditType::RemoteSend,
-                    &path_str,
-                    Self::get_files_for_audit(fs::JobType::Generic, file_entries),
-                    json!({}),
-                );
+                let files = Self::get_files_for_audit(fs::JobType::Generic, file_entries);
+                #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                self.plugin_listen_file_transfer_start("send", id, &path_str, &files);
+                self.post_file_audit(FileAuditType::RemoteSend, &path_str, files, json!({}));
 
                 // CM will handle the actual file reading and send blocks via IPC
                 self.file_transferred = true;
@@ -4760,6 +5048,13 @@ impl Connection {
             return;
         }
 
+        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        self.plugin_listen_event(
+            crate::plugin::EVENT_ON_FILE_TRANSFER_DONE,
+            json!({ "direction": "send", "id": id, "file_num": file_num, "error": "" }),
+        );
+
         // Forward done message to client
         let mut done = FileTransferDone::new();
         done.id = id;
@@ -4783,6 +5078,13 @@ impl Connection {
             return;
         }
 
+        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        self.plugin_listen_event(
+            crate::plugin::EVENT_ON_FILE_TRANSFER_DONE,
+            json!({ "direction": "send", "id": id, "file_num": file_num, "error": &err }),
+        );
+
         // Forward error to client
         self.send(fs::new_error(id, err, file_num)).await;
     }
@@ -4824,6 +5126,7 @@ impl Connection {
     async fn process_new_read_job(&mut self, mut job: fs::TransferJob, path: String) {
         let files = job.files().to_owned();
         let job_type = job.r#type;
+        let _id = job.id;
         self.send(fs::new_dir(job.id, path.clone(), files.clone()))
             .await;
         job.is_remote = true;
@@ -4835,12 +5138,11 @@ impl Connection {
         } else {
             path
         };
-        self.post_file_audit(
-            FileAuditType::RemoteSend,
-            &audit_path,
-            Self::get_files_for_audit(job_type, files),
-            json!({}),
-        );
+        let files = Self::get_files_for_audit(job_type, files);
+        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        self.plugin_listen_file_transfer_start("send", _id, &audit_path, &files);
+        self.post_file_audit(FileAuditType::RemoteSend, &audit_path, files, json!({}));
     }
 
     async fn handle_all_files_result(
@@ -5106,8 +5408,36 @@ impl Connection {
         false
     }
 
+    fn report_telemetry(&mut self) {
+        let elapsed = self.telemetry_instant.elapsed().as_secs_f32().max(0.001);
+        self.telemetry_instant = Instant::now();
+        let (frames, bytes) = std::mem::take(&mut self.telemetry_video_sent);
+        let id = self.inner.id();
+        let video_qos = video_service::VIDEO_QOS.lock().unwrap();
+        let stats = crate::telemetry::SessionStats {
+            session: crate::telemetry::server_session(id),
+            side: "server",
+            peer_id: self.lr.my_id.clone(),
+            codec: format!("{:?}", scrap::codec::Encoder::negotiated_codec()),
+            direct: Some(!self.relay),
+            fps: (frames as f32 / elapsed).round() as _,
+            bitrate_kbps: (bytes as f32 * 8.0 / 1000.0 / elapsed).round() as _,
+            target_bitrate_kbps: Some(video_qos.user_bitrate(id)),
+            encode_ms: video_service::encode_ms(video_qos.user_tier(id)),
+            rtt_ms: video_qos.user_rtt(id),
+            delay_ms: Some(self.network_delay),
+            ..Default::default()
+        };
+        drop(video_qos);
+        crate::telemetry::report(stats);
+    }
+
     #[cfg(feature = "unix-file-copy-paste")]
     async fn handle_file_clip(&mut self, clip: clipboard::ClipboardFile) {
+        // Same as the windows `ipc::Data::ClipboardFile` path, files are not sent if not allowed.
+        if !clipboard_dlp::is_file_allowed() {
+            return;
+        }
         let is_stopping_allowed = clip.is_stopping_allowed();
         let file_transfer_enabled = self.file_transfer_enabled();
         let stop = is_stopping_allowed && !file_transfer_enabled;
@@ -5199,6 +5529,19 @@ impl Connection {
             // unreacheable, but keep it for safety
             bail!("Terminal user token is not set.");
         };
+        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        match &action.union {
+            Some(terminal_action::Union::Open(open)) => self.plugin_listen_event(
+                crate::plugin::EVENT_ON_TERMINAL_OPEN,
+                json!({ "terminal_id": open.terminal_id }),
+            ),
+            Some(terminal_action::Union::Close(close)) => self.plugin_listen_event(
+                crate::plugin::EVENT_ON_TERMINAL_CLOSE,
+                json!({ "terminal_id": close.terminal_id }),
+            ),
+            _ => {}
+        }
         let mut proxy = terminal_service::TerminalServiceProxy::new(
             self.terminal_service_id.clone(),
             Some(self.terminal_persistent),
@@ -5500,6 +5843,8 @@ pub enum AlarmAuditType {
     ExceedIPv6PrefixAttempts = 6,
     TerminalOsLoginBackoff = 7,
     TerminalOsLoginConcurrency = 8,
+    AccessSchedule = 9,
+    ClipboardBlocked = 10,
 }
 
 pub enum FileAuditType {
@@ -5930,10 +6275,14 @@ mod raii {
                 shutdown_hooks::add_shutdown_hook(connection_shutdown_hook);
             });
             if conn_type == AuthConnType::Remote || conn_type == AuthConnType::ViewCamera {
-                video_service::VIDEO_QOS
-                    .lock()
-                    .unwrap()
-                    .on_connection_open(conn_id);
+                let mut video_qos = video_service::VIDEO_QOS.lock().unwrap();
+                video_qos.on_connection_open(conn_id);
+                let decoding = lr
+                    .option
+                    .as_ref()
+                    .and_then(|o| o.supported_decoding.as_ref());
+                video_qos.user_support_scaling(conn_id, decoding.is_some_and(|d| d.scaled_video));
+                video_qos.user_support_hdr(conn_id, decoding.is_some_and(|d| d.hdr));
             }
             Self(conn_id, conn_type)
         }
@@ -6056,6 +6405,8 @@ mod raii {
                     .lock()
                     .unwrap()
                     .on_connection_close(self.0);
+                video_service::set_lossless_tiles(self.0, false);
+                crate::telemetry::remove(&crate::telemetry::server_session(self.0));
             }
             // Clear per-connection state to avoid stale behavior if conn ids are reused.
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -0,0 +1,359 @@
+use cidr_utils::cidr::IpCidr;
+use hbb_common::{
+    config::Config,
+    log, timeout,
+    tokio::{self, net::lookup_host, time::Instant},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::Duration,
+};
+
+// Entries of option `whitelist` are separated by ",". Supported entries:
+// - `0.0.0.0`: any address
+// - an IPv4 / IPv6 address or CIDR prefix, e.g. `192.168.1.0/24`, `2001:db8::/32`
+// - a hostname, e.g. `office.example.com`, resolved periodically in the background,
+//   a denied hostname which can not be resolved denies all addresses
+// - any of the above prefixed with `!` to deny
+// Deny entries take precedence over allow entries.
+// If there are only deny entries, all other addresses are allowed.
+pub const OPTION_WHITELIST: &str = "whitelist";
+
+const ANY: &str = "0.0.0.0";
+const DENY_PREFIX: char = '!';
+const RESOLVE_TTL: Duration = Duration::from_secs(300);
+const RESOLVE_RETRY: Duration = Duration::from_secs(30);
+const RESOLVE_TIMEOUT_MS: u64 = 3_000;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+lazy_static::lazy_static! {
+    static ref RESOLVED_HOSTS: Mutex<HashMap<String, Resolved>> = Default::default();
+}
+
+// The addresses of a host, refreshed by `start`, so no lookup is on the accept path.
+#[derive(Debug, Clone)]
+struct Resolved {
+    tm: Instant, // Of the last attempt
+    failed: bool,
+    ips: Option<Vec<IpAddr>>, // The last successful result
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    Any,
+    Cidr(IpCidr),
+    Host(String),
+    // Never matches, but still makes the list restrictive, so a typo does not open the door.
+    Invalid,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    raw: String,
+    deny: bool,
+    target: Target,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    // The entry which decides the result, empty if no entry matches.
+    pub entry: String,
+}
+
+fn is_hostname(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 253
+        && s.chars().any(|c| c.is_ascii_alphabetic())
+        && s.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+impl Entry {
+    pub fn parse(s: &str) -> Option<Self> {
+        let raw = s.trim();
+        let (deny, v) = match raw.strip_prefix(DENY_PREFIX) {
+            Some(v) => (true, v.trim()),
+            None => (false, raw),
+        };
+        let target = if v == ANY {
+            Target::Any
+        } else if let Ok(cidr) = IpCidr::from_str(v) {
+            Target::Cidr(cidr)
+        } else if is_hostname(v) {
+            Target::Host(v.to_lowercase())
+        } else {
+            return None;
+        };
+        Some(Self {
+            raw: raw.to_owned(),
+            deny,
+            target,
+        })
+    }
+
+    fn contains(&self, ip: IpAddr, hosts: &HashMap<String, Resolved>) -> bool {
+        match &self.target {
+            Target::Any => true,
+            Target::Cidr(cidr) => cidr.contains(ip),
+            // A deny host which is not resolved matches all addresses,
+            // otherwise a dns failure would let the denied host in.
+            Target::Host(host) => match hosts.get(host).and_then(|r| r.ips.as_ref()) {
+                Some(ips) => ips.contains(&ip),
+                None => self.deny,
+            },
+            Target::Invalid => false,
+        }
+    }
+}
+
+pub fn parse(list: &str) -> Vec<Entry> {
+    list.split(',')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            Entry::parse(x).unwrap_or_else(|| {
+                log::warn!("Invalid whitelist entry: {}", x);
+                Entry {
+                    raw: x.to_owned(),
+                    deny: false,
+                    target: Target::Invalid,
+                }
+            })
+        })
+        .collect()
+}
+
+fn hosts(entries: &[Entry]) -> HashSet<String> {
+    entries
+        .iter()
+        .filter_map(|e| match &e.target {
+            Target::Host(host) => Some(host.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+async fn lookup(host: String) -> Option<Vec<IpAddr>> {
+    match timeout(RESOLVE_TIMEOUT_MS, lookup_host((host.as_str(), 0))).await {
+        Ok(Ok(addrs)) => {
+            let ips: Vec<IpAddr> = addrs.map(|a| normalize(a.ip())).collect();
+            log::debug!("Whitelist host {} resolved to {:?}", host, ips);
+            Some(ips)
+        }
+        Ok(Err(e)) => {
+            log::warn!("Failed to resolve whitelist host {}: {}", host, e);
+            None
+        }
+        Err(_) => {
+            log::warn!("Timeout to resolve whitelist host {}", host);
+            None
+        }
+    }
+}
+
+// Resolves the hosts which are new or due, and forgets the hosts removed from the list.
+// Keep the previous result if the resolution fails, a dns hiccup should not lock out the office.
+async fn refresh<F, Fut>(
+    cache: &Mutex<HashMap<String, Resolved>>,
+    hosts: HashSet<String>,
+    resolve: F,
+) where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Option<Vec<IpAddr>>>,
+{
+    let due: Vec<String> = {
+        let mut cache = cache.lock().unwrap();
+        cache.retain(|host, _| hosts.contains(host));
+        hosts
+            .into_iter()
+            .filter(|host| match cache.get(host) {
+                Some(r) => r.tm.elapsed() >= if r.failed { RESOLVE_RETRY } else { RESOLVE_TTL },
+                None => true,
+            })
+            .collect()
+    };
+    for host in due {
+        let ips = resolve(host.clone()).await;
+        let mut cache = cache.lock().unwrap();
+        let resolved = cache.entry(host).or_insert(Resolved {
+            tm: Instant::now(),
+            failed: false,
+            ips: None,
+        });
+        resolved.tm = Instant::now();
+        resolved.failed = ips.is_none();
+        if ips.is_some() {
+            resolved.ips = ips;
+        }
+    }
+}
+
+// Resolves the hosts of the list in the background.
+pub fn start() {
+    tokio::spawn(async {
+        loop {
+            let hosts = hosts(&parse(&Config::get_option(OPTION_WHITELIST)));
+            refresh(&RESOLVED_HOSTS, hosts, lookup).await;
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+// Connections to a dual-stack listener may come with an ipv4-mapped ipv6 address.
+pub(super) fn normalize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+fn check(entries: &[Entry], ip: IpAddr, hosts: &HashMap<String, Resolved>) -> Decision {
+    let ip = normalize(ip);
+    for entry in entries.iter().filter(|e| e.deny) {
+        if entry.contains(ip, hosts) {
+            return Decision {
+                allowed: false,
+                entry: entry.raw.clone(),
+            };
+        }
+    }
+    let mut has_allow_entry = false;
+    for entry in entries.iter().filter(|e| !e.deny) {
+        has_allow_entry = true;
+        if entry.contains(ip, hosts) {
+            return Decision {
+                allowed: true,
+                entry: entry.raw.clone(),
+            };
+        }
+    }
+    Decision {
+        allowed: !has_allow_entry,
+        entry: "".to_owned(),
+    }
+}
+
+// The hosts are checked against the addresses resolved by `start`.
+pub fn check_address(ip: IpAddr) -> Decision {
+    let entries = parse(&Config::get_option(OPTION_WHITELIST));
+    check(&entries, ip, &RESOLVED_HOSTS.lock().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    fn check_no_hosts(list: &str, addr: &str) -> Decision {
+        check(&parse(list), ip(addr), &Default::default())
+    }
+
+    // Resolves `office.example.com` only, and counts the lookups.
+    async fn refresh_stub(
+        cache: &Mutex<HashMap<String, Resolved>>,
+        list: &str,
+        office: Option<&str>,
+    ) -> usize {
+        let lookups = std::sync::atomic::AtomicUsize::new(0);
+        refresh(cache, hosts(&parse(list)), |host| {
+            lookups.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let ips = (host == "office.example.com")
+                .then(|| office.map(|x| vec![ip(x)]))
+                .flatten();
+            async move { ips }
+        })
+        .await;
+        lookups.into_inner()
+    }
+
+    #[test]
+    fn test_parse() {
+        let entries = parse(
+            " 192.168.1.0/24,,!192.168.1.13, 2001:db8::/32,office.example.com, bad entry,-x.com",
+        );
+        assert_eq!(entries.len(), 6);
+        assert!(entries[1].deny);
+        assert!(matches!(entries[3].target, Target::Host(_)));
+        assert!(matches!(entries[4].target, Target::Invalid));
+        assert!(Entry::parse("192.168.1").is_none());
+        assert!(Entry::parse("!0.0.0.0").unwrap().deny);
+    }
+
+    #[test]
+    fn test_check() {
+        let list = "192.168.1.0/24,!192.168.1.13,2001:db8::/32";
+        assert!(check_no_hosts(list, "192.168.1.2").allowed);
+        let d = check_no_hosts(list, "192.168.1.13");
+        assert!(!d.allowed);
+        assert_eq!(d.entry, "!192.168.1.13");
+        assert!(!check_no_hosts(list, "10.0.0.1").allowed);
+        assert!(check_no_hosts(list, "2001:db8::1").allowed);
+        assert!(!check_no_hosts(list, "2001:db9::1").allowed);
+        assert!(check_no_hosts(list, "::ffff:192.168.1.2").allowed);
+    }
+
+    #[test]
+    fn test_check_deny_only() {
+        assert!(check_no_hosts("!10.0.0.0/8", "192.168.1.2").allowed);
+        assert!(!check_no_hosts("!10.0.0.0/8", "10.1.2.3").allowed);
+        assert!(check_no_hosts("", "10.1.2.3").allowed);
+        assert!(check_no_hosts(",", "10.1.2.3").allowed);
+        assert!(check_no_hosts("1.1.1.1,0.0.0.0", "10.1.2.3").allowed);
+        assert!(!check_no_hosts("1.1.1", "10.1.2.3").allowed);
+    }
+
+    #[test]
+    fn test_unresolved_host() {
+        let d = check_no_hosts("!office.example.com", "10.1.2.3");
+        assert!(!d.allowed);
+        assert_eq!(d.entry, "!office.example.com");
+        assert!(check_no_hosts("office.example.com,10.1.2.3", "10.1.2.3").allowed);
+        assert!(!check_no_hosts("office.example.com", "10.1.2.3").allowed);
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let cache = Mutex::new(HashMap::new());
+        let list = "office.example.com,!other.example.com";
+        assert_eq!(refresh_stub(&cache, list, Some("10.1.2.3")).await, 2);
+        let decide = |addr: &str| check(&parse(list), ip(addr), &cache.lock().unwrap());
+        // The unresolved deny host denies all
+        assert!(!decide("10.1.2.3").allowed);
+        assert!(!decide("10.9.9.9").allowed);
+        let list = "office.example.com";
+        assert!(check(&parse(list), ip("10.1.2.3"), &cache.lock().unwrap()).allowed);
+        assert!(!check(&parse(list), ip("10.1.2.4"), &cache.lock().unwrap()).allowed);
+
+        // Not due yet
+        assert_eq!(refresh_stub(&cache, list, Some("10.1.2.4")).await, 0);
+        // The removed host is forgotten
+        assert!(!cache.lock().unwrap().contains_key("other.example.com"));
+
+        // A failure keeps the previous result
+        cache
+            .lock()
+            .unwrap()
+            .get_mut("office.example.com")
+            .unwrap()
+            .tm -= RESOLVE_TTL;
+        assert_eq!(refresh_stub(&cache, list, None).await, 1);
+        assert!(check(&parse(list), ip("10.1.2.3"), &cache.lock().unwrap()).allowed);
+        assert!(cache.lock().unwrap()["office.example.com"].failed);
+    }
+}
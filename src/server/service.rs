@@ -188,6 +188,16 @@ impl<T: Subscriber + From<ConnInner>> ServiceTmpl<T> {
         self.0.read().unwrap().has_subscribes()
     }
 
+    // Including the new subscribers which have not received the snapshot yet
+    pub fn subscribe_ids(&self) -> HashSet<i32> {
+        let lock = self.0.read().unwrap();
+        lock.subscribes
+            .keys()
+            .chain(lock.new_subscribes.keys())
+            .copied()
+            .collect()
+    }
+
     pub fn snapshot<F>(&self, callback: F) -> ResultType<()>
     where
         F: FnMut(ServiceSwap<T>) -> ResultType<()>,
@@ -228,16 +238,23 @@ impl<T: Subscriber + From<ConnInner>> ServiceTmpl<T> {
         }
     }
 
-    pub fn send_video_frame(&self, msg: Message) -> HashSet<i32> {
-        self.send_video_frame_shared(Arc::new(msg))
+    pub fn send_video_frame(&self, msg: Message, filter: impl Fn(i32) -> bool) -> HashSet<i32> {
+        self.send_video_frame_shared(Arc::new(msg), filter)
     }
 
-    pub fn send_video_frame_shared(&self, msg: Arc<Message>) -> HashSet<i32> {
+    // `filter` selects the subscribers of the frame, e.g. the connections of a quality tier
+    pub fn send_video_frame_shared(
+        &self,
+        msg: Arc<Message>,
+        filter: impl Fn(i32) -> bool,
+    ) -> HashSet<i32> {
         let mut conn_ids = HashSet::new();
         let mut lock = self.0.write().unwrap();
         for s in lock.subscribes.values_mut() {
-            s.send(msg.clone());
-            conn_ids.insert(s.id());
+            if filter(s.id()) {
+                s.send(msg.clone());
+                conn_ids.insert(s.id());
+            }
         }
         conn_ids
     }
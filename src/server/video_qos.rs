@@ -1,7 +1,8 @@
 use super::*;
+use estimator::BandwidthEstimator;
 use scrap::codec::{Quality, BR_BALANCED, BR_BEST, BR_SPEED};
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     time::{Duration, Instant},
 };
 
@@ -25,8 +26,35 @@ adjust between FPS and ratio:
 
 delay:
     use delay minus RTT as the actual network delay
+
+bandwidth estimation:
+    each connection estimates the bandwidth with the delay gradient of the acknowledged frames and the send queue delay,
+    see estimator.rs
+    the estimate replaces the 3 seconds ratio adjustment once available, and is applied every 250ms
+    when the estimate is below the minimum ratio of the quality, the ratio goes lower and fps is reduced as well
+
+tiers:
+    with "enable-tiered-encoding", the slow users are moved to the low tier, which has its own encoder,
+    so that they do not degrade the others
+    each tier adjusts its fps and ratio with its own users as above
+    the tiers are checked every 10 seconds, the video services are restarted when they change
+
+scale:
+    when the estimate of the high tier can not afford the minimum ratio for 3 seconds, the frames are encoded
+    at a lower resolution, 2/3 and then 1/2, and the client scales them up to the original size
+    the resolution goes back up when the estimate affords it with a margin for 10 seconds
+    only when all users and displays support it, the video services are restarted when it changes
+
+hdr:
+    opt-in with "enable-hdr", an HDR display is captured in 16-bit float and encoded in 10-bit VP9
+    or AV1, the clients tone map it to SDR if they can not display it, see scrap/src/common/hdr.rs
+    only when all users support it, the video services are restarted when it changes
 */
 
+mod estimator;
+#[cfg(test)]
+mod sim;
+
 // Constants
 pub const FPS: u32 = 30;
 pub const MIN_FPS: u32 = 1;
@@ -43,6 +71,22 @@ const HISTORY_DELAY_LEN: usize = 2;
 const ADJUST_RATIO_INTERVAL: usize = 3; // Adjust quality ratio every 3 seconds
 const DYNAMIC_SCREEN_THRESHOLD: usize = 2; // Allow increase quality ratio if encode more than 2 times in one second
 const DELAY_THRESHOLD_150MS: u32 = 150; // 150ms is the threshold for good network condition
+const APPLY_ESTIMATE_INTERVAL: Duration = Duration::from_millis(250);
+
+const OPTION_ENABLE_TIERED_ENCODING: &str = "enable-tiered-encoding";
+const UPDATE_TIERS_INTERVAL: Duration = Duration::from_secs(10);
+const LOW_TIER_DELAY: u32 = 300; // Move to the low tier if the delay is higher
+const LOW_TIER_KBPS_FACTOR: f32 = 0.5; // Move to the low tier if the estimate is lower than the best one by this factor
+const HIGH_TIER_KBPS_FACTOR: f32 = 0.8; // Move back if the estimate is higher than the best one by this factor
+const LOW_TIER_INIT_RATIO_FACTOR: f32 = 0.5;
+
+const OPTION_ENABLE_ADAPTIVE_SCALING: &str = "enable-adaptive-scaling";
+const SCALES: [f32; 3] = [1.0, 2.0 / 3.0, 0.5];
+const SCALE_DOWN_DELAY: Duration = Duration::from_secs(3);
+const SCALE_UP_DELAY: Duration = Duration::from_secs(10);
+const SCALE_UP_MARGIN: f32 = 1.5; // Scale up if the estimate affords the higher resolution by this factor
+
+const OPTION_ENABLE_HDR: &str = "enable-hdr";
 
 #[derive(Default, Debug, Clone)]
 struct UserDelay {
@@ -93,51 +137,114 @@ struct UserData {
     quality: Option<(i64, Quality)>, // (time, quality)
     delay: UserDelay,
     record: bool,
+    estimator: BandwidthEstimator,
+    tier: Tier,
+    support_scaling: bool, // The client scales the frames up
+    support_hdr: bool,     // The client decodes the 10-bit frames
 }
 
 #[derive(Default, Debug, Clone)]
 struct DisplayData {
     send_counter: usize, // Number of times encode during period
     support_changing_quality: bool,
+    support_tiers: bool,
+    support_scaling: bool,
 }
 
-// Main QoS controller structure
-pub struct VideoQoS {
+// Quality tier of the users, each tier is encoded separately
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tier {
+    #[default]
+    High,
+    Low,
+}
+
+// Encoding state of a tier
+#[derive(Debug, Clone)]
+struct TierData {
     fps: u32,
     ratio: f32,
-    users: HashMap<i32, UserData>,
-    displays: HashMap<String, DisplayData>,
     bitrate_store: u32,
+    bitrate_ratio: f32, // The ratio of the stored bitrate
     adjust_ratio_instant: Instant,
+    apply_estimate_instant: Option<Instant>,
+    estimate_fps: Option<u32>, // Lower fps when the estimate is below the minimum ratio
+}
+
+impl Default for TierData {
+    fn default() -> Self {
+        TierData {
+            fps: FPS,
+            ratio: BR_BALANCED,
+            bitrate_store: 0,
+            bitrate_ratio: BR_BALANCED,
+            adjust_ratio_instant: Instant::now(),
+            apply_estimate_instant: None,
+            estimate_fps: None,
+        }
+    }
+}
+
+// Main QoS controller structure
+pub struct VideoQoS {
+    tiers: [TierData; 2],
+    users: HashMap<i32, UserData>,
+    displays: HashMap<String, DisplayData>,
     abr_config: bool,
+    tiers_config: bool,
     new_user_instant: Instant,
+    update_tiers_instant: Instant,
+    tiers_version: usize, // Increased when the tier of any user changes
+    scaling_config: bool,
+    scale_index: usize,
+    scale_pending: Option<(usize, Instant)>, // The scale to change to, and since when
+    hdr_config: bool,
 }
 
 impl Default for VideoQoS {
     fn default() -> Self {
         VideoQoS {
-            fps: FPS,
-            ratio: BR_BALANCED,
+            tiers: Default::default(),
             users: Default::default(),
             displays: Default::default(),
-            bitrate_store: 0,
-            adjust_ratio_instant: Instant::now(),
             abr_config: true,
+            tiers_config: false,
             new_user_instant: Instant::now(),
+            update_tiers_instant: Instant::now(),
+            tiers_version: 0,
+            scaling_config: true,
+            scale_index: 0,
+            scale_pending: None,
+            hdr_config: false,
         }
     }
 }
 
 // Basic functionality
 impl VideoQoS {
+    #[inline]
+    fn tier(&self, tier: Tier) -> &TierData {
+        &self.tiers[tier as usize]
+    }
+
+    #[inline]
+    fn tier_mut(&mut self, tier: Tier) -> &mut TierData {
+        &mut self.tiers[tier as usize]
+    }
+
+    #[inline]
+    fn tier_users(&self, tier: Tier) -> impl Iterator<Item = &UserData> {
+        self.users.values().filter(move |u| u.tier == tier)
+    }
+
     // Calculate seconds per frame based on current FPS
-    pub fn spf(&self) -> Duration {
-        Duration::from_secs_f32(1. / (self.fps() as f32))
+    pub fn spf(&self, tier: Tier) -> Duration {
+        Duration::from_secs_f32(1. / (self.fps(tier) as f32))
     }
 
     // Get current FPS within valid range
-    pub fn fps(&self) -> u32 {
-        let fps = self.fps;
+    pub fn fps(&self, tier: Tier) -> u32 {
+        let fps = self.tier(tier).fps;
         if fps >= MIN_FPS && fps <= MAX_FPS {
             fps
         } else {
@@ -146,21 +253,61 @@ impl VideoQoS {
     }
 
     // Store bitrate for later use
-    pub fn store_bitrate(&mut self, bitrate: u32) {
-        self.bitrate_store = bitrate;
+    pub fn store_bitrate(&mut self, tier: Tier, bitrate: u32) {
+        let data = self.tier_mut(tier);
+        data.bitrate_store = bitrate;
+        data.bitrate_ratio = data.ratio;
     }
 
     // Get stored bitrate
-    pub fn bitrate(&self) -> u32 {
-        self.bitrate_store
+    pub fn bitrate(&self, tier: Tier) -> u32 {
+        self.tier(tier).bitrate_store
     }
 
     // Get current bitrate ratio with bounds checking
-    pub fn ratio(&mut self) -> f32 {
-        if self.ratio < BR_MIN_HIGH_RESOLUTION || self.ratio > BR_MAX {
-            self.ratio = BR_BALANCED;
+    pub fn ratio(&mut self, tier: Tier) -> f32 {
+        let data = self.tier_mut(tier);
+        if data.ratio < BR_MIN_HIGH_RESOLUTION || data.ratio > BR_MAX {
+            data.ratio = BR_BALANCED;
         }
-        self.ratio
+        data.ratio
+    }
+
+    pub fn user_tier(&self, id: i32) -> Tier {
+        self.users.get(&id).map(|u| u.tier).unwrap_or_default()
+    }
+
+    pub fn user_rtt(&self, id: i32) -> Option<u32> {
+        self.users
+            .get(&id)
+            .and_then(|u| u.delay.rtt_calculator.get_rtt())
+    }
+
+    // The stored bitrate of the user's tier
+    pub fn user_bitrate(&self, id: i32) -> u32 {
+        self.bitrate(self.user_tier(id))
+    }
+
+    pub fn tier_conn_ids(&self, tier: Tier) -> HashSet<i32> {
+        self.users
+            .iter()
+            .filter(|u| u.1.tier == tier)
+            .map(|u| *u.0)
+            .collect()
+    }
+
+    pub fn tiers_version(&self) -> usize {
+        self.tiers_version
+    }
+
+    // The encode scale of the frames, shared by the tiers as they encode the same yuv data
+    pub fn scale(&self) -> f32 {
+        SCALES[self.scale_index]
+    }
+
+    // Encode the HDR displays in 10-bit, only when all users support it
+    pub fn hdr(&self) -> bool {
+        self.hdr_config && !self.users.is_empty() && self.users.values().all(|u| u.support_hdr)
     }
 
     // Check if any user is in recording mode
@@ -174,6 +321,18 @@ impl VideoQoS {
         }
     }
 
+    pub fn set_support_tiers(&mut self, video_service_name: &str, support: bool) {
+        if let Some(display) = self.displays.get_mut(video_service_name) {
+            display.support_tiers = support;
+        }
+    }
+
+    pub fn set_support_scaling(&mut self, video_service_name: &str, support: bool) {
+        if let Some(display) = self.displays.get_mut(video_service_name) {
+            display.support_scaling = support;
+        }
+    }
+
     // Check if variable bitrate encoding is supported and enabled
     pub fn in_vbr_state(&self) -> bool {
         self.abr_config && self.displays.iter().all(|e| e.1.support_changing_quality)
@@ -186,6 +345,9 @@ impl VideoQoS {
     pub fn on_connection_open(&mut self, id: i32) {
         self.users.insert(id, UserData::default());
         self.abr_config = Config::get_option("enable-abr") != "N";
+        self.tiers_config = Config::get_option(OPTION_ENABLE_TIERED_ENCODING) == "Y";
+        self.scaling_config = Config::get_option(OPTION_ENABLE_ADAPTIVE_SCALING) != "N";
+        self.hdr_config = Config::get_option(OPTION_ENABLE_HDR) == "Y";
         self.new_user_instant = Instant::now();
     }
 
@@ -194,6 +356,15 @@ impl VideoQoS {
         self.users.remove(&id);
         if self.users.is_empty() {
             *self = Default::default();
+            return;
+        }
+        for tier in [Tier::High, Tier::Low] {
+            if !self.estimating(tier) {
+                self.tier_mut(tier).estimate_fps = None;
+            }
+        }
+        if self.tier_users(Tier::High).next().is_none() {
+            self.update_tiers(Instant::now(), true);
         }
     }
 
@@ -233,7 +404,8 @@ impl VideoQoS {
         if let Some(user) = self.users.get_mut(&id) {
             user.quality = quality;
             // update ratio directly
-            self.ratio = self.latest_quality().ratio();
+            let tier = user.tier;
+            self.tier_mut(tier).ratio = self.latest_quality(tier).ratio();
         }
     }
 
@@ -243,9 +415,22 @@ impl VideoQoS {
         }
     }
 
+    pub fn user_support_scaling(&mut self, id: i32, support: bool) {
+        if let Some(user) = self.users.get_mut(&id) {
+            user.support_scaling = support;
+        }
+    }
+
+    pub fn user_support_hdr(&mut self, id: i32, support: bool) {
+        if let Some(user) = self.users.get_mut(&id) {
+            user.support_hdr = support;
+        }
+    }
+
     pub fn user_network_delay(&mut self, id: i32, delay: u32) {
-        let highest_fps = self.highest_fps();
-        let target_ratio = self.latest_quality().ratio();
+        let tier = self.user_tier(id);
+        let highest_fps = self.highest_fps(tier);
+        let target_ratio = self.latest_quality(tier).ratio();
 
         // For bad network, small fps means quick reaction and high quality
         let (min_fps, normal_fps) = if target_ratio >= BR_BEST {
@@ -266,7 +451,7 @@ impl VideoQoS {
             user.delay.add_delay(delay);
             let mut avg_delay = user.delay.avg_delay();
             avg_delay = avg_delay.max(10);
-            let mut fps = self.fps;
+            let mut fps = self.tiers[tier as usize].fps;
 
             // Adaptive FPS adjustment based on network delay:
             if avg_delay < 50 {
@@ -326,10 +511,45 @@ impl VideoQoS {
             adjust_ratio = user.delay.fps.is_none();
             user.delay.fps = Some(fps);
         }
-        self.adjust_fps();
+        self.adjust_fps(tier);
         if adjust_ratio && !cfg!(target_os = "linux") {
             //Reduce the possibility of vaapi being created twice
-            self.adjust_ratio(false);
+            self.adjust_ratio(tier, false);
+        }
+    }
+
+    // A video frame is written to the socket of the user.
+    pub fn on_video_written(&mut self, id: i32, bytes: usize, queue_delay: Duration) {
+        self.on_video_written_at(id, bytes, queue_delay, Instant::now());
+    }
+
+    // The bytes sent with the video frames to the user, e.g. the lossless tiles.
+    pub fn on_video_extra_written(&mut self, id: i32, bytes: usize) {
+        if let Some(user) = self.users.get_mut(&id) {
+            user.estimator.on_extra_written(bytes);
+        }
+    }
+
+    // The user acknowledges a video frame.
+    pub fn on_video_acked(&mut self, id: i32) {
+        self.on_video_acked_at(id, Instant::now());
+    }
+
+    fn on_video_written_at(&mut self, id: i32, bytes: usize, queue_delay: Duration, now: Instant) {
+        let tier = self.user_tier(id);
+        let bitrate = self.bitrate(tier);
+        if let Some(user) = self.users.get_mut(&id) {
+            user.estimator
+                .on_frame_written(bytes, queue_delay, bitrate, now);
+            self.apply_estimate(tier, now);
+        }
+    }
+
+    fn on_video_acked_at(&mut self, id: i32, now: Instant) {
+        if let Some(user) = self.users.get_mut(&id) {
+            user.estimator.on_frame_acked(now);
+            let tier = user.tier;
+            self.apply_estimate(tier, now);
         }
     }
 
@@ -338,7 +558,8 @@ impl VideoQoS {
             user.delay.response_delayed = elapsed > 2000;
             if user.delay.response_delayed {
                 user.delay.add_delay(elapsed as u32);
-                self.adjust_fps();
+                let tier = user.tier;
+                self.adjust_fps(tier);
             }
         }
     }
@@ -359,26 +580,41 @@ impl VideoQoS {
         if let Some(display) = self.displays.get_mut(video_service_name) {
             display.send_counter += send_counter;
         }
-        self.adjust_fps();
         let abr_enabled = self.in_vbr_state();
-        if abr_enabled {
-            if self.adjust_ratio_instant.elapsed().as_secs() >= ADJUST_RATIO_INTERVAL as u64 {
-                let dynamic_screen = self
-                    .displays
-                    .iter()
-                    .any(|d| d.1.send_counter >= ADJUST_RATIO_INTERVAL * DYNAMIC_SCREEN_THRESHOLD);
-                self.displays.iter_mut().for_each(|d| {
-                    d.1.send_counter = 0;
-                });
-                self.adjust_ratio(dynamic_screen);
+        let dynamic_screen = self
+            .displays
+            .iter()
+            .any(|d| d.1.send_counter >= ADJUST_RATIO_INTERVAL * DYNAMIC_SCREEN_THRESHOLD);
+        let mut adjusted = false;
+        for tier in [Tier::High, Tier::Low] {
+            self.adjust_fps(tier);
+            if abr_enabled {
+                if self.estimating(tier) {
+                    // Adjusted by the bandwidth estimation.
+                } else if self.tier(tier).adjust_ratio_instant.elapsed().as_secs()
+                    >= ADJUST_RATIO_INTERVAL as u64
+                {
+                    self.adjust_ratio(tier, dynamic_screen);
+                    adjusted = true;
+                }
+            } else {
+                self.tier_mut(tier).ratio = self.latest_quality(tier).ratio();
             }
-        } else {
-            self.ratio = self.latest_quality().ratio();
+        }
+        if adjusted {
+            self.displays.iter_mut().for_each(|d| {
+                d.1.send_counter = 0;
+            });
+        }
+        self.update_tiers(Instant::now(), false);
+        if !self.scaling_enabled() {
+            self.scale_index = 0;
+            self.scale_pending = None;
         }
     }
 
     #[inline]
-    fn highest_fps(&self) -> u32 {
+    fn highest_fps(&self, tier: Tier) -> u32 {
         let user_fps = |u: &UserData| {
             let mut fps = u.custom_fps.unwrap_or(FPS);
             if let Some(auto_adjust_fps) = u.auto_adjust_fps {
@@ -390,9 +626,8 @@ impl VideoQoS {
         };
 
         let fps = self
-            .users
-            .iter()
-            .map(|(_, u)| user_fps(u))
+            .tier_users(tier)
+            .map(user_fps)
             .filter(|u| *u >= MIN_FPS)
             .min()
             .unwrap_or(FPS);
@@ -401,10 +636,9 @@ impl VideoQoS {
     }
 
     // Get latest quality settings from all users
-    pub fn latest_quality(&self) -> Quality {
-        self.users
-            .iter()
-            .map(|(_, u)| u.quality)
+    pub fn latest_quality(&self, tier: Tier) -> Quality {
+        self.tier_users(tier)
+            .map(|u| u.quality)
             .filter(|q| *q != None)
             .max_by(|a, b| a.unwrap_or_default().0.cmp(&b.unwrap_or_default().0))
             .flatten()
@@ -413,27 +647,18 @@ impl VideoQoS {
     }
 
     // Adjust quality ratio based on network delay and screen changes
-    fn adjust_ratio(&mut self, dynamic_screen: bool) {
+    fn adjust_ratio(&mut self, tier: Tier, dynamic_screen: bool) {
         if !self.in_vbr_state() {
             return;
         }
         // Get maximum delay from all users
-        let max_delay = self.users.iter().map(|u| u.1.delay.avg_delay()).max();
+        let max_delay = self.tier_users(tier).map(|u| u.delay.avg_delay()).max();
         let Some(max_delay) = max_delay else {
             return;
         };
 
-        let target_quality = self.latest_quality();
-        let target_ratio = self.latest_quality().ratio();
-        let current_ratio = self.ratio;
-        let current_bitrate = self.bitrate();
-
-        // Calculate minimum ratio for high resolution (1Mbps baseline)
-        let ratio_1mbps = if current_bitrate > 0 {
-            Some((current_ratio * 1000.0 / current_bitrate as f32).max(BR_MIN_HIGH_RESOLUTION))
-        } else {
-            None
-        };
+        let current_ratio = self.tier(tier).ratio;
+        let current_bitrate = self.bitrate(tier);
 
         // Calculate ratio for adding 150kbps bandwidth
         let ratio_add_150kbps = if current_bitrate > 0 {
@@ -442,31 +667,7 @@ impl VideoQoS {
             None
         };
 
-        // Set minimum ratio based on quality mode
-        let min = match target_quality {
-            Quality::Best => {
-                // For Best quality, ensure minimum 1Mbps for high resolution
-                let mut min = BR_BEST / 2.5;
-                if let Some(ratio_1mbps) = ratio_1mbps {
-                    if min > ratio_1mbps {
-                        min = ratio_1mbps;
-                    }
-                }
-                min.max(BR_MIN)
-            }
-            Quality::Balanced => {
-                let mut min = (BR_BALANCED / 2.0).min(0.4);
-                if let Some(ratio_1mbps) = ratio_1mbps {
-                    if min > ratio_1mbps {
-                        min = ratio_1mbps;
-                    }
-                }
-                min.max(BR_MIN_HIGH_RESOLUTION)
-            }
-            Quality::Low => BR_MIN_HIGH_RESOLUTION,
-            Quality::Custom(_) => BR_MIN_HIGH_RESOLUTION,
-        };
-        let max = target_ratio * MAX_BR_MULTIPLE;
+        let (min, max) = self.ratio_range(tier);
 
         let mut v = current_ratio;
 
@@ -503,22 +704,147 @@ impl VideoQoS {
             }
         }
 
-        self.ratio = v.clamp(min, max);
-        self.adjust_ratio_instant = Instant::now();
+        let data = self.tier_mut(tier);
+        data.ratio = v.clamp(min, max);
+        data.adjust_ratio_instant = Instant::now();
+    }
+
+    // The range of the ratio for the latest quality
+    fn ratio_range(&self, tier: Tier) -> (f32, f32) {
+        let target_quality = self.latest_quality(tier);
+        let target_ratio = target_quality.ratio();
+        let current_ratio = self.tier(tier).ratio;
+        let current_bitrate = self.bitrate(tier);
+
+        // Calculate minimum ratio for high resolution (1Mbps baseline)
+        let ratio_1mbps = if current_bitrate > 0 {
+            Some((current_ratio * 1000.0 / current_bitrate as f32).max(BR_MIN_HIGH_RESOLUTION))
+        } else {
+            None
+        };
+
+        // Set minimum ratio based on quality mode
+        let min = match target_quality {
+            Quality::Best => {
+                // For Best quality, ensure minimum 1Mbps for high resolution
+                let mut min = BR_BEST / 2.5;
+                if let Some(ratio_1mbps) = ratio_1mbps {
+                    if min > ratio_1mbps {
+                        min = ratio_1mbps;
+                    }
+                }
+                min.max(BR_MIN)
+            }
+            Quality::Balanced => {
+                let mut min = (BR_BALANCED / 2.0).min(0.4);
+                if let Some(ratio_1mbps) = ratio_1mbps {
+                    if min > ratio_1mbps {
+                        min = ratio_1mbps;
+                    }
+                }
+                min.max(BR_MIN_HIGH_RESOLUTION)
+            }
+            Quality::Low => BR_MIN_HIGH_RESOLUTION,
+            Quality::Custom(_) => BR_MIN_HIGH_RESOLUTION,
+        };
+        (min, target_ratio * MAX_BR_MULTIPLE)
+    }
+
+    #[inline]
+    fn estimating(&self, tier: Tier) -> bool {
+        self.tier_users(tier)
+            .any(|u| u.estimator.target_kbps().is_some())
+    }
+
+    // Apply the lowest bandwidth estimate of all users
+    fn apply_estimate(&mut self, tier: Tier, now: Instant) {
+        let data = self.tier(tier);
+        if data
+            .apply_estimate_instant
+            .is_some_and(|t| now.saturating_duration_since(t) < APPLY_ESTIMATE_INTERVAL)
+        {
+            return;
+        }
+        if !self.in_vbr_state() || data.bitrate_store == 0 {
+            return;
+        }
+        let Some(target_kbps) = self
+            .tier_users(tier)
+            .filter_map(|u| u.estimator.target_kbps())
+            .min()
+        else {
+            return;
+        };
+        let v = target_kbps as f32 * data.bitrate_ratio / data.bitrate_store as f32;
+        let (min, max) = self.ratio_range(tier);
+        let highest_fps = self.highest_fps(tier);
+        let data = self.tier_mut(tier);
+        data.apply_estimate_instant = Some(now);
+        data.ratio = v.min(max).max(BR_MIN_HIGH_RESOLUTION);
+        // Fewer frames for the same bitrate, so that each frame is still readable
+        data.estimate_fps = if v < min {
+            Some(((highest_fps as f32 * v / min).round() as u32).max(MIN_FPS))
+        } else {
+            None
+        };
+        self.adjust_fps(tier);
+        if tier == Tier::High {
+            self.update_scale(v, min, now);
+        }
+    }
+
+    #[inline]
+    fn scaling_enabled(&self) -> bool {
+        self.scaling_config
+            && self.in_vbr_state()
+            && self.users.values().all(|u| u.support_scaling)
+            && !self.displays.is_empty()
+            && self.displays.iter().all(|d| d.1.support_scaling)
+    }
+
+    // `v` is the ratio the estimate affords at the current scale
+    fn update_scale(&mut self, v: f32, min: f32, now: Instant) {
+        if !self.scaling_enabled() {
+            self.scale_index = 0;
+            self.scale_pending = None;
+            return;
+        }
+        let index = self.scale_index;
+        // The bitrate grows slower than the pixels, so the ratio at a higher scale is not lower than this
+        let higher_scale_ratio = |i: usize| v * (SCALES[i] / SCALES[i - 1]).powi(2);
+        let (target, delay) = if v < min && index + 1 < SCALES.len() {
+            (index + 1, SCALE_DOWN_DELAY)
+        } else if index > 0 && higher_scale_ratio(index) >= min * SCALE_UP_MARGIN {
+            (index - 1, SCALE_UP_DELAY)
+        } else {
+            self.scale_pending = None;
+            return;
+        };
+        let since = match self.scale_pending {
+            Some((pending, since)) if pending == target => since,
+            _ => {
+                self.scale_pending = Some((target, now));
+                now
+            }
+        };
+        if now.saturating_duration_since(since) >= delay {
+            self.scale_index = target;
+            self.scale_pending = None;
+            log::info!("video scale changed: {}", self.scale());
+        }
     }
 
     // Adjust fps based on network delay and user response time
-    fn adjust_fps(&mut self) {
-        let highest_fps = self.highest_fps();
+    fn adjust_fps(&mut self, tier: Tier) {
+        let highest_fps = self.highest_fps(tier);
         // Get minimum fps from all users
         let mut fps = self
-            .users
-            .iter()
-            .map(|u| u.1.delay.fps.unwrap_or(INIT_FPS))
+            .tier_users(tier)
+            .map(|u| u.delay.fps.unwrap_or(INIT_FPS))
             .min()
             .unwrap_or(INIT_FPS);
 
-        if self.users.iter().any(|u| u.1.delay.response_delayed) {
+        if self.tier_users(tier).any(|u| u.delay.response_delayed) {
             if fps > MIN_FPS + 1 {
                 fps = MIN_FPS + 1;
             }
@@ -531,8 +857,95 @@ impl VideoQoS {
             }
         }
 
+        if let Some(estimate_fps) = self.tier(tier).estimate_fps {
+            fps = fps.min(estimate_fps);
+        }
+
         // Ensure fps stays within valid range
-        self.fps = fps.clamp(MIN_FPS, highest_fps);
+        self.tier_mut(tier).fps = fps.clamp(MIN_FPS, highest_fps);
+    }
+
+    #[inline]
+    fn tiers_enabled(&self) -> bool {
+        self.tiers_config
+            && self.users.len() > 1
+            && !self.displays.is_empty()
+            && self.displays.iter().all(|d| d.1.support_tiers)
+    }
+
+    // Move the slow users to the low tier, and back when they recover
+    fn update_tiers(&mut self, now: Instant, force: bool) {
+        if !force
+            && now.saturating_duration_since(self.update_tiers_instant) < UPDATE_TIERS_INTERVAL
+        {
+            return;
+        }
+        self.update_tiers_instant = now;
+        let mut tiers: HashMap<i32, Tier> = HashMap::new();
+        if self.tiers_enabled() {
+            let best_kbps = self
+                .users
+                .iter()
+                .filter_map(|u| u.1.estimator.target_kbps())
+                .max();
+            for (id, u) in self.users.iter() {
+                let below_best = |factor: f32| {
+                    u.estimator
+                        .target_kbps()
+                        .zip(best_kbps)
+                        .is_some_and(|(kbps, best)| (kbps as f32) < best as f32 * factor)
+                };
+                let tier = match u.tier {
+                    Tier::High
+                        if u.delay.response_delayed
+                            || u.delay.avg_delay() >= LOW_TIER_DELAY
+                            || below_best(LOW_TIER_KBPS_FACTOR) =>
+                    {
+                        Tier::Low
+                    }
+                    Tier::Low
+                        if !u.delay.response_delayed
+                            && u.delay.avg_delay() < DELAY_THRESHOLD_150MS
+                            && !below_best(HIGH_TIER_KBPS_FACTOR) =>
+                    {
+                        Tier::High
+                    }
+                    tier => tier,
+                };
+                tiers.insert(*id, tier);
+            }
+            // One encoder is enough if all users are slow
+            if tiers.values().all(|t| *t == Tier::Low) {
+                tiers.clear();
+            }
+        }
+        let changed = self
+            .users
+            .iter()
+            .any(|u| u.1.tier != tiers.get(u.0).copied().unwrap_or_default());
+        if !changed {
+            return;
+        }
+        let low_tier_empty = self.tier_users(Tier::Low).next().is_none();
+        for (id, u) in self.users.iter_mut() {
+            u.tier = tiers.get(id).copied().unwrap_or_default();
+        }
+        if low_tier_empty {
+            // Start from a lower quality than the high tier
+            let ratio = (self.tier(Tier::High).ratio * LOW_TIER_INIT_RATIO_FACTOR)
+                .max(BR_MIN_HIGH_RESOLUTION);
+            *self.tier_mut(Tier::Low) = TierData {
+                ratio,
+                ..Default::default()
+            };
+        }
+        self.adjust_fps(Tier::High);
+        self.adjust_fps(Tier::Low);
+        self.tiers_version += 1;
+        log::info!(
+            "video tiers changed, low tier: {:?}",
+            self.tier_conn_ids(Tier::Low)
+        );
     }
 }
 
@@ -593,3 +1006,106 @@ impl RttCalculator {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiers() {
+        let mut qos = VideoQoS::default();
+        qos.on_connection_open(1);
+        qos.on_connection_open(2);
+        qos.tiers_config = true;
+        qos.new_display("display".to_owned());
+        qos.set_support_tiers("display", true);
+        for _ in 0..3 {
+            qos.user_network_delay(1, 30);
+            qos.user_network_delay(2, 800);
+        }
+        let now = Instant::now();
+        qos.update_tiers(now, true);
+        assert_eq!(qos.user_tier(2), Tier::Low);
+        assert_eq!(qos.tier_conn_ids(Tier::High), HashSet::from([1]));
+        assert!(qos.ratio(Tier::Low) < qos.ratio(Tier::High));
+        assert_eq!(qos.tiers_version(), 1);
+
+        // Not checked again within the interval
+        for _ in 0..3 {
+            qos.user_network_delay(2, 30);
+        }
+        qos.update_tiers(now, false);
+        assert_eq!(qos.user_tier(2), Tier::Low);
+        qos.update_tiers(now + UPDATE_TIERS_INTERVAL, false);
+        assert_eq!(qos.user_tier(2), Tier::High);
+        assert_eq!(qos.tiers_version(), 2);
+
+        // The last user of the high tier leaves
+        for _ in 0..3 {
+            qos.user_network_delay(2, 800);
+        }
+        qos.update_tiers(now, true);
+        assert_eq!(qos.user_tier(2), Tier::Low);
+        qos.on_connection_close(1);
+        assert_eq!(qos.user_tier(2), Tier::High);
+        assert_eq!(qos.tiers_version(), 4);
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut qos = VideoQoS::default();
+        qos.on_connection_open(1);
+        qos.user_support_scaling(1, true);
+        qos.new_display("display".to_owned());
+        qos.set_support_changing_quality("display", true);
+        qos.set_support_scaling("display", true);
+        let now = Instant::now();
+        let secs = |n: u64| now + Duration::from_secs(n);
+
+        // Scale down after the pressure lasts
+        qos.update_scale(0.1, 0.4, now);
+        assert_eq!(qos.scale(), 1.0);
+        qos.update_scale(0.1, 0.4, secs(3));
+        assert_eq!(qos.scale(), 2.0 / 3.0);
+        qos.update_scale(0.1, 0.4, secs(3));
+        qos.update_scale(0.1, 0.4, secs(6));
+        assert_eq!(qos.scale(), 0.5);
+        qos.update_scale(0.1, 0.4, secs(9));
+        assert_eq!(qos.scale(), 0.5);
+
+        // Not enough margin to scale up
+        qos.update_scale(0.8, 0.4, secs(10));
+        qos.update_scale(0.8, 0.4, secs(20));
+        assert_eq!(qos.scale(), 0.5);
+        qos.update_scale(1.2, 0.4, secs(20));
+        qos.update_scale(0.4, 0.4, secs(25));
+        qos.update_scale(1.2, 0.4, secs(30));
+        assert_eq!(qos.scale(), 0.5);
+        qos.update_scale(1.2, 0.4, secs(35));
+        assert_eq!(qos.scale(), 0.5);
+        qos.update_scale(1.2, 0.4, secs(40));
+        assert_eq!(qos.scale(), 2.0 / 3.0);
+
+        // Back to the full resolution if a user does not support it
+        qos.on_connection_open(2);
+        qos.update_scale(0.1, 0.4, secs(45));
+        assert_eq!(qos.scale(), 1.0);
+    }
+
+    #[test]
+    fn test_hdr() {
+        let mut qos = VideoQoS::default();
+        qos.on_connection_open(1);
+        qos.user_support_hdr(1, true);
+        // Opt-in
+        assert!(!qos.hdr());
+        qos.hdr_config = true;
+        assert!(qos.hdr());
+        qos.users.insert(2, UserData::default());
+        assert!(!qos.hdr());
+        qos.user_support_hdr(2, true);
+        assert!(qos.hdr());
+        qos.hdr_config = false;
+        assert!(!qos.hdr());
+    }
+}
@@ -0,0 +1,341 @@
+// GCC-like bandwidth estimation, one per connection.
+//
+// The delay gradient is the change of the one way delay between the frames: the difference between
+// the intervals the frames are acknowledged by the peer and the intervals they are written to the socket.
+// A growing queue on the path makes the gradient positive before the delay itself gets large.
+// The transport is reliable, so the losses show up as the delay spikes of the retransmissions.
+// The gradient does not see a standing queue, so the delay above the minimum is checked as well.
+// The occupancy of the send buffer covers the congestion in front of the socket.
+// The estimate is used only after the peer acknowledges the frames, the old peers don't.
+//
+// All the functions take the time as an argument, so that the simulation is deterministic.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Trendline filter of the accumulated delay.
+const TRENDLINE_WINDOW: usize = 20;
+const TRENDLINE_SMOOTHING: f64 = 0.9;
+const TRENDLINE_GAIN: f64 = 4.0;
+const TRENDLINE_MAX_COUNT: usize = 60;
+// Adaptive threshold of the overuse detector, in ms.
+const INIT_THRESHOLD: f64 = 12.5;
+const MIN_THRESHOLD: f64 = 6.0;
+const MAX_THRESHOLD: f64 = 600.0;
+const THRESHOLD_K_UP: f64 = 0.0087;
+const THRESHOLD_K_DOWN: f64 = 0.039;
+// Longer than the original 10ms, a single retransmission is not the congestion.
+const OVERUSE_TIME: Duration = Duration::from_millis(200);
+// The frames waiting longer than this in the send buffer.
+const QUEUE_DELAY_OVERUSE: Duration = Duration::from_millis(100);
+const QUEUE_DELAY_SMOOTHING: f64 = 0.8;
+// The acknowledged delay above the minimum in the window.
+const QUEUING_DELAY_OVERUSE: Duration = Duration::from_millis(100);
+const BASE_DELAY_WINDOW: Duration = Duration::from_secs(10);
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+const MAX_PENDING_FRAMES: usize = 600;
+// AIMD rate control.
+const DECREASE_FACTOR: f64 = 0.85;
+const DECREASE_INTERVAL: Duration = Duration::from_millis(300);
+const INCREASE_FACTOR_PER_SEC: f64 = 1.08;
+const ADDITIVE_INCREASE_KBPS_PER_SEC: f64 = 50.0;
+const MIN_KBPS: f64 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BandwidthUsage {
+    Normal,
+    Overusing,
+    Underusing,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Trendline {
+    first_arrival: Option<Instant>,
+    accumulated_delay: f64,
+    smoothed_delay: f64,
+    samples: VecDeque<(f64, f64)>,
+    count: usize,
+}
+
+impl Trendline {
+    // Returns the modified trend, None if there are not enough samples.
+    fn update(&mut self, delta_ms: f64, arrival: Instant) -> Option<f64> {
+        let first_arrival = *self.first_arrival.get_or_insert(arrival);
+        self.count += 1;
+        self.accumulated_delay += delta_ms;
+        self.smoothed_delay = TRENDLINE_SMOOTHING * self.smoothed_delay
+            + (1.0 - TRENDLINE_SMOOTHING) * self.accumulated_delay;
+        let x = arrival.duration_since(first_arrival).as_secs_f64() * 1000.0;
+        if self.samples.len() >= TRENDLINE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((x, self.smoothed_delay));
+        if self.samples.len() < TRENDLINE_WINDOW {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean_x = self.samples.iter().map(|s| s.0).sum::<f64>() / n;
+        let mean_y = self.samples.iter().map(|s| s.1).sum::<f64>() / n;
+        let (mut numerator, mut denominator) = (0.0, 0.0);
+        for (x, y) in self.samples.iter() {
+            numerator += (x - mean_x) * (y - mean_y);
+            denominator += (x - mean_x) * (x - mean_x);
+        }
+        if denominator == 0.0 {
+            return None;
+        }
+        let slope = numerator / denominator;
+        Some(slope * self.count.min(TRENDLINE_MAX_COUNT) as f64 * TRENDLINE_GAIN)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct OveruseDetector {
+    threshold: f64,
+    overuse_start: Option<Instant>,
+    prev_trend: f64,
+    last_update: Option<Instant>,
+    usage: BandwidthUsage,
+}
+
+impl Default for OveruseDetector {
+    fn default() -> Self {
+        Self {
+            threshold: INIT_THRESHOLD,
+            overuse_start: None,
+            prev_trend: 0.0,
+            last_update: None,
+            usage: BandwidthUsage::Normal,
+        }
+    }
+}
+
+impl OveruseDetector {
+    fn detect(&mut self, trend: f64, now: Instant) -> BandwidthUsage {
+        if trend > self.threshold {
+            let start = *self.overuse_start.get_or_insert(now);
+            if now.duration_since(start) >= OVERUSE_TIME && trend >= self.prev_trend {
+                self.usage = BandwidthUsage::Overusing;
+            }
+        } else if trend < -self.threshold {
+            self.overuse_start = None;
+            self.usage = BandwidthUsage::Underusing;
+        } else {
+            self.overuse_start = None;
+            self.usage = BandwidthUsage::Normal;
+        }
+        self.prev_trend = trend;
+        self.update_threshold(trend, now);
+        self.usage
+    }
+
+    fn update_threshold(&mut self, trend: f64, now: Instant) {
+        let last_update = self.last_update.replace(now);
+        let Some(last_update) = last_update else {
+            return;
+        };
+        // Do not adapt to the spikes.
+        if trend.abs() > self.threshold + 15.0 {
+            return;
+        }
+        let k = if trend.abs() > self.threshold {
+            THRESHOLD_K_UP
+        } else {
+            THRESHOLD_K_DOWN
+        };
+        let dt = (now.duration_since(last_update).as_secs_f64() * 1000.0).min(100.0);
+        self.threshold = (self.threshold + k * (trend.abs() - self.threshold) * dt)
+            .clamp(MIN_THRESHOLD, MAX_THRESHOLD);
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BandwidthEstimator {
+    trendline: Trendline,
+    detector: OveruseDetector,
+    // (written time, bytes) of the frames not acknowledged yet.
+    pending: VecDeque<(Instant, usize)>,
+    // (written time, acknowledged time) of the last acknowledged frame.
+    last_acked: Option<(Instant, Instant)>,
+    acked: VecDeque<(Instant, usize)>,
+    written: VecDeque<(Instant, usize)>,
+    has_ack: bool,
+    queue_delay_ms: f64,
+    // (acknowledged time, delay) with increasing delays, the front is the minimum.
+    base_delay: VecDeque<(Instant, Duration)>,
+    queuing_delay_ms: f64,
+    target_kbps: Option<f64>,
+    last_update: Option<Instant>,
+    last_decrease: Option<Instant>,
+    // The throughput when the congestion is detected.
+    link_capacity_kbps: Option<f64>,
+    // The bytes sent besides the frames, counted with the next frame as they are not acknowledged.
+    extra_bytes: usize,
+}
+
+impl BandwidthEstimator {
+    // None until the peer acknowledges the frames, the others are adjusted by the network delay.
+    pub fn target_kbps(&self) -> Option<u32> {
+        if !self.has_ack {
+            return None;
+        }
+        self.target_kbps.map(|v| v as u32)
+    }
+
+    // `queue_delay` is the time the frame waits in the send queue, `init_kbps` is the current bitrate.
+    pub fn on_frame_written(
+        &mut self,
+        bytes: usize,
+        queue_delay: Duration,
+        init_kbps: u32,
+        now: Instant,
+    ) {
+        // The acknowledgements stop, e.g. the peer is closing. They can not be matched with
+        // the frames anymore, so start over.
+        if self.pending.len() >= MAX_PENDING_FRAMES {
+            *self = Self::default();
+        }
+        if self.target_kbps.is_none() && init_kbps > 0 {
+            self.target_kbps = Some(init_kbps as f64);
+        }
+        let bytes = bytes + std::mem::take(&mut self.extra_bytes);
+        self.pending.push_back((now, bytes));
+        if !self.has_ack {
+            self.written.push_back((now, bytes));
+        }
+        self.queue_delay_ms = QUEUE_DELAY_SMOOTHING * self.queue_delay_ms
+            + (1.0 - QUEUE_DELAY_SMOOTHING) * queue_delay.as_secs_f64() * 1000.0;
+        if !self.has_ack {
+            self.update_rate(self.with_queue_delay(BandwidthUsage::Normal), now);
+        }
+    }
+
+    pub fn on_extra_written(&mut self, bytes: usize) {
+        self.extra_bytes += bytes;
+    }
+
+    pub fn on_frame_acked(&mut self, now: Instant) {
+        let Some((written, bytes)) = self.pending.pop_front() else {
+            return;
+        };
+        if !self.has_ack {
+            self.has_ack = true;
+            self.written.clear();
+        }
+        self.acked.push_back((now, bytes));
+        self.update_queuing_delay(now.saturating_duration_since(written), now);
+        let mut usage = self.detector.usage;
+        if let Some((last_written, last_acked)) = self.last_acked {
+            let delta_ms = now.saturating_duration_since(last_acked).as_secs_f64() * 1000.0
+                - written
+                    .saturating_duration_since(last_written)
+                    .as_secs_f64()
+                    * 1000.0;
+            if let Some(trend) = self.trendline.update(delta_ms, now) {
+                usage = self.detector.detect(trend, now);
+            }
+        }
+        self.last_acked = Some((written, now));
+        self.update_rate(self.with_queue_delay(usage), now);
+    }
+
+    fn update_queuing_delay(&mut self, delay: Duration, now: Instant) {
+        while self.base_delay.back().is_some_and(|(_, d)| *d >= delay) {
+            self.base_delay.pop_back();
+        }
+        self.base_delay.push_back((now, delay));
+        while self
+            .base_delay
+            .front()
+            .is_some_and(|(t, _)| now.saturating_duration_since(*t) > BASE_DELAY_WINDOW)
+        {
+            self.base_delay.pop_front();
+        }
+        let base = self.base_delay.front().map(|(_, d)| *d).unwrap_or(delay);
+        self.queuing_delay_ms = QUEUE_DELAY_SMOOTHING * self.queuing_delay_ms
+            + (1.0 - QUEUE_DELAY_SMOOTHING) * delay.saturating_sub(base).as_secs_f64() * 1000.0;
+    }
+
+    #[inline]
+    fn with_queue_delay(&self, usage: BandwidthUsage) -> BandwidthUsage {
+        if self.queue_delay_ms > QUEUE_DELAY_OVERUSE.as_secs_f64() * 1000.0
+            || self.queuing_delay_ms > QUEUING_DELAY_OVERUSE.as_secs_f64() * 1000.0
+        {
+            BandwidthUsage::Overusing
+        } else {
+            usage
+        }
+    }
+
+    // The acknowledged bitrate, or the written bitrate if the peer does not acknowledge the frames.
+    fn throughput_kbps(&mut self, now: Instant) -> Option<f64> {
+        let samples = if self.has_ack {
+            &mut self.acked
+        } else {
+            &mut self.written
+        };
+        while samples
+            .front()
+            .is_some_and(|(t, _)| now.saturating_duration_since(*t) > THROUGHPUT_WINDOW)
+        {
+            samples.pop_front();
+        }
+        if samples.is_empty() {
+            return None;
+        }
+        let bytes = samples.iter().map(|(_, b)| *b).sum::<usize>();
+        Some(bytes as f64 * 8.0 / 1000.0 / THROUGHPUT_WINDOW.as_secs_f64())
+    }
+
+    fn update_rate(&mut self, usage: BandwidthUsage, now: Instant) {
+        let throughput = self.throughput_kbps(now);
+        let dt = self
+            .last_update
+            .replace(now)
+            .map(|t| now.saturating_duration_since(t).as_secs_f64())
+            .unwrap_or_default();
+        let Some(target) = self.target_kbps else {
+            return;
+        };
+        match usage {
+            BandwidthUsage::Overusing => {
+                if self
+                    .last_decrease
+                    .is_some_and(|t| now.saturating_duration_since(t) < DECREASE_INTERVAL)
+                {
+                    return;
+                }
+                self.last_decrease = Some(now);
+                let base = throughput.unwrap_or(target);
+                self.target_kbps = Some(target.min((base * DECREASE_FACTOR).max(MIN_KBPS)));
+                if let Some(throughput) = throughput {
+                    self.link_capacity_kbps = Some(match self.link_capacity_kbps {
+                        Some(capacity) => 0.95 * capacity + 0.05 * throughput,
+                        None => throughput,
+                    });
+                }
+            }
+            // Wait for the queues to drain.
+            BandwidthUsage::Underusing => {}
+            BandwidthUsage::Normal => {
+                if self.link_capacity_kbps.is_some_and(|c| target > c * 1.5) {
+                    self.link_capacity_kbps = None;
+                }
+                let mut v = match self.link_capacity_kbps {
+                    // Close to the capacity, probe slowly.
+                    Some(capacity) if target >= capacity * 0.9 => {
+                        target + ADDITIVE_INCREASE_KBPS_PER_SEC * dt
+                    }
+                    _ => target * INCREASE_FACTOR_PER_SEC.powf(dt),
+                };
+                // Do not grow far beyond what is sent, e.g. the screen is static.
+                if let Some(throughput) = throughput {
+                    v = v.min(target.max(throughput * 1.5 + 10.0));
+                }
+                self.target_kbps = Some(v);
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// Replays the network traces against `VideoQoS`, without the real network and clock.
+//
+// The bottleneck link is a FIFO queue with the capacity and one way delay of the trace.
+// The socket write completes once the queue is under the send buffer size.
+// A lost frame is retransmitted after a round trip, the later frames are acknowledged after it.
+
+use super::*;
+
+const CONN_ID: i32 = 1;
+const DISPLAY: &str = "sim";
+// The encoder bitrate of ratio 1.0, about 1080p.
+const KBPS_PER_RATIO: f32 = 3000.0;
+const SEND_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TracePoint {
+    at: Duration,
+    capacity_kbps: u32,
+    delay: Duration,
+    loss: f32,
+}
+
+// One point per line: "seconds capacity_kbps one_way_delay_ms loss", '#' for comments.
+fn parse_trace(s: &str) -> Vec<TracePoint> {
+    s.lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty())
+        .filter_map(|l| {
+            let v = l.split_whitespace().collect::<Vec<_>>();
+            if v.len() != 4 {
+                return None;
+            }
+            Some(TracePoint {
+                at: Duration::from_secs_f64(v[0].parse().ok()?),
+                capacity_kbps: v[1].parse().ok()?,
+                delay: Duration::from_millis(v[2].parse().ok()?),
+                loss: v[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    at: Duration,
+    capacity_kbps: u32,
+    bitrate_kbps: u32,
+    fps: u32,
+    // From the capture to the end of the bottleneck.
+    latency: Duration,
+}
+
+// Deterministic random numbers in [0, 1).
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1103515245).wrapping_add(12345) & 0x7FFF_FFFF;
+        self.0 as f32 / 0x8000_0000u32 as f32
+    }
+}
+
+fn simulate(trace: &[TracePoint], duration: Duration) -> Vec<Sample> {
+    let base = Instant::now();
+    let mut qos = VideoQoS::default();
+    qos.on_connection_open(CONN_ID);
+    qos.abr_config = true;
+    // Skip the fps limit of the new connections, which uses the real clock.
+    qos.new_user_instant = base.checked_sub(Duration::from_secs(10)).unwrap_or(base);
+    qos.new_display(DISPLAY.to_owned());
+    qos.set_support_changing_quality(DISPLAY, true);
+
+    let mut rng = Lcg(1);
+    let mut samples = Vec::new();
+    let mut ratio = 0.0;
+    // (end of the bottleneck, bytes)
+    let mut queue: VecDeque<(Duration, usize)> = VecDeque::new();
+    let mut acks: VecDeque<Duration> = VecDeque::new();
+    let mut link_free = Duration::ZERO;
+    let mut next_second = Duration::ZERO;
+    let mut send_counter = 0;
+    let mut t = Duration::ZERO;
+    while t < duration {
+        let point = trace
+            .iter()
+            .take_while(|p| p.at <= t)
+            .last()
+            .copied()
+            .unwrap_or(trace[0]);
+        while acks.front().is_some_and(|a| *a <= t) {
+            let ack = acks.pop_front().unwrap_or_default();
+            qos.on_video_acked_at(CONN_ID, base + ack);
+        }
+        while queue.front().is_some_and(|(end, _)| *end <= t) {
+            queue.pop_front();
+        }
+        let capacity = point.capacity_kbps.max(1) as f64 * 1000.0 / 8.0;
+        let queued = queue.iter().map(|(_, b)| *b).sum::<usize>();
+        if t >= next_second {
+            next_second += Duration::from_secs(1);
+            let delay = point.delay * 2 + Duration::from_secs_f64(queued as f64 / capacity);
+            qos.user_network_delay(CONN_ID, delay.as_millis() as _);
+            qos.update_display_data(DISPLAY, send_counter);
+            send_counter = 0;
+        }
+        // Same as `check_qos`.
+        if ratio != qos.ratio(Tier::High) {
+            ratio = qos.ratio(Tier::High);
+            qos.store_bitrate(Tier::High, (ratio * KBPS_PER_RATIO) as u32);
+        }
+        let fps = qos.fps(Tier::High);
+        let bitrate = qos.bitrate(Tier::High);
+        let bytes =
+            (bitrate as f32 * 1000.0 / 8.0 / fps as f32 * (0.7 + 0.6 * rng.next())) as usize;
+
+        let queue_delay =
+            Duration::from_secs_f64(queued.saturating_sub(SEND_BUFFER) as f64 / capacity);
+        let start = t.max(link_free);
+        link_free = start + Duration::from_secs_f64(bytes as f64 / capacity);
+        let mut end = link_free;
+        if rng.next() < point.loss {
+            end += point.delay * 2;
+        }
+        queue.push_back((end, bytes));
+        let ack = (end + point.delay * 2).max(acks.back().copied().unwrap_or_default());
+        acks.push_back(ack);
+        qos.on_video_written_at(CONN_ID, bytes, queue_delay, base + t + queue_delay);
+        send_counter += 1;
+
+        samples.push(Sample {
+            at: t,
+            capacity_kbps: point.capacity_kbps,
+            bitrate_kbps: bitrate,
+            fps,
+            latency: end - t,
+        });
+        t += Duration::from_secs_f64(1.0 / fps as f64);
+    }
+    samples
+}
+
+// (mean bitrate, coefficient of variation of the bitrate, p95 latency)
+fn stats(samples: &[Sample], from: u64, to: u64) -> (f32, f32, Duration) {
+    let range = Duration::from_secs(from)..Duration::from_secs(to);
+    let samples = samples
+        .iter()
+        .filter(|s| range.contains(&s.at))
+        .collect::<Vec<_>>();
+    let n = samples.len() as f32;
+    let mean = samples.iter().map(|s| s.bitrate_kbps as f32).sum::<f32>() / n;
+    let variance = samples
+        .iter()
+        .map(|s| (s.bitrate_kbps as f32 - mean).powi(2))
+        .sum::<f32>()
+        / n;
+    let mut latencies = samples.iter().map(|s| s.latency).collect::<Vec<_>>();
+    latencies.sort();
+    let p95 = latencies[(latencies.len() as f32 * 0.95) as usize];
+    (mean, variance.sqrt() / mean, p95)
+}
+
+const CELLULAR_TRACE: &str = "
+# seconds capacity_kbps one_way_delay_ms loss
+0  2500 60 0.02
+5  1800 60 0.02
+10 1200 60 0.02
+15 2200 60 0.02
+20 3000 60 0.02
+25 1500 60 0.02
+30 900  60 0.02
+35 2000 60 0.02
+40 2600 60 0.02
+45 1400 60 0.02
+50 2400 60 0.02
+55 1700 60 0.02
+";
+
+#[test]
+fn test_parse_trace() {
+    let trace = parse_trace(CELLULAR_TRACE);
+    assert_eq!(trace.len(), 12);
+    assert_eq!(
+        trace[1],
+        TracePoint {
+            at: Duration::from_secs(5),
+            capacity_kbps: 1800,
+            delay: Duration::from_millis(60),
+            loss: 0.02,
+        }
+    );
+    assert!(parse_trace("1 2 3").is_empty());
+}
+
+#[test]
+fn test_capacity_drop() {
+    let trace = parse_trace("0 4000 30 0\n20 1000 30 0");
+    let samples = simulate(&trace, Duration::from_secs(60));
+    let (mean, _, _) = stats(&samples, 10, 20);
+    assert!(mean > 1800.0, "mean {mean}");
+    let (mean, _, p95) = stats(&samples, 30, 60);
+    assert!(mean > 700.0 && mean < 1050.0, "mean {mean}");
+    assert!(p95 < Duration::from_millis(600), "p95 {p95:?}");
+}
+
+#[test]
+fn test_lossy_link_stable() {
+    let trace = parse_trace("0 1500 60 0.02");
+    let samples = simulate(&trace, Duration::from_secs(90));
+    let (mean, cv, p95) = stats(&samples, 15, 90);
+    assert!(mean > 1000.0, "mean {mean}");
+    assert!(cv < 0.25, "cv {cv}");
+    assert!(p95 < Duration::from_millis(600), "p95 {p95:?}");
+}
+
+#[test]
+fn test_cellular_trace() {
+    let trace = parse_trace(CELLULAR_TRACE);
+    let samples = simulate(&trace, Duration::from_secs(60));
+    let max_kbps = BR_BALANCED * KBPS_PER_RATIO;
+    let samples = samples
+        .into_iter()
+        .filter(|s| s.at >= Duration::from_secs(5))
+        .collect::<Vec<_>>();
+    let utilization = samples
+        .iter()
+        .map(|s| s.bitrate_kbps as f32 / (s.capacity_kbps as f32).min(max_kbps))
+        .sum::<f32>()
+        / samples.len() as f32;
+    assert!(utilization > 0.6, "utilization {utilization}");
+    let (_, _, p95) = stats(&samples, 5, 60);
+    assert!(p95 < Duration::from_secs(1), "p95 {p95:?}");
+    assert!(samples.iter().all(|s| s.fps >= MIN_FPS));
+}
+
+#[test]
+fn test_acks_required() {
+    let base = Instant::now();
+    let mut qos = VideoQoS::default();
+    qos.on_connection_open(CONN_ID);
+    qos.abr_config = true;
+    qos.store_bitrate(Tier::High, 3000);
+    let write = |qos: &mut VideoQoS, i: u64| {
+        let at = base + Duration::from_millis(i * 33);
+        qos.on_video_written_at(CONN_ID, 10_000, Duration::ZERO, at);
+    };
+    for i in 0..10 {
+        write(&mut qos, i);
+    }
+    // The old peers do not acknowledge the frames.
+    assert!(!qos.estimating(Tier::High));
+    qos.on_video_acked_at(CONN_ID, base + Duration::from_millis(400));
+    assert!(qos.estimating(Tier::High));
+    // The acknowledgements stop.
+    for i in 10..1000 {
+        write(&mut qos, i);
+    }
+    assert!(!qos.estimating(Tier::High));
+}
+
+#[test]
+fn test_deterministic() {
+    let trace = parse_trace(CELLULAR_TRACE);
+    let a = simulate(&trace, Duration::from_secs(20));
+    let b = simulate(&trace, Duration::from_secs(20));
+    assert_eq!(a, b);
+}
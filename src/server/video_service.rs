@@ -18,7 +18,12 @@
 // to-do:
 // https://slhck.info/video/2017/03/01/rate-control.html
 
-use super::{display_service::check_display_changed, service::ServiceTmpl, video_qos::VideoQoS, *};
+use super::{
+    display_service::check_display_changed,
+    service::ServiceTmpl,
+    video_qos::{Tier, VideoQoS},
+    *,
+};
 #[cfg(target_os = "linux")]
 use crate::common::SimpleCallOnReturn;
 #[cfg(target_os = "linux")]
@@ -40,13 +45,17 @@ use hbb_common::{
 };
 #[cfg(feature = "hwcodec")]
 use scrap::hwcodec::{HwRamEncoder, HwRamEncoderConfig};
+#[cfg(feature = "openh264")]
+use scrap::openh264::OpenH264EncoderConfig;
 #[cfg(feature = "vram")]
 use scrap::vram::{VRamEncoder, VRamEncoderConfig};
 #[cfg(not(windows))]
 use scrap::Capturer;
 use scrap::{
     aom::AomEncoderConfig,
-    codec::{Encoder, EncoderCfg},
+    codec::{Encoder, EncoderCfg, EncoderTuning},
+    dirty::{DirtyTracker, RoiTracker},
+    lossless::{TileRefiner, TileUpdate},
     record::{Recorder, RecorderContext},
     vpxcodec::{VpxEncoderConfig, VpxVideoCodecId},
     CodecFormat, Display, EncodeInput, TraitCapturer, TraitPixelBuffer,
@@ -78,6 +87,11 @@ lazy_static::lazy_static! {
     pub static ref IS_UAC_RUNNING: Arc<Mutex<bool>> = Default::default();
     pub static ref IS_FOREGROUND_WINDOW_ELEVATED: Arc<Mutex<bool>> = Default::default();
     static ref SCREENSHOTS: Mutex<HashMap<usize, Screenshot>> = Default::default();
+    // The connections which draw the lossless tiles over the video frames.
+    static ref LOSSLESS_TILES_CONN_IDS: Arc<Mutex<HashSet<i32>>> = Default::default();
+    // The smoothed encoding time of each display and tier in ms,
+    // for the telemetry and pacing the low tier.
+    static ref ENCODE_MS: Mutex<HashMap<(usize, Tier), f32>> = Default::default();
 }
 
 struct Screenshot {
@@ -93,6 +107,27 @@ pub fn notify_video_frame_fetched(display_idx: usize, conn_id: i32, frame_tm: Op
     }
 }
 
+// Returns true if changed.
+pub fn set_lossless_tiles(conn_id: i32, on: bool) -> bool {
+    let mut conn_ids = LOSSLESS_TILES_CONN_IDS.lock().unwrap();
+    if on {
+        conn_ids.insert(conn_id)
+    } else {
+        conn_ids.remove(&conn_id)
+    }
+}
+
+// The encoding time of the slowest display of the tier
+pub fn encode_ms(tier: Tier) -> Option<f32> {
+    ENCODE_MS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(k, _)| k.1 == tier)
+        .map(|(_, v)| *v)
+        .reduce(f32::max)
+}
+
 #[inline]
 pub fn notify_video_frame_fetched_by_conn_id(conn_id: i32, frame_tm: Option<Instant>) {
     let vec_display_idx: Vec<usize> = {
@@ -569,14 +604,43 @@ fn run(vs: VideoService) -> ResultType<()> {
         c.set_gdi();
     }
     let mut video_qos = VIDEO_QOS.lock().unwrap();
-    let mut spf = video_qos.spf();
-    let mut quality = video_qos.ratio();
+    let mut spf = video_qos.spf(Tier::High);
+    let mut quality = video_qos.ratio(Tier::High);
     let record_incoming = config::option2bool(
         "allow-auto-record-incoming",
         &Config::get_option("allow-auto-record-incoming"),
     );
     let client_record = video_qos.record();
+    let mut tiers_version = video_qos.tiers_version();
+    // Only the low tier users of this display matter
+    let subscribe_ids = sp.subscribe_ids();
+    let tier_conn_ids: HashSet<i32> = video_qos
+        .tier_conn_ids(Tier::Low)
+        .into_iter()
+        .filter(|id| subscribe_ids.contains(id))
+        .collect();
+    let mut low_tier_conn_ids = tier_conn_ids.clone();
+    let scale = video_qos.scale();
+    let hdr_enabled = video_qos.hdr();
     drop(video_qos);
+    // The recordings keep 8-bit, and only VP9 and AV1 are encoded in 10-bit
+    let mut hdr = c.set_hdr(
+        hdr_enabled
+            && !client_record
+            && !record_incoming
+            && vs.source.is_monitor()
+            && matches!(
+                Encoder::negotiated_codec(),
+                CodecFormat::VP9 | CodecFormat::AV1
+            ),
+    );
+    // The recordings keep the full resolution, and the 16-bit frames are not scaled
+    let support_scaling = !client_record && !record_incoming && !hdr;
+    let scaled_size = if support_scaling && scale < 1.0 {
+        Some(scrap::scale::scaled_size(c.width, c.height, scale))
+    } else {
+        None
+    };
     let (mut encoder, encoder_cfg, codec_format, use_i444, recorder) = match setup_encoder(
         &c,
         sp.name(),
@@ -586,16 +650,23 @@ fn run(vs: VideoService) -> ResultType<()> {
         last_portable_service_running,
         vs.source,
         display_idx,
+        scaled_size,
+        hdr,
     ) {
         Ok(result) => result,
         Err(err) => {
             log::error!("Failed to create encoder: {err:?}, fallback to VP9");
+            if hdr {
+                hdr = c.set_hdr(false);
+            }
             Encoder::set_fallback(&EncoderCfg::VPX(VpxEncoderConfig {
                 width: c.width as _,
                 height: c.height as _,
                 quality,
                 codec: VpxVideoCodecId::VP9,
                 keyframe_interval: None,
+                tuning: Default::default(),
+                hdr: false,
             }));
             setup_encoder(
                 &c,
@@ -606,9 +677,14 @@ fn run(vs: VideoService) -> ResultType<()> {
                 last_portable_service_running,
                 vs.source,
                 display_idx,
+                scaled_size,
+                hdr,
             )?
         }
     };
+    if hdr {
+        log::info!("encode in 10-bit hdr");
+    }
     #[cfg(feature = "vram")]
     c.set_output_texture(encoder.input_texture());
     #[cfg(target_os = "android")]
@@ -618,12 +694,62 @@ fn run(vs: VideoService) -> ResultType<()> {
             bail!(e);
         }
     }
-    VIDEO_QOS.lock().unwrap().store_bitrate(encoder.bitrate());
+    VIDEO_QOS
+        .lock()
+        .unwrap()
+        .store_bitrate(Tier::High, encoder.bitrate());
     VIDEO_QOS
         .lock()
         .unwrap()
         .set_support_changing_quality(&sp.name(), encoder.support_changing_quality());
     log::info!("initial quality: {quality:?}");
+    // The low tier encodes the same yuv data, only the software encoders are used.
+    let mut support_tiers = matches!(encoder_cfg, EncoderCfg::VPX(_) | EncoderCfg::AOM(_));
+    #[cfg(feature = "openh264")]
+    {
+        support_tiers |= matches!(encoder_cfg, EncoderCfg::OpenH264(_));
+    }
+    let mut low_tier = None;
+    if support_tiers && !low_tier_conn_ids.is_empty() {
+        match LowTier::new(&encoder_cfg, use_i444) {
+            Ok(tier) => low_tier = Some(tier),
+            Err(e) => {
+                log::error!("Failed to create the low tier encoder: {e:?}");
+                support_tiers = false;
+            }
+        }
+    }
+    if low_tier.is_none() {
+        low_tier_conn_ids.clear();
+    }
+    VIDEO_QOS
+        .lock()
+        .unwrap()
+        .set_support_tiers(&sp.name(), support_tiers);
+    // Only the software encoders are scaled, the client decodes them into pixel buffers to scale up.
+    let scalable = matches!(encoder_cfg, EncoderCfg::VPX(_) | EncoderCfg::AOM(_));
+    VIDEO_QOS
+        .lock()
+        .unwrap()
+        .set_support_scaling(&sp.name(), support_scaling && scalable);
+    let scaled_size = scaled_size.filter(|_| scalable);
+    // OpenH264 pads the odd sizes to even, the client crops the padding with the original size.
+    #[allow(unused_mut)]
+    let mut padded = false;
+    #[cfg(feature = "openh264")]
+    {
+        padded = matches!(encoder_cfg, EncoderCfg::OpenH264(_)) && (c.width | c.height) & 1 == 1;
+    }
+    if let Some(size) = scaled_size {
+        log::info!("encode at {size:?}, scale: {scale}");
+    }
+    let frame_info = FrameInfo {
+        original_size: (scaled_size.is_some() || padded).then_some((c.width, c.height)),
+        color: hdr.then_some(scrap::hdr::VideoColor {
+            primaries: scrap::hdr::Primaries::Bt2020,
+            transfer: scrap::hdr::Transfer::Pq,
+        }),
+    };
 
     if sp.is_option_true(OPTION_REFRESH) {
         sp.set_option_bool(OPTION_REFRESH, false);
@@ -644,6 +770,7 @@ fn run(vs: VideoService) -> ResultType<()> {
     let mut would_block_count = 0u32;
     let mut yuv = Vec::new();
     let mut mid_data = Vec::new();
+    let mut scaled_data = Vec::new();
     let mut repeat_encode_counter = 0;
     let repeat_encode_max = 10;
     let mut encode_fail_counter = 0;
@@ -651,6 +778,20 @@ fn run(vs: VideoService) -> ResultType<()> {
     let capture_width = c.width;
     let capture_height = c.height;
     let (mut second_instant, mut send_counter) = (Instant::now(), 0);
+    let mut roi_tracker =
+        if encoder.support_roi() && scrap::codec::enable_roi_encoding() && scaled_size.is_none() {
+            Some(RoiTracker::default())
+        } else {
+            None
+        };
+    // The service is refreshed when a connection enables the lossless tiles.
+    let mut tile_refiner =
+        if vs.source.is_monitor() && !LOSSLESS_TILES_CONN_IDS.lock().unwrap().is_empty() {
+            Some(TileRefiner::new(display_idx))
+        } else {
+            None
+        };
+    let mut dirty_tracker = DirtyTracker::default();
 
     while sp.ok() {
         #[cfg(windows)]
@@ -662,8 +803,15 @@ fn run(vs: VideoService) -> ResultType<()> {
             client_record,
             &mut send_counter,
             &mut second_instant,
-            &sp.name(),
+            &sp,
+            &mut tiers_version,
+            &tier_conn_ids,
+            scale,
+            hdr_enabled,
         )?;
+        if let Some(tier) = low_tier.as_mut() {
+            tier.check_qos();
+        }
         if sp.is_option_true(OPTION_REFRESH) {
             if vs.source.is_monitor() {
                 let _ = try_broadcast_display_changed(&sp, display_idx, &c, true);
@@ -714,9 +862,18 @@ fn run(vs: VideoService) -> ResultType<()> {
         }
 
         frame_controller.reset();
+        let lossless_conn_ids = LOSSLESS_TILES_CONN_IDS.lock().unwrap().clone();
+        if lossless_conn_ids.is_empty() {
+            tile_refiner = None;
+        }
 
         let time = now - start;
         let ms = (time.as_secs() * 1000 + time.subsec_millis() as u64) as i64;
+        let cursor = if roi_tracker.is_some() && vs.source.is_monitor() {
+            cursor_in_frame(c.origin)
+        } else {
+            None
+        };
         let res = match c.frame(spf) {
             Ok(frame) => {
                 repeat_encode_counter = 0;
@@ -764,19 +921,80 @@ fn run(vs: VideoService) -> ResultType<()> {
                         }
                     }
 
-                    let frame = frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
-                    let send_conn_ids = handle_one_frame(
+                    let dirty = match &frame {
+                        scrap::Frame::PixelBuffer(f)
+                            if roi_tracker.is_some() || tile_refiner.is_some() =>
+                        {
+                            Some(dirty_tracker.update(f))
+                        }
+                        _ => None,
+                    };
+                    if let (Some(refiner), Some(dirty), scrap::Frame::PixelBuffer(f)) =
+                        (tile_refiner.as_mut(), dirty.as_ref(), &frame)
+                    {
+                        // Remove the changed tiles before the frame is drawn.
+                        if let Some(update) = refiner.update(f, dirty) {
+                            send_lossless_tiles(&sp, &lossless_conn_ids, &update);
+                        }
+                    }
+                    let roi_res = match (roi_tracker.as_mut(), dirty.as_ref()) {
+                        (Some(tracker), Some(dirty)) => {
+                            tracker.update(dirty, cursor);
+                            encoder.set_roi(tracker.roi().as_ref())
+                        }
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = roi_res {
+                        log::error!("Failed to set roi, disable it: {e:?}");
+                        encoder.set_roi(None).ok();
+                        roi_tracker = None;
+                    }
+                    let frame = match scaled_size {
+                        Some(size) => frame.to_scaled(
+                            size,
+                            encoder.yuvfmt(),
+                            &mut yuv,
+                            &mut mid_data,
+                            &mut scaled_data,
+                        )?,
+                        None => frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?,
+                    };
+                    let (send_conn_ids, mut key_frame) = handle_one_frame(
                         display_idx,
                         &sp,
                         frame,
                         ms,
                         &mut encoder,
+                        Tier::High,
+                        &frame_info,
                         recorder.clone(),
                         &mut encode_fail_counter,
                         &mut first_frame,
                         capture_width,
                         capture_height,
+                        |id| !low_tier_conn_ids.contains(&id),
                     )?;
+                    if let Some(tracker) = roi_tracker.as_mut() {
+                        tracker.encoded(encode_fail_counter == 0);
+                    }
+                    // Not waited by the frame controller, the low tier is paced by its own fps.
+                    if let Some(tier) = low_tier.as_mut() {
+                        key_frame |= tier.encode(
+                            display_idx,
+                            &sp,
+                            &yuv,
+                            ms,
+                            now,
+                            spf,
+                            &frame_info,
+                            &low_tier_conn_ids,
+                            capture_width,
+                            capture_height,
+                        )?;
+                    }
+                    if key_frame {
+                        clear_lossless_tiles(&sp, &lossless_conn_ids, tile_refiner.as_mut());
+                    }
                     frame_controller.set_send(now, send_conn_ids);
                     send_counter += 1;
                 }
@@ -824,18 +1042,24 @@ fn run(vs: VideoService) -> ResultType<()> {
                     // yun.len() > 0 means the frame is not texture.
                     if repeat_encode_counter < repeat_encode_max {
                         repeat_encode_counter += 1;
-                        let send_conn_ids = handle_one_frame(
+                        let (send_conn_ids, key_frame) = handle_one_frame(
                             display_idx,
                             &sp,
                             EncodeInput::YUV(&yuv),
                             ms,
                             &mut encoder,
+                            Tier::High,
+                            &frame_info,
                             recorder.clone(),
                             &mut encode_fail_counter,
                             &mut first_frame,
                             capture_width,
                             capture_height,
+                            |id| !low_tier_conn_ids.contains(&id),
                         )?;
+                        if key_frame {
+                            clear_lossless_tiles(&sp, &lossless_conn_ids, tile_refiner.as_mut());
+                        }
                         frame_controller.set_send(now, send_conn_ids);
                         send_counter += 1;
                     }
@@ -863,6 +1087,9 @@ fn run(vs: VideoService) -> ResultType<()> {
                 }
             }
         }
+        if let Some(update) = tile_refiner.as_mut().and_then(|r| r.refine()) {
+            send_lossless_tiles(&sp, &lossless_conn_ids, &update);
+        }
 
         let mut fetched_conn_ids = HashSet::new();
         let timeout_millis = 3_000u64;
@@ -919,6 +1146,10 @@ impl Drop for Raii {
         Encoder::update(scrap::codec::EncodingUpdate::Check);
         VIDEO_QOS.lock().unwrap().remove_display(&self.name);
         DISPLAY_CONN_IDS.lock().unwrap().remove(&self.display_idx);
+        ENCODE_MS
+            .lock()
+            .unwrap()
+            .retain(|k, _| k.0 != self.display_idx);
     }
 }
 
@@ -931,6 +1162,8 @@ fn setup_encoder(
     last_portable_service_running: bool,
     source: VideoSource,
     display_idx: usize,
+    scaled_size: Option<(usize, usize)>,
+    hdr: bool,
 ) -> ResultType<(
     Encoder,
     EncoderCfg,
@@ -945,6 +1178,8 @@ fn setup_encoder(
         client_record || record_incoming,
         last_portable_service_running,
         source,
+        scaled_size,
+        hdr,
     );
     Encoder::set_fallback(&encoder_cfg);
     let codec_format = Encoder::negotiated_codec();
@@ -961,6 +1196,8 @@ fn get_encoder_config(
     record: bool,
     _portable_service: bool,
     _source: VideoSource,
+    scaled_size: Option<(usize, usize)>,
+    hdr: bool,
 ) -> EncoderCfg {
     #[cfg(all(windows, feature = "vram"))]
     if _portable_service || c.is_gdi() || _source == VideoSource::Camera {
@@ -969,9 +1206,25 @@ fn get_encoder_config(
     }
     #[cfg(feature = "vram")]
     Encoder::update(scrap::codec::EncodingUpdate::Check);
-    // https://www.wowza.com/community/t/the-correct-keyframe-interval-in-obs-studio/95162
-    let keyframe_interval = if record { Some(240) } else { None };
     let negotiated_codec = Encoder::negotiated_codec();
+    let tuning = EncoderTuning::get(negotiated_codec);
+    // https://www.wowza.com/community/t/the-correct-keyframe-interval-in-obs-studio/95162
+    let record_keyframe_interval = if record { Some(240) } else { None };
+    let keyframe_interval = tuning.keyframe_interval.or(record_keyframe_interval);
+    // The software encoders may encode at a lower resolution
+    let (width, height) = scaled_size.unwrap_or((c.width, c.height));
+    let vp9 = || {
+        let tuning = EncoderTuning::get(CodecFormat::VP9);
+        EncoderCfg::VPX(VpxEncoderConfig {
+            width: width as _,
+            height: height as _,
+            quality,
+            codec: VpxVideoCodecId::VP9,
+            keyframe_interval: tuning.keyframe_interval.or(record_keyframe_interval),
+            tuning,
+            hdr,
+        })
+    };
     match negotiated_codec {
         CodecFormat::H264 | CodecFormat::H265 => {
             #[cfg(feature = "vram")]
@@ -996,17 +1249,21 @@ fn get_encoder_config(
                     keyframe_interval,
                 });
             }
-            EncoderCfg::VPX(VpxEncoderConfig {
-                width: c.width as _,
-                height: c.height as _,
-                quality,
-                codec: VpxVideoCodecId::VP9,
-                keyframe_interval,
-            })
+            #[cfg(feature = "openh264")]
+            if negotiated_codec == CodecFormat::H264 {
+                return EncoderCfg::OpenH264(OpenH264EncoderConfig {
+                    width: c.width,
+                    height: c.height,
+                    quality,
+                    keyframe_interval,
+                    tuning,
+                });
+            }
+            vp9()
         }
         format @ (CodecFormat::VP8 | CodecFormat::VP9) => EncoderCfg::VPX(VpxEncoderConfig {
-            width: c.width as _,
-            height: c.height as _,
+            width: width as _,
+            height: height as _,
             quality,
             codec: if format == CodecFormat::VP8 {
                 VpxVideoCodecId::VP8
@@ -1014,20 +1271,18 @@ fn get_encoder_config(
                 VpxVideoCodecId::VP9
             },
             keyframe_interval,
+            tuning,
+            hdr: hdr && format == CodecFormat::VP9,
         }),
         CodecFormat::AV1 => EncoderCfg::AOM(AomEncoderConfig {
-            width: c.width as _,
-            height: c.height as _,
-            quality,
-            keyframe_interval,
-        }),
-        _ => EncoderCfg::VPX(VpxEncoderConfig {
-            width: c.width as _,
-            height: c.height as _,
+            width: width as _,
+            height: height as _,
             quality,
-            codec: VpxVideoCodecId::VP9,
             keyframe_interval,
+            tuning,
+            hdr,
         }),
+        _ => vp9(),
     }
 }
 
@@ -1132,12 +1387,15 @@ fn handle_one_frame(
     frame: EncodeInput,
     ms: i64,
     encoder: &mut Encoder,
+    tier: Tier,
+    info: &FrameInfo,
     recorder: Arc<Mutex<Option<Recorder>>>,
     encode_fail_counter: &mut usize,
     first_frame: &mut bool,
     width: usize,
     height: usize,
-) -> ResultType<HashSet<i32>> {
+    filter: impl Fn(i32) -> bool,
+) -> ResultType<(HashSet<i32>, bool)> {
     sp.snapshot(|sps| {
         // so that new sub and old sub share the same encoder after switch
         if sps.has_subscribes() {
@@ -1148,12 +1406,23 @@ fn handle_one_frame(
     })?;
 
     let mut send_conn_ids: HashSet<i32> = Default::default();
+    let mut key_frame = false;
     let first = *first_frame;
     *first_frame = false;
+    let encode_start = Instant::now();
     match encoder.encode_to_message(frame, ms) {
         Ok(mut vf) => {
             *encode_fail_counter = 0;
+            let encode_ms = encode_start.elapsed().as_secs_f32() * 1000.0;
+            ENCODE_MS
+                .lock()
+                .unwrap()
+                .entry((display, tier))
+                .and_modify(|v| *v = *v * 0.9 + encode_ms * 0.1)
+                .or_insert(encode_ms);
             vf.display = display as _;
+            info.set(&mut vf);
+            key_frame = is_key_frame(&vf);
             let mut msg = Message::new();
             msg.set_video_frame(vf);
             recorder
@@ -1161,7 +1430,7 @@ fn handle_one_frame(
                 .unwrap()
                 .as_mut()
                 .map(|r| r.write_message(&msg, width, height));
-            send_conn_ids = sp.send_video_frame(msg);
+            send_conn_ids = sp.send_video_frame(msg, filter);
         }
         Err(e) => {
             *encode_fail_counter += 1;
@@ -1194,7 +1463,179 @@ fn handle_one_frame(
             }
         }
     }
-    Ok(send_conn_ids)
+    Ok((send_conn_ids, key_frame))
+}
+
+// Set on every video frame of the display.
+#[derive(Debug, Default)]
+struct FrameInfo {
+    // The client scales the frames up to the original size, or crops the padding.
+    original_size: Option<(usize, usize)>,
+    // The color of the 10-bit frames, VP9 has no transfer in the bitstream.
+    color: Option<scrap::hdr::VideoColor>,
+}
+
+impl FrameInfo {
+    fn set(&self, vf: &mut VideoFrame) {
+        if let Some((width, height)) = self.original_size {
+            vf.original_size = Some(OriginalSize {
+                width: width as _,
+                height: height as _,
+                ..Default::default()
+            })
+            .into();
+        }
+        if let Some(color) = self.color {
+            vf.color = Some(hbb_common::message_proto::VideoColor::from(color)).into();
+        }
+    }
+}
+
+fn is_key_frame(vf: &VideoFrame) -> bool {
+    match &vf.union {
+        Some(video_frame::Union::Vp8s(frames))
+        | Some(video_frame::Union::Vp9s(frames))
+        | Some(video_frame::Union::Av1s(frames))
+        | Some(video_frame::Union::H264s(frames))
+        | Some(video_frame::Union::H265s(frames)) => frames.frames.iter().any(|f| f.key),
+        _ => false,
+    }
+}
+
+// The low tier is encoded on the capture thread after the high tier, it's skipped if both do not
+// fit in the frame interval of the high tier, but not for longer than this.
+const LOW_TIER_MAX_SKIP: Duration = Duration::from_secs(1);
+
+// Whether encoding both tiers of the display takes longer than `spf`.
+fn exceeds_spf(display: usize, spf: Duration) -> bool {
+    let encode_ms = ENCODE_MS.lock().unwrap();
+    match (
+        encode_ms.get(&(display, Tier::High)),
+        encode_ms.get(&(display, Tier::Low)),
+    ) {
+        (Some(high), Some(low)) => high + low > spf.as_secs_f32() * 1000.0,
+        _ => false,
+    }
+}
+
+// The encoder of the low tier, see video_qos.rs
+struct LowTier {
+    encoder: Encoder,
+    quality: f32,
+    spf: Duration,
+    last_encode: Option<Instant>,
+    encode_fail_counter: usize,
+    first_frame: bool,
+}
+
+impl LowTier {
+    fn new(encoder_cfg: &EncoderCfg, use_i444: bool) -> ResultType<Self> {
+        let quality = VIDEO_QOS.lock().unwrap().ratio(Tier::Low);
+        let mut encoder = Encoder::new(encoder_cfg.clone(), use_i444)?;
+        encoder.set_quality(quality)?;
+        let mut video_qos = VIDEO_QOS.lock().unwrap();
+        video_qos.store_bitrate(Tier::Low, encoder.bitrate());
+        log::info!("low tier quality: {quality:?}");
+        Ok(Self {
+            encoder,
+            quality,
+            spf: video_qos.spf(Tier::Low),
+            last_encode: None,
+            encode_fail_counter: 0,
+            first_frame: true,
+        })
+    }
+
+    fn check_qos(&mut self) {
+        let mut video_qos = VIDEO_QOS.lock().unwrap();
+        self.spf = video_qos.spf(Tier::Low);
+        let ratio = video_qos.ratio(Tier::Low);
+        if self.quality != ratio {
+            self.quality = ratio;
+            allow_err!(self.encoder.set_quality(ratio));
+            video_qos.store_bitrate(Tier::Low, self.encoder.bitrate());
+        }
+    }
+
+    fn encode(
+        &mut self,
+        display: usize,
+        sp: &GenericService,
+        yuv: &[u8],
+        ms: i64,
+        now: Instant,
+        high_spf: Duration,
+        info: &FrameInfo,
+        conn_ids: &HashSet<i32>,
+        width: usize,
+        height: usize,
+    ) -> ResultType<bool> {
+        let elapsed = self.last_encode.map(|t| now.saturating_duration_since(t));
+        if yuv.is_empty()
+            || elapsed.is_some_and(|e| e < self.spf)
+            || (elapsed.is_some_and(|e| e < LOW_TIER_MAX_SKIP) && exceeds_spf(display, high_spf))
+        {
+            return Ok(false);
+        }
+        self.last_encode = Some(now);
+        let (_, key_frame) = handle_one_frame(
+            display,
+            sp,
+            EncodeInput::YUV(yuv),
+            ms,
+            &mut self.encoder,
+            Tier::Low,
+            info,
+            Default::default(),
+            &mut self.encode_fail_counter,
+            &mut self.first_frame,
+            width,
+            height,
+            |id| conn_ids.contains(&id),
+        )?;
+        Ok(key_frame)
+    }
+}
+
+fn send_lossless_tiles(sp: &GenericService, conn_ids: &HashSet<i32>, update: &TileUpdate) {
+    let tiles = update.to_message();
+    let size = tiles.compute_size() as usize;
+    let mut video_qos = VIDEO_QOS.lock().unwrap();
+    for conn_id in conn_ids.iter() {
+        video_qos.on_video_extra_written(*conn_id, size);
+        let mut misc = Misc::new();
+        misc.set_lossless_tiles(tiles.clone());
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        sp.send_to(msg, *conn_id);
+    }
+}
+
+// A key frame replaces the whole picture, the tiles may cover the changes the capturer missed.
+fn clear_lossless_tiles(
+    sp: &GenericService,
+    conn_ids: &HashSet<i32>,
+    tile_refiner: Option<&mut TileRefiner>,
+) {
+    if let Some(update) = tile_refiner.and_then(|r| r.clear()) {
+        send_lossless_tiles(sp, conn_ids, &update);
+    }
+}
+
+// The cursor position in the captured frame, for the roi encoding.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn cursor_in_frame(origin: (i32, i32)) -> Option<(usize, usize)> {
+    let (x, y) = crate::get_cursor_pos()?;
+    let (x, y) = (x - origin.0, y - origin.1);
+    if x < 0 || y < 0 {
+        return None;
+    }
+    Some((x as _, y as _))
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn cursor_in_frame(_origin: (i32, i32)) -> Option<(usize, usize)> {
+    None
 }
 
 #[inline]
@@ -1314,18 +1755,22 @@ fn check_qos(
     client_record: bool,
     send_counter: &mut usize,
     second_instant: &mut Instant,
-    name: &str,
+    sp: &GenericService,
+    tiers_version: &mut usize,
+    tier_conn_ids: &HashSet<i32>,
+    scale: f32,
+    hdr: bool,
 ) -> ResultType<()> {
     let mut video_qos = VIDEO_QOS.lock().unwrap();
-    *spf = video_qos.spf();
-    if *ratio != video_qos.ratio() {
-        *ratio = video_qos.ratio();
+    *spf = video_qos.spf(Tier::High);
+    if *ratio != video_qos.ratio(Tier::High) {
+        *ratio = video_qos.ratio(Tier::High);
         if encoder.support_changing_quality() {
             allow_err!(encoder.set_quality(*ratio));
-            video_qos.store_bitrate(encoder.bitrate());
+            video_qos.store_bitrate(Tier::High, encoder.bitrate());
         } else {
             // Now only vaapi doesn't support changing quality
-            if !video_qos.in_vbr_state() && !video_qos.latest_quality().is_custom() {
+            if !video_qos.in_vbr_state() && !video_qos.latest_quality(Tier::High).is_custom() {
                 log::info!("switch to change quality");
                 bail!("SWITCH");
             }
@@ -1335,9 +1780,30 @@ fn check_qos(
         log::info!("switch due to record changed");
         bail!("SWITCH");
     }
+    if *tiers_version != video_qos.tiers_version() {
+        *tiers_version = video_qos.tiers_version();
+        // Not restarted if the users whose tier changed don't subscribe to this display
+        let low_tier = video_qos.tier_conn_ids(Tier::Low);
+        if sp
+            .subscribe_ids()
+            .iter()
+            .any(|id| low_tier.contains(id) != tier_conn_ids.contains(id))
+        {
+            log::info!("switch due to tiers changed");
+            bail!("SWITCH");
+        }
+    }
+    if scale != video_qos.scale() {
+        log::info!("switch due to scale changed");
+        bail!("SWITCH");
+    }
+    if hdr != video_qos.hdr() {
+        log::info!("switch due to hdr changed");
+        bail!("SWITCH");
+    }
     if second_instant.elapsed() > Duration::from_secs(1) {
         *second_instant = Instant::now();
-        video_qos.update_display_data(&name, *send_counter);
+        video_qos.update_display_data(&sp.name(), *send_counter);
         *send_counter = 0;
     }
     drop(video_qos);
@@ -1356,6 +1822,7 @@ pub fn set_take_screenshot(display_idx: usize, sid: String, tx: Sender) {
 }
 
 // We need to this function, because the `stride` may be larger than `width * 4`.
+// The HDR frames are converted with their stride.
 fn get_rgba_from_pixelbuf<'a>(pixbuf: &scrap::PixelBuffer<'a>) -> ResultType<Vec<u8>> {
     let w = pixbuf.width();
     let h = pixbuf.height();
@@ -1364,7 +1831,7 @@ fn get_rgba_from_pixelbuf<'a>(pixbuf: &scrap::PixelBuffer<'a>) -> ResultType<Vec
         bail!("Invalid pixel buf stride.")
     };
 
-    if *s == w * 4 {
+    if *s == w * 4 || pixbuf.pixfmt() == scrap::Pixfmt::RGBAF16 {
         let mut rgba = vec![];
         scrap::convert(pixbuf, scrap::Pixfmt::RGBA, &mut rgba)?;
         Ok(rgba)
@@ -0,0 +1,311 @@
+// Session quality telemetry for monitoring.
+//
+// Both the controlled side and the controlling side report the stats of each session every second.
+// - Option `telemetry-log`, "Y" to append the stats to `<log dir>/telemetry/telemetry.jsonl`.
+// - Option `telemetry-port`, serve the stats in the Prometheus text format on `127.0.0.1:<port>`.
+//   Only the first process binding the port serves it, usually the server process.
+
+use hbb_common::{config::Config, log};
+use serde_derive::Serialize;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+pub const OPTION_TELEMETRY_LOG: &str = "telemetry-log";
+pub const OPTION_TELEMETRY_PORT: &str = "telemetry-port";
+
+const LOG_NAME: &str = "telemetry";
+const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+// The sessions not reported for a while are removed from the metrics.
+const STALE_TIMEOUT: Duration = Duration::from_secs(10);
+const METRIC_PREFIX: &str = "rustdesk_session_";
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<String, (Instant, SessionStats)>> = Default::default();
+    // The port being served, and the signal to stop serving it.
+    static ref LISTENER: Mutex<Option<(u16, Arc<AtomicBool>)>> = Default::default();
+    static ref WRITER: Mutex<()> = Default::default();
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct SessionStats {
+    pub session: String,
+    pub side: &'static str, // "server" or "client"
+    pub peer_id: String,
+    pub codec: String,
+    pub direct: Option<bool>,
+    pub fps: u32,
+    pub bitrate_kbps: u32,
+    pub target_bitrate_kbps: Option<u32>,
+    pub encode_ms: Option<f32>,
+    pub decode_ms: Option<f32>,
+    pub rtt_ms: Option<u32>,
+    pub delay_ms: Option<u32>,
+    pub frame_drops: Option<u64>, // Total since the session starts, only known by the receiver
+}
+
+#[inline]
+fn log_enabled() -> bool {
+    Config::get_option(OPTION_TELEMETRY_LOG) == "Y"
+}
+
+#[inline]
+fn port() -> Option<u16> {
+    Config::get_option(OPTION_TELEMETRY_PORT)
+        .parse()
+        .ok()
+        .filter(|p| *p > 0)
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    log_enabled() || port().is_some()
+}
+
+#[inline]
+pub fn server_session(conn_id: i32) -> String {
+    format!("server-{conn_id}")
+}
+
+#[inline]
+pub fn client_session(session_id: u64) -> String {
+    format!("client-{session_id}")
+}
+
+pub fn report(stats: SessionStats) {
+    if log_enabled() {
+        if let Err(e) = append(&Config::log_path().join(LOG_NAME), &make_record(&stats)) {
+            log::error!("Failed to write telemetry log: {}", e);
+        }
+    }
+    if let Some(port) = port() {
+        start_listener(port);
+        SESSIONS
+            .lock()
+            .unwrap()
+            .insert(stats.session.clone(), (Instant::now(), stats));
+    }
+}
+
+pub fn remove(session: &str) {
+    SESSIONS.lock().unwrap().remove(session);
+}
+
+fn make_record(stats: &SessionStats) -> String {
+    let mut record = serde_json::json!({
+        "time": chrono::Local::now().to_rfc3339(),
+    });
+    if let (Some(record), Ok(serde_json::Value::Object(v))) =
+        (record.as_object_mut(), serde_json::to_value(stats))
+    {
+        record.extend(v);
+    }
+    record.to_string()
+}
+
+// telemetry.jsonl -> telemetry.1.jsonl, the older one is removed.
+fn append(dir: &Path, line: &str) -> std::io::Result<()> {
+    let _lock = WRITER.lock().unwrap();
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{LOG_NAME}.jsonl"));
+    if fs::metadata(&path).map_or(false, |m| m.len() >= MAX_FILE_SIZE) {
+        fs::rename(&path, dir.join(format!("{LOG_NAME}.1.jsonl")))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(format!("{line}\n").as_bytes())
+}
+
+// Retried on the next report if the port can't be bound.
+fn start_listener(port: u16) {
+    let mut current = LISTENER.lock().unwrap();
+    if current.as_ref().is_some_and(|(p, _)| *p == port) {
+        return;
+    }
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .and_then(|l| l.set_nonblocking(true).map(|_| l))
+    {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::debug!("Failed to serve telemetry on port {port}: {e}");
+            return;
+        }
+    };
+    if let Some((_, stop)) = current.take() {
+        stop.store(true, Ordering::SeqCst);
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *current = Some((port, stop.clone()));
+    drop(current);
+    log::info!("Serving telemetry on port {port}");
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::SeqCst) && port() == Some(port) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(false).and_then(|_| serve(stream)) {
+                        log::debug!("Failed to serve telemetry: {e}");
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_INTERVAL);
+                }
+                Err(e) => {
+                    log::debug!("Failed to accept telemetry connection: {e}");
+                    std::thread::sleep(ACCEPT_INTERVAL);
+                }
+            }
+        }
+        drop(listener);
+        let mut current = LISTENER.lock().unwrap();
+        if current.as_ref().is_some_and(|(_, s)| Arc::ptr_eq(s, &stop)) {
+            *current = None;
+        }
+        log::info!("Stop serving telemetry on port {port}");
+    });
+}
+
+fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let response = if request.starts_with("GET / ") || request.starts_with("GET /metrics ") {
+        let body = {
+            let mut sessions = SESSIONS.lock().unwrap();
+            sessions.retain(|_, (t, _)| t.elapsed() < STALE_TIMEOUT);
+            let mut stats = sessions.values().map(|(_, s)| s).collect::<Vec<_>>();
+            stats.sort_by(|a, b| a.session.cmp(&b.session));
+            metrics_text(&stats)
+        };
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+    };
+    stream.write_all(response.as_bytes())
+}
+
+fn escape_label(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn metrics_text(stats: &[&SessionStats]) -> String {
+    let metrics: [(&str, &str, &str, fn(&SessionStats) -> Option<f64>); 9] = [
+        ("fps", "gauge", "Video frames per second.", |s| {
+            Some(s.fps as _)
+        }),
+        ("bitrate_kbps", "gauge", "Video bitrate in kbps.", |s| {
+            Some(s.bitrate_kbps as _)
+        }),
+        (
+            "target_bitrate_kbps",
+            "gauge",
+            "Target bitrate of the encoder in kbps.",
+            |s| s.target_bitrate_kbps.map(|v| v as _),
+        ),
+        ("encode_ms", "gauge", "Average encoding time in ms.", |s| {
+            s.encode_ms.map(|v| v as _)
+        }),
+        ("decode_ms", "gauge", "Average decoding time in ms.", |s| {
+            s.decode_ms.map(|v| v as _)
+        }),
+        ("rtt_ms", "gauge", "Round trip time in ms.", |s| {
+            s.rtt_ms.map(|v| v as _)
+        }),
+        ("delay_ms", "gauge", "Network delay in ms.", |s| {
+            s.delay_ms.map(|v| v as _)
+        }),
+        (
+            "frame_drops_total",
+            "counter",
+            "Video frames dropped by the receiver.",
+            |s| s.frame_drops.map(|v| v as _),
+        ),
+        ("direct", "gauge", "1 for direct, 0 for relay.", |s| {
+            s.direct.map(|v| if v { 1.0 } else { 0.0 })
+        }),
+    ];
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics.iter() {
+        text += &format!("# HELP {METRIC_PREFIX}{name} {help}\n");
+        text += &format!("# TYPE {METRIC_PREFIX}{name} {kind}\n");
+        for s in stats.iter() {
+            if let Some(v) = value(s) {
+                text += &format!(
+                    "{METRIC_PREFIX}{name}{{session=\"{}\",side=\"{}\",peer_id=\"{}\",codec=\"{}\"}} {v}\n",
+                    escape_label(&s.session),
+                    s.side,
+                    escape_label(&s.peer_id),
+                    escape_label(&s.codec),
+                );
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> SessionStats {
+        SessionStats {
+            session: server_session(1),
+            side: "server",
+            peer_id: "123\"456".to_owned(),
+            codec: "VP9".to_owned(),
+            direct: Some(false),
+            fps: 30,
+            bitrate_kbps: 1500,
+            target_bitrate_kbps: Some(2000),
+            encode_ms: Some(4.5),
+            rtt_ms: Some(40),
+            frame_drops: Some(2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let v: serde_json::Value = serde_json::from_str(&make_record(&stats())).unwrap();
+        assert!(v["time"].is_string());
+        assert_eq!(v["session"], "server-1");
+        assert_eq!(v["fps"], 30);
+        assert_eq!(v["direct"], false);
+        assert!(v["decode_ms"].is_null());
+        assert_eq!(v["frame_drops"], 2);
+    }
+
+    #[test]
+    fn test_metrics_text() {
+        let s = stats();
+        let text = metrics_text(&[&s]);
+        let labels = r#"{session="server-1",side="server",peer_id="123\"456",codec="VP9"}"#;
+        assert!(text.contains("# TYPE rustdesk_session_fps gauge\n"));
+        assert!(text.contains(&format!("rustdesk_session_fps{labels} 30\n")));
+        assert!(text.contains(&format!("rustdesk_session_encode_ms{labels} 4.5\n")));
+        assert!(text.contains(&format!("rustdesk_session_frame_drops_total{labels} 2\n")));
+        assert!(text.contains(&format!("rustdesk_session_direct{labels} 0\n")));
+        assert!(!text.contains("rustdesk_session_decode_ms{"));
+
+        let s = SessionStats {
+            frame_drops: None,
+            ..stats()
+        };
+        assert!(!metrics_text(&[&s]).contains("rustdesk_session_frame_drops_total{"));
+    }
+}
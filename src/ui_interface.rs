@@ -357,6 +357,16 @@ pub fn test_if_valid_server(host: String, test_with_proxy: bool) -> String {
     hbb_common::socket_client::test_if_valid_server(&host, test_with_proxy)
 }
 
+// Returns the json of `audit_queue::Status`, pending, dropped and dead-lettered audit events
+// of the server.
+#[inline]
+pub fn get_audit_queue_status() -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return serde_json::to_string(&crate::server::audit_queue::status()).unwrap_or_default();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return ipc::get_audit_queue_status();
+}
+
 #[inline]
 #[cfg(feature = "flutter")]
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -420,6 +430,19 @@ pub fn set_options(m: HashMap<String, String>) {
 
 #[inline]
 pub fn set_option(key: String, value: String) {
+    if key == crate::server::access_schedule::OPTION_ACCESS_SCHEDULE && !value.trim().is_empty() {
+        if let Err(e) = crate::server::access_schedule::AccessSchedule::check(&value) {
+            log::error!("Reject invalid access schedule: {e}");
+            return;
+        }
+    }
+    if key == crate::server::access_control::OPTION_ACCESS_CONTROL_LIST && !value.trim().is_empty()
+    {
+        if let Err(e) = crate::server::access_control::AccessControlList::check(&value) {
+            log::error!("Reject invalid access control list: {e}");
+            return;
+        }
+    }
     if &key == "stop-service" {
         #[cfg(target_os = "macos")]
         {
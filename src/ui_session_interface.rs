@@ -33,14 +33,17 @@ use std::{
     ops::{Deref, DerefMut},
     str::FromStr,
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex, RwLock,
     },
     time::SystemTime,
 };
 use uuid::Uuid;
 
+use crate::client::clipboard_history::ClipboardHistory;
 use crate::client::io_loop::Remote;
+use crate::client::keystrokes::{self, KeystrokeMode};
+use crate::client::macros::{self, MacroRecorder, Playback};
 use crate::client::{
     check_if_retry, handle_hash, handle_login_error, handle_login_from_ui, handle_test_delay,
     input_os_password, send_mouse, send_pointer_device_event, FileManager, Key, LoginConfigHandler,
@@ -48,7 +51,7 @@ use crate::client::{
 };
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 use crate::common::GrabState;
-use crate::keyboard;
+use crate::keyboard::{self, remap::KeyRemap};
 use crate::{client::Data, client::Interface};
 
 const CHANGE_RESOLUTION_VALID_TIMEOUT_SECS: u64 = 15;
@@ -72,6 +75,13 @@ pub struct Session<T: InvokeUiSession> {
     pub reconnect_count: Arc<AtomicUsize>,
     pub last_audit_note: Arc<Mutex<String>>,
     pub audit_guid: Arc<Mutex<String>>,
+    pub clipboard_history: Arc<Mutex<ClipboardHistory>>,
+    pub macro_recorder: Arc<Mutex<Option<MacroRecorder>>>,
+    // Set with `macro_recorder`, checked on every message without taking the lock.
+    pub macro_recording: Arc<AtomicBool>,
+    pub macro_playback: Arc<Playback>,
+    // The option and the parsed remap, parsed again only when the option changes.
+    pub key_remap: Arc<Mutex<(String, Arc<KeyRemap>)>>,
 }
 
 #[derive(Clone)]
@@ -380,7 +390,9 @@ impl<T: InvokeUiSession> Session<T> {
         if name == keys::OPTION_ENABLE_FILE_COPY_PASTE {
             self.send(Data::ToggleClipboardFile);
         }
-        if let Some(msg) = msg {
+        if name == "lossless-text-tiles" {
+            self.send(Data::LosslessTiles(self.get_toggle_option(name)));
+        } else if let Some(msg) = msg {
             self.send(Data::Message(msg));
         }
     }
@@ -590,6 +602,111 @@ impl<T: InvokeUiSession> Session<T> {
         });
     }
 
+    pub fn start_macro_recording(&self) {
+        *self.macro_recorder.lock().unwrap() = Some(MacroRecorder::default());
+        self.macro_recording.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_macro_recording(&self) -> bool {
+        self.macro_recording.load(Ordering::SeqCst)
+    }
+
+    // Returns the error message, empty on success.
+    // An empty name discards the recording.
+    pub fn stop_macro_recording(&self, name: String, hotkey: String) -> String {
+        let recorder = {
+            let mut lock = self.macro_recorder.lock().unwrap();
+            self.macro_recording.store(false, Ordering::SeqCst);
+            lock.take()
+        };
+        let Some(recorder) = recorder else {
+            return "".to_owned();
+        };
+        if name.trim().is_empty() {
+            return "".to_owned();
+        }
+        match macros::save_macro(recorder.finish(name.trim().to_owned(), hotkey)) {
+            Ok(()) => "".to_owned(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn play_macro(&self, name: &str, speed: f64) -> bool {
+        let Some(m) = macros::get_macro(name) else {
+            return false;
+        };
+        macros::play(m, speed, self.sender.clone(), self.macro_playback.clone())
+    }
+
+    pub fn play_macro_by_hotkey(&self, hotkey: &str) -> bool {
+        if self.is_macro_recording() {
+            return false;
+        }
+        let Some(m) = macros::get_macro_by_hotkey(hotkey) else {
+            return false;
+        };
+        macros::play(m, 1.0, self.sender.clone(), self.macro_playback.clone())
+    }
+
+    // Type the text with throttled key events, shares the playback with macros.
+    pub fn paste_as_keystrokes(&self, text: &str, mode: &str, interval_ms: u64) -> bool {
+        let mut peer = self.peer_platform().to_lowercase();
+        peer.retain(|c| !c.is_whitespace());
+        let messages =
+            keystrokes::text_to_messages(&peer, text, KeystrokeMode::parse(mode), interval_ms);
+        if messages.is_empty() {
+            return false;
+        }
+        macros::play_messages(
+            "keystrokes".to_owned(),
+            messages,
+            1.0,
+            self.sender.clone(),
+            self.macro_playback.clone(),
+        )
+    }
+
+    pub fn stop_macro(&self) {
+        self.macro_playback.stop();
+    }
+
+    pub fn is_macro_playing(&self) -> bool {
+        self.macro_playback.is_playing()
+    }
+
+    pub fn get_clipboard_history(&self) -> String {
+        ClipboardHistory::list(&self.clipboard_history).to_string()
+    }
+
+    // Push an entry of the clipboard history to the remote side again.
+    pub fn resend_clipboard_history(&self, id: u64) -> bool {
+        if !self.is_text_clipboard_required() {
+            return false;
+        }
+        let Some(clipboards) = self.clipboard_history.lock().unwrap().get(id) else {
+            return false;
+        };
+        let multi_clipboards = MultiClipboards {
+            clipboards,
+            ..Default::default()
+        };
+        #[cfg(not(target_os = "ios"))]
+        if let Some(pi) = self.lc.read().unwrap().peer_info.as_ref() {
+            if let Some(msg) = crate::clipboard::get_msg_if_not_support_multi_clip(
+                &pi.version,
+                &pi.platform,
+                &multi_clipboards,
+            ) {
+                self.send(Data::Message(msg));
+                return true;
+            }
+        }
+        let mut msg = Message::new();
+        msg.set_multi_clipboards(multi_clipboards);
+        self.send(Data::Message(msg));
+        true
+    }
+
     #[cfg(not(feature = "flutter"))]
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     pub fn is_xfce(&self) -> bool {
@@ -631,6 +748,34 @@ impl<T: InvokeUiSession> Session<T> {
         self.lc.read().unwrap().get_option(&k)
     }
 
+    pub fn get_key_remap(&self) -> Arc<KeyRemap> {
+        let option = self
+            .lc
+            .read()
+            .unwrap()
+            .get_option(keyboard::remap::OPTION_KEY_REMAP);
+        let mut cache = self.key_remap.lock().unwrap();
+        if cache.0 != option {
+            let remap = Arc::new(KeyRemap::from_option(&option));
+            *cache = (option, remap);
+        }
+        cache.1.clone()
+    }
+
+    // Returns the error message, empty on success. The table is stored normalized.
+    pub fn set_key_remap(&self, json: String) -> String {
+        match KeyRemap::parse(&json) {
+            Ok(remap) => {
+                self.lc.write().unwrap().set_option(
+                    keyboard::remap::OPTION_KEY_REMAP.to_owned(),
+                    remap.to_json(),
+                );
+                "".to_owned()
+            }
+            Err(e) => e.to_string(),
+        }
+    }
+
     pub fn set_option(&self, k: String, mut v: String) {
         let mut lc = self.lc.write().unwrap();
         if k.eq("remote_dir") {
@@ -1752,6 +1897,11 @@ impl<T: InvokeUiSession> Interface for Session<T> {
     }
 
     fn send(&self, data: Data) {
+        if let (Data::Message(msg), true) = (&data, self.is_macro_recording()) {
+            if let Some(recorder) = self.macro_recorder.lock().unwrap().as_mut() {
+                recorder.push(msg);
+            }
+        }
         if let Some(sender) = self.sender.read().unwrap().as_ref() {
             sender.send(data).ok();
         }